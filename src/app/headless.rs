@@ -0,0 +1,92 @@
+//! Headless front-end for kiosk and meeting-room PCs
+//!
+//! No tray icon, menu, hotkeys or dialogs. Notifications go to the log,
+//! settings come from `config.toml` and are applied when the file changes,
+//! and actions arrive over the command line and the control APIs, which
+//! carry them out without asking for confirmation.
+
+use super::{AppCore, Frontend};
+use crate::audio::monitor::MonitorState;
+use crate::error::Result;
+use crate::settings::ConfigWatcher;
+use log::{debug, info, warn};
+use std::time::{Duration, Instant};
+
+/// How often the config file is checked for changes
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Runs the core without a user interface
+pub struct HeadlessFrontend {
+    config_watcher: Option<ConfigWatcher>,
+    last_config_check: Instant,
+}
+
+impl HeadlessFrontend {
+    pub fn new() -> Self {
+        Self {
+            config_watcher: None,
+            last_config_check: Instant::now(),
+        }
+    }
+
+    /// Apply the config file if it was edited since the last check
+    fn reload_config(&mut self, core: &mut AppCore) -> Result<()> {
+        let Some(watcher) = self.config_watcher.as_mut() else {
+            return Ok(());
+        };
+        if !watcher.changed() {
+            return Ok(());
+        }
+
+        // Loading a missing file would fall back to the defaults
+        if !core.config_manager.config_path().exists() {
+            warn!("Config file was removed, keeping the running settings");
+            return Ok(());
+        }
+
+        // Keep the running config while the file is half-written or invalid
+        match core.config_manager.load() {
+            Ok(new_config) if new_config == core.config => debug!("Config file rewritten without changes"),
+            Ok(new_config) => {
+                core.apply_config(new_config)?;
+                info!("Config file changed, settings applied");
+            }
+            Err(e) => warn!("Ignoring config file change: {}", e),
+        }
+        Ok(())
+    }
+}
+
+impl Default for HeadlessFrontend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    fn init(&mut self, core: &mut AppCore) -> Result<()> {
+        core.notification_manager.set_log_only(true);
+        self.config_watcher = Some(ConfigWatcher::new(core.config_manager.config_path()));
+        info!(
+            "Running headless; settings are read from {}",
+            core.config_manager.config_path().display()
+        );
+        Ok(())
+    }
+
+    fn poll(&mut self, core: &mut AppCore) -> Result<()> {
+        if self.last_config_check.elapsed() < CONFIG_CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last_config_check = Instant::now();
+        self.reload_config(core)
+    }
+
+    fn show_state(&mut self, _core: &AppCore, _state: &MonitorState) -> Result<()> {
+        Ok(())
+    }
+
+    fn open_settings(&mut self, _core: &AppCore) -> Result<bool> {
+        Ok(false)
+    }
+}
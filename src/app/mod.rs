@@ -0,0 +1,1215 @@
+//! Application core and its front-ends
+//!
+//! `AppCore` owns the monitor, the policy state (forced-stereo devices,
+//! reconnects, watchdog, hooks) and the control servers, and knows nothing
+//! about windows or the platform; it reaches devices through `Backends`. A `Frontend` adds a user interface on top: `tray` is the
+//! normal system tray app, `headless` runs the core alone for kiosk and
+//! meeting-room PCs, controlled through config, the command line and IPC.
+
+pub mod headless;
+#[cfg(windows)]
+pub mod tray;
+
+use crate::audio::monitor::MonitorState;
+use crate::audio::{
+    AudioMonitor, BusMessage, EventBus, MonitorError, MonitorEvent, MonitorHealth, MonitorSupervisor, MuteOutcome,
+    RecordingBackend, RecordingLimits, Subscription, SupervisorAction, SupervisorSettings, WatchdogOutcome, WatchdogRecord,
+    WatchdogSettings,
+};
+use crate::auth;
+use crate::bluetooth::battery::LowBatteryTracker;
+use crate::bluetooth::names::paired_name_for_endpoint;
+use crate::bluetooth::PairedDevice;
+use crate::cli::{self, ActionReport, CliBackend, CliError, DeviceEntry, DirectBackend, StatusReport};
+use crate::error::{AppError, ErrorSeverity, Result};
+use crate::hooks::{self, HookEvent, HookRunner};
+use crate::http::server::HttpServer;
+#[cfg(windows)]
+use crate::ipc::pipe::PipeServer;
+use crate::ipc::{self, IpcCall};
+use crate::metrics::server::MetricsServer;
+use crate::metrics::{ForceStereoReason, Metrics, ReconnectSource};
+use crate::mqtt::client::{MqttClient, MqttSettings};
+use crate::mqtt::PublishedState;
+use crate::notifications::{NotificationManager, NotificationType};
+use crate::platform::Backends;
+use crate::process::ProcessManager;
+#[cfg(windows)]
+use crate::rpc::pipe::RpcPipeServer;
+use crate::rpc::EventHub;
+use crate::settings::{AppConfig, ConfigManager, HooksConfig};
+use crate::snapshot::StateSnapshot;
+use crate::update::UpdateChecker;
+use log::{error, info, warn};
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long to wait for the monitor to answer a command
+const MONITOR_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A user interface driven by the core's main loop
+pub trait Frontend {
+    /// Set up the interface; called before the monitor starts
+    fn init(&mut self, core: &mut AppCore) -> Result<()>;
+
+    /// Handle pending user input; called on every loop iteration
+    fn poll(&mut self, core: &mut AppCore) -> Result<()>;
+
+    /// Show the latest state from the monitor
+    ///
+    /// Called when the state, the core's forced-stereo devices, paired
+    /// devices or language, or whether the monitor is stale changed, not on
+    /// every poll.
+    fn show_state(&mut self, core: &AppCore, state: &MonitorState) -> Result<()>;
+
+    /// Open the settings UI; returns `false` if this front-end has none
+    fn open_settings(&mut self, core: &AppCore) -> Result<bool>;
+
+    /// Release interface resources before the core shuts down
+    fn shutdown(&mut self) {}
+}
+
+/// RAII guard to ensure device is removed from reconnecting set even on panic
+struct ReconnectGuard {
+    device_name: String,
+    reconnecting_devices: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ReconnectGuard {
+    fn new(device_name: &str, reconnecting_devices: Arc<Mutex<HashSet<String>>>) -> Self {
+        Self {
+            device_name: device_name.to_string(),
+            reconnecting_devices,
+        }
+    }
+}
+
+impl Drop for ReconnectGuard {
+    fn drop(&mut self) {
+        // Remove device from reconnecting set
+        if let Ok(mut reconnecting) = self.reconnecting_devices.lock() {
+            reconnecting.remove(&self.device_name);
+        }
+    }
+}
+
+/// UI-free application state and policy
+pub struct AppCore {
+    backends: Backends,
+    config_manager: ConfigManager,
+    config: AppConfig,
+    audio_monitor: Option<AudioMonitor>,
+    /// Restarts the audio monitor when it stops or stalls
+    monitor_supervisor: MonitorSupervisor,
+    /// Monitor events, shared by the core, the control servers and anything else that subscribes
+    events: EventBus,
+    /// The core's own subscription, drained by the main loop
+    monitor_events: Subscription,
+    /// Monitor events were dropped; resync from the shared state after the queue
+    resync_pending: bool,
+    process_manager: ProcessManager,
+    notification_manager: NotificationManager,
+    update_checker: UpdateChecker,
+    mic_apps: Arc<Mutex<Vec<crate::audio::MicUsingApp>>>,
+    reconnecting_devices: Arc<Mutex<HashSet<String>>>,
+    /// Devices that have been forced to stereo mode (HFP disabled), by paired device name
+    forced_stereo_devices: HashSet<String>,
+    /// Latest paired device inventory (includes disconnected devices)
+    paired_devices: Vec<PairedDevice>,
+    /// Whether the first inventory arrived (device-connected hooks start after it)
+    paired_inventory_loaded: bool,
+    /// Something the front-end shows changed since it last rendered
+    render_pending: bool,
+    /// Runs user hook commands on events
+    hook_runner: HookRunner,
+    /// Receives commands from second instances
+    #[cfg(windows)]
+    ipc_server: Option<PipeServer>,
+    /// Commands from IPC server threads, answered by the main loop
+    ipc_calls: Receiver<IpcCall>,
+    ipc_tx: Sender<IpcCall>,
+    /// Serves the JSON-RPC API when enabled in config
+    #[cfg(windows)]
+    rpc_server: Option<RpcPipeServer>,
+    /// Serves the loopback HTTP endpoint when enabled in config
+    http_server: Option<HttpServer>,
+    /// Publishes state to an MQTT broker when enabled in config
+    mqtt_client: Option<MqttClient>,
+    /// Counters and gauges for the metrics endpoint, always collected
+    metrics: Metrics,
+    /// Serves metrics when enabled in config
+    metrics_server: Option<MetricsServer>,
+    /// Monitor events as JSON for RPC and HTTP subscribers
+    event_hub: EventHub,
+    /// Devices already warned about low battery
+    low_battery: LowBatteryTracker,
+    /// Running on simulated devices (`--simulate`)
+    simulated: bool,
+    /// Start the RPC, HTTP and metrics servers enabled in config
+    serve_apis: bool,
+    running: bool,
+    last_update_check: Instant,
+}
+
+impl AppCore {
+    /// Create the core with the saved configuration on the given backends
+    pub fn new(mut backends: Backends) -> Result<Self> {
+        let config_manager = ConfigManager::new()?;
+        let config = config_manager.load()?;
+
+        // Debug option: keep every raw poll for bug reports; runs without it on failure
+        if config.logging.record_polls {
+            let limits = RecordingLimits::from(&config.logging);
+            match RecordingBackend::create(Arc::clone(&backends.audio), &config_manager.log_dir(), limits) {
+                Ok(recorder) => backends.audio = Arc::new(recorder),
+                Err(e) => warn!("Failed to start recording audio polls: {}", e),
+            }
+        }
+
+        let mic_apps = Arc::new(Mutex::new(Vec::new()));
+        let process_manager = ProcessManager::new(Arc::clone(&mic_apps));
+
+        let notification_manager = NotificationManager::new();
+        let update_checker = UpdateChecker::default();
+        let (ipc_tx, ipc_calls) = mpsc::channel();
+        let hook_runner = HookRunner::new(&config.hooks);
+        let events = EventBus::new();
+        let monitor_events = events.subscribe(None);
+        let event_hub = EventHub::on(events.clone());
+
+        Ok(Self {
+            backends,
+            config_manager,
+            config,
+            audio_monitor: None,
+            monitor_supervisor: MonitorSupervisor::new(SupervisorSettings::default(), Instant::now()),
+            events,
+            monitor_events,
+            resync_pending: false,
+            process_manager,
+            notification_manager,
+            update_checker,
+            mic_apps,
+            reconnecting_devices: Arc::new(Mutex::new(HashSet::new())),
+            forced_stereo_devices: HashSet::new(),
+            paired_devices: Vec::new(),
+            paired_inventory_loaded: false,
+            render_pending: false,
+            hook_runner,
+            #[cfg(windows)]
+            ipc_server: None,
+            ipc_calls,
+            ipc_tx,
+            #[cfg(windows)]
+            rpc_server: None,
+            http_server: None,
+            mqtt_client: None,
+            metrics: Metrics::new(),
+            metrics_server: None,
+            event_hub,
+            low_battery: LowBatteryTracker::new(),
+            simulated: false,
+            serve_apis: true,
+            running: true,
+            last_update_check: Instant::now(),
+        })
+    }
+
+    /// Mark the core as running on simulated devices
+    ///
+    /// Simulated process ids may belong to real processes, so terminating
+    /// apps is refused, and the made-up state is not published to MQTT.
+    /// Hooks are not run, and the control servers stay off unless
+    /// `with_control_servers` asks for them.
+    pub fn simulated(mut self) -> Self {
+        self.simulated = true;
+        self.serve_apis = false;
+        self.hook_runner = HookRunner::new(&HooksConfig::default());
+        self
+    }
+
+    /// Start the RPC, HTTP and metrics servers enabled in config even when simulated
+    pub fn with_control_servers(mut self) -> Self {
+        self.serve_apis = true;
+        self
+    }
+
+    /// Whether the core runs on simulated devices
+    pub fn is_simulated(&self) -> bool {
+        self.simulated
+    }
+
+    /// Initialize the front-end, then start the monitor and control servers
+    pub fn init(&mut self, frontend: &mut dyn Frontend) -> Result<()> {
+        // Update notification settings from config
+        self.apply_notification_settings();
+
+        frontend.init(self)?;
+
+        // Start audio monitor
+        self.start_audio_monitor()?;
+
+        // Accept commands from second instances; the app works without it
+        #[cfg(windows)]
+        match PipeServer::start(self.ipc_tx.clone()) {
+            Ok(server) => self.ipc_server = Some(server),
+            Err(e) => warn!("Failed to start IPC server: {}", e),
+        }
+
+        if !self.serve_apis {
+            info!("Not starting the control servers in simulation mode");
+        }
+
+        self.restart_rpc_server();
+        self.restart_http_server();
+        self.start_mqtt_client();
+        self.start_metrics_server();
+
+        info!("Application initialized successfully");
+        Ok(())
+    }
+
+    /// Stop the JSON-RPC server if running, then start it if enabled
+    fn restart_rpc_server(&mut self) {
+        #[cfg(windows)]
+        if let Some(mut server) = self.rpc_server.take() {
+            // Calls waiting for the main loop would keep the server from stopping
+            while self.ipc_calls.try_recv().is_ok() {}
+            server.shutdown();
+        }
+        if self.config.rpc.enabled && self.serve_apis {
+            if let Err(e) = self.start_rpc_server() {
+                warn!("Failed to start RPC server: {}", e);
+            }
+        }
+    }
+
+    /// Stop the HTTP server if running, then start it if enabled
+    fn restart_http_server(&mut self) {
+        if let Some(mut server) = self.http_server.take() {
+            // Calls waiting for the main loop would keep the server from stopping
+            while self.ipc_calls.try_recv().is_ok() {}
+            server.shutdown();
+        }
+        if self.config.http.enabled && self.serve_apis {
+            if let Err(e) = self.start_http_server() {
+                warn!("Failed to start HTTP server: {}", e);
+            }
+        }
+    }
+
+    /// Start the JSON-RPC server, generating an access token on first use
+    #[cfg(windows)]
+    fn start_rpc_server(&mut self) -> Result<()> {
+        if self.config.rpc.token.is_empty() {
+            self.config.rpc.token = auth::generate_token()?;
+            self.config_manager.save(&self.config)?;
+            info!("Generated RPC access token");
+        }
+
+        let server = RpcPipeServer::start(
+            self.config.rpc.token.clone(),
+            self.ipc_tx.clone(),
+            self.event_hub.clone(),
+        )?;
+        self.rpc_server = Some(server);
+        Ok(())
+    }
+
+    /// The JSON-RPC API is served over a named pipe, which only exists on Windows
+    #[cfg(not(windows))]
+    fn start_rpc_server(&mut self) -> Result<()> {
+        Err(crate::error::AppError::ConfigError(
+            "The JSON-RPC server is not available on this platform".to_string(),
+        ))
+    }
+
+    /// The audio and Bluetooth backends the core runs on
+    pub fn backends(&self) -> &Backends {
+        &self.backends
+    }
+
+    /// Serializable snapshot of the current state
+    pub fn snapshot(&self) -> StateSnapshot {
+        let state = self.audio_monitor.as_ref().map(|m| m.get_state()).unwrap_or_default();
+        StateSnapshot::new(&state, SystemTime::now()).with_forced_stereo(&self.forced_stereo_devices)
+    }
+
+    /// Start the loopback HTTP endpoint, generating a bearer token on first use
+    fn start_http_server(&mut self) -> Result<()> {
+        if self.config.http.token.is_empty() {
+            self.config.http.token = auth::generate_token()?;
+            self.config_manager.save(&self.config)?;
+            info!("Generated HTTP bearer token");
+        }
+
+        let server = HttpServer::start(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, self.config.http.port)),
+            self.config.http.token.clone(),
+            self.ipc_tx.clone(),
+            self.event_hub.clone(),
+        )?;
+        self.http_server = Some(server);
+        Ok(())
+    }
+
+    /// Start the MQTT client if enabled; it connects and reconnects in the background
+    fn start_mqtt_client(&mut self) {
+        if !self.config.mqtt.enabled {
+            return;
+        }
+        if self.simulated {
+            info!("Not starting the MQTT client in simulation mode");
+            return;
+        }
+        match MqttClient::start(MqttSettings::from_config(&self.config.mqtt), self.ipc_tx.clone()) {
+            Ok(client) => self.mqtt_client = Some(client),
+            Err(e) => warn!("Failed to start MQTT client: {}", e),
+        }
+    }
+
+    /// Start the metrics endpoint if enabled
+    fn start_metrics_server(&mut self) {
+        if !self.config.metrics.enabled || !self.serve_apis {
+            return;
+        }
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, self.config.metrics.port));
+        match MetricsServer::start(addr, self.metrics.clone()) {
+            Ok(server) => self.metrics_server = Some(server),
+            Err(e) => warn!("Failed to start metrics server: {}", e),
+        }
+    }
+
+    /// Share a changed mic app list with process termination and fire hooks for new apps
+    fn update_mic_apps(&mut self, mic_using_apps: &[crate::audio::MicUsingApp]) {
+        // Acquire the TOCTOU operation lock before updating mic apps
+        // This ensures atomicity with process termination validation
+        let operation_lock = self.process_manager.get_operation_lock();
+        let _guard = operation_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Update shared mic apps list (while holding operation lock)
+        // Keep mic_apps for process termination validation
+        let mut previous_pids = HashSet::new();
+        if let Ok(mut apps) = self.mic_apps.lock() {
+            previous_pids = apps.iter().map(|app| app.process_id).collect();
+            *apps = mic_using_apps.to_vec();
+        }
+
+        // Release operation lock before firing hooks (drop _guard)
+        drop(_guard);
+
+        for app in mic_using_apps.iter().filter(|app| !previous_pids.contains(&app.process_id)) {
+            self.hook_runner.fire(&HookEvent::MicSessionStarted {
+                pid: app.process_id,
+                process_name: app.process_name.clone(),
+                display_name: app.display_name.clone(),
+                bluetooth_mic: app.is_using_bluetooth_mic,
+            });
+        }
+    }
+
+    /// Process events from the audio monitor
+    fn process_audio_events(&mut self) -> Result<()> {
+        while let Ok(message) = self.monitor_events.try_recv() {
+            match message {
+                BusMessage::Event(event) => self.handle_monitor_event(event)?,
+                BusMessage::Lagged(missed) => {
+                    warn!("Main loop fell behind, {} monitor events dropped", missed);
+                    self.resync_pending = true;
+                }
+            }
+        }
+
+        // Queued updates are older than the shared state, so resync after them
+        if self.resync_pending {
+            self.resync_pending = false;
+            if let Some(ref monitor) = self.audio_monitor {
+                let event = MonitorEvent::resync(&monitor.get_state());
+                self.handle_monitor_event(event)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Act on one monitor event
+    fn handle_monitor_event(&mut self, event: MonitorEvent) -> Result<()> {
+        match event {
+            MonitorEvent::StateUpdate { mode, mic_using_apps, devices, changes, .. } => {
+                if changes.mic_apps {
+                    self.update_mic_apps(&mic_using_apps);
+                }
+
+                if changes.devices {
+                    // Warn once per device when the battery runs low
+                    let threshold = self.config.notifications.low_battery_threshold;
+                    self.low_battery.retain_devices(devices.iter().map(|d| d.device.name.as_str()));
+                    for device in &devices {
+                        if let Some(level) = device.battery_level {
+                            if self.low_battery.check(&device.device.name, level, threshold) {
+                                self.notification_manager.show(NotificationType::LowBattery {
+                                    device: device.device.name.clone(),
+                                    level,
+                                })?;
+                            }
+                        }
+                    }
+                    self.metrics.set_devices(&devices);
+                }
+
+                if changes.mode || changes.devices || changes.mic_apps {
+                    if let Some(ref client) = self.mqtt_client {
+                        client.publish_state(PublishedState::new(mode, &devices, &mic_using_apps));
+                    }
+                }
+
+                self.render_pending = true;
+            }
+            MonitorEvent::ModeChanged { old_mode, new_mode } => {
+                self.metrics.record_mode_transition(old_mode, new_mode);
+                self.hook_runner.fire(&HookEvent::ModeChanged {
+                    old_mode: cli::mode_id(old_mode).to_string(),
+                    new_mode: cli::mode_id(new_mode).to_string(),
+                });
+                self.notification_manager.show(NotificationType::ModeChange {
+                    old: old_mode,
+                    new: new_mode,
+                })?;
+            }
+            MonitorEvent::PairedDevicesUpdated(paired_devices) => {
+                if self.paired_inventory_loaded {
+                    for device in hooks::newly_connected(&self.paired_devices, &paired_devices) {
+                        self.hook_runner.fire(&HookEvent::DeviceConnected {
+                            device: device.name.clone(),
+                            address: device.address.to_string(),
+                        });
+                    }
+                }
+                self.paired_inventory_loaded = true;
+
+                self.paired_devices = paired_devices;
+                self.render_pending = true;
+
+                // Devices forced before the inventory arrived are keyed by endpoint name
+                let keys: HashSet<String> = self.forced_stereo_devices.iter().map(|name| self.device_key(name)).collect();
+                if keys != self.forced_stereo_devices {
+                    self.forced_stereo_devices = keys;
+                    self.sync_forced_stereo_devices();
+                }
+            }
+            MonitorEvent::ForceStereoReapplied(device) => {
+                self.metrics.record_force_stereo(ForceStereoReason::Reconnect);
+                self.hook_runner.fire(&HookEvent::ForceStereoApplied {
+                    device: device.clone(),
+                    reason: "reconnect".to_string(),
+                });
+                self.notification_manager.show(NotificationType::Info {
+                    title: rust_i18n::t!("notify_stereo_reapplied").to_string(),
+                    message: rust_i18n::t!("msg_stereo_reapplied", device = &device).to_string(),
+                })?;
+            }
+            MonitorEvent::ForceStereoReapplyFailed { device, error } => {
+                self.notification_manager.show(NotificationType::Error {
+                    message: rust_i18n::t!("msg_stereo_reapply_failed", device = &device, error = &error).to_string(),
+                    severity: ErrorSeverity::Recoverable,
+                })?;
+            }
+            MonitorEvent::Watchdog(record) => {
+                match &record.outcome {
+                    WatchdogOutcome::Succeeded => self.metrics.record_reconnect(ReconnectSource::Watchdog, true),
+                    WatchdogOutcome::Failed(_) => self.metrics.record_reconnect(ReconnectSource::Watchdog, false),
+                    WatchdogOutcome::Triggered | WatchdogOutcome::GaveUp => {}
+                }
+                self.show_watchdog_notification(&record)?;
+            }
+            MonitorEvent::Error(msg) => {
+                warn!("Audio monitor error: {}", msg);
+            }
+            MonitorEvent::Shutdown => {
+                info!("Audio monitor shutdown");
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the audio monitor and hand it the core's settings
+    ///
+    /// Also used to replace a monitor that stopped, so everything the
+    /// monitor keeps in its own state is sent again.
+    fn start_audio_monitor(&mut self) -> Result<()> {
+        self.audio_monitor = Some(AudioMonitor::start(self.backends.clone(), self.events.clone())?);
+        self.apply_watchdog_config();
+        self.sync_forced_stereo_devices();
+        if let Some(ref monitor) = self.audio_monitor {
+            if let Err(e) = monitor.set_metrics(self.metrics.clone()) {
+                warn!("Failed to share metrics with the audio monitor: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the monitor stopped or stalled and the shown state may be outdated
+    pub fn is_monitor_stale(&self) -> bool {
+        self.monitor_supervisor.is_down()
+    }
+
+    /// Check on the audio monitor and restart it if it stopped or stalled
+    fn supervise_monitor(&mut self) {
+        let now = Instant::now();
+        // A restart that failed to start leaves no monitor; keep scheduling restarts
+        let health = match self.audio_monitor {
+            Some(ref monitor) => monitor.health(now, self.monitor_supervisor.settings().stall_timeout),
+            None => MonitorHealth::Dead,
+        };
+
+        for action in self.monitor_supervisor.check(now, health) {
+            match action {
+                SupervisorAction::MarkStale(health) => {
+                    warn!("Audio monitor {}; the shown state is stale", health);
+                    self.render_pending = true;
+                }
+                SupervisorAction::Restart { attempt } => {
+                    info!("Restarting audio monitor (attempt {})", attempt);
+                    // A stalled thread cannot be joined; let it exit on its own
+                    if let Some(old) = self.audio_monitor.take() {
+                        old.abandon();
+                    }
+                    if let Err(e) = self.start_audio_monitor() {
+                        error!("Failed to restart audio monitor: {}", e);
+                    }
+                }
+                SupervisorAction::ReportFailing { attempts } => {
+                    error!("Audio monitor keeps failing after {} restarts", attempts);
+                    let _ = self.notification_manager.show(NotificationType::Error {
+                        message: rust_i18n::t!("msg_monitor_failing", attempts = attempts).to_string(),
+                        severity: ErrorSeverity::Recoverable,
+                    });
+                }
+                SupervisorAction::Recovered => {
+                    info!("Audio monitor is running again");
+                    self.render_pending = true;
+                }
+            }
+        }
+    }
+
+    /// Show the latest state if it, or what the core shows with it, changed
+    fn render_if_pending(&mut self, frontend: &mut dyn Frontend) -> Result<()> {
+        if !std::mem::take(&mut self.render_pending) {
+            return Ok(());
+        }
+        let state = self.audio_monitor.as_ref().map(|m| m.get_state()).unwrap_or_default();
+        frontend.show_state(self, &state)
+    }
+
+    /// Share the forced-stereo set with the audio monitor so it survives reconnects
+    ///
+    /// The front-end shows the set, so this also schedules a re-render.
+    fn sync_forced_stereo_devices(&mut self) {
+        self.render_pending = true;
+        if let Some(ref monitor) = self.audio_monitor {
+            if let Err(e) = monitor.set_forced_stereo_devices(self.forced_stereo_devices.clone()) {
+                warn!("Failed to update forced stereo devices: {}", e);
+            }
+        }
+    }
+
+    /// Send the watchdog configuration to the audio monitor
+    fn apply_watchdog_config(&self) {
+        if let Some(ref monitor) = self.audio_monitor {
+            let settings = self
+                .config
+                .watchdog
+                .enabled
+                .then(|| WatchdogSettings::from(&self.config.watchdog));
+            if let Err(e) = monitor.configure_watchdog(settings) {
+                warn!("Failed to configure watchdog: {}", e);
+            }
+        }
+    }
+
+    fn apply_notification_settings(&mut self) {
+        self.notification_manager.update_settings(
+            self.config.notifications.notify_mode_change,
+            self.config.notifications.notify_mic_usage,
+            self.config.notifications.notify_errors,
+            self.config.notifications.notify_updates,
+        );
+    }
+
+    /// Notify the user about a watchdog action
+    fn show_watchdog_notification(&self, record: &WatchdogRecord) -> Result<()> {
+        let title = rust_i18n::t!("notify_watchdog").to_string();
+        match &record.outcome {
+            WatchdogOutcome::Triggered => {
+                self.notification_manager.show(NotificationType::Info {
+                    title,
+                    message: rust_i18n::t!(
+                        "msg_watchdog_reconnecting",
+                        device = &record.device,
+                        seconds = record.stuck_for.as_secs(),
+                        attempt = record.attempt,
+                        max = record.max_attempts
+                    )
+                    .to_string(),
+                })?;
+            }
+            WatchdogOutcome::Succeeded => {
+                self.notification_manager.show(NotificationType::Info {
+                    title,
+                    message: rust_i18n::t!("msg_watchdog_recovered", device = &record.device).to_string(),
+                })?;
+            }
+            WatchdogOutcome::Failed(error) => {
+                self.notification_manager.show(NotificationType::Error {
+                    message: rust_i18n::t!("msg_watchdog_failed", device = &record.device, error = error).to_string(),
+                    severity: ErrorSeverity::Recoverable,
+                })?;
+            }
+            WatchdogOutcome::GaveUp => {
+                self.notification_manager.show(NotificationType::Error {
+                    message: rust_i18n::t!(
+                        "msg_watchdog_gave_up",
+                        device = &record.device,
+                        max = record.max_attempts
+                    )
+                    .to_string(),
+                    severity: ErrorSeverity::Recoverable,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Terminate an app holding the microphone, asking for confirmation first
+    pub fn terminate_app(&mut self, pid: u32) -> Result<()> {
+        if self.simulated {
+            return Err(AppError::ConfigError(format!(
+                "Process {} is simulated and cannot be terminated",
+                pid
+            )));
+        }
+        self.process_manager.terminate_process(pid, true)
+    }
+
+    /// Mute or unmute an app's microphone on the monitor thread
+    ///
+    /// Waits for the monitor's reply, so callers learn whether it worked.
+    pub fn set_app_muted(&self, pid: u32, muted: bool) -> std::result::Result<MuteOutcome, MonitorError> {
+        let Some(ref monitor) = self.audio_monitor else {
+            return Err(MonitorError::Stopped);
+        };
+        let pending = if muted { monitor.mute_app(pid) } else { monitor.unmute_app(pid) };
+        pending.wait(MONITOR_REPLY_TIMEOUT)
+    }
+
+    /// Name a device is tracked under in the forced-stereo set
+    ///
+    /// The tray and hotkeys name devices by audio endpoint ("Headphones
+    /// (WH-1000XM4)"), the control APIs by paired device; both map to the
+    /// paired device's name. Before the first inventory the name is kept.
+    fn device_key(&self, device_name: &str) -> String {
+        paired_name_for_endpoint(device_name, self.paired_devices.iter().map(|d| d.name.as_str()))
+            .unwrap_or(device_name)
+            .to_string()
+    }
+
+    /// Disable HFP for a device and remember it as forced to stereo
+    ///
+    /// Force stereo is quick - just disable HFP service.
+    pub fn force_stereo(&mut self, device_name: &str) -> Result<()> {
+        let device_name = &self.device_key(device_name);
+        if let Err(e) = self.backends.bluetooth.disable_hfp(device_name) {
+            error!("Failed to force stereo for {}: {}", device_name, e);
+            return Err(e);
+        }
+
+        // Track that this device has been forced to stereo
+        self.forced_stereo_devices.insert(device_name.to_string());
+        self.sync_forced_stereo_devices();
+        self.metrics.record_force_stereo(ForceStereoReason::User);
+        self.hook_runner.fire(&HookEvent::ForceStereoApplied {
+            device: device_name.to_string(),
+            reason: "user".to_string(),
+        });
+        self.notification_manager.show(NotificationType::Info {
+            title: rust_i18n::t!("notify_stereo_mode").to_string(),
+            message: rust_i18n::t!("msg_device_stereo", device = device_name).to_string(),
+        })
+    }
+
+    /// Re-enable HFP for a device and stop re-applying force stereo
+    pub fn allow_hands_free(&mut self, device_name: &str) -> Result<()> {
+        let device_name = &self.device_key(device_name);
+        if let Err(e) = self.backends.bluetooth.enable_hfp(device_name) {
+            error!("Failed to enable hands-free for {}: {}", device_name, e);
+            return Err(e);
+        }
+
+        // Remove from forced stereo tracking
+        self.forced_stereo_devices.remove(device_name);
+        self.sync_forced_stereo_devices();
+        self.notification_manager.show(NotificationType::Info {
+            title: rust_i18n::t!("notify_hands_free_enabled").to_string(),
+            message: rust_i18n::t!("msg_device_hands_free", device = device_name).to_string(),
+        })
+    }
+
+    /// Whether a connect or reconnect of the device is in progress
+    fn is_reconnecting(&self, device_name: &str) -> bool {
+        self.reconnecting_devices.lock().unwrap().contains(device_name)
+    }
+
+    /// Reconnect a device on a background thread
+    ///
+    /// Returns `false` if the device is already reconnecting. The result is
+    /// reported as a notification.
+    pub fn start_reconnect(&mut self, device_name: &str) -> Result<bool> {
+        // Check if device is already reconnecting
+        if self.is_reconnecting(device_name) {
+            self.notification_manager.show(NotificationType::Info {
+                title: rust_i18n::t!("notify_already_reconnecting").to_string(),
+                message: rust_i18n::t!("msg_device_already_reconnecting", device = device_name).to_string(),
+            })?;
+            return Ok(false);
+        }
+
+        // Show reconnecting notification
+        self.notification_manager.show(NotificationType::Info {
+            title: rust_i18n::t!("notify_reconnecting").to_string(),
+            message: rust_i18n::t!("msg_device_reconnecting", device = device_name).to_string(),
+        })?;
+
+        // Spawn background thread for reconnect
+        let name = device_name.to_string();
+        let reconnecting_devices = Arc::clone(&self.reconnecting_devices);
+        let notification_manager = self.notification_manager.clone();
+        let metrics = self.metrics.clone();
+        let bluetooth = Arc::clone(&self.backends.bluetooth);
+
+        std::thread::spawn(move || {
+            // Use guard to ensure device is removed from set even on panic
+            let _guard = ReconnectGuard::new(&name, Arc::clone(&reconnecting_devices));
+
+            // Add device to reconnecting set
+            {
+                let mut reconnecting = reconnecting_devices.lock().unwrap();
+                reconnecting.insert(name.clone());
+            }
+
+            // Perform reconnect
+            let result = bluetooth.reconnect(&name);
+            metrics.record_reconnect(ReconnectSource::User, result.is_ok());
+            match result {
+                Ok(_) => {
+                    info!("Successfully reconnected {}", name);
+                    let _ = notification_manager.show(NotificationType::Info {
+                        title: rust_i18n::t!("notify_reconnected").to_string(),
+                        message: rust_i18n::t!("msg_device_reconnected", device = &name).to_string(),
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to reconnect {}: {}", name, e);
+                    let _ = notification_manager.show(NotificationType::Error {
+                        message: rust_i18n::t!("msg_reconnect_failed", device = &name, error = e.to_string()).to_string(),
+                        severity: ErrorSeverity::Recoverable,
+                    });
+                }
+            }
+        });
+
+        Ok(true)
+    }
+
+    /// Connect a paired but disconnected device on a background thread
+    pub fn start_connect(&mut self, device_name: &str) -> Result<()> {
+        // Connecting shares the in-progress set with reconnects
+        if self.is_reconnecting(device_name) {
+            return self.notification_manager.show(NotificationType::Info {
+                title: rust_i18n::t!("notify_already_reconnecting").to_string(),
+                message: rust_i18n::t!("msg_device_already_reconnecting", device = device_name).to_string(),
+            });
+        }
+
+        self.notification_manager.show(NotificationType::Info {
+            title: rust_i18n::t!("notify_connecting").to_string(),
+            message: rust_i18n::t!("msg_device_connecting", device = device_name).to_string(),
+        })?;
+
+        let name = device_name.to_string();
+        let reconnecting_devices = Arc::clone(&self.reconnecting_devices);
+        let notification_manager = self.notification_manager.clone();
+        let bluetooth = Arc::clone(&self.backends.bluetooth);
+
+        std::thread::spawn(move || {
+            let _guard = ReconnectGuard::new(&name, Arc::clone(&reconnecting_devices));
+            reconnecting_devices.lock().unwrap().insert(name.clone());
+
+            // The device shows up in the menu once its audio endpoint appears
+            if let Err(e) = bluetooth.connect(&name) {
+                error!("Failed to connect {}: {}", name, e);
+                let _ = notification_manager.show(NotificationType::Error {
+                    message: rust_i18n::t!("msg_connect_failed", device = &name, error = e.to_string()).to_string(),
+                    severity: ErrorSeverity::Recoverable,
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Report hook commands that failed, timed out or were skipped
+    fn process_hook_failures(&mut self) -> Result<()> {
+        while let Some(failure) = self.hook_runner.try_recv_failure() {
+            self.notification_manager.show(NotificationType::Error {
+                message: rust_i18n::t!(
+                    "msg_hook_failed",
+                    command = &failure.command,
+                    error = &failure.error
+                )
+                .to_string(),
+                severity: ErrorSeverity::Recoverable,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Answer commands forwarded by a second instance or a control server
+    fn process_ipc_requests(&mut self, frontend: &mut dyn Frontend) {
+        while let Ok(call) = self.ipc_calls.try_recv() {
+            let response = ipc::dispatch(&call.request, &mut CoreBackend { core: self, frontend: &mut *frontend });
+            call.reply(response);
+        }
+    }
+
+    /// Check for updates
+    pub fn check_for_updates(&mut self) -> Result<()> {
+        info!("Checking for updates...");
+        match self.update_checker.check_for_updates() {
+            Ok(Some(update_info)) => {
+                self.notification_manager.show(NotificationType::UpdateAvailable {
+                    version: update_info.version,
+                })?;
+            }
+            Ok(None) => {
+                info!("No updates available");
+                self.notification_manager.show(NotificationType::Info {
+                    title: rust_i18n::t!("notify_up_to_date").to_string(),
+                    message: rust_i18n::t!("msg_latest_version", version = env!("CARGO_PKG_VERSION")).to_string(),
+                })?;
+            }
+            Err(e) => {
+                warn!("Update check failed: {}", e);
+                self.notification_manager.show(NotificationType::Info {
+                    title: rust_i18n::t!("notify_update_check_failed").to_string(),
+                    message: rust_i18n::t!("msg_update_check_error", error = e.to_string()).to_string(),
+                })?;
+            }
+        }
+        self.last_update_check = Instant::now();
+        Ok(())
+    }
+
+    /// Switch to a new configuration, restarting what depends on changed sections
+    ///
+    /// Does not save; returns the previous configuration so front-ends can
+    /// react to their own sections.
+    pub fn apply_config(&mut self, new_config: AppConfig) -> Result<AppConfig> {
+        // Check if language changed
+        let language_changed = new_config.general.language != self.config.general.language;
+        let hooks_changed = new_config.hooks != self.config.hooks;
+        let mqtt_changed = new_config.mqtt != self.config.mqtt;
+        let metrics_changed = new_config.metrics != self.config.metrics;
+        let rpc_changed = new_config.rpc != self.config.rpc;
+        let http_changed = new_config.http != self.config.http;
+
+        // Handle auto-start change
+        if new_config.general.auto_start != self.config.general.auto_start {
+            self.config_manager.set_auto_start(new_config.general.auto_start)?;
+        }
+
+        let old_config = std::mem::replace(&mut self.config, new_config);
+
+        // Update notification settings
+        self.apply_notification_settings();
+
+        // Update watchdog settings
+        self.apply_watchdog_config();
+
+        // Restart hook workers with the new commands (simulations run none)
+        if hooks_changed && !self.simulated {
+            self.hook_runner = HookRunner::new(&self.config.hooks);
+        }
+
+        // Reconnect with the new broker settings
+        if mqtt_changed {
+            if let Some(mut client) = self.mqtt_client.take() {
+                client.shutdown();
+            }
+            self.start_mqtt_client();
+        }
+
+        // Start, stop or re-key the control APIs
+        if rpc_changed {
+            self.restart_rpc_server();
+        }
+        if http_changed {
+            self.restart_http_server();
+        }
+
+        // Rebind the metrics endpoint
+        if metrics_changed {
+            if let Some(mut server) = self.metrics_server.take() {
+                server.shutdown();
+            }
+            self.start_metrics_server();
+        }
+
+        // Handle language change
+        if language_changed {
+            // Reinitialize i18n with new language
+            crate::i18n::init(self.config.general.language.as_deref());
+            // Rebuild the menu with the new language
+            self.render_pending = true;
+            info!("Language changed, i18n reinitialized");
+        }
+
+        Ok(old_config)
+    }
+
+    /// Save the current configuration
+    pub fn save_config(&self) -> Result<()> {
+        self.config_manager.save(&self.config)
+    }
+
+    /// Leave the main loop after the current iteration
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Run the main event loop until stopped or `shutdown` is set
+    pub fn run(&mut self, frontend: &mut dyn Frontend, shutdown: &AtomicBool) -> Result<()> {
+        info!("Starting main event loop");
+
+        // Auto update check interval
+        let update_check_interval = Duration::from_secs(
+            self.config.updates.check_interval_hours as u64 * 3600
+        );
+
+        while self.running && !shutdown.load(Ordering::SeqCst) {
+            // Process user input
+            if let Err(e) = frontend.poll(self) {
+                error!("Front-end error: {}", e);
+            }
+
+            // Restart the monitor if it stopped or stalled
+            self.supervise_monitor();
+
+            // Process audio events
+            if let Err(e) = self.process_audio_events() {
+                error!("Audio event error: {}", e);
+            }
+
+            // Process commands forwarded by a second instance
+            self.process_ipc_requests(frontend);
+
+            // Re-render only after something shown by the front-end changed
+            if let Err(e) = self.render_if_pending(frontend) {
+                error!("Front-end error: {}", e);
+            }
+
+            // Report failed hook commands
+            if let Err(e) = self.process_hook_failures() {
+                error!("Hook failure notification error: {}", e);
+            }
+
+            // Auto update check
+            if self.config.updates.auto_check
+                && self.last_update_check.elapsed() > update_check_interval
+            {
+                let _ = self.check_for_updates();
+            }
+
+            // Sleep to prevent busy loop
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(())
+    }
+
+    /// Shut down the front-end, the control servers and the monitor
+    pub fn shutdown(&mut self, frontend: &mut dyn Frontend) {
+        info!("Shutting down application");
+        frontend.shutdown();
+
+        // Refuse queued commands so waiting server threads return immediately
+        #[cfg(windows)]
+        {
+            while self.ipc_calls.try_recv().is_ok() {}
+            if let Some(mut server) = self.ipc_server.take() {
+                server.shutdown();
+            }
+            while self.ipc_calls.try_recv().is_ok() {}
+            if let Some(mut server) = self.rpc_server.take() {
+                server.shutdown();
+            }
+        }
+        while self.ipc_calls.try_recv().is_ok() {}
+        if let Some(mut server) = self.http_server.take() {
+            server.shutdown();
+        }
+        while self.ipc_calls.try_recv().is_ok() {}
+        if let Some(mut client) = self.mqtt_client.take() {
+            client.shutdown();
+        }
+        if let Some(mut server) = self.metrics_server.take() {
+            server.shutdown();
+        }
+
+        if let Some(mut monitor) = self.audio_monitor.take() {
+            // Joining a stalled monitor would hang the exit
+            if monitor.health(Instant::now(), self.monitor_supervisor.settings().stall_timeout) == MonitorHealth::Stalled {
+                monitor.abandon();
+            } else {
+                monitor.shutdown();
+            }
+        }
+
+        // Save config on exit
+        if let Err(e) = self.save_config() {
+            error!("Failed to save config on exit: {}", e);
+        }
+    }
+}
+
+/// Carries out commands forwarded by a second instance
+///
+/// Commands that change app state go through `AppCore` so the front-end
+/// and force-stereo tracking stay in sync; mutes run on the monitor thread,
+/// status comes from the monitor's state, and other queries use the same
+/// in-process code as the standalone CLI.
+struct CoreBackend<'a> {
+    core: &'a mut AppCore,
+    frontend: &'a mut dyn Frontend,
+}
+
+impl CoreBackend<'_> {
+    /// In-process backend on the core's platform backends
+    fn direct(&self) -> std::result::Result<DirectBackend, CliError> {
+        DirectBackend::with_backends(self.core.backends.clone())
+    }
+
+    /// Mute or unmute through the monitor and report its reply
+    fn set_muted(&mut self, pid: u32, muted: bool) -> std::result::Result<ActionReport, CliError> {
+        let display_name = self
+            .core
+            .mic_apps
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|app| app.process_id == pid)
+            .map(|app| app.display_name.clone())
+            .ok_or_else(|| CliError::NotFound(format!("Process {} is not using a microphone", pid)))?;
+
+        let (action, done) = if muted { ("mute", "Muted") } else { ("unmute", "Unmuted") };
+        match self.core.set_app_muted(pid, muted) {
+            Ok(_) => Ok(ActionReport {
+                action: action.to_string(),
+                target: Some(pid.to_string()),
+                message: format!("{} {} (PID {})", done, display_name, pid),
+            }),
+            Err(MonitorError::Backend(e)) => Err(CliError::Failed(format!("Failed to {} process {}: {}", action, pid, e))),
+            Err(e) => Err(CliError::Unavailable(e.to_string())),
+        }
+    }
+}
+
+impl CliBackend for CoreBackend<'_> {
+    /// From the monitor's latest poll, so no audio enumeration runs on this thread
+    fn status(&mut self) -> std::result::Result<StatusReport, CliError> {
+        let monitor = self
+            .core
+            .audio_monitor
+            .as_ref()
+            .ok_or_else(|| CliError::Unavailable("The audio monitor is not running".to_string()))?;
+        Ok(StatusReport::from(&monitor.get_state()))
+    }
+
+    fn devices(&mut self) -> std::result::Result<Vec<DeviceEntry>, CliError> {
+        self.direct()?.devices()
+    }
+
+    fn force_stereo(&mut self, device: &str) -> std::result::Result<ActionReport, CliError> {
+        let name = self.direct()?.resolve_device(device)?;
+        self.core
+            .force_stereo(&name)
+            .map_err(|e| CliError::Failed(format!("Failed to force stereo for '{}': {}", name, e)))?;
+        Ok(ActionReport {
+            action: "force-stereo".to_string(),
+            message: format!("Hands-free disabled for '{}'", name),
+            target: Some(name),
+        })
+    }
+
+    fn allow_hands_free(&mut self, device: &str) -> std::result::Result<ActionReport, CliError> {
+        let name = self.direct()?.resolve_device(device)?;
+        self.core
+            .allow_hands_free(&name)
+            .map_err(|e| CliError::Failed(format!("Failed to enable hands-free for '{}': {}", name, e)))?;
+        Ok(ActionReport {
+            action: "allow-hands-free".to_string(),
+            message: format!("Hands-free enabled for '{}'", name),
+            target: Some(name),
+        })
+    }
+
+    fn reconnect(&mut self, device: &str) -> std::result::Result<ActionReport, CliError> {
+        let name = self.direct()?.resolve_device(device)?;
+        match self.core.start_reconnect(&name) {
+            Ok(true) => Ok(ActionReport {
+                action: "reconnect".to_string(),
+                message: format!("Reconnecting '{}'; the result is shown as a notification", name),
+                target: Some(name),
+            }),
+            Ok(false) => Err(CliError::Failed(format!("'{}' is already reconnecting", name))),
+            Err(e) => Err(CliError::Failed(format!("Failed to reconnect '{}': {}", name, e))),
+        }
+    }
+
+    fn mute(&mut self, pid: u32) -> std::result::Result<ActionReport, CliError> {
+        self.set_muted(pid, true)
+    }
+
+    fn unmute(&mut self, pid: u32) -> std::result::Result<ActionReport, CliError> {
+        self.set_muted(pid, false)
+    }
+
+    fn restore(&mut self) -> std::result::Result<ActionReport, CliError> {
+        let mut direct = self.direct()?.with_forced_stereo(&self.core.forced_stereo_devices);
+        let report = direct.restore()?;
+        // Stop re-applying force stereo on reconnect where hands-free is back on
+        self.core.forced_stereo_devices = direct.forced_stereo().clone();
+        self.core.sync_forced_stereo_devices();
+        Ok(report)
+    }
+
+    fn open_settings(&mut self) -> std::result::Result<ActionReport, CliError> {
+        let opened = self
+            .frontend
+            .open_settings(self.core)
+            .map_err(|e| CliError::Failed(format!("Failed to open settings: {}", e)))?;
+        if !opened {
+            return Err(CliError::Unavailable(format!(
+                "No settings window in headless mode; edit {} instead",
+                self.core.config_manager.config_path().display()
+            )));
+        }
+        Ok(ActionReport {
+            action: "open-settings".to_string(),
+            target: None,
+            message: "Settings opened".to_string(),
+        })
+    }
+}
@@ -0,0 +1,308 @@
+//! System tray front-end
+//!
+//! Tray icon and menu, global hotkeys, the settings window and the about
+//! dialog. Menu and hotkey actions are carried out by the core.
+
+use super::{AppCore, Frontend};
+use crate::audio::monitor::MonitorState;
+use crate::audio::wasapi::DeviceManager;
+use crate::error::{ErrorSeverity, Result};
+use crate::hotkeys::register::RegisteredHotkeys;
+use crate::hotkeys::{self, HotkeyAction};
+use crate::notifications::NotificationType;
+use crate::settings::window::SettingsMessage;
+use crate::settings::SettingsWindow;
+use crate::tray::{MenuBuilder, MenuContent, MenuEvent, TrayIconManager};
+use log::{error, info, warn};
+use muda::MenuEvent as MudaMenuEvent;
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::WindowsAndMessaging::{
+    DispatchMessageW, MessageBoxW, PeekMessageW, TranslateMessage, MB_ICONINFORMATION, MB_OK, MB_SETFOREGROUND, MSG,
+    PM_REMOVE,
+};
+
+/// The normal tray application
+pub struct TrayFrontend {
+    tray_manager: Option<TrayIconManager>,
+    menu_builder: MenuBuilder,
+    /// What the current menu shows, to skip rebuilding an unchanged menu
+    menu_content: Option<MenuContent>,
+    settings_window: SettingsWindow,
+    /// Global hotkeys registered on the main thread
+    hotkeys: Option<RegisteredHotkeys>,
+    /// Mark tooltip and menu as a simulation
+    simulation: bool,
+}
+
+impl TrayFrontend {
+    pub fn new() -> Self {
+        Self {
+            tray_manager: None,
+            menu_builder: MenuBuilder::new(),
+            menu_content: None,
+            settings_window: SettingsWindow::new(),
+            hotkeys: None,
+            simulation: false,
+        }
+    }
+
+    /// Tray for `--simulate`, marked as a simulation in tooltip and menu
+    pub fn for_simulation() -> Self {
+        Self {
+            menu_builder: MenuBuilder::for_simulation(),
+            simulation: true,
+            ..Self::new()
+        }
+    }
+
+    /// (Re-)register the configured hotkeys, reporting invalid, conflicting or taken ones
+    fn register_hotkeys(&mut self, core: &AppCore) -> Result<()> {
+        // Release the old set first so an unchanged hotkey can be registered again
+        self.hotkeys = None;
+
+        let (bindings, mut problems) = hotkeys::bindings(&core.config.hotkeys);
+        let (registered, refused) = RegisteredHotkeys::register(&bindings);
+        problems.extend(refused);
+        self.hotkeys = Some(registered);
+
+        for problem in problems {
+            warn!("Hotkey not active: {}", problem);
+            core.notification_manager.show(NotificationType::Error {
+                message: rust_i18n::t!("msg_hotkey_problem", problem = problem.to_string()).to_string(),
+                severity: ErrorSeverity::Recoverable,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Run a hotkey's action on the active headset
+    fn handle_hotkey(&mut self, core: &mut AppCore, action: HotkeyAction) -> Result<()> {
+        let default_output = DeviceManager::new()
+            .and_then(|manager| manager.get_default_render_device())
+            .ok()
+            .flatten()
+            .map(|device| device.name);
+        let devices = core
+            .audio_monitor
+            .as_ref()
+            .map(|monitor| monitor.get_state().bluetooth_devices)
+            .unwrap_or_default();
+
+        match hotkeys::active_device(default_output.as_deref(), &devices) {
+            Some(device) => {
+                info!("Hotkey for {} pressed, active headset: {}", action.config_key(), device);
+                self.dispatch_menu_event(core, action.menu_event(device))
+            }
+            None => core.notification_manager.show(NotificationType::Info {
+                title: rust_i18n::t!("notify_hotkey").to_string(),
+                message: rust_i18n::t!("msg_hotkey_no_device").to_string(),
+            }),
+        }
+    }
+
+    /// Handle menu events
+    fn handle_menu_event(&mut self, core: &mut AppCore, event: &MudaMenuEvent) -> Result<()> {
+        match self.menu_builder.handle_event(event) {
+            Some(menu_event) => self.dispatch_menu_event(core, menu_event),
+            None => Ok(()),
+        }
+    }
+
+    /// Carry out a menu action, whether it came from the tray menu or a hotkey
+    fn dispatch_menu_event(&mut self, core: &mut AppCore, menu_event: MenuEvent) -> Result<()> {
+        match menu_event {
+            MenuEvent::TerminateApp(pid) => {
+                info!("Terminate app {} requested", pid);
+                if let Err(e) = core.terminate_app(pid) {
+                    core.notification_manager.show(NotificationType::Error {
+                        message: e.to_string(),
+                        severity: ErrorSeverity::Recoverable,
+                    })?;
+                }
+            }
+            MenuEvent::ForceStereo(device_name) => {
+                info!("Force stereo requested for: {}", device_name);
+                if let Err(e) = core.force_stereo(&device_name) {
+                    core.notification_manager.show(NotificationType::Error {
+                        message: rust_i18n::t!("msg_stereo_failed", error = e.to_string()).to_string(),
+                        severity: ErrorSeverity::Recoverable,
+                    })?;
+                }
+            }
+            MenuEvent::AllowHandsFree(device_name) => {
+                info!("Allow hands-free requested for: {}", device_name);
+                if let Err(e) = core.allow_hands_free(&device_name) {
+                    core.notification_manager.show(NotificationType::Error {
+                        message: rust_i18n::t!("msg_hands_free_failed", error = e.to_string()).to_string(),
+                        severity: ErrorSeverity::Recoverable,
+                    })?;
+                }
+            }
+            MenuEvent::ReconnectDevice(device_name) => {
+                info!("Reconnect requested for: {}", device_name);
+                core.start_reconnect(&device_name)?;
+            }
+            MenuEvent::ConnectDevice(device_name) => {
+                info!("Connect requested for: {}", device_name);
+                core.start_connect(&device_name)?;
+            }
+            MenuEvent::OpenSettings => {
+                info!("Open settings requested");
+                self.settings_window.open(core.config.clone(), &core.config_manager)?;
+            }
+            MenuEvent::CheckUpdates => {
+                info!("Check updates requested");
+                core.check_for_updates()?;
+            }
+            MenuEvent::ShowAbout => {
+                info!("Show about requested");
+                show_about_dialog();
+            }
+            MenuEvent::Exit => {
+                info!("Exit requested");
+                core.stop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Process settings window messages
+    fn process_settings_events(&mut self, core: &mut AppCore) -> Result<()> {
+        if let Some(msg) = self.settings_window.try_recv() {
+            match msg {
+                SettingsMessage::Closed(Some(new_config)) => {
+                    let old_config = core.apply_config(new_config)?;
+                    core.save_config()?;
+
+                    if old_config.hotkeys != core.config.hotkeys {
+                        self.register_hotkeys(core)?;
+                    }
+
+                    info!("Settings saved");
+                }
+                SettingsMessage::Closed(None) => {
+                    info!("Settings cancelled");
+                }
+                SettingsMessage::Error(e) => {
+                    warn!("Settings error: {}", e);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for TrayFrontend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frontend for TrayFrontend {
+    fn init(&mut self, core: &mut AppCore) -> Result<()> {
+        // Build initial menu
+        let content = MenuContent::new(&MonitorState::default(), &core.forced_stereo_devices, &core.paired_devices);
+        let menu = self.menu_builder.build(&content)?;
+        self.menu_content = Some(content);
+
+        // Create tray icon
+        let mut tray_manager = TrayIconManager::new(menu)?;
+        if self.simulation {
+            tray_manager.mark_simulation()?;
+        }
+        self.tray_manager = Some(tray_manager);
+
+        self.register_hotkeys(core)
+    }
+
+    fn poll(&mut self, core: &mut AppCore) -> Result<()> {
+        let mut msg = MSG::default();
+
+        // Process Windows messages
+        unsafe {
+            while PeekMessageW(&mut msg, HWND::default(), 0, 0, PM_REMOVE).as_bool() {
+                if let Some(action) = self.hotkeys.as_ref().and_then(|h| h.action_for(&msg)) {
+                    if let Err(e) = self.handle_hotkey(core, action) {
+                        error!("Hotkey error: {}", e);
+                    }
+                    continue;
+                }
+                let _ = TranslateMessage(&msg);
+                let _ = DispatchMessageW(&msg);
+            }
+        }
+
+        // Process menu events
+        if let Ok(event) = MudaMenuEvent::receiver().try_recv() {
+            if let Err(e) = self.handle_menu_event(core, &event) {
+                error!("Menu event error: {}", e);
+            }
+        }
+
+        // Process settings events
+        self.process_settings_events(core)
+    }
+
+    fn show_state(&mut self, core: &AppCore, state: &MonitorState) -> Result<()> {
+        if let Some(ref mut tray) = self.tray_manager {
+            tray.update_mode(state.current_mode)?;
+            tray.update_battery(&state.bluetooth_devices)?;
+            tray.set_stale(core.is_monitor_stale())?;
+
+            // Rebuild the menu only if what it shows changed
+            let content = MenuContent::new(state, &core.forced_stereo_devices, &core.paired_devices);
+            if self.menu_content.as_ref() != Some(&content) {
+                let menu = self.menu_builder.build(&content)?;
+                tray.update_menu(menu)?;
+                self.menu_content = Some(content);
+            }
+        }
+        Ok(())
+    }
+
+    fn open_settings(&mut self, core: &AppCore) -> Result<bool> {
+        self.settings_window.open(core.config.clone(), &core.config_manager)?;
+        Ok(true)
+    }
+
+    fn shutdown(&mut self) {
+        self.hotkeys = None;
+    }
+}
+
+/// Show about dialog
+fn show_about_dialog() {
+    let version = env!("CARGO_PKG_VERSION");
+    let message = format!(
+        "{}\n\n{}\n\n{}\n\n{}\n{}",
+        rust_i18n::t!("about_app_name"),
+        rust_i18n::t!("about_version", version = version),
+        rust_i18n::t!("about_description"),
+        rust_i18n::t!("about_author"),
+        rust_i18n::t!("about_license")
+    );
+
+    let title = rust_i18n::t!("about_title");
+
+    let message_wide: Vec<u16> = OsStr::new(&message)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let title_wide: Vec<u16> = OsStr::new(&*title)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+
+    unsafe {
+        MessageBoxW(
+            HWND::default(),
+            PCWSTR::from_raw(message_wide.as_ptr()),
+            PCWSTR::from_raw(title_wide.as_ptr()),
+            MB_OK | MB_ICONINFORMATION | MB_SETFOREGROUND,
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Platform audio backend
+//!
+//! The monitor and the command line read the audio stack only through
+//! `AudioBackend`. A backend reports raw poll results (endpoints, formats,
+//! meter channels, capture sessions); turning those into a mode is done by
+//! the portable code in `monitor`.
+
+use crate::audio::device::AudioDevice;
+use crate::audio::session::{HfpUsingApp, MicUsingApp};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// An active output endpoint as seen by one poll
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub device: AudioDevice,
+    /// Mix format sample rate, if the format could be read
+    pub sample_rate: Option<u32>,
+    /// Mix format channel count, if the format could be read
+    pub channels: Option<u16>,
+    /// Peak meter channel count (1 = hands-free, 2 = stereo); only read for Bluetooth endpoints
+    pub meter_channels: Option<u32>,
+}
+
+impl Endpoint {
+    pub fn new(device: AudioDevice) -> Self {
+        Self {
+            device,
+            sample_rate: None,
+            channels: None,
+            meter_channels: None,
+        }
+    }
+}
+
+/// Raw result of polling the audio stack, before mode detection
+///
+/// Serializable so poll recordings can be replayed (see `recording`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioPoll {
+    /// Active output endpoints, Bluetooth or not
+    pub endpoints: Vec<Endpoint>,
+    /// Apps with an active capture session on any input device
+    pub mic_apps: Vec<MicUsingApp>,
+}
+
+/// Access to the platform's audio endpoints and sessions
+///
+/// Shared between the monitor thread and command handlers, so methods take
+/// `&self`. Threads call `attach_thread` before first use.
+pub trait AudioBackend: Send + Sync {
+    /// Prepare the calling thread for audio calls (COM on Windows)
+    fn attach_thread(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Undo `attach_thread` before the thread stops using the backend
+    fn detach_thread(&self) {}
+
+    /// Read endpoints, formats, meters and capture sessions
+    fn poll(&self) -> Result<AudioPoll>;
+
+    /// Apps playing audio to a Bluetooth output (these may have triggered hands-free)
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp>;
+
+    /// Mute or unmute an app's capture sessions on every input device
+    fn set_app_muted(&self, process_id: u32, muted: bool) -> Result<()>;
+
+    /// Mute every capture session on the default input device (force stereo)
+    fn mute_all(&self) -> Result<()>;
+}
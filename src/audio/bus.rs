@@ -0,0 +1,377 @@
+//! Broadcast bus for monitor events
+//!
+//! The monitor publishes every event once; each subscriber gets its own
+//! bounded queue filtered by topic. Publishing never waits for a consumer:
+//! when a queue is full its oldest event is dropped and the subscriber is
+//! told how many it missed (`BusMessage::Lagged`) before the next event.
+
+use crate::audio::monitor::MonitorEvent;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Queue length for subscribers that do not pick their own
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Kind of monitor event, one per `MonitorEvent` variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    StateUpdate,
+    ModeChanged,
+    PairedDevicesUpdated,
+    Watchdog,
+    ForceStereoReapplied,
+    ForceStereoReapplyFailed,
+    Error,
+    Shutdown,
+}
+
+impl Topic {
+    pub const ALL: &'static [Topic] = &[
+        Topic::StateUpdate,
+        Topic::ModeChanged,
+        Topic::PairedDevicesUpdated,
+        Topic::Watchdog,
+        Topic::ForceStereoReapplied,
+        Topic::ForceStereoReapplyFailed,
+        Topic::Error,
+        Topic::Shutdown,
+    ];
+
+    /// Name used by the JSON-RPC and HTTP event streams
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::StateUpdate => "stateUpdate",
+            Topic::ModeChanged => "modeChanged",
+            Topic::PairedDevicesUpdated => "pairedDevicesUpdated",
+            Topic::Watchdog => "watchdog",
+            Topic::ForceStereoReapplied => "forceStereoReapplied",
+            Topic::ForceStereoReapplyFailed => "forceStereoReapplyFailed",
+            Topic::Error => "error",
+            Topic::Shutdown => "shutdown",
+        }
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Topic::ALL
+            .iter()
+            .copied()
+            .find(|topic| topic.name() == s)
+            .ok_or_else(|| format!("Unknown event type '{}'", s))
+    }
+}
+
+impl MonitorEvent {
+    /// The topic this event is published under
+    pub fn topic(&self) -> Topic {
+        match self {
+            MonitorEvent::StateUpdate { .. } => Topic::StateUpdate,
+            MonitorEvent::ModeChanged { .. } => Topic::ModeChanged,
+            MonitorEvent::PairedDevicesUpdated(_) => Topic::PairedDevicesUpdated,
+            MonitorEvent::Watchdog(_) => Topic::Watchdog,
+            MonitorEvent::ForceStereoReapplied(_) => Topic::ForceStereoReapplied,
+            MonitorEvent::ForceStereoReapplyFailed { .. } => Topic::ForceStereoReapplyFailed,
+            MonitorEvent::Error(_) => Topic::Error,
+            MonitorEvent::Shutdown => Topic::Shutdown,
+        }
+    }
+}
+
+/// What a subscriber receives
+#[derive(Debug, Clone)]
+pub enum BusMessage {
+    Event(MonitorEvent),
+    /// This many events were dropped because the queue was full
+    Lagged(u64),
+}
+
+/// Identifies a subscription for `EventBus::unsubscribe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriberId(u64);
+
+#[derive(Default)]
+struct QueueState {
+    events: VecDeque<MonitorEvent>,
+    lagged: u64,
+    closed: bool,
+}
+
+struct Queue {
+    id: SubscriberId,
+    /// Topics to deliver; `None` means all
+    topics: Option<HashSet<Topic>>,
+    capacity: usize,
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+impl Queue {
+    fn push(&self, event: &MonitorEvent) {
+        let mut state = self.state.lock().unwrap();
+        if state.events.len() >= self.capacity {
+            state.events.pop_front();
+            state.lagged += 1;
+        }
+        state.events.push_back(event.clone());
+        self.ready.notify_one();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+}
+
+#[derive(Default)]
+struct BusInner {
+    subscribers: Mutex<Vec<Arc<Queue>>>,
+    next_id: AtomicU64,
+}
+
+impl Drop for BusInner {
+    fn drop(&mut self) {
+        let subscribers = self.subscribers.get_mut().unwrap_or_else(|e| e.into_inner());
+        for queue in subscribers.drain(..) {
+            queue.close();
+        }
+    }
+}
+
+/// Fans monitor events out to any number of subscribers
+///
+/// Cloning shares the subscriber list. Once every clone is dropped,
+/// subscribers drain what is queued and then see the bus as disconnected.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    inner: Arc<BusInner>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to the given topics (`None` = all) with the default queue length
+    pub fn subscribe(&self, topics: Option<HashSet<Topic>>) -> Subscription {
+        self.subscribe_with_capacity(topics, DEFAULT_CAPACITY)
+    }
+
+    /// Subscribe with a queue of at most `capacity` events
+    pub fn subscribe_with_capacity(&self, topics: Option<HashSet<Topic>>, capacity: usize) -> Subscription {
+        let id = SubscriberId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let queue = Arc::new(Queue {
+            id,
+            topics,
+            capacity: capacity.max(1),
+            state: Mutex::new(QueueState::default()),
+            ready: Condvar::new(),
+        });
+        self.inner.subscribers.lock().unwrap().push(Arc::clone(&queue));
+        Subscription {
+            queue,
+            bus: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Stop delivering to a subscription; it sees the bus as disconnected
+    pub fn unsubscribe(&self, id: SubscriberId) {
+        let removed = {
+            let mut subscribers = self.inner.subscribers.lock().unwrap();
+            let position = subscribers.iter().position(|queue| queue.id == id);
+            position.map(|position| subscribers.remove(position))
+        };
+        if let Some(queue) = removed {
+            queue.close();
+        }
+    }
+
+    /// Number of live subscriptions
+    pub fn subscriber_count(&self) -> usize {
+        self.inner.subscribers.lock().unwrap().len()
+    }
+
+    /// Deliver an event to every subscriber of its topic
+    pub fn publish(&self, event: MonitorEvent) {
+        let topic = event.topic();
+        let subscribers = self.inner.subscribers.lock().unwrap();
+        for queue in subscribers.iter() {
+            if queue.topics.as_ref().is_none_or(|topics| topics.contains(&topic)) {
+                queue.push(&event);
+            }
+        }
+    }
+}
+
+/// One subscriber's queue; dropping it unsubscribes
+pub struct Subscription {
+    queue: Arc<Queue>,
+    bus: Weak<BusInner>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> SubscriberId {
+        self.queue.id
+    }
+
+    /// Take the next message without waiting
+    pub fn try_recv(&self) -> Result<BusMessage, TryRecvError> {
+        let mut state = self.queue.state.lock().unwrap();
+        match take(&mut state) {
+            Some(message) => Ok(message),
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Wait up to `timeout` for the next message
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<BusMessage, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.queue.state.lock().unwrap();
+        loop {
+            if let Some(message) = take(&mut state) {
+                return Ok(message);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self.queue.ready.wait_timeout(state, remaining).unwrap().0;
+        }
+    }
+
+    /// Messages queued right now
+    pub fn try_iter(&self) -> impl Iterator<Item = BusMessage> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(inner) = self.bus.upgrade() {
+            EventBus { inner }.unsubscribe(self.queue.id);
+        }
+    }
+}
+
+/// Report missed events before the next queued one
+fn take(state: &mut QueueState) -> Option<BusMessage> {
+    if state.lagged > 0 {
+        return Some(BusMessage::Lagged(std::mem::take(&mut state.lagged)));
+    }
+    state.events.pop_front().map(BusMessage::Event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(message: &str) -> MonitorEvent {
+        MonitorEvent::Error(message.to_string())
+    }
+
+    fn message_of(message: BusMessage) -> String {
+        match message {
+            BusMessage::Event(MonitorEvent::Error(message)) => message,
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_topic_names_round_trip() {
+        for topic in Topic::ALL {
+            assert_eq!(topic.name().parse::<Topic>(), Ok(*topic));
+        }
+        assert!("bogus".parse::<Topic>().is_err());
+    }
+
+    #[test]
+    fn test_subscribers_get_their_topics() {
+        let bus = EventBus::new();
+        let all = bus.subscribe(None);
+        let shutdown_only = bus.subscribe(Some(HashSet::from([Topic::Shutdown])));
+
+        bus.publish(error("boom"));
+        bus.publish(MonitorEvent::Shutdown);
+
+        assert_eq!(message_of(all.try_recv().unwrap()), "boom");
+        assert!(matches!(all.try_recv(), Ok(BusMessage::Event(MonitorEvent::Shutdown))));
+        assert!(matches!(shutdown_only.try_recv(), Ok(BusMessage::Event(MonitorEvent::Shutdown))));
+        assert!(matches!(shutdown_only.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest_and_reports_lag() {
+        let bus = EventBus::new();
+        let slow = bus.subscribe_with_capacity(None, 2);
+
+        for i in 0..5 {
+            bus.publish(error(&i.to_string()));
+        }
+
+        assert!(matches!(slow.try_recv(), Ok(BusMessage::Lagged(3))));
+        assert_eq!(message_of(slow.try_recv().unwrap()), "3");
+        assert_eq!(message_of(slow.try_recv().unwrap()), "4");
+        assert!(matches!(slow.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[test]
+    fn test_drop_and_unsubscribe_remove_subscribers() {
+        let bus = EventBus::new();
+        let kept = bus.subscribe(None);
+        let removed = bus.subscribe(None);
+        drop(bus.subscribe(None));
+        assert_eq!(bus.subscriber_count(), 2);
+
+        bus.unsubscribe(removed.id());
+        assert_eq!(bus.subscriber_count(), 1);
+        assert!(matches!(removed.try_recv(), Err(TryRecvError::Disconnected)));
+
+        bus.publish(MonitorEvent::Shutdown);
+        assert!(kept.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_dropping_the_bus_disconnects_after_draining() {
+        let bus = EventBus::new();
+        let subscription = bus.subscribe(None);
+        bus.publish(MonitorEvent::Shutdown);
+        drop(bus);
+
+        assert!(subscription.recv_timeout(Duration::from_secs(1)).is_ok());
+        assert!(matches!(
+            subscription.recv_timeout(Duration::from_secs(1)),
+            Err(RecvTimeoutError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn test_recv_timeout_wakes_on_publish() {
+        let bus = EventBus::new();
+        let subscription = bus.subscribe(None);
+        let publisher = bus.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            publisher.publish(MonitorEvent::Shutdown);
+        });
+
+        assert!(subscription.recv_timeout(Duration::from_secs(5)).is_ok());
+        handle.join().unwrap();
+    }
+}
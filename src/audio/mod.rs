@@ -1,9 +1,28 @@
+pub mod backend;
+pub mod bus;
 pub mod device;
 pub mod monitor;
+pub mod presence;
+pub mod pulse;
+pub mod recording;
 pub mod session;
+pub mod supervisor;
 pub mod traits;
+#[cfg(windows)]
+pub mod wasapi;
+pub mod watchdog;
 
+pub use backend::{AudioBackend, AudioPoll, Endpoint};
+pub use bus::{BusMessage, EventBus, SubscriberId, Subscription, Topic};
 pub use device::{AudioDevice, AudioMode, BluetoothAudioDevice};
-pub use monitor::{AudioMonitor, MonitorCommand, MonitorEvent};
-pub use session::{AudioSession, MicUsingApp, HfpUsingApp, get_apps_using_bluetooth_output};
+pub use monitor::{
+    AudioMonitor, MonitorCommand, MonitorError, MonitorEvent, MonitorLoop, MuteOutcome, PendingReply, Reply, StateChanges,
+    TaskMode,
+};
+pub use recording::{PollRecord, Recording, RecordingBackend, RecordingLimits};
+pub use session::{MicUsingApp, HfpUsingApp};
+pub use supervisor::{Heartbeat, MonitorHealth, MonitorSupervisor, SupervisorAction, SupervisorSettings};
 pub use traits::{AudioSessionManager, AudioSessionEnumerator};
+#[cfg(windows)]
+pub use wasapi::{AudioSession, WasapiBackend, get_apps_using_bluetooth_output};
+pub use watchdog::{HandsFreeWatchdog, WatchdogOutcome, WatchdogRecord, WatchdogSettings};
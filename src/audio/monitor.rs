@@ -1,27 +1,134 @@
 //! Background monitoring thread for audio mode changes
 
-use crate::audio::device::{AudioMode, BluetoothAudioDevice, DeviceManager};
-use crate::audio::session::{CaptureSessionManager, MicUsingApp};
+use crate::audio::backend::{AudioBackend, AudioPoll};
+use crate::audio::bus::EventBus;
+use crate::audio::device::{AudioMode, BluetoothAudioDevice};
+use crate::audio::presence::DevicePresence;
+use crate::audio::session::{HfpUsingApp, MicUsingApp};
+use crate::audio::supervisor::{Heartbeat, MonitorHealth};
+use crate::audio::watchdog::{HandsFreeWatchdog, WatchdogOutcome, WatchdogRecord, WatchdogSettings};
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::bluetooth::battery::match_battery_level;
+use crate::bluetooth::names::paired_name_for_endpoint;
+use crate::bluetooth::inventory::PairedDevice;
 use crate::error::Result;
+use crate::metrics::Metrics;
+use crate::platform::Backends;
 use log::{debug, error, info, warn};
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Why a monitor command did not succeed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorError {
+    /// The monitor thread is not running
+    Stopped,
+    /// No reply arrived in time
+    Timeout,
+    /// The audio backend refused the command
+    Backend(String),
+}
+
+impl fmt::Display for MonitorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonitorError::Stopped => write!(f, "The audio monitor is not running"),
+            MonitorError::Timeout => write!(f, "Timed out waiting for the audio monitor"),
+            MonitorError::Backend(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MonitorError {}
+
+/// What a mute command changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MuteOutcome {
+    /// The app's capture sessions were muted
+    Muted(u32),
+    /// The app's capture sessions were unmuted
+    Unmuted(u32),
+    /// Every capture session on the default input device was muted
+    AllMuted,
+}
+
+/// Where the monitor sends the result of a command
+///
+/// `Reply::none()` is for callers that do not wait; failures are still logged.
+#[derive(Debug, Clone)]
+pub struct Reply<T>(Option<Sender<std::result::Result<T, MonitorError>>>);
+
+impl<T> Reply<T> {
+    /// Create a reply and the handle its result arrives on
+    pub fn channel() -> (Self, PendingReply<T>) {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        (Self(Some(reply_tx)), PendingReply(reply_rx))
+    }
+
+    /// A reply nobody waits for
+    pub fn none() -> Self {
+        Self(None)
+    }
+
+    /// Hand the result to the waiting caller, if any
+    pub fn send(self, result: std::result::Result<T, MonitorError>) {
+        if let Some(reply_tx) = self.0 {
+            let _ = reply_tx.send(result);
+        }
+    }
+}
+
+/// A command result that is still to arrive from the monitor
+///
+/// If the monitor drops the command unanswered (it stopped), the result is
+/// `MonitorError::Stopped`.
+#[derive(Debug)]
+pub struct PendingReply<T>(Receiver<std::result::Result<T, MonitorError>>);
+
+impl<T> PendingReply<T> {
+    /// Block until the result arrives or `timeout` passes
+    pub fn wait(self, timeout: Duration) -> std::result::Result<T, MonitorError> {
+        match self.0.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(MonitorError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(MonitorError::Stopped),
+        }
+    }
+
+    /// The result if it has arrived (non-blocking)
+    pub fn try_take(&self) -> Option<std::result::Result<T, MonitorError>> {
+        match self.0.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(MonitorError::Stopped)),
+        }
+    }
+}
 
 /// Commands sent to the monitor thread
 #[derive(Debug, Clone)]
 pub enum MonitorCommand {
-    /// Request current state
-    GetState,
+    /// Request the state, answered after the next poll
+    GetState(Reply<MonitorState>),
     /// Force refresh of device list
     RefreshDevices,
     /// Mute a specific app
-    MuteApp(u32),
+    MuteApp(u32, Reply<MuteOutcome>),
     /// Unmute a specific app
-    UnmuteApp(u32),
+    UnmuteApp(u32, Reply<MuteOutcome>),
     /// Mute all mic-using apps (force stereo)
-    MuteAll,
+    MuteAll(Reply<MuteOutcome>),
+    /// Enable (Some) or disable (None) the hands-free watchdog
+    ConfigureWatchdog(Option<WatchdogSettings>),
+    /// Set the devices whose force-stereo preference is re-applied on reconnect
+    SetForcedStereoDevices(HashSet<String>),
+    /// Record poll durations into these metrics
+    SetMetrics(Metrics),
     /// Shutdown the monitor
     Shutdown,
 }
@@ -29,29 +136,97 @@ pub enum MonitorCommand {
 /// Events sent from the monitor thread
 #[derive(Debug, Clone)]
 pub enum MonitorEvent {
-    /// Current state update
+    /// The state after a poll that changed it (always sent after the first poll)
     StateUpdate {
         mode: AudioMode,
         mic_using_apps: Vec<MicUsingApp>,
         devices: Vec<BluetoothAudioDevice>,
+        hfp_apps: Vec<HfpUsingApp>,
+        changes: StateChanges,
     },
     /// Mode changed
     ModeChanged {
         old_mode: AudioMode,
         new_mode: AudioMode,
     },
+    /// Paired device inventory was refreshed (includes disconnected devices)
+    PairedDevicesUpdated(Vec<PairedDevice>),
+    /// Hands-free watchdog took an action or finished a reconnect
+    Watchdog(WatchdogRecord),
+    /// HFP was re-enabled by Windows after a reconnect and has been disabled again
+    ForceStereoReapplied(String),
+    /// Re-applying force-stereo after a reconnect failed
+    ForceStereoReapplyFailed { device: String, error: String },
     /// Error occurred
     Error(String),
     /// Monitor is shutting down
     Shutdown,
 }
 
+impl MonitorEvent {
+    /// A `StateUpdate` carrying all of `state`, with every field marked changed
+    ///
+    /// Updates are published only on change, so a subscriber that missed
+    /// some (`BusMessage::Lagged`) resyncs with this instead of keeping stale
+    /// data until the same field happens to change again.
+    pub fn resync(state: &MonitorState) -> Self {
+        MonitorEvent::StateUpdate {
+            mode: state.current_mode,
+            mic_using_apps: state.mic_using_apps.clone(),
+            devices: state.bluetooth_devices.clone(),
+            hfp_apps: state.hfp_apps.clone(),
+            changes: StateChanges::ALL,
+        }
+    }
+}
+
+/// Which parts of the state a `StateUpdate` changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateChanges {
+    pub mode: bool,
+    pub mic_apps: bool,
+    pub devices: bool,
+    pub hfp_apps: bool,
+}
+
+impl StateChanges {
+    /// Everything, for the first state after startup
+    pub const ALL: StateChanges = StateChanges {
+        mode: true,
+        mic_apps: true,
+        devices: true,
+        hfp_apps: true,
+    };
+
+    /// Compare the polled parts of two states, ignoring their timestamps
+    pub fn between(old: &MonitorState, new: &MonitorState) -> Self {
+        Self {
+            mode: old.current_mode != new.current_mode,
+            mic_apps: old.mic_using_apps != new.mic_using_apps,
+            devices: old.bluetooth_devices != new.bluetooth_devices,
+            hfp_apps: old.hfp_apps != new.hfp_apps,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.mode || self.mic_apps || self.devices || self.hfp_apps
+    }
+}
+
 /// Shared state between monitor thread and main thread
+///
+/// `snapshot::StateSnapshot` is its serializable form.
+#[derive(Debug, Clone)]
 pub struct MonitorState {
     pub current_mode: AudioMode,
     pub mic_using_apps: Vec<MicUsingApp>,
     pub bluetooth_devices: Vec<BluetoothAudioDevice>,
+    /// Apps playing to a Bluetooth output (these may have triggered hands-free)
+    pub hfp_apps: Vec<HfpUsingApp>,
+    /// Monotonic time of the last successful poll
     pub last_update: std::time::Instant,
+    /// Wall-clock time of the last successful poll; `None` before the first
+    pub updated_at: Option<SystemTime>,
 }
 
 impl Default for MonitorState {
@@ -60,7 +235,9 @@ impl Default for MonitorState {
             current_mode: AudioMode::Unknown,
             mic_using_apps: Vec::new(),
             bluetooth_devices: Vec::new(),
+            hfp_apps: Vec::new(),
             last_update: std::time::Instant::now(),
+            updated_at: None,
         }
     }
 }
@@ -68,27 +245,36 @@ impl Default for MonitorState {
 /// Audio monitor that runs in a background thread
 pub struct AudioMonitor {
     command_tx: Sender<MonitorCommand>,
-    event_rx: Receiver<MonitorEvent>,
+    events: EventBus,
     state: Arc<Mutex<MonitorState>>,
+    heartbeat: Heartbeat,
+    started_at: Instant,
     thread_handle: Option<JoinHandle<()>>,
 }
 
 impl AudioMonitor {
-    /// Create and start a new audio monitor
-    pub fn start() -> Result<Self> {
+    /// Create and start a new audio monitor on the given backends
+    ///
+    /// Events are published to `events`; subscribe before starting to see
+    /// the first ones.
+    pub fn start(backends: Backends, events: EventBus) -> Result<Self> {
         let (command_tx, command_rx) = mpsc::channel();
-        let (event_tx, event_rx) = mpsc::channel();
         let state = Arc::new(Mutex::new(MonitorState::default()));
         let state_clone = Arc::clone(&state);
+        let thread_events = events.clone();
+        let heartbeat = Heartbeat::new();
+        let thread_heartbeat = heartbeat.clone();
 
         let thread_handle = thread::spawn(move || {
-            monitor_thread(command_rx, event_tx, state_clone);
+            monitor_thread(backends, command_rx, thread_events, state_clone, thread_heartbeat);
         });
 
         Ok(Self {
             command_tx,
-            event_rx,
+            events,
             state,
+            heartbeat,
+            started_at: Instant::now(),
             thread_handle: Some(thread_handle),
         })
     }
@@ -100,29 +286,67 @@ impl AudioMonitor {
             .map_err(|e| crate::error::AppError::AudioSessionError(e.to_string()))
     }
 
-    /// Try to receive an event (non-blocking)
-    pub fn try_recv_event(&self) -> Option<MonitorEvent> {
-        self.event_rx.try_recv().ok()
+    /// Send a command that carries a reply and return the pending result
+    ///
+    /// If the monitor has stopped, the command is dropped and the result is
+    /// `MonitorError::Stopped`.
+    pub fn request<T>(&self, command: impl FnOnce(Reply<T>) -> MonitorCommand) -> PendingReply<T> {
+        let (reply, pending) = Reply::channel();
+        let _ = self.command_tx.send(command(reply));
+        pending
+    }
+
+    /// The bus the monitor publishes its events to
+    pub fn events(&self) -> &EventBus {
+        &self.events
     }
 
     /// Get the current state
+    ///
+    /// Still readable after the monitor thread panicked while holding the lock.
     pub fn get_state(&self) -> MonitorState {
-        self.state.lock().unwrap().clone()
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Whether the thread is alive and has polled within `stall_timeout`
+    pub fn health(&self, now: Instant, stall_timeout: Duration) -> MonitorHealth {
+        let finished = self.thread_handle.as_ref().is_none_or(|handle| handle.is_finished());
+        MonitorHealth::assess(finished, self.started_at, self.heartbeat.last(), now, stall_timeout)
     }
 
-    /// Request a state update
-    pub fn request_state(&self) -> Result<()> {
-        self.send_command(MonitorCommand::GetState)
+    /// Request the state after the next poll
+    pub fn request_state(&self) -> PendingReply<MonitorState> {
+        self.request(MonitorCommand::GetState)
     }
 
     /// Mute a specific app
-    pub fn mute_app(&self, process_id: u32) -> Result<()> {
-        self.send_command(MonitorCommand::MuteApp(process_id))
+    pub fn mute_app(&self, process_id: u32) -> PendingReply<MuteOutcome> {
+        self.request(|reply| MonitorCommand::MuteApp(process_id, reply))
+    }
+
+    /// Unmute a specific app
+    pub fn unmute_app(&self, process_id: u32) -> PendingReply<MuteOutcome> {
+        self.request(|reply| MonitorCommand::UnmuteApp(process_id, reply))
     }
 
     /// Mute all apps (force stereo mode)
-    pub fn force_stereo(&self) -> Result<()> {
-        self.send_command(MonitorCommand::MuteAll)
+    pub fn force_stereo(&self) -> PendingReply<MuteOutcome> {
+        self.request(MonitorCommand::MuteAll)
+    }
+
+    /// Set the devices that should stay in stereo mode across reconnects
+    pub fn set_forced_stereo_devices(&self, devices: HashSet<String>) -> Result<()> {
+        self.send_command(MonitorCommand::SetForcedStereoDevices(devices))
+    }
+
+    /// Record poll durations into the shared metrics
+    pub fn set_metrics(&self, metrics: Metrics) -> Result<()> {
+        self.send_command(MonitorCommand::SetMetrics(metrics))
+    }
+
+    /// Enable or disable the hands-free watchdog
+    pub fn configure_watchdog(&self, settings: Option<WatchdogSettings>) -> Result<()> {
+        self.send_command(MonitorCommand::ConfigureWatchdog(settings))
     }
 
     /// Shutdown the monitor
@@ -132,16 +356,14 @@ impl AudioMonitor {
             let _ = handle.join();
         }
     }
-}
 
-impl Clone for MonitorState {
-    fn clone(&self) -> Self {
-        Self {
-            current_mode: self.current_mode,
-            mic_using_apps: self.mic_using_apps.clone(),
-            bluetooth_devices: self.bluetooth_devices.clone(),
-            last_update: self.last_update,
-        }
+    /// Ask the monitor to stop without waiting for it
+    ///
+    /// Used to replace a stalled monitor: its thread exits once the blocked
+    /// call returns and it reads the shutdown command.
+    pub fn abandon(mut self) {
+        let _ = self.send_command(MonitorCommand::Shutdown);
+        self.thread_handle.take();
     }
 }
 
@@ -151,107 +373,369 @@ impl Drop for AudioMonitor {
     }
 }
 
-/// The main monitor thread function
-fn monitor_thread(
-    command_rx: Receiver<MonitorCommand>,
-    event_tx: Sender<MonitorEvent>,
-    state: Arc<Mutex<MonitorState>>,
-) {
-    info!("Audio monitor thread started");
+/// How often battery levels are re-read from the device properties
+const BATTERY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
-    // Initialize COM for this thread
-    unsafe {
-        use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
-        let hr = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
-        if hr.is_err() {
-            error!("Failed to initialize COM in monitor thread: {:?}", hr);
-            let _ = event_tx.send(MonitorEvent::Error(format!("COM init failed: {:?}", hr)));
-            return;
+/// How often the paired device inventory is refreshed without a device change
+const INVENTORY_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between two polls of the audio backend
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Where the monitor runs slow Bluetooth calls (inventory, reconnects, re-applying force stereo)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskMode {
+    /// On their own threads, so the poll loop never blocks
+    Background,
+    /// Synchronously within the poll, for deterministic simulations
+    Inline,
+}
+
+impl TaskMode {
+    fn spawn(self, task: impl FnOnce() + Send + 'static) {
+        match self {
+            TaskMode::Background => {
+                thread::spawn(task);
+            }
+            TaskMode::Inline => task(),
         }
     }
+}
+
+/// The monitor's poll loop, driven one step at a time
+///
+/// `AudioMonitor` runs it on a thread in real time; simulations call
+/// `handle_command` and `poll` directly with a virtual clock.
+pub struct MonitorLoop {
+    backends: Backends,
+    events: EventBus,
+    state: Arc<Mutex<MonitorState>>,
+    tasks: TaskMode,
+    last_mode: AudioMode,
+    /// Hands-free watchdog (opt-in) and channel for results of its reconnects
+    watchdog: Option<HandsFreeWatchdog>,
+    watchdog_tx: Sender<(String, std::result::Result<(), String>)>,
+    watchdog_rx: Receiver<(String, std::result::Result<(), String>)>,
+    /// Forced-stereo devices and arrival tracking to re-apply the preference
+    forced_stereo: HashSet<String>,
+    presence: DevicePresence,
+    reapplying: Arc<Mutex<HashSet<String>>>,
+    /// Paired device inventory is refreshed on connect/disconnect and periodically
+    inventory_running: Arc<AtomicBool>,
+    last_inventory: Option<Instant>,
+    /// Battery levels change slowly; read them once a minute or on device change
+    battery_levels: HashMap<String, u8>,
+    last_battery: Option<Instant>,
+    metrics: Option<Metrics>,
+    /// `GetState` requests waiting for the next poll
+    state_replies: Vec<Reply<MonitorState>>,
+}
 
-    let poll_interval = Duration::from_millis(500);
-    let mut last_mode = AudioMode::Unknown;
+impl MonitorLoop {
+    pub fn new(
+        backends: Backends,
+        events: EventBus,
+        state: Arc<Mutex<MonitorState>>,
+        tasks: TaskMode,
+    ) -> Self {
+        let (watchdog_tx, watchdog_rx) = mpsc::channel();
+        Self {
+            backends,
+            events,
+            state,
+            tasks,
+            last_mode: AudioMode::Unknown,
+            watchdog: None,
+            watchdog_tx,
+            watchdog_rx,
+            forced_stereo: HashSet::new(),
+            presence: DevicePresence::new(),
+            reapplying: Arc::new(Mutex::new(HashSet::new())),
+            inventory_running: Arc::new(AtomicBool::new(false)),
+            last_inventory: None,
+            battery_levels: HashMap::new(),
+            last_battery: None,
+            metrics: None,
+            state_replies: Vec::new(),
+        }
+    }
 
-    loop {
-        // Check for commands (non-blocking)
-        match command_rx.try_recv() {
-            Ok(MonitorCommand::Shutdown) => {
+    /// Apply a command; returns `false` once the loop should stop
+    pub fn handle_command(&mut self, command: MonitorCommand) -> bool {
+        let audio = self.backends.audio.as_ref();
+        match command {
+            MonitorCommand::Shutdown => {
                 info!("Monitor thread received shutdown command");
-                let _ = event_tx.send(MonitorEvent::Shutdown);
-                break;
+                self.events.publish(MonitorEvent::Shutdown);
+                return false;
+            }
+            MonitorCommand::MuteApp(pid, reply) => {
+                reply.send(handle_mute_app(audio, pid));
             }
-            Ok(MonitorCommand::MuteApp(pid)) => {
-                handle_mute_app(pid, &event_tx);
+            MonitorCommand::MuteAll(reply) => {
+                reply.send(handle_mute_all(audio));
             }
-            Ok(MonitorCommand::MuteAll) => {
-                handle_mute_all(&event_tx);
+            MonitorCommand::UnmuteApp(pid, reply) => {
+                reply.send(handle_unmute_app(audio, pid));
             }
-            Ok(MonitorCommand::UnmuteApp(pid)) => {
-                handle_unmute_app(pid, &event_tx);
+            MonitorCommand::ConfigureWatchdog(settings) => {
+                match (settings, self.watchdog.as_mut()) {
+                    (Some(settings), Some(existing)) => existing.set_settings(settings),
+                    (Some(settings), None) => {
+                        info!("Hands-free watchdog enabled: {:?}", settings);
+                        self.watchdog = Some(HandsFreeWatchdog::new(settings));
+                    }
+                    (None, _) => {
+                        if self.watchdog.take().is_some() {
+                            info!("Hands-free watchdog disabled");
+                        }
+                    }
+                }
             }
-            Ok(MonitorCommand::GetState) | Ok(MonitorCommand::RefreshDevices) => {
-                // Will be handled in the regular poll below
+            MonitorCommand::SetForcedStereoDevices(devices) => {
+                debug!("Forced stereo devices: {:?}", devices);
+                self.forced_stereo = devices;
             }
-            Err(mpsc::TryRecvError::Empty) => {
-                // No command, continue polling
+            MonitorCommand::SetMetrics(shared) => {
+                self.metrics = Some(shared);
             }
-            Err(mpsc::TryRecvError::Disconnected) => {
-                info!("Command channel disconnected, shutting down monitor");
-                break;
+            MonitorCommand::RefreshDevices => {
+                // Audio state is refreshed by the regular poll
+                self.last_inventory = None;
+            }
+            MonitorCommand::GetState(reply) => {
+                // Answered by the regular poll
+                self.state_replies.push(reply);
             }
         }
+        true
+    }
 
-        // Poll current state
-        match poll_audio_state() {
-            Ok((mode, mic_apps, devices)) => {
-                // Update shared state
-                {
-                    let mut state_guard = state.lock().unwrap();
-                    state_guard.current_mode = mode;
-                    state_guard.mic_using_apps = mic_apps.clone();
-                    state_guard.bluetooth_devices = devices.clone();
-                    state_guard.last_update = std::time::Instant::now();
+    /// Poll the backend once and emit events; `now` is the current time
+    ///
+    /// Returns whether the backend poll succeeded.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let poll_started = Instant::now();
+        let polled = poll_audio_state(self.backends.audio.as_ref());
+        if let Some(ref metrics) = self.metrics {
+            metrics.observe_poll_duration(poll_started.elapsed());
+        }
+        let (mode, mic_apps, mut devices) = match polled {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Error polling audio state: {}", e);
+                for reply in self.state_replies.drain(..) {
+                    reply.send(Err(MonitorError::Backend(e.to_string())));
                 }
+                self.events.publish(MonitorEvent::Error(e.to_string()));
+                return false;
+            }
+        };
+
+        // Attach battery levels (re-read periodically, cached in between)
+        let battery_due = self
+            .last_battery
+            .is_none_or(|t| now.duration_since(t) >= BATTERY_REFRESH_INTERVAL);
+        if battery_due && !devices.is_empty() {
+            match self.backends.bluetooth.battery_levels() {
+                Ok(levels) => self.battery_levels = levels,
+                Err(e) => debug!("Failed to read battery levels: {}", e),
+            }
+            self.last_battery = Some(now);
+        }
+        for device in &mut devices {
+            device.battery_level = match_battery_level(&device.device.name, &self.battery_levels);
+        }
 
-                // Check for mode change
-                if mode != last_mode && last_mode != AudioMode::Unknown {
-                    info!("Audio mode changed: {:?} -> {:?}", last_mode, mode);
-                    let _ = event_tx.send(MonitorEvent::ModeChanged {
-                        old_mode: last_mode,
-                        new_mode: mode,
-                    });
-                }
-                last_mode = mode;
+        // Enumerating output sessions is only worth it with a Bluetooth output
+        let hfp_apps = if devices.is_empty() {
+            Vec::new()
+        } else {
+            self.backends.audio.bluetooth_output_apps()
+        };
+
+        // Update shared state, noting what changed since the last poll
+        let changes = {
+            let mut state_guard = self.state.lock().unwrap();
+            let polled = MonitorState {
+                current_mode: mode,
+                mic_using_apps: mic_apps.clone(),
+                bluetooth_devices: devices.clone(),
+                hfp_apps: hfp_apps.clone(),
+                last_update: now,
+                updated_at: Some(SystemTime::now()),
+            };
+            let changes = match state_guard.updated_at {
+                Some(_) => StateChanges::between(&state_guard, &polled),
+                None => StateChanges::ALL,
+            };
+            *state_guard = polled;
+            for reply in self.state_replies.drain(..) {
+                reply.send(Ok(state_guard.clone()));
+            }
+            changes
+        };
+
+        // Check for mode change
+        if mode != self.last_mode && self.last_mode != AudioMode::Unknown {
+            info!("Audio mode changed: {:?} -> {:?}", self.last_mode, mode);
+            self.events.publish(MonitorEvent::ModeChanged {
+                old_mode: self.last_mode,
+                new_mode: mode,
+            });
+        }
+        self.last_mode = mode;
 
-                // Send state update
-                let _ = event_tx.send(MonitorEvent::StateUpdate {
-                    mode,
-                    mic_using_apps: mic_apps,
-                    devices,
-                });
+        let change = self.presence.update(&devices);
+        if !change.is_empty() {
+            self.last_inventory = None;
+            self.last_battery = None;
+        }
+
+        for device in change.arrived {
+            // Arrivals are endpoint names, the forced set holds paired device names
+            if let Some(key) = paired_name_for_endpoint(&device, self.forced_stereo.iter().map(String::as_str)) {
+                reapply_force_stereo(&self.backends.bluetooth, key.to_string(), &self.reapplying, &self.events, self.tasks);
             }
-            Err(e) => {
-                warn!("Error polling audio state: {}", e);
-                let _ = event_tx.send(MonitorEvent::Error(e.to_string()));
+        }
+
+        let inventory_due = self
+            .last_inventory
+            .is_none_or(|t| now.duration_since(t) >= INVENTORY_REFRESH_INTERVAL);
+        if inventory_due
+            && refresh_paired_devices(&self.backends.bluetooth, &self.inventory_running, &self.events, self.tasks)
+        {
+            self.last_inventory = Some(now);
+        }
+
+        self.run_watchdog(now, &mic_apps, &devices);
+
+        // Subscribers only hear about polls that changed something
+        if changes.any() {
+            self.events.publish(MonitorEvent::StateUpdate {
+                mode,
+                mic_using_apps: mic_apps,
+                devices,
+                hfp_apps,
+                changes,
+            });
+        }
+
+        true
+    }
+
+    /// Feed the latest poll into the watchdog and start any reconnects it requests
+    ///
+    /// Reconnects run as background tasks (they take over a second) and report
+    /// back through `watchdog_tx`; results are collected on the next poll.
+    fn run_watchdog(&mut self, now: Instant, mic_apps: &[MicUsingApp], devices: &[BluetoothAudioDevice]) {
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return;
+        };
+
+        // Collect results of reconnects started earlier
+        while let Ok((device, result)) = self.watchdog_rx.try_recv() {
+            let record = watchdog.record_result(now, &device, result);
+            self.events.publish(MonitorEvent::Watchdog(record));
+        }
+
+        let bt_capture_active = mic_apps.iter().any(|app| app.is_using_bluetooth_mic);
+
+        for record in watchdog.evaluate(now, devices, bt_capture_active) {
+            if record.outcome == WatchdogOutcome::Triggered {
+                let device = record.device.clone();
+                let result_tx = self.watchdog_tx.clone();
+                let bluetooth = Arc::clone(&self.backends.bluetooth);
+                self.tasks.spawn(move || {
+                    let result = bluetooth.reconnect(&device).map_err(|e| e.to_string());
+                    let _ = result_tx.send((device, result));
+                });
             }
+
+            self.events.publish(MonitorEvent::Watchdog(record));
         }
+    }
+}
+
+/// The main monitor thread function
+fn monitor_thread(
+    backends: Backends,
+    command_rx: Receiver<MonitorCommand>,
+    events: EventBus,
+    state: Arc<Mutex<MonitorState>>,
+    heartbeat: Heartbeat,
+) {
+    info!("Audio monitor thread started");
 
-        thread::sleep(poll_interval);
+    let audio = Arc::clone(&backends.audio);
+    if let Err(e) = audio.attach_thread() {
+        error!("Failed to initialize audio in monitor thread: {}", e);
+        events.publish(MonitorEvent::Error(e.to_string()));
+        return;
     }
 
-    // Cleanup COM
-    unsafe {
-        windows::Win32::System::Com::CoUninitialize();
+    let mut monitor = MonitorLoop::new(backends, events, state, TaskMode::Background);
+
+    'running: loop {
+        // A monitor whose polls keep failing is as good as stalled
+        if monitor.poll(Instant::now()) {
+            heartbeat.beat(Instant::now());
+        }
+
+        // Handle commands as they arrive until the next poll is due, so
+        // callers waiting for a reply are answered right away
+        let next_poll = Instant::now() + POLL_INTERVAL;
+        loop {
+            match command_rx.recv_timeout(next_poll.saturating_duration_since(Instant::now())) {
+                Ok(command) => {
+                    if !monitor.handle_command(command) {
+                        break 'running;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    info!("Command channel disconnected, shutting down monitor");
+                    break 'running;
+                }
+            }
+        }
     }
 
+    audio.detach_thread();
+
     info!("Audio monitor thread stopped");
 }
 
 /// Poll the current audio state
-fn poll_audio_state() -> Result<(AudioMode, Vec<MicUsingApp>, Vec<BluetoothAudioDevice>)> {
-    let device_manager = DeviceManager::new()?;
-    let devices = device_manager.get_bluetooth_devices()?;
+///
+/// Also used by the command line for one-shot `status` queries.
+pub fn poll_audio_state(
+    audio: &dyn AudioBackend,
+) -> Result<(AudioMode, Vec<MicUsingApp>, Vec<BluetoothAudioDevice>)> {
+    Ok(detect_state(audio.poll()?))
+}
+
+/// Derive the mode, mic apps and Bluetooth devices from a raw poll
+pub fn detect_state(poll: AudioPoll) -> (AudioMode, Vec<MicUsingApp>, Vec<BluetoothAudioDevice>) {
+    let AudioPoll { endpoints, mic_apps } = poll;
+
+    // The first Bluetooth endpoint with a readable peak meter decides the mode
+    let meter_channels = endpoints
+        .iter()
+        .filter(|endpoint| endpoint.device.is_bluetooth)
+        .find_map(|endpoint| endpoint.meter_channels);
+
+    let devices: Vec<BluetoothAudioDevice> = endpoints
+        .into_iter()
+        .filter(|endpoint| endpoint.device.is_bluetooth)
+        .map(|endpoint| {
+            let mut device = BluetoothAudioDevice::new(endpoint.device);
+            device.sample_rate = endpoint.sample_rate;
+            device.channels = endpoint.channels;
+            device.detect_mode_from_format();
+            device
+        })
+        .collect();
 
     // Log detected Bluetooth devices at debug level
     for device in &devices {
@@ -263,9 +747,6 @@ fn poll_audio_state() -> Result<(AudioMode, Vec<MicUsingApp>, Vec<BluetoothAudio
         );
     }
 
-    // Get mic-using apps from all capture devices (for display in menu)
-    let mic_apps = get_all_mic_using_apps();
-
     // Log mic apps at debug level
     for app in &mic_apps {
         debug!("Mic app: {} (PID: {}) - BT mic: {}",
@@ -279,15 +760,15 @@ fn poll_audio_state() -> Result<(AudioMode, Vec<MicUsingApp>, Vec<BluetoothAudio
     let mode = if devices.is_empty() {
         AudioMode::Unknown
     } else {
-        match device_manager.is_bluetooth_device_in_hfp_mode() {
-            Ok(Some(true)) => {
+        match meter_channels {
+            Some(1) => {
                 debug!("Mode: HandsFree (mono audio detected via peak meter)");
                 AudioMode::HandsFree
             }
-            Ok(Some(false)) => {
+            Some(_) => {
                 AudioMode::Stereo
             }
-            Ok(None) | Err(_) => {
+            None => {
                 // Fallback: check if BT mic is in use
                 let bt_mic_in_use = mic_apps.iter().any(|app| app.is_using_bluetooth_mic);
                 if bt_mic_in_use {
@@ -306,51 +787,124 @@ fn poll_audio_state() -> Result<(AudioMode, Vec<MicUsingApp>, Vec<BluetoothAudio
         device.current_mode = mode;
     }
 
-    Ok((mode, mic_apps, devices))
+    (mode, mic_apps, devices)
+}
+
+/// Refresh the paired device inventory as a background task
+///
+/// Enumerating services for every paired device can take a while, so this
+/// never blocks the poll loop. Returns `false` if a refresh is already running.
+fn refresh_paired_devices(
+    bluetooth: &Arc<dyn BluetoothBackend>,
+    running: &Arc<AtomicBool>,
+    events: &EventBus,
+    tasks: TaskMode,
+) -> bool {
+    if running.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+
+    let bluetooth = Arc::clone(bluetooth);
+    let running = Arc::clone(running);
+    let events = events.clone();
+
+    tasks.spawn(move || {
+        match bluetooth.list_paired_devices() {
+            Ok(devices) => {
+                events.publish(MonitorEvent::PairedDevicesUpdated(devices));
+            }
+            Err(e) => {
+                warn!("Failed to list paired devices: {}", e);
+            }
+        }
+        running.store(false, Ordering::SeqCst);
+    });
+
+    true
 }
 
-/// Get mic-using apps from all capture devices
-fn get_all_mic_using_apps() -> Vec<MicUsingApp> {
-    // Check ALL capture devices, not just the default
-    CaptureSessionManager::get_all_mic_using_apps()
+/// Re-apply force-stereo for a device that just (re)connected
+///
+/// Windows may re-enable HFP after re-pairing or a power cycle. The check and
+/// the fix both go through the Bluetooth APIs, so they run as a background task;
+/// `reapplying` prevents overlapping attempts when the endpoint flaps.
+fn reapply_force_stereo(
+    bluetooth: &Arc<dyn BluetoothBackend>,
+    device: String,
+    reapplying: &Arc<Mutex<HashSet<String>>>,
+    events: &EventBus,
+    tasks: TaskMode,
+) {
+    if !reapplying.lock().unwrap().insert(device.clone()) {
+        debug!("Force stereo re-apply already running for {}", device);
+        return;
+    }
+
+    info!("Forced-stereo device arrived: {}", device);
+    let bluetooth = Arc::clone(bluetooth);
+    let reapplying = Arc::clone(reapplying);
+    let events = events.clone();
+
+    tasks.spawn(move || {
+        match bluetooth.reapply_disabled_hfp(&device) {
+            Ok(true) => {
+                info!("Re-applied force stereo for {}", device);
+                events.publish(MonitorEvent::ForceStereoReapplied(device.clone()));
+            }
+            Ok(false) => {
+                debug!("HFP still disabled for {}, nothing to re-apply", device);
+            }
+            Err(e) => {
+                warn!("Failed to re-apply force stereo for {}: {}", device, e);
+                events.publish(MonitorEvent::ForceStereoReapplyFailed {
+                    device: device.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+
+        reapplying.lock().unwrap().remove(&device);
+    });
 }
 
 /// Handle mute app command - searches ALL capture devices
-fn handle_mute_app(pid: u32, event_tx: &Sender<MonitorEvent>) {
-    if let Err(e) = CaptureSessionManager::mute_app_on_all_devices(pid) {
-        let _ = event_tx.send(MonitorEvent::Error(format!("Failed to mute app: {}", e)));
-    } else {
-        info!("Muted app with PID {}", pid);
+fn handle_mute_app(audio: &dyn AudioBackend, pid: u32) -> std::result::Result<MuteOutcome, MonitorError> {
+    match audio.set_app_muted(pid, true) {
+        Ok(()) => {
+            info!("Muted app with PID {}", pid);
+            Ok(MuteOutcome::Muted(pid))
+        }
+        Err(e) => {
+            warn!("Failed to mute app with PID {}: {}", pid, e);
+            Err(MonitorError::Backend(e.to_string()))
+        }
     }
 }
 
 /// Handle unmute app command - searches ALL capture devices
-fn handle_unmute_app(pid: u32, event_tx: &Sender<MonitorEvent>) {
-    if let Err(e) = CaptureSessionManager::unmute_app_on_all_devices(pid) {
-        let _ = event_tx.send(MonitorEvent::Error(format!("Failed to unmute app: {}", e)));
-    } else {
-        info!("Unmuted app with PID {}", pid);
+fn handle_unmute_app(audio: &dyn AudioBackend, pid: u32) -> std::result::Result<MuteOutcome, MonitorError> {
+    match audio.set_app_muted(pid, false) {
+        Ok(()) => {
+            info!("Unmuted app with PID {}", pid);
+            Ok(MuteOutcome::Unmuted(pid))
+        }
+        Err(e) => {
+            warn!("Failed to unmute app with PID {}: {}", pid, e);
+            Err(MonitorError::Backend(e.to_string()))
+        }
     }
 }
 
 /// Handle mute all command (force stereo)
-fn handle_mute_all(event_tx: &Sender<MonitorEvent>) {
-    match CaptureSessionManager::new_default() {
-        Ok(session_manager) => {
-            if let Err(e) = session_manager.mute_all() {
-                let _ = event_tx.send(MonitorEvent::Error(format!(
-                    "Failed to mute all apps: {}",
-                    e
-                )));
-            } else {
-                info!("Muted all mic-using apps to force stereo mode");
-            }
+fn handle_mute_all(audio: &dyn AudioBackend) -> std::result::Result<MuteOutcome, MonitorError> {
+    match audio.mute_all() {
+        Ok(()) => {
+            info!("Muted all mic-using apps to force stereo mode");
+            Ok(MuteOutcome::AllMuted)
         }
         Err(e) => {
-            let _ = event_tx.send(MonitorEvent::Error(format!(
-                "Failed to access capture device: {}",
-                e
-            )));
+            warn!("Failed to mute all apps: {}", e);
+            Err(MonitorError::Backend(e.to_string()))
         }
     }
 }
@@ -0,0 +1,106 @@
+//! Tracking of Bluetooth audio device arrivals
+//!
+//! Windows only exposes endpoints for connected headsets, so a device that
+//! disappears from the poll results and comes back has reconnected (or was
+//! re-paired). The monitor uses this to re-apply per-device preferences.
+
+use crate::audio::device::BluetoothAudioDevice;
+use std::collections::HashSet;
+
+/// Devices that appeared or disappeared since the previous poll
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PresenceChange {
+    pub arrived: Vec<String>,
+    pub departed: Vec<String>,
+}
+
+impl PresenceChange {
+    pub fn is_empty(&self) -> bool {
+        self.arrived.is_empty() && self.departed.is_empty()
+    }
+}
+
+/// Remembers which devices were present in the previous poll
+#[derive(Debug, Default)]
+pub struct DevicePresence {
+    present: HashSet<String>,
+    initialized: bool,
+}
+
+impl DevicePresence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update with the latest poll and return the devices that arrived or left
+    ///
+    /// The first update only records a baseline: devices that were already
+    /// connected when monitoring started are not reported as arrivals.
+    pub fn update(&mut self, devices: &[BluetoothAudioDevice]) -> PresenceChange {
+        let current: HashSet<String> = devices.iter().map(|d| d.device.name.clone()).collect();
+
+        let mut change = PresenceChange::default();
+        if self.initialized {
+            change.arrived = current.difference(&self.present).cloned().collect();
+            change.departed = self.present.difference(&current).cloned().collect();
+            change.arrived.sort();
+            change.departed.sort();
+        }
+
+        self.present = current;
+        self.initialized = true;
+        change
+    }
+
+    /// Whether a device was present in the last poll
+    pub fn is_present(&self, name: &str) -> bool {
+        self.present.contains(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::device::AudioDevice;
+
+    fn device(name: &str) -> BluetoothAudioDevice {
+        BluetoothAudioDevice::new(AudioDevice {
+            id: format!("id-{}", name),
+            name: name.to_string(),
+            is_bluetooth: true,
+        })
+    }
+
+    #[test]
+    fn test_first_poll_is_baseline() {
+        let mut presence = DevicePresence::new();
+        assert!(presence.update(&[device("Headset")]).is_empty());
+        assert!(presence.is_present("Headset"));
+    }
+
+    #[test]
+    fn test_reconnect_is_reported_once() {
+        let mut presence = DevicePresence::new();
+        presence.update(&[device("Headset")]);
+
+        // Disconnected
+        let change = presence.update(&[]);
+        assert!(change.arrived.is_empty());
+        assert_eq!(change.departed, vec!["Headset".to_string()]);
+        assert!(!presence.is_present("Headset"));
+
+        // Reconnected
+        assert_eq!(presence.update(&[device("Headset")]).arrived, vec!["Headset".to_string()]);
+        assert!(presence.update(&[device("Headset")]).is_empty());
+    }
+
+    #[test]
+    fn test_new_device_arrival() {
+        let mut presence = DevicePresence::new();
+        presence.update(&[]);
+        assert_eq!(
+            presence.update(&[device("B"), device("A")]).arrived,
+            vec!["A".to_string(), "B".to_string()]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Typed view of `pactl --format=json list ...` output
+//!
+//! Only the fields the backend needs are kept. PipeWire (`pipewire-pulse`)
+//! and PulseAudio produce the same layout but differ in property names and
+//! profile spelling (`a2dp-sink` vs `a2dp_sink`), which the helpers here
+//! paper over.
+
+use crate::bluetooth::inventory::{decode_class_of_device, BluetoothAddress, DeviceType};
+use crate::error::{AppError, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// String properties attached to every pactl object
+pub type Properties = BTreeMap<String, String>;
+
+/// A card (one per connected Bluetooth device)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Card {
+    pub index: u32,
+    pub name: String,
+    #[serde(default)]
+    pub properties: Properties,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, CardProfile>,
+    #[serde(default)]
+    pub active_profile: Option<String>,
+}
+
+/// A profile a card can be switched to
+#[derive(Debug, Clone, Deserialize)]
+pub struct CardProfile {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub priority: u32,
+    #[serde(default = "default_available")]
+    pub available: bool,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+/// A playback device
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sink {
+    pub index: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub sample_specification: String,
+    #[serde(default)]
+    pub properties: Properties,
+}
+
+/// A capture device (microphones and sink monitors)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Source {
+    pub index: u32,
+    pub name: String,
+    #[serde(default)]
+    pub properties: Properties,
+}
+
+/// An application stream recording from a source or playing to a sink
+#[derive(Debug, Clone, Deserialize)]
+pub struct Stream {
+    pub index: u32,
+    /// Source index for source-outputs
+    #[serde(default)]
+    pub source: Option<u32>,
+    /// Sink index for sink-inputs
+    #[serde(default)]
+    pub sink: Option<u32>,
+    #[serde(default)]
+    pub corked: bool,
+    #[serde(default)]
+    pub mute: bool,
+    #[serde(default)]
+    pub properties: Properties,
+}
+
+/// Which kind of profile a Bluetooth card is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileKind {
+    /// A2DP playback (stereo)
+    A2dp,
+    /// HSP/HFP head unit (mono with microphone)
+    HeadsetHeadUnit,
+    /// Card switched off or a profile this backend does not use
+    Other,
+}
+
+impl ProfileKind {
+    /// Classify a card profile name from PipeWire or PulseAudio
+    ///
+    /// Covers `a2dp-sink`, `a2dp-sink-aac`, `a2dp_sink`, `headset-head-unit`,
+    /// `headset-head-unit-msbc`, `headset_head_unit` and `handsfree_head_unit`.
+    pub fn of(profile: &str) -> Self {
+        let profile = profile.replace('_', "-");
+        if profile.starts_with("a2dp-sink") {
+            ProfileKind::A2dp
+        } else if profile.starts_with("headset-head-unit") || profile.starts_with("handsfree-head-unit") {
+            ProfileKind::HeadsetHeadUnit
+        } else {
+            ProfileKind::Other
+        }
+    }
+}
+
+/// Parse the JSON array printed by `pactl --format=json list <kind>`
+pub fn parse_list<T: DeserializeOwned>(json: &str) -> Result<Vec<T>> {
+    serde_json::from_str(json)
+        .map_err(|e| AppError::AudioSessionError(format!("Unexpected pactl output: {}", e)))
+}
+
+/// Sample rate and channel count from a spec such as `s16le 1ch 16000Hz`
+pub fn parse_sample_spec(spec: &str) -> (Option<u32>, Option<u16>) {
+    let mut rate = None;
+    let mut channels = None;
+    for part in spec.split_whitespace() {
+        if let Some(value) = part.strip_suffix("Hz") {
+            rate = value.parse().ok();
+        } else if let Some(value) = part.strip_suffix("ch") {
+            channels = value.parse().ok();
+        }
+    }
+    (rate, channels)
+}
+
+/// Whether an object belongs to a Bluetooth device
+pub fn is_bluetooth(name: &str, properties: &Properties) -> bool {
+    properties.get("device.bus").map(String::as_str) == Some("bluetooth")
+        || properties.get("device.api").map(String::as_str) == Some("bluez5")
+        || name.starts_with("bluez_")
+}
+
+/// Bluetooth address of a card, sink or source
+///
+/// PipeWire sets `api.bluez5.address`, PulseAudio `device.string`; both also
+/// encode it in the object name (`bluez_card.AC_80_0A_2B_3C_4D`).
+pub fn bluetooth_address(name: &str, properties: &Properties) -> Option<BluetoothAddress> {
+    ["api.bluez5.address", "device.string"]
+        .iter()
+        .filter_map(|key| properties.get(*key))
+        .find_map(|value| value.parse().ok())
+        .or_else(|| {
+            let (_, rest) = name.split_once('.')?;
+            rest.get(..17)?.parse().ok()
+        })
+}
+
+impl Card {
+    /// Friendly name, as shown in the sink description
+    pub fn display_name(&self) -> &str {
+        ["device.description", "device.alias", "bluez.alias"]
+            .iter()
+            .find_map(|key| self.properties.get(*key))
+            .map(String::as_str)
+            .unwrap_or(&self.name)
+    }
+
+    pub fn is_bluetooth(&self) -> bool {
+        is_bluetooth(&self.name, &self.properties)
+    }
+
+    pub fn address(&self) -> Option<BluetoothAddress> {
+        bluetooth_address(&self.name, &self.properties)
+    }
+
+    /// Kind of the active profile
+    pub fn active_kind(&self) -> ProfileKind {
+        self.active_profile
+            .as_deref()
+            .map(ProfileKind::of)
+            .unwrap_or(ProfileKind::Other)
+    }
+
+    /// The available profile of a kind with the highest priority
+    pub fn best_profile(&self, kind: ProfileKind) -> Option<&str> {
+        self.profiles
+            .iter()
+            .filter(|(name, profile)| profile.available && ProfileKind::of(name) == kind)
+            .max_by_key(|(_, profile)| profile.priority)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Device type from the class of device, falling back to the form factor
+    pub fn device_type(&self) -> DeviceType {
+        let class = ["api.bluez5.class", "bluetooth.class"]
+            .iter()
+            .filter_map(|key| self.properties.get(*key))
+            .find_map(|value| u32::from_str_radix(value.trim_start_matches("0x"), 16).ok());
+        if let Some(class) = class {
+            return decode_class_of_device(class);
+        }
+
+        match self.properties.get("device.form_factor").map(String::as_str) {
+            Some("headset") => DeviceType::Headset,
+            Some("hands-free") => DeviceType::HandsFree,
+            Some("headphone") => DeviceType::Headphones,
+            Some("speaker") => DeviceType::Loudspeaker,
+            Some("portable") => DeviceType::PortableAudio,
+            Some("car") => DeviceType::CarAudio,
+            Some("hifi") => DeviceType::HifiAudio,
+            Some("microphone") => DeviceType::Microphone,
+            _ => DeviceType::OtherAudioVideo,
+        }
+    }
+}
+
+impl Stream {
+    /// Process id of the application owning the stream
+    pub fn process_id(&self) -> Option<u32> {
+        self.properties.get("application.process.id")?.parse().ok()
+    }
+
+    /// Executable name, falling back to the application name
+    pub fn process_name(&self) -> String {
+        self.properties
+            .get("application.process.binary")
+            .or_else(|| self.properties.get("application.name"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Application name for display, falling back to the executable
+    pub fn display_name(&self) -> String {
+        self.properties
+            .get("application.name")
+            .or_else(|| self.properties.get("application.process.binary"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Peak meters (pavucontrol, desktop volume applets) are not real captures
+    pub fn is_peak_detect(&self) -> bool {
+        self.properties
+            .get("stream.monitor")
+            .is_some_and(|value| value == "true")
+            || self
+                .properties
+                .get("media.name")
+                .is_some_and(|value| value == "Peak detect")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_kind() {
+        assert_eq!(ProfileKind::of("a2dp-sink"), ProfileKind::A2dp);
+        assert_eq!(ProfileKind::of("a2dp-sink-aac"), ProfileKind::A2dp);
+        assert_eq!(ProfileKind::of("a2dp_sink"), ProfileKind::A2dp);
+        assert_eq!(ProfileKind::of("headset-head-unit-msbc"), ProfileKind::HeadsetHeadUnit);
+        assert_eq!(ProfileKind::of("handsfree_head_unit"), ProfileKind::HeadsetHeadUnit);
+        assert_eq!(ProfileKind::of("off"), ProfileKind::Other);
+        assert_eq!(ProfileKind::of("a2dp-source"), ProfileKind::Other);
+    }
+
+    #[test]
+    fn test_parse_sample_spec() {
+        assert_eq!(parse_sample_spec("s16le 1ch 16000Hz"), (Some(16000), Some(1)));
+        assert_eq!(parse_sample_spec("float32le 2ch 48000Hz"), (Some(48000), Some(2)));
+        assert_eq!(parse_sample_spec(""), (None, None));
+    }
+
+    #[test]
+    fn test_address_from_name() {
+        let props = Properties::new();
+        assert_eq!(
+            bluetooth_address("bluez_output.AC_80_0A_2B_3C_4D.1", &props),
+            Some(BluetoothAddress(0xAC80_0A2B_3C4D))
+        );
+        assert_eq!(bluetooth_address("alsa_output.pci-0000_00_1f.3", &props), None);
+    }
+}
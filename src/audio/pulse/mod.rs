@@ -0,0 +1,342 @@
+//! PipeWire/PulseAudio backend for Linux
+//!
+//! A Bluetooth headset shows up as a card whose profile decides the mode:
+//! `a2dp-sink` is stereo, `headset-head-unit` is hands-free. Everything is
+//! read and changed through `pactl` (its JSON output, so PipeWire's
+//! `pipewire-pulse` and PulseAudio both work). The backend implements both
+//! `AudioBackend` and `BluetoothBackend`: forcing stereo switches the card
+//! profile instead of disabling a service.
+
+pub mod json;
+
+use crate::audio::backend::{AudioBackend, AudioPoll, Endpoint};
+use crate::audio::device::AudioDevice;
+use crate::audio::session::{HfpUsingApp, MicUsingApp};
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::bluetooth::inventory::{BluetoothAddress, BluetoothProfile, PairedDevice};
+use crate::bluetooth::names::find_best_match;
+use crate::error::{AppError, Result};
+use json::{Card, ProfileKind, Sink, Source, Stream};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Arc;
+
+/// Runs `pactl` with the given arguments and returns its standard output
+///
+/// The indirection lets tests replay recorded output.
+pub trait Pactl: Send + Sync {
+    fn run(&self, args: &[&str]) -> Result<String>;
+}
+
+/// Runs the `pactl` executable
+#[derive(Debug, Default)]
+pub struct PactlCommand;
+
+impl Pactl for PactlCommand {
+    fn run(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("pactl").args(args).env("LC_ALL", "C").output()?;
+        if !output.status.success() {
+            return Err(AppError::AudioSessionError(format!(
+                "pactl {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Audio and Bluetooth backend on top of `pactl`
+#[derive(Clone)]
+pub struct PulseBackend {
+    pactl: Arc<dyn Pactl>,
+}
+
+impl Default for PulseBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PulseBackend {
+    /// Use the `pactl` executable from `PATH`
+    pub fn new() -> Self {
+        Self::with_pactl(Arc::new(PactlCommand))
+    }
+
+    pub fn with_pactl(pactl: Arc<dyn Pactl>) -> Self {
+        Self { pactl }
+    }
+
+    fn list<T: serde::de::DeserializeOwned>(&self, kind: &str) -> Result<Vec<T>> {
+        json::parse_list(&self.pactl.run(&["--format=json", "list", kind])?)
+    }
+
+    fn bluetooth_cards(&self) -> Result<Vec<Card>> {
+        Ok(self.list::<Card>("cards")?.into_iter().filter(Card::is_bluetooth).collect())
+    }
+
+    /// The Bluetooth card whose device name best matches `name`
+    fn find_card(&self, name: &str) -> Result<Card> {
+        let cards = self.bluetooth_cards()?;
+        find_best_match(name, &cards, Card::display_name)
+            .cloned()
+            .ok_or_else(|| AppError::ConfigError(format!("Bluetooth device '{}' not found", name)))
+    }
+
+    fn set_profile(&self, card: &Card, kind: ProfileKind) -> Result<()> {
+        let profile = card.best_profile(kind).ok_or_else(|| {
+            AppError::ConfigError(format!(
+                "'{}' has no {} profile",
+                card.display_name(),
+                match kind {
+                    ProfileKind::A2dp => "A2DP",
+                    _ => "hands-free",
+                }
+            ))
+        })?;
+        info!("Switching '{}' to profile {}", card.display_name(), profile);
+        self.pactl.run(&["set-card-profile", &card.name, profile])?;
+        Ok(())
+    }
+
+    /// Capture streams, without peak meters
+    fn capture_streams(&self) -> Result<Vec<Stream>> {
+        Ok(self
+            .list::<Stream>("source-outputs")?
+            .into_iter()
+            .filter(|stream| !stream.is_peak_detect())
+            .collect())
+    }
+
+    fn set_stream_muted(&self, stream: &Stream, muted: bool) -> Result<()> {
+        let index = stream.index.to_string();
+        self.pactl
+            .run(&["set-source-output-mute", &index, if muted { "1" } else { "0" }])?;
+        Ok(())
+    }
+}
+
+/// Mic-using apps from capture streams, one entry per process
+pub fn mic_apps(streams: &[Stream], sources: &[Source]) -> Vec<MicUsingApp> {
+    let mut apps: Vec<MicUsingApp> = Vec::new();
+
+    for stream in streams.iter().filter(|s| !s.corked) {
+        let Some(pid) = stream.process_id() else {
+            continue;
+        };
+        let source = sources.iter().find(|s| Some(s.index) == stream.source);
+        // Recording a sink monitor is not using a microphone
+        if source.is_some_and(|s| s.name.ends_with(".monitor")) {
+            continue;
+        }
+        let bluetooth = source.is_some_and(|s| json::is_bluetooth(&s.name, &s.properties));
+
+        match apps.iter_mut().find(|app| app.process_id == pid) {
+            Some(app) => {
+                app.is_muted &= stream.mute;
+                app.is_using_bluetooth_mic |= bluetooth;
+            }
+            None => {
+                let mut app = MicUsingApp::new(pid, stream.process_name(), stream.display_name());
+                app.is_muted = stream.mute;
+                app.is_using_bluetooth_mic = bluetooth;
+                apps.push(app);
+            }
+        }
+    }
+
+    apps
+}
+
+/// Output endpoints from sinks; Bluetooth sinks get the channel count of
+/// their card's profile as meter channels (1 = hands-free, 2 = stereo)
+pub fn endpoints(sinks: &[Sink], cards: &[Card]) -> Vec<Endpoint> {
+    sinks
+        .iter()
+        .filter(|sink| !sink.name.ends_with(".monitor"))
+        .map(|sink| {
+            let is_bluetooth = json::is_bluetooth(&sink.name, &sink.properties);
+            let mut endpoint = Endpoint::new(AudioDevice {
+                id: sink.name.clone(),
+                name: if sink.description.is_empty() {
+                    sink.name.clone()
+                } else {
+                    sink.description.clone()
+                },
+                is_bluetooth,
+            });
+            (endpoint.sample_rate, endpoint.channels) = json::parse_sample_spec(&sink.sample_specification);
+
+            if is_bluetooth {
+                let address = json::bluetooth_address(&sink.name, &sink.properties);
+                let card = cards.iter().find(|card| address.is_some() && card.address() == address);
+                endpoint.meter_channels = match card.map(Card::active_kind) {
+                    Some(ProfileKind::HeadsetHeadUnit) => Some(1),
+                    Some(ProfileKind::A2dp) => Some(2),
+                    _ => None,
+                };
+            }
+            endpoint
+        })
+        .collect()
+}
+
+/// Paired device entry for a connected Bluetooth card
+pub fn paired_device(card: &Card) -> PairedDevice {
+    let mut profiles = Vec::new();
+    if card.best_profile(ProfileKind::A2dp).is_some() {
+        profiles.push(BluetoothProfile::A2dpSink);
+    }
+    if card.best_profile(ProfileKind::HeadsetHeadUnit).is_some() {
+        profiles.push(BluetoothProfile::HandsFree);
+    }
+
+    PairedDevice {
+        address: card.address().unwrap_or(BluetoothAddress(0)),
+        name: card.display_name().to_string(),
+        class_of_device: 0,
+        device_type: card.device_type(),
+        // Cards only exist while the device is connected
+        connected: true,
+        remembered: true,
+        authenticated: true,
+        last_seen: None,
+        last_used: None,
+        profiles,
+    }
+}
+
+impl AudioBackend for PulseBackend {
+    fn poll(&self) -> Result<AudioPoll> {
+        let cards = self.bluetooth_cards()?;
+        let sinks: Vec<Sink> = self.list("sinks")?;
+        let sources: Vec<Source> = self.list("sources")?;
+        let streams = self.capture_streams()?;
+
+        Ok(AudioPoll {
+            endpoints: endpoints(&sinks, &cards),
+            mic_apps: mic_apps(&streams, &sources),
+        })
+    }
+
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp> {
+        let result = self.list::<Sink>("sinks").and_then(|sinks| {
+            let streams: Vec<Stream> = self.list("sink-inputs")?;
+            Ok((sinks, streams))
+        });
+        let (sinks, streams) = match result {
+            Ok(lists) => lists,
+            Err(e) => {
+                debug!("Failed to list playback streams: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut apps: Vec<HfpUsingApp> = Vec::new();
+        for stream in streams.iter().filter(|s| !s.corked) {
+            let on_bluetooth = sinks
+                .iter()
+                .find(|sink| Some(sink.index) == stream.sink)
+                .is_some_and(|sink| json::is_bluetooth(&sink.name, &sink.properties));
+            match stream.process_id() {
+                Some(pid) if on_bluetooth && !apps.iter().any(|a| a.process_id == pid) => {
+                    apps.push(HfpUsingApp::new(pid, stream.process_name(), stream.display_name()));
+                }
+                _ => {}
+            }
+        }
+        apps
+    }
+
+    fn set_app_muted(&self, process_id: u32, muted: bool) -> Result<()> {
+        let streams: Vec<Stream> = self
+            .capture_streams()?
+            .into_iter()
+            .filter(|stream| stream.process_id() == Some(process_id))
+            .collect();
+        if streams.is_empty() {
+            return Err(AppError::AudioSessionError(format!(
+                "No capture stream found for process {}",
+                process_id
+            )));
+        }
+        for stream in &streams {
+            self.set_stream_muted(stream, muted)?;
+        }
+        Ok(())
+    }
+
+    fn mute_all(&self) -> Result<()> {
+        let default_source = self.pactl.run(&["get-default-source"])?.trim().to_string();
+        let sources: Vec<Source> = self.list("sources")?;
+        let Some(source) = sources.iter().find(|s| s.name == default_source) else {
+            return Err(AppError::AudioSessionError(format!(
+                "Default source '{}' not found",
+                default_source
+            )));
+        };
+
+        for stream in self
+            .capture_streams()?
+            .iter()
+            .filter(|stream| stream.source == Some(source.index))
+        {
+            self.set_stream_muted(stream, true)?;
+        }
+        Ok(())
+    }
+}
+
+impl BluetoothBackend for PulseBackend {
+    /// Connected devices only; paired but disconnected devices have no card
+    fn list_paired_devices(&self) -> Result<Vec<PairedDevice>> {
+        Ok(self.bluetooth_cards()?.iter().map(paired_device).collect())
+    }
+
+    fn connect(&self, name: &str) -> Result<()> {
+        Err(AppError::IoError(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("Cannot connect '{}': connecting needs the BlueZ backend", name),
+        )))
+    }
+
+    /// Cycle the card through `off` and back to A2DP
+    ///
+    /// This tears down and re-creates the audio transport, which is what the
+    /// watchdog needs to leave a stuck hands-free profile.
+    fn reconnect(&self, name: &str) -> Result<()> {
+        let card = self.find_card(name)?;
+        self.pactl.run(&["set-card-profile", &card.name, "off"])?;
+        let result = self.set_profile(&card, ProfileKind::A2dp);
+        if result.is_err() {
+            if let Some(previous) = card.active_profile.as_deref() {
+                warn!("Restoring profile {} for '{}'", previous, card.display_name());
+                let _ = self.pactl.run(&["set-card-profile", &card.name, previous]);
+            }
+        }
+        result
+    }
+
+    /// Whether the card is currently in a hands-free profile
+    fn is_hfp_enabled(&self, name: &str) -> Result<bool> {
+        Ok(self.find_card(name)?.active_kind() == ProfileKind::HeadsetHeadUnit)
+    }
+
+    fn disable_hfp(&self, name: &str) -> Result<()> {
+        let card = self.find_card(name)?;
+        self.set_profile(&card, ProfileKind::A2dp)
+    }
+
+    /// Switch to the hands-free profile so the headset microphone can be used
+    fn enable_hfp(&self, name: &str) -> Result<()> {
+        let card = self.find_card(name)?;
+        self.set_profile(&card, ProfileKind::HeadsetHeadUnit)
+    }
+
+    /// `pactl` does not report battery levels
+    fn battery_levels(&self) -> Result<HashMap<String, u8>> {
+        Ok(HashMap::new())
+    }
+}
@@ -0,0 +1,308 @@
+//! Recording of raw audio polls
+//!
+//! With `logging.record_polls` enabled, every poll result (endpoints,
+//! formats, meter channels, capture sessions) is appended to a JSONL file in
+//! the log directory. The first line is a `RecordingHeader`, each following
+//! line a `PollRecord`:
+//!
+//! ```text
+//! {"format":1,"app_version":"0.3.1","os":"windows","started":1760790000}
+//! {"elapsed_ms":0,"poll":{"endpoints":[...],"mic_apps":[...]}}
+//! {"elapsed_ms":503,"error":"Audio session error: ..."}
+//! ```
+//!
+//! Recordings attached to bug reports can be fed back through mode detection
+//! and the monitor's policy with `simulation::ReplayBackend`.
+//!
+//! Like the log, recordings are capped by `logging.max_file_size`: a full
+//! file is closed and recording goes on in `polls-<time>.<part>.jsonl`,
+//! which starts with its own header so every part replays on its own. Only
+//! the newest `logging.max_files` recordings are kept.
+
+use crate::audio::backend::{AudioBackend, AudioPoll};
+use crate::audio::session::HfpUsingApp;
+use crate::error::{AppError, Result};
+use crate::settings::config::LoggingConfig;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Version of the recording format, bumped on incompatible changes
+pub const RECORDING_FORMAT: u32 = 1;
+
+/// First line of a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub format: u32,
+    pub app_version: String,
+    pub os: String,
+    /// Unix time the recording started
+    pub started: u64,
+}
+
+impl RecordingHeader {
+    fn now() -> Self {
+        Self {
+            format: RECORDING_FORMAT,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// One recorded poll: its result or the error it failed with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PollRecord {
+    /// Milliseconds since the recording started
+    pub elapsed_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<AudioPoll>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A recording read back from disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub records: Vec<PollRecord>,
+}
+
+impl Recording {
+    /// Read a recording, rejecting formats this version does not understand
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let invalid = |line: usize, e: serde_json::Error| {
+            AppError::ConfigError(format!("Recording line {}: {}", line, e))
+        };
+
+        let mut lines = reader.lines().enumerate().filter(|(_, line)| {
+            line.as_ref().map_or(true, |line| !line.trim().is_empty())
+        });
+        let header: RecordingHeader = match lines.next() {
+            Some((_, line)) => serde_json::from_str(&line?).map_err(|e| invalid(1, e))?,
+            None => return Err(AppError::ConfigError("Recording is empty".to_string())),
+        };
+        if header.format != RECORDING_FORMAT {
+            return Err(AppError::ConfigError(format!(
+                "Unsupported recording format {} (expected {})",
+                header.format, RECORDING_FORMAT
+            )));
+        }
+
+        let records = lines
+            .map(|(number, line)| serde_json::from_str(&line?).map_err(|e| invalid(number + 1, e)))
+            .collect::<Result<Vec<PollRecord>>>()?;
+        Ok(Self { header, records })
+    }
+}
+
+/// Size cap per recording file and number of recordings kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingLimits {
+    /// Start a new file once the current one reaches this many bytes
+    pub max_file_size: u64,
+    /// Recordings kept in the directory, the current one included
+    pub max_files: u32,
+}
+
+impl From<&LoggingConfig> for RecordingLimits {
+    fn from(config: &LoggingConfig) -> Self {
+        Self {
+            max_file_size: config.max_file_size,
+            max_files: config.max_files,
+        }
+    }
+}
+
+/// Where rotated recording files go
+struct Rotation {
+    dir: PathBuf,
+    /// Unix time the first file was started, shared by all parts
+    session: u64,
+    part: u32,
+    limits: RecordingLimits,
+}
+
+impl Rotation {
+    fn path(&self) -> PathBuf {
+        match self.part {
+            1 => self.dir.join(format!("polls-{}.jsonl", self.session)),
+            part => self.dir.join(format!("polls-{}.{}.jsonl", self.session, part)),
+        }
+    }
+
+    /// Delete the oldest recordings beyond `max_files`
+    fn prune(&self) -> Result<()> {
+        let mut recordings: Vec<(SystemTime, PathBuf)> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("polls-") && name.ends_with(".jsonl")
+            })
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect();
+        recordings.sort();
+
+        let keep = self.limits.max_files.max(1) as usize;
+        let excess = recordings.len().saturating_sub(keep);
+        for (_, path) in recordings.into_iter().take(excess) {
+            debug!("Removing old recording {:?}", path);
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+}
+
+/// The file (or writer) polls currently go to
+struct Sink {
+    writer: Box<dyn Write + Send>,
+    /// Bytes written to the current file
+    written: u64,
+    started: Instant,
+    /// `None` for caller-supplied writers, which are never rotated
+    rotation: Option<Rotation>,
+}
+
+impl Sink {
+    fn new(mut writer: Box<dyn Write + Send>, rotation: Option<Rotation>) -> Result<Self> {
+        let written = write_line(&mut writer, &RecordingHeader::now())?;
+        Ok(Self {
+            writer,
+            written,
+            started: Instant::now(),
+            rotation,
+        })
+    }
+
+    /// Open the next file if the current one is full
+    fn rotate_if_full(&mut self) -> Result<()> {
+        let Some(rotation) = self.rotation.as_mut() else {
+            return Ok(());
+        };
+        if self.written < rotation.limits.max_file_size {
+            return Ok(());
+        }
+
+        rotation.part += 1;
+        let path = rotation.path();
+        info!("Recording is full, continuing in {:?}", path);
+        let rotation = self.rotation.take();
+        *self = Sink::new(Box::new(BufWriter::new(File::create(&path)?)), rotation)?;
+        if let Some(rotation) = &self.rotation {
+            rotation.prune()?;
+        }
+        Ok(())
+    }
+}
+
+/// Audio backend that records every poll of the backend it wraps
+pub struct RecordingBackend {
+    inner: Arc<dyn AudioBackend>,
+    sink: Mutex<Sink>,
+    /// Set after the first write error so a full disk is logged only once
+    failed: AtomicBool,
+}
+
+impl RecordingBackend {
+    /// Record to a new `polls-<unix time>.jsonl` in `dir`, rotated by `limits`
+    pub fn create(inner: Arc<dyn AudioBackend>, dir: &Path, limits: RecordingLimits) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let rotation = Rotation {
+            dir: dir.to_path_buf(),
+            session: RecordingHeader::now().started,
+            part: 1,
+            limits,
+        };
+        let path = rotation.path();
+        let file = File::create(&path)?;
+        info!("Recording audio polls to {:?}", path);
+        rotation.prune()?;
+        Ok(Self::with_sink(inner, Sink::new(Box::new(BufWriter::new(file)), Some(rotation))?))
+    }
+
+    /// Record to any writer, without a size limit
+    pub fn with_writer(inner: Arc<dyn AudioBackend>, writer: Box<dyn Write + Send>) -> Result<Self> {
+        Ok(Self::with_sink(inner, Sink::new(writer, None)?))
+    }
+
+    fn with_sink(inner: Arc<dyn AudioBackend>, sink: Sink) -> Self {
+        Self {
+            inner,
+            sink: Mutex::new(sink),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    fn record(&self, result: &Result<AudioPoll>) {
+        let mut sink = self.sink.lock().unwrap();
+        let written = sink.rotate_if_full().and_then(|()| {
+            let record = PollRecord {
+                elapsed_ms: sink.started.elapsed().as_millis() as u64,
+                poll: result.as_ref().ok().cloned(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            };
+            write_line(&mut sink.writer, &record)
+        });
+        match written {
+            Ok(bytes) => sink.written += bytes,
+            Err(e) => {
+                if !self.failed.swap(true, Ordering::Relaxed) {
+                    warn!("Failed to record audio poll: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Write one JSON line and flush, so a crash keeps everything up to it
+///
+/// Returns the number of bytes written.
+fn write_line(writer: &mut dyn Write, value: &impl Serialize) -> Result<u64> {
+    let line = serde_json::to_string(value)
+        .map_err(|e| AppError::ConfigError(format!("Could not serialize recording: {}", e)))?;
+    writeln!(writer, "{}", line)?;
+    writer.flush()?;
+    Ok(line.len() as u64 + 1)
+}
+
+impl AudioBackend for RecordingBackend {
+    fn attach_thread(&self) -> Result<()> {
+        self.inner.attach_thread()
+    }
+
+    fn detach_thread(&self) {
+        self.inner.detach_thread()
+    }
+
+    fn poll(&self) -> Result<AudioPoll> {
+        let result = self.inner.poll();
+        self.record(&result);
+        result
+    }
+
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp> {
+        self.inner.bluetooth_output_apps()
+    }
+
+    fn set_app_muted(&self, process_id: u32, muted: bool) -> Result<()> {
+        self.inner.set_app_muted(process_id, muted)
+    }
+
+    fn mute_all(&self) -> Result<()> {
+        self.inner.mute_all()
+    }
+}
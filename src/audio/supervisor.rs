@@ -0,0 +1,364 @@
+//! Supervision of the audio monitor thread
+//!
+//! The monitor thread can exit early (audio initialization failed, a panic)
+//! or hang inside a backend call. It beats a `Heartbeat` after every
+//! successful poll, so a backend that only returns errors counts as stalled
+//! too. `AudioMonitor::health` turns that and the thread's liveness into a
+//! `MonitorHealth`, and `MonitorSupervisor` decides when the shown state is
+//! stale, when to start a new monitor and when to tell the user that
+//! restarts keep failing. Restarts back off exponentially and the failure
+//! count only resets once a restarted monitor has stayed up for a while.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time of the monitor thread's last successful poll
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat(Arc<Mutex<Option<Instant>>>);
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a successful poll
+    pub fn beat(&self, now: Instant) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(now);
+    }
+
+    /// When the last poll succeeded, `None` before the first one
+    pub fn last(&self) -> Option<Instant> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Whether the monitor thread is doing its job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorHealth {
+    /// Started but has not polled successfully yet
+    Starting,
+    /// Polling normally
+    Running,
+    /// No poll succeeded within the stall timeout
+    Stalled,
+    /// The thread has exited
+    Dead,
+}
+
+impl MonitorHealth {
+    /// Health of a monitor started at `started_at` with the given heartbeat
+    pub fn assess(
+        finished: bool,
+        started_at: Instant,
+        last_beat: Option<Instant>,
+        now: Instant,
+        stall_timeout: Duration,
+    ) -> Self {
+        if finished {
+            return MonitorHealth::Dead;
+        }
+        let since = last_beat.unwrap_or(started_at);
+        if now.saturating_duration_since(since) > stall_timeout {
+            MonitorHealth::Stalled
+        } else if last_beat.is_none() {
+            MonitorHealth::Starting
+        } else {
+            MonitorHealth::Running
+        }
+    }
+
+    /// The monitor is gone or stuck and has to be replaced
+    pub fn is_failed(self) -> bool {
+        matches!(self, MonitorHealth::Stalled | MonitorHealth::Dead)
+    }
+}
+
+impl std::fmt::Display for MonitorHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorHealth::Starting => write!(f, "starting"),
+            MonitorHealth::Running => write!(f, "running"),
+            MonitorHealth::Stalled => write!(f, "stalled"),
+            MonitorHealth::Dead => write!(f, "stopped"),
+        }
+    }
+}
+
+/// Timing and limits for restarting the monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupervisorSettings {
+    /// How long without a successful poll before the monitor counts as stalled
+    pub stall_timeout: Duration,
+    /// Delay before the first restart of an outage
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between restarts
+    pub max_backoff: Duration,
+    /// How long a restarted monitor must run before earlier failures are forgotten
+    pub stable_after: Duration,
+    /// Consecutive restarts after which the user is told
+    pub report_after: u32,
+}
+
+impl Default for SupervisorSettings {
+    fn default() -> Self {
+        Self {
+            stall_timeout: Duration::from_secs(10),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            stable_after: Duration::from_secs(60),
+            report_after: 3,
+        }
+    }
+}
+
+impl SupervisorSettings {
+    /// Delay before the restart that follows `failures` earlier restarts
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.min(16));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// What the caller should do after a health check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupervisorAction {
+    /// The monitor stopped or stalled; show the current state as stale
+    MarkStale(MonitorHealth),
+    /// Replace the monitor with a new one
+    Restart { attempt: u32 },
+    /// Restarts keep failing; tell the user (once per outage)
+    ReportFailing { attempts: u32 },
+    /// The restarted monitor is polling again; the state is fresh
+    Recovered,
+}
+
+/// A period in which the monitor was not running
+#[derive(Debug, Clone, Copy)]
+struct Outage {
+    next_restart: Instant,
+    reported: bool,
+}
+
+/// Decides when to restart the monitor
+#[derive(Debug, Clone)]
+pub struct MonitorSupervisor {
+    settings: SupervisorSettings,
+    /// Restarts since the monitor last ran stably
+    failures: u32,
+    /// When the current monitor was started
+    started_at: Instant,
+    outage: Option<Outage>,
+}
+
+impl MonitorSupervisor {
+    /// Supervise a monitor started at `now`
+    pub fn new(settings: SupervisorSettings, now: Instant) -> Self {
+        Self {
+            settings,
+            failures: 0,
+            started_at: now,
+            outage: None,
+        }
+    }
+
+    pub fn settings(&self) -> &SupervisorSettings {
+        &self.settings
+    }
+
+    /// The monitor is currently considered down
+    pub fn is_down(&self) -> bool {
+        self.outage.is_some()
+    }
+
+    /// Restarts since the monitor last ran stably
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Check the monitor's health at `now`
+    ///
+    /// A `Restart` action assumes the caller starts the new monitor right
+    /// away; the new monitor is then judged on the following checks.
+    pub fn check(&mut self, now: Instant, health: MonitorHealth) -> Vec<SupervisorAction> {
+        let mut actions = Vec::new();
+
+        match health {
+            MonitorHealth::Starting => {}
+            MonitorHealth::Running => {
+                if self.outage.take().is_some() {
+                    actions.push(SupervisorAction::Recovered);
+                }
+                if self.failures > 0
+                    && now.saturating_duration_since(self.started_at) >= self.settings.stable_after
+                {
+                    self.failures = 0;
+                }
+            }
+            MonitorHealth::Stalled | MonitorHealth::Dead => {
+                let outage = self.outage.get_or_insert_with(|| {
+                    actions.push(SupervisorAction::MarkStale(health));
+                    Outage {
+                        next_restart: now + self.settings.backoff(self.failures),
+                        reported: false,
+                    }
+                });
+
+                if now >= outage.next_restart {
+                    self.failures += 1;
+                    self.started_at = now;
+                    outage.next_restart = now + self.settings.backoff(self.failures);
+                    actions.push(SupervisorAction::Restart {
+                        attempt: self.failures,
+                    });
+
+                    if self.failures >= self.settings.report_after && !outage.reported {
+                        outage.reported = true;
+                        actions.push(SupervisorAction::ReportFailing {
+                            attempts: self.failures,
+                        });
+                    }
+                }
+            }
+        }
+
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_assess_health() {
+        let start = Instant::now();
+        let timeout = secs(10);
+
+        assert_eq!(
+            MonitorHealth::assess(false, start, None, start + secs(1), timeout),
+            MonitorHealth::Starting
+        );
+        assert_eq!(
+            MonitorHealth::assess(false, start, None, start + secs(11), timeout),
+            MonitorHealth::Stalled
+        );
+        assert_eq!(
+            MonitorHealth::assess(
+                false,
+                start,
+                Some(start + secs(5)),
+                start + secs(11),
+                timeout
+            ),
+            MonitorHealth::Running
+        );
+        assert_eq!(
+            MonitorHealth::assess(
+                false,
+                start,
+                Some(start + secs(5)),
+                start + secs(16),
+                timeout
+            ),
+            MonitorHealth::Stalled
+        );
+        assert_eq!(
+            MonitorHealth::assess(true, start, Some(start + secs(5)), start + secs(6), timeout),
+            MonitorHealth::Dead
+        );
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let settings = SupervisorSettings::default();
+        assert_eq!(settings.backoff(0), secs(1));
+        assert_eq!(settings.backoff(1), secs(2));
+        assert_eq!(settings.backoff(3), secs(8));
+        assert_eq!(settings.backoff(6), secs(60));
+        assert_eq!(settings.backoff(100), secs(60));
+    }
+
+    #[test]
+    fn test_restart_after_backoff_and_recover() {
+        let start = Instant::now();
+        let mut supervisor = MonitorSupervisor::new(SupervisorSettings::default(), start);
+
+        assert!(supervisor.check(start, MonitorHealth::Running).is_empty());
+        assert_eq!(
+            supervisor.check(start + secs(5), MonitorHealth::Dead),
+            vec![SupervisorAction::MarkStale(MonitorHealth::Dead)]
+        );
+        assert!(supervisor
+            .check(
+                start + secs(5) + Duration::from_millis(500),
+                MonitorHealth::Dead
+            )
+            .is_empty());
+        assert_eq!(
+            supervisor.check(start + secs(6), MonitorHealth::Dead),
+            vec![SupervisorAction::Restart { attempt: 1 }]
+        );
+        assert!(supervisor
+            .check(start + secs(7), MonitorHealth::Starting)
+            .is_empty());
+        assert!(supervisor.is_down());
+        assert_eq!(
+            supervisor.check(start + secs(8), MonitorHealth::Running),
+            vec![SupervisorAction::Recovered]
+        );
+        assert!(!supervisor.is_down());
+
+        // Failures are forgotten once the new monitor has run stably
+        assert_eq!(supervisor.failures(), 1);
+        supervisor.check(start + secs(66), MonitorHealth::Running);
+        assert_eq!(supervisor.failures(), 0);
+    }
+
+    #[test]
+    fn test_repeated_failures_back_off_and_report_once() {
+        let start = Instant::now();
+        let mut supervisor = MonitorSupervisor::new(SupervisorSettings::default(), start);
+        let mut restarts = Vec::new();
+        let mut reports = Vec::new();
+
+        for tick in 0..120 {
+            let now = start + Duration::from_millis(500 * tick);
+            for action in supervisor.check(now, MonitorHealth::Dead) {
+                match action {
+                    SupervisorAction::Restart { attempt } => restarts.push((attempt, now - start)),
+                    SupervisorAction::ReportFailing { attempts } => reports.push(attempts),
+                    _ => {}
+                }
+            }
+        }
+
+        // 1s, then 2s, 4s, 8s, 16s after each restart
+        assert_eq!(
+            restarts,
+            vec![
+                (1, secs(1)),
+                (2, secs(3)),
+                (3, secs(7)),
+                (4, secs(15)),
+                (5, secs(31))
+            ]
+        );
+        assert_eq!(reports, vec![3]);
+    }
+
+    #[test]
+    fn test_stall_marks_stale() {
+        let start = Instant::now();
+        let mut supervisor = MonitorSupervisor::new(SupervisorSettings::default(), start);
+        assert_eq!(
+            supervisor.check(start + secs(11), MonitorHealth::Stalled),
+            vec![SupervisorAction::MarkStale(MonitorHealth::Stalled)]
+        );
+    }
+}
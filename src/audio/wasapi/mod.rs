@@ -0,0 +1,71 @@
+//! WASAPI audio backend
+//!
+//! Endpoints, formats and peak meters come from `device`, capture and
+//! render sessions from `session`. Every thread using the backend needs COM,
+//! which `attach_thread` initializes.
+
+pub mod device;
+pub mod session;
+
+pub use device::DeviceManager;
+pub use session::{get_apps_using_bluetooth_output, AudioSession, CaptureSessionManager};
+
+use crate::audio::backend::{AudioBackend, AudioPoll};
+use crate::audio::session::HfpUsingApp;
+use crate::error::{AppError, Result};
+use crate::retry::{classify_app_error, RetryPolicy};
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED};
+
+/// Audio backend for Windows
+#[derive(Debug, Default)]
+pub struct WasapiBackend;
+
+impl WasapiBackend {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl AudioBackend for WasapiBackend {
+    fn attach_thread(&self) -> Result<()> {
+        unsafe {
+            let hr = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            if hr.is_err() {
+                return Err(AppError::ComInitFailed(format!("{:?}", hr)));
+            }
+        }
+        Ok(())
+    }
+
+    fn detach_thread(&self) {
+        unsafe { CoUninitialize() };
+    }
+
+    fn poll(&self) -> Result<AudioPoll> {
+        let endpoints = DeviceManager::new()?.render_endpoints()?;
+        // Check ALL capture devices, not just the default
+        let mic_apps = CaptureSessionManager::get_all_mic_using_apps();
+        Ok(AudioPoll { endpoints, mic_apps })
+    }
+
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp> {
+        get_apps_using_bluetooth_output()
+    }
+
+    fn set_app_muted(&self, process_id: u32, muted: bool) -> Result<()> {
+        if muted {
+            CaptureSessionManager::mute_app_on_all_devices(process_id)
+        } else {
+            CaptureSessionManager::unmute_app_on_all_devices(process_id)
+        }
+    }
+
+    fn mute_all(&self) -> Result<()> {
+        let session_manager = RetryPolicy::AUDIO_SESSION.run(
+            "Open default capture device",
+            classify_app_error,
+            |_| CaptureSessionManager::new_default(),
+        )?;
+        session_manager.mute_all()
+    }
+}
@@ -0,0 +1,462 @@
+//! Watchdog that recovers headsets stuck in hands-free mode
+//!
+//! After a call ends Windows sometimes leaves the headset in mono HFP even
+//! though nothing is capturing from it any more. The watchdog tracks how long
+//! each device has been in `AudioMode::HandsFree` without an active Bluetooth
+//! capture session, and asks for a reconnect once that exceeds a timeout.
+//! Attempts are limited by a cooldown and a maximum count per episode.
+
+use crate::audio::device::{AudioMode, BluetoothAudioDevice};
+use crate::settings::config::WatchdogConfig;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Maximum number of records kept in the watchdog history
+const MAX_HISTORY: usize = 100;
+
+/// Timing and limits for the watchdog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogSettings {
+    /// How long a device must be stuck in hands-free mode before acting
+    pub timeout: Duration,
+    /// Minimum time between two reconnect attempts for the same device
+    pub cooldown: Duration,
+    /// Maximum reconnect attempts before giving up until the device recovers
+    pub max_attempts: u32,
+}
+
+impl From<&WatchdogConfig> for WatchdogSettings {
+    fn from(config: &WatchdogConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.hands_free_timeout_secs as u64),
+            cooldown: Duration::from_secs(config.cooldown_secs as u64),
+            max_attempts: config.max_attempts,
+        }
+    }
+}
+
+/// Outcome recorded for a watchdog action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchdogOutcome {
+    /// A reconnect was requested; the caller should perform it
+    Triggered,
+    /// The reconnect completed successfully
+    Succeeded,
+    /// The reconnect failed
+    Failed(String),
+    /// No further attempts will be made
+    GaveUp,
+}
+
+/// Audit record of a watchdog action
+#[derive(Debug, Clone)]
+pub struct WatchdogRecord {
+    pub timestamp: SystemTime,
+    pub device: String,
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// How long the device had been stuck when the record was made
+    pub stuck_for: Duration,
+    pub outcome: WatchdogOutcome,
+}
+
+/// Per-device tracking state
+#[derive(Debug, Default)]
+struct DeviceWatch {
+    /// When the device was first seen stuck (hands-free, no BT capture)
+    stuck_since: Option<Instant>,
+    last_attempt: Option<Instant>,
+    attempts: u32,
+    in_flight: bool,
+    gave_up: bool,
+    /// When the device was last missing from a poll (reconnects take it away)
+    missing_since: Option<Instant>,
+}
+
+/// Hands-free watchdog state machine
+///
+/// Time is passed in explicitly so the logic can be tested without sleeping.
+pub struct HandsFreeWatchdog {
+    settings: WatchdogSettings,
+    devices: HashMap<String, DeviceWatch>,
+    history: Vec<WatchdogRecord>,
+}
+
+impl HandsFreeWatchdog {
+    /// Create a new watchdog
+    pub fn new(settings: WatchdogSettings) -> Self {
+        Self {
+            settings,
+            devices: HashMap::new(),
+            history: Vec::new(),
+        }
+    }
+
+    /// Get the current settings
+    pub fn settings(&self) -> WatchdogSettings {
+        self.settings
+    }
+
+    /// Update settings without losing per-device state
+    pub fn set_settings(&mut self, settings: WatchdogSettings) {
+        self.settings = settings;
+    }
+
+    /// Evaluate the latest poll result and return the actions taken
+    ///
+    /// Records with `WatchdogOutcome::Triggered` ask the caller to reconnect
+    /// the device and report back through `record_result`; records with
+    /// `WatchdogOutcome::GaveUp` are informational.
+    ///
+    /// # Arguments
+    /// * `now` - Current time
+    /// * `devices` - Bluetooth devices from the latest poll
+    /// * `bt_capture_active` - Whether any app is capturing from a Bluetooth mic
+    pub fn evaluate(
+        &mut self,
+        now: Instant,
+        devices: &[BluetoothAudioDevice],
+        bt_capture_active: bool,
+    ) -> Vec<WatchdogRecord> {
+        let mut actions = Vec::new();
+
+        // Forget devices only once they have been gone longer than the
+        // cooldown; a reconnect takes the device away for several polls and
+        // must not reset its attempts
+        for (name, watch) in self.devices.iter_mut() {
+            if devices.iter().any(|d| &d.device.name == name) {
+                watch.missing_since = None;
+            } else {
+                watch.missing_since.get_or_insert(now);
+            }
+        }
+        let cooldown = self.settings.cooldown;
+        self.devices.retain(|_, watch| {
+            watch.in_flight
+                || watch
+                    .missing_since
+                    .is_none_or(|since| now.saturating_duration_since(since) < cooldown)
+        });
+
+        for device in devices {
+            let name = &device.device.name;
+            let watch = self.devices.entry(name.clone()).or_default();
+
+            if device.current_mode != AudioMode::HandsFree {
+                // Device recovered (or mode unknown) - start a fresh episode next time
+                if device.current_mode == AudioMode::Stereo {
+                    *watch = DeviceWatch::default();
+                } else {
+                    watch.stuck_since = None;
+                }
+                continue;
+            }
+
+            if bt_capture_active {
+                // Hands-free is legitimate while something is using the mic
+                watch.stuck_since = None;
+                continue;
+            }
+
+            let stuck_since = *watch.stuck_since.get_or_insert(now);
+            let stuck_for = now.saturating_duration_since(stuck_since);
+
+            if stuck_for < self.settings.timeout || watch.in_flight || watch.gave_up {
+                continue;
+            }
+
+            if let Some(last) = watch.last_attempt {
+                if now.saturating_duration_since(last) < self.settings.cooldown {
+                    continue;
+                }
+            }
+
+            if watch.attempts >= self.settings.max_attempts {
+                watch.gave_up = true;
+                let attempts = watch.attempts;
+                actions.push(self.push_record(name, attempts, stuck_for, WatchdogOutcome::GaveUp));
+                continue;
+            }
+
+            watch.attempts += 1;
+            watch.last_attempt = Some(now);
+            watch.in_flight = true;
+            let attempt = watch.attempts;
+            actions.push(self.push_record(name, attempt, stuck_for, WatchdogOutcome::Triggered));
+        }
+
+        actions
+    }
+
+    /// Record the result of a reconnect requested by `evaluate`
+    ///
+    /// Returns the record that was added to the history.
+    pub fn record_result(
+        &mut self,
+        now: Instant,
+        device: &str,
+        result: std::result::Result<(), String>,
+    ) -> WatchdogRecord {
+        let (attempt, stuck_for) = match self.devices.get_mut(device) {
+            Some(watch) => {
+                watch.in_flight = false;
+                let stuck_for = watch
+                    .stuck_since
+                    .map(|since| now.saturating_duration_since(since))
+                    .unwrap_or_default();
+                (watch.attempts, stuck_for)
+            }
+            None => (0, Duration::ZERO),
+        };
+
+        let outcome = match result {
+            Ok(()) => WatchdogOutcome::Succeeded,
+            Err(e) => WatchdogOutcome::Failed(e),
+        };
+
+        self.push_record(device, attempt, stuck_for, outcome)
+    }
+
+    /// Get the recorded watchdog actions, oldest first
+    pub fn history(&self) -> &[WatchdogRecord] {
+        &self.history
+    }
+
+    fn push_record(
+        &mut self,
+        device: &str,
+        attempt: u32,
+        stuck_for: Duration,
+        outcome: WatchdogOutcome,
+    ) -> WatchdogRecord {
+        let record = WatchdogRecord {
+            timestamp: SystemTime::now(),
+            device: device.to_string(),
+            attempt,
+            max_attempts: self.settings.max_attempts,
+            stuck_for,
+            outcome,
+        };
+
+        match &record.outcome {
+            WatchdogOutcome::Failed(e) => warn!(
+                "Watchdog: reconnect of '{}' failed (attempt {}/{}): {}",
+                device, attempt, record.max_attempts, e
+            ),
+            outcome => info!(
+                "Watchdog: '{}' stuck in hands-free for {:?} - {:?} (attempt {}/{})",
+                device, stuck_for, outcome, attempt, record.max_attempts
+            ),
+        }
+
+        self.history.push(record.clone());
+        // Keep only the most recent entries
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::device::AudioDevice;
+
+    fn device(name: &str, mode: AudioMode) -> BluetoothAudioDevice {
+        let mut device = BluetoothAudioDevice::new(AudioDevice {
+            id: format!("{{bth}}{}", name),
+            name: name.to_string(),
+            is_bluetooth: true,
+        });
+        device.current_mode = mode;
+        device
+    }
+
+    fn settings() -> WatchdogSettings {
+        WatchdogSettings {
+            timeout: Duration::from_secs(10),
+            cooldown: Duration::from_secs(30),
+            max_attempts: 2,
+        }
+    }
+
+    fn outcomes(records: &[WatchdogRecord]) -> Vec<(String, u32, WatchdogOutcome)> {
+        records
+            .iter()
+            .map(|r| (r.device.clone(), r.attempt, r.outcome.clone()))
+            .collect()
+    }
+
+    fn triggered(attempt: u32) -> Vec<(String, u32, WatchdogOutcome)> {
+        vec![("Headset".to_string(), attempt, WatchdogOutcome::Triggered)]
+    }
+
+    #[test]
+    fn test_triggers_after_timeout() {
+        let mut watchdog = HandsFreeWatchdog::new(settings());
+        let start = Instant::now();
+        let devices = [device("Headset", AudioMode::HandsFree)];
+
+        assert!(watchdog.evaluate(start, &devices, false).is_empty());
+        assert!(watchdog
+            .evaluate(start + Duration::from_secs(9), &devices, false)
+            .is_empty());
+
+        let records = watchdog.evaluate(start + Duration::from_secs(10), &devices, false);
+        assert_eq!(outcomes(&records), triggered(1));
+        assert_eq!(records[0].stuck_for, Duration::from_secs(10));
+        assert_eq!(watchdog.history().len(), 1);
+    }
+
+    #[test]
+    fn test_active_capture_resets_timer() {
+        let mut watchdog = HandsFreeWatchdog::new(settings());
+        let start = Instant::now();
+        let devices = [device("Headset", AudioMode::HandsFree)];
+
+        watchdog.evaluate(start, &devices, false);
+        watchdog.evaluate(start + Duration::from_secs(8), &devices, true);
+        assert!(watchdog
+            .evaluate(start + Duration::from_secs(12), &devices, false)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_cooldown_and_max_attempts() {
+        let mut watchdog = HandsFreeWatchdog::new(settings());
+        let start = Instant::now();
+        let devices = [device("Headset", AudioMode::HandsFree)];
+
+        watchdog.evaluate(start, &devices, false);
+        let t1 = start + Duration::from_secs(10);
+        assert_eq!(outcomes(&watchdog.evaluate(t1, &devices, false)), triggered(1));
+        let record = watchdog.record_result(t1, "Headset", Err("busy".to_string()));
+        assert_eq!(record.outcome, WatchdogOutcome::Failed("busy".to_string()));
+
+        // Within cooldown
+        assert!(watchdog
+            .evaluate(t1 + Duration::from_secs(29), &devices, false)
+            .is_empty());
+
+        let t2 = t1 + Duration::from_secs(30);
+        assert_eq!(outcomes(&watchdog.evaluate(t2, &devices, false)), triggered(2));
+        watchdog.record_result(t2, "Headset", Ok(()));
+
+        let t3 = t2 + Duration::from_secs(30);
+        assert_eq!(
+            outcomes(&watchdog.evaluate(t3, &devices, false)),
+            vec![("Headset".to_string(), 2, WatchdogOutcome::GaveUp)]
+        );
+        // Gives up only once
+        assert!(watchdog
+            .evaluate(t3 + Duration::from_secs(60), &devices, false)
+            .is_empty());
+        // Triggered, Failed, Triggered, Succeeded, GaveUp
+        assert_eq!(watchdog.history().len(), 5);
+    }
+
+    #[test]
+    fn test_recovery_resets_attempts() {
+        let mut watchdog = HandsFreeWatchdog::new(settings());
+        let start = Instant::now();
+        let stuck = [device("Headset", AudioMode::HandsFree)];
+        let stereo = [device("Headset", AudioMode::Stereo)];
+
+        watchdog.evaluate(start, &stuck, false);
+        let t1 = start + Duration::from_secs(10);
+        watchdog.evaluate(t1, &stuck, false);
+        watchdog.record_result(t1, "Headset", Ok(()));
+        watchdog.evaluate(t1 + Duration::from_secs(1), &stereo, false);
+
+        // New episode: timeout applies again, but no cooldown from the old attempt
+        let t2 = t1 + Duration::from_secs(2);
+        watchdog.evaluate(t2, &stuck, false);
+        assert_eq!(
+            outcomes(&watchdog.evaluate(t2 + Duration::from_secs(10), &stuck, false)),
+            triggered(1)
+        );
+    }
+
+    #[test]
+    fn test_no_new_attempt_while_in_flight() {
+        let mut watchdog = HandsFreeWatchdog::new(WatchdogSettings {
+            cooldown: Duration::ZERO,
+            ..settings()
+        });
+        let start = Instant::now();
+        let devices = [device("Headset", AudioMode::HandsFree)];
+
+        watchdog.evaluate(start, &devices, false);
+        assert_eq!(
+            watchdog
+                .evaluate(start + Duration::from_secs(10), &devices, false)
+                .len(),
+            1
+        );
+        assert!(watchdog
+            .evaluate(start + Duration::from_secs(11), &devices, false)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_attempts_survive_device_missing_during_reconnect() {
+        let mut watchdog = HandsFreeWatchdog::new(settings());
+        let start = Instant::now();
+        let stuck = [device("Headset", AudioMode::HandsFree)];
+        let ms = Duration::from_millis;
+
+        watchdog.evaluate(start, &stuck, false);
+        let t1 = start + Duration::from_secs(10);
+        assert_eq!(outcomes(&watchdog.evaluate(t1, &stuck, false)), triggered(1));
+
+        // The reconnect takes the device away for several polls
+        for poll in 1..=6 {
+            assert!(watchdog.evaluate(t1 + ms(500 * poll), &[], false).is_empty());
+        }
+        watchdog.record_result(t1 + ms(3000), "Headset", Ok(()));
+
+        // It comes back still in hands-free: the cooldown and count still apply
+        assert!(watchdog
+            .evaluate(t1 + Duration::from_secs(4), &stuck, false)
+            .is_empty());
+        let t2 = t1 + Duration::from_secs(30);
+        assert_eq!(outcomes(&watchdog.evaluate(t2, &stuck, false)), triggered(2));
+
+        for poll in 1..=6 {
+            assert!(watchdog.evaluate(t2 + ms(500 * poll), &[], false).is_empty());
+        }
+        watchdog.record_result(t2 + ms(3000), "Headset", Ok(()));
+
+        let t3 = t2 + Duration::from_secs(30);
+        assert_eq!(
+            outcomes(&watchdog.evaluate(t3, &stuck, false)),
+            vec![("Headset".to_string(), 2, WatchdogOutcome::GaveUp)]
+        );
+    }
+
+    #[test]
+    fn test_forgets_device_missing_past_cooldown() {
+        let mut watchdog = HandsFreeWatchdog::new(settings());
+        let start = Instant::now();
+        let stuck = [device("Headset", AudioMode::HandsFree)];
+
+        watchdog.evaluate(start, &stuck, false);
+        let t1 = start + Duration::from_secs(10);
+        watchdog.evaluate(t1, &stuck, false);
+        watchdog.record_result(t1, "Headset", Ok(()));
+
+        watchdog.evaluate(t1 + Duration::from_secs(1), &[], false);
+        watchdog.evaluate(t1 + Duration::from_secs(31), &[], false);
+
+        // Back after a long absence: a fresh episode with its own timeout
+        let t2 = t1 + Duration::from_secs(40);
+        assert!(watchdog.evaluate(t2, &stuck, false).is_empty());
+        assert_eq!(
+            outcomes(&watchdog.evaluate(t2 + Duration::from_secs(10), &stuck, false)),
+            triggered(1)
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Access tokens for the local control APIs
+
+use crate::error::Result;
+
+/// Random bytes per generated token (hex-encoded to twice the length)
+const TOKEN_BYTES: usize = 32;
+
+/// Generate a random access token from the system RNG
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    fill_random(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+#[cfg(windows)]
+fn fill_random(bytes: &mut [u8]) -> Result<()> {
+    use windows::Win32::Security::Cryptography::{
+        BCryptGenRandom, BCRYPT_ALG_HANDLE, BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+    };
+
+    unsafe {
+        BCryptGenRandom(BCRYPT_ALG_HANDLE::default(), bytes, BCRYPT_USE_SYSTEM_PREFERRED_RNG).ok()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn fill_random(bytes: &mut [u8]) -> Result<()> {
+    use std::io::Read;
+
+    std::fs::File::open("/dev/urandom")?.read_exact(bytes)?;
+    Ok(())
+}
+
+/// Compare a presented token with the configured one in constant time
+///
+/// An empty configured token never matches, so a missing token cannot be
+/// used to bypass authentication.
+pub fn tokens_match(expected: &str, presented: &str) -> bool {
+    let (expected, presented) = (expected.as_bytes(), presented.as_bytes());
+    if expected.is_empty() || expected.len() != presented.len() {
+        return false;
+    }
+    expected
+        .iter()
+        .zip(presented)
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secret2"));
+        assert!(!tokens_match("", ""));
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert_ne!(token, generate_token().unwrap());
+    }
+}
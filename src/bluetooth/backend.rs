@@ -0,0 +1,51 @@
+//! Platform Bluetooth backend
+//!
+//! Everything the core does to a headset goes through `BluetoothBackend`:
+//! listing paired devices, (re)connecting, switching the hands-free service
+//! and reading battery levels. Devices are addressed by friendly name, which
+//! backends match with `names::check_name_match`.
+
+use crate::bluetooth::inventory::PairedDevice;
+use crate::error::Result;
+use std::collections::HashMap;
+
+/// Device control for paired Bluetooth audio devices
+///
+/// Shared by the main loop, the monitor thread and background reconnect
+/// threads, so methods take `&self`. Calls may block for a second or more.
+pub trait BluetoothBackend: Send + Sync {
+    /// All paired devices, connected or not, with their enabled profiles
+    fn list_paired_devices(&self) -> Result<Vec<PairedDevice>>;
+
+    /// Connect a paired but disconnected device
+    fn connect(&self, name: &str) -> Result<()>;
+
+    /// Drop and re-establish the connection to a device
+    fn reconnect(&self, name: &str) -> Result<()>;
+
+    /// Whether the hands-free service is enabled for a device
+    fn is_hfp_enabled(&self, name: &str) -> Result<bool>;
+
+    /// Disable the hands-free service so the device stays in stereo
+    fn disable_hfp(&self, name: &str) -> Result<()>;
+
+    /// Re-enable the hands-free service
+    fn enable_hfp(&self, name: &str) -> Result<()>;
+
+    /// Disable hands-free again on a forced-stereo device that reconnected
+    ///
+    /// Returns whether hands-free had to be turned off. Backends that cannot
+    /// tell whether the headset brought it back on its own override this and
+    /// turn it off unconditionally.
+    fn reapply_disabled_hfp(&self, name: &str) -> Result<bool> {
+        if self.is_hfp_enabled(name)? {
+            self.disable_hfp(name)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Battery percentages keyed by `battery::base_device_name`
+    fn battery_levels(&self) -> Result<HashMap<String, u8>>;
+}
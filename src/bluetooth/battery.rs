@@ -0,0 +1,206 @@
+//! Headset battery level reporting
+//!
+//! Headsets report their battery over HFP (Apple accessory `+IPHONEACCEV`,
+//! Plantronics `+XEVENT`, HF indicators `+BIEV` or the `battchg` CIND
+//! indicator) or through the GATT Battery Service. Windows collects whichever
+//! the headset uses and exposes the result as a device property, which
+//! `win32::battery` reads; this module contains pure decoders for all of the
+//! above and the name matching shared by every platform.
+
+use std::collections::{HashMap, HashSet};
+
+/// Raw `DEVPROP_TYPE_BYTE` / `DEVPROP_TYPE_UINT32` values
+pub const PROPERTY_TYPE_BYTE: u32 = 0x03;
+pub const PROPERTY_TYPE_UINT32: u32 = 0x07;
+
+/// Suffixes Windows appends to the per-profile device nodes of a headset
+const PROFILE_NODE_SUFFIXES: &[&str] = &[
+    " hands-free ag audio",
+    " hands-free ag",
+    " hands-free audio",
+    " hands-free",
+    " avrcp transport",
+    " stereo",
+];
+
+/// Percentage rise above the threshold needed before warning again
+const LOW_BATTERY_HYSTERESIS: u8 = 5;
+
+/// Decode the raw value of the Windows battery device property
+///
+/// Windows stores the percentage as a byte; some drivers use a UINT32.
+/// Values above 100 are rejected.
+pub fn decode_battery_property(property_type: u32, data: &[u8]) -> Option<u8> {
+    let value = match property_type {
+        PROPERTY_TYPE_BYTE => *data.first()? as u32,
+        PROPERTY_TYPE_UINT32 => u32::from_le_bytes(data.get(..4)?.try_into().ok()?),
+        _ => return None,
+    };
+    (value <= 100).then_some(value as u8)
+}
+
+/// Decode a GATT Battery Level characteristic (0x2A19) value
+pub fn decode_gatt_battery_level(data: &[u8]) -> Option<u8> {
+    data.first().copied().filter(|&level| level <= 100)
+}
+
+/// Split an AT command or result into its arguments after the given prefix
+///
+/// Accepts both the command (`AT+XEVENT=...`) and result (`+XEVENT: ...`) forms.
+fn at_arguments<'a>(line: &'a str, name: &str) -> Option<Vec<&'a str>> {
+    let line = line.trim();
+    let line = line.strip_prefix("AT").unwrap_or(line);
+    let rest = line.strip_prefix('+')?.strip_prefix(name)?;
+    let rest = rest.strip_prefix('=').or_else(|| rest.strip_prefix(':'))?;
+    Some(rest.split(',').map(str::trim).collect())
+}
+
+/// Decode Apple's `AT+IPHONEACCEV=<count>,<key>,<value>,...`
+///
+/// Key 1 is the battery level as 0-9, meaning 10%-100%.
+pub fn parse_iphoneaccev(line: &str) -> Option<u8> {
+    let args = at_arguments(line, "IPHONEACCEV")?;
+    let count: usize = args.first()?.parse().ok()?;
+
+    (0..count).find_map(|i| {
+        let key: u32 = args.get(1 + i * 2)?.parse().ok()?;
+        let value: u8 = args.get(2 + i * 2)?.parse().ok()?;
+        (key == 1 && value <= 9).then(|| (value + 1) * 10)
+    })
+}
+
+/// Decode `AT+XEVENT=BATTERY,<level>,<levels>,<mV>,<charging>`
+///
+/// `level` runs from 0 to `levels - 1`.
+pub fn parse_xevent_battery(line: &str) -> Option<u8> {
+    let args = at_arguments(line, "XEVENT")?;
+    if !args.first()?.eq_ignore_ascii_case("BATTERY") {
+        return None;
+    }
+
+    let level: u32 = args.get(1)?.parse().ok()?;
+    let levels: u32 = args.get(2)?.parse().ok()?;
+    if levels < 2 || level >= levels {
+        return None;
+    }
+    Some((level * 100 / (levels - 1)) as u8)
+}
+
+/// Decode an HF indicator update `AT+BIEV=2,<percent>` (indicator 2 = battery)
+pub fn parse_biev_battery(line: &str) -> Option<u8> {
+    let args = at_arguments(line, "BIEV")?;
+    let indicator: u32 = args.first()?.parse().ok()?;
+    let value: u8 = args.get(1)?.parse().ok()?;
+    (indicator == 2 && value <= 100).then_some(value)
+}
+
+/// Convert a CIND `battchg` indicator value (0-5) to a percentage
+pub fn battchg_to_percent(level: u8) -> Option<u8> {
+    (level <= 5).then_some(level * 20)
+}
+
+/// Strip the profile suffix from a device node name ("WH-1000XM4 Hands-Free AG")
+pub fn base_device_name(node_name: &str) -> String {
+    let lower = node_name.trim().to_lowercase();
+    PROFILE_NODE_SUFFIXES
+        .iter()
+        .find_map(|suffix| lower.strip_suffix(suffix))
+        .unwrap_or(&lower)
+        .trim()
+        .to_string()
+}
+
+/// Find the battery level for an audio endpoint name
+///
+/// `levels` is keyed by `base_device_name`. Endpoint names usually embed the
+/// Bluetooth name ("Headphones (WH-1000XM4)"), so an exact match is preferred
+/// and the longest contained name wins otherwise.
+pub fn match_battery_level(endpoint_name: &str, levels: &HashMap<String, u8>) -> Option<u8> {
+    let endpoint = base_device_name(endpoint_name);
+
+    if let Some(level) = levels.get(&endpoint) {
+        return Some(*level);
+    }
+
+    levels
+        .iter()
+        .filter(|(name, _)| !name.is_empty() && endpoint.contains(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, level)| *level)
+}
+
+/// Tracks which devices were already warned about low battery
+///
+/// A device is warned once when it drops to the threshold, and again only
+/// after it was charged a few percent above it (or disconnected).
+#[derive(Debug, Default)]
+pub struct LowBatteryTracker {
+    warned: HashSet<String>,
+}
+
+impl LowBatteryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if a low-battery warning should be shown now
+    ///
+    /// A `threshold` of 0 disables warnings.
+    pub fn check(&mut self, device: &str, level: u8, threshold: u8) -> bool {
+        if threshold == 0 {
+            return false;
+        }
+
+        if level <= threshold {
+            self.warned.insert(device.to_string())
+        } else {
+            if level > threshold.saturating_add(LOW_BATTERY_HYSTERESIS) {
+                self.warned.remove(device);
+            }
+            false
+        }
+    }
+
+    /// Forget devices that are no longer connected
+    pub fn retain_devices<'a>(&mut self, present: impl IntoIterator<Item = &'a str>) {
+        let present: HashSet<&str> = present.into_iter().collect();
+        self.warned.retain(|name| present.contains(name.as_str()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_device_name() {
+        assert_eq!(base_device_name("WH-1000XM4 Hands-Free AG"), "wh-1000xm4");
+        assert_eq!(base_device_name("Jabra Evolve2 65 Hands-Free"), "jabra evolve2 65");
+        assert_eq!(base_device_name("AirPods Pro"), "airpods pro");
+    }
+
+    #[test]
+    fn test_low_battery_tracker_hysteresis() {
+        let mut tracker = LowBatteryTracker::new();
+        assert!(!tracker.check("Headset", 50, 20));
+        assert!(tracker.check("Headset", 20, 20));
+        // Only once while low
+        assert!(!tracker.check("Headset", 10, 20));
+        // Small rise does not re-arm
+        assert!(!tracker.check("Headset", 22, 20));
+        assert!(!tracker.check("Headset", 19, 20));
+        // Charged above threshold + hysteresis re-arms
+        assert!(!tracker.check("Headset", 60, 20));
+        assert!(tracker.check("Headset", 15, 20));
+    }
+
+    #[test]
+    fn test_low_battery_tracker_disabled_and_retain() {
+        let mut tracker = LowBatteryTracker::new();
+        assert!(!tracker.check("Headset", 5, 0));
+
+        assert!(tracker.check("Headset", 5, 20));
+        tracker.retain_devices(["Other"]);
+        assert!(tracker.check("Headset", 5, 20));
+    }
+}
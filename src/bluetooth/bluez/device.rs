@@ -0,0 +1,151 @@
+//! Decoding BlueZ `Device1` and `Battery1` objects
+
+use crate::bluetooth::inventory::{decode_class_of_device, BluetoothAddress, BluetoothProfile, DeviceType, PairedDevice};
+use std::collections::HashMap;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+pub const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+pub const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+
+/// Hands-Free Profile, hands-free unit role
+pub const HFP_HF_UUID: &str = "0000111e-0000-1000-8000-00805f9b34fb";
+/// Headset Profile, headset role
+pub const HSP_HS_UUID: &str = "00001108-0000-1000-8000-00805f9b34fb";
+
+/// Interfaces and their properties, as returned by `GetManagedObjects`
+pub type Interfaces = HashMap<String, HashMap<String, OwnedValue>>;
+
+/// A device object exported by BlueZ
+#[derive(Debug, Clone)]
+pub struct BluezDevice {
+    pub path: OwnedObjectPath,
+    pub address: BluetoothAddress,
+    /// User-visible name: the alias if set, else the remote name
+    pub name: String,
+    pub class: u32,
+    pub icon: Option<String>,
+    pub paired: bool,
+    pub trusted: bool,
+    pub connected: bool,
+    /// Service UUIDs the device advertises, lower case
+    pub uuids: Vec<String>,
+    pub battery: Option<u8>,
+}
+
+impl BluezDevice {
+    /// Decode a managed object; `None` if it is not a device
+    pub fn from_interfaces(path: OwnedObjectPath, interfaces: &Interfaces) -> Option<Self> {
+        let props = interfaces.get(DEVICE_INTERFACE)?;
+        let string = |key: &str| props.get(key).and_then(|v| String::try_from(v.clone()).ok());
+        let flag = |key: &str| props.get(key).and_then(|v| bool::try_from(v).ok()).unwrap_or(false);
+
+        let address: BluetoothAddress = string("Address")?.parse().ok()?;
+        let name = string("Alias")
+            .or_else(|| string("Name"))
+            .unwrap_or_else(|| address.to_string());
+        let uuids = props
+            .get("UUIDs")
+            .and_then(|v| Vec::<String>::try_from(v.clone()).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|uuid| uuid.to_lowercase())
+            .collect();
+        let battery = interfaces
+            .get(BATTERY_INTERFACE)
+            .and_then(|battery| battery.get("Percentage"))
+            .and_then(|v| u8::try_from(v).ok());
+
+        Some(Self {
+            path,
+            address,
+            name,
+            class: props.get("Class").and_then(|v| u32::try_from(v).ok()).unwrap_or(0),
+            icon: string("Icon"),
+            paired: flag("Paired") || flag("Bonded"),
+            trusted: flag("Trusted"),
+            connected: flag("Connected"),
+            uuids,
+            battery,
+        })
+    }
+
+    pub fn has_uuid(&self, uuid: &str) -> bool {
+        self.uuids.iter().any(|u| u == uuid)
+    }
+
+    /// HFP and HSP UUIDs the device supports, HFP first
+    pub fn hands_free_uuids(&self) -> Vec<&'static str> {
+        [HFP_HF_UUID, HSP_HS_UUID]
+            .into_iter()
+            .filter(|uuid| self.has_uuid(uuid))
+            .collect()
+    }
+
+    /// Device type from the class of device, or from the icon for LE devices
+    pub fn device_type(&self) -> DeviceType {
+        if self.class != 0 {
+            return decode_class_of_device(self.class);
+        }
+        match self.icon.as_deref() {
+            Some("audio-headset") => DeviceType::Headset,
+            Some("audio-headphones") => DeviceType::Headphones,
+            Some("audio-card") => DeviceType::Loudspeaker,
+            Some(icon) if icon.starts_with("audio") => DeviceType::OtherAudioVideo,
+            _ => DeviceType::NonAudio,
+        }
+    }
+
+    /// Inventory entry; `hfp_disabled` drops the hands-free services like a
+    /// disabled Windows service
+    pub fn paired_device(&self, hfp_disabled: bool) -> PairedDevice {
+        let profiles = self
+            .uuids
+            .iter()
+            .filter_map(|uuid| parse_uuid(uuid))
+            .map(BluetoothProfile::from_uuid)
+            .filter(|profile| {
+                !(hfp_disabled && matches!(profile, BluetoothProfile::HandsFree | BluetoothProfile::Headset))
+            })
+            .collect();
+
+        PairedDevice {
+            address: self.address,
+            name: self.name.clone(),
+            class_of_device: self.class,
+            device_type: self.device_type(),
+            connected: self.connected,
+            remembered: self.paired || self.trusted,
+            authenticated: self.paired,
+            last_seen: None,
+            last_used: None,
+            profiles,
+        }
+    }
+}
+
+/// Parse a 128-bit UUID string such as `0000110b-0000-1000-8000-00805f9b34fb`
+pub fn parse_uuid(uuid: &str) -> Option<u128> {
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uuid() {
+        assert_eq!(
+            parse_uuid(HFP_HF_UUID).map(BluetoothProfile::from_uuid),
+            Some(BluetoothProfile::HandsFree)
+        );
+        assert_eq!(
+            parse_uuid("0000110B-0000-1000-8000-00805F9B34FB").map(BluetoothProfile::from_uuid),
+            Some(BluetoothProfile::A2dpSink)
+        );
+        assert_eq!(parse_uuid("110b"), None);
+    }
+}
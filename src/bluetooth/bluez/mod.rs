@@ -0,0 +1,218 @@
+//! BlueZ Bluetooth backend for Linux
+//!
+//! Talks to `bluetoothd` over the system D-Bus: devices are the
+//! `org.bluez.Device1` objects from `GetManagedObjects`, battery levels come
+//! from `org.bluez.Battery1`. Forcing stereo disconnects the HFP/HSP profiles
+//! with `DisconnectProfile`; BlueZ has no per-device switch to keep them off,
+//! so the backend remembers which devices it disabled and keeps them out of
+//! the profile list until hands-free is enabled again.
+
+pub mod device;
+
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::bluetooth::battery::base_device_name;
+use crate::bluetooth::inventory::PairedDevice;
+use crate::bluetooth::names::find_best_match;
+use crate::error::{AppError, Result};
+use device::BluezDevice;
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use zbus::blocking::fdo::ObjectManagerProxy;
+use zbus::blocking::{Connection, Proxy};
+
+/// Well-known bus name of `bluetoothd`
+pub const BLUEZ_SERVICE: &str = "org.bluez";
+
+/// D-Bus errors BlueZ returns when there is nothing to disconnect
+const NOT_CONNECTED_ERRORS: &[&str] = &["org.bluez.Error.NotConnected", "org.bluez.Error.DoesNotExist"];
+
+/// Bluetooth backend on top of BlueZ
+pub struct BluezBluetooth {
+    connection: Connection,
+    /// Object paths of devices whose hands-free profiles were disconnected
+    hfp_disabled: Mutex<HashSet<String>>,
+}
+
+impl BluezBluetooth {
+    /// Connect to BlueZ on the system bus
+    pub fn system() -> Result<Self> {
+        Ok(Self::with_connection(Connection::system()?))
+    }
+
+    /// Use an existing bus connection on which `org.bluez` is available
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            hfp_disabled: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// All device objects, paired or not
+    fn devices(&self) -> Result<Vec<BluezDevice>> {
+        let manager = ObjectManagerProxy::builder(&self.connection)
+            .destination(BLUEZ_SERVICE)?
+            .path("/")?
+            .build()?;
+
+        let mut devices: Vec<BluezDevice> = manager
+            .get_managed_objects()?
+            .into_iter()
+            .filter_map(|(path, interfaces)| {
+                let interfaces = interfaces
+                    .into_iter()
+                    .map(|(name, props)| (name.to_string(), props))
+                    .collect();
+                BluezDevice::from_interfaces(path, &interfaces)
+            })
+            .collect();
+        devices.sort_by(|a, b| a.path.as_str().cmp(b.path.as_str()));
+        Ok(devices)
+    }
+
+    /// The paired device whose name best matches `name`
+    fn find_device(&self, name: &str) -> Result<BluezDevice> {
+        let devices: Vec<BluezDevice> = self.devices()?.into_iter().filter(|d| d.paired).collect();
+        find_best_match(name, &devices, |d| d.name.as_str())
+            .cloned()
+            .ok_or_else(|| AppError::ConfigError(format!("Bluetooth device '{}' not found", name)))
+    }
+
+    fn call(&self, device: &BluezDevice, method: &str, uuid: Option<&str>) -> zbus::Result<()> {
+        let proxy = Proxy::new(
+            &self.connection,
+            BLUEZ_SERVICE,
+            device.path.as_str().to_string(),
+            device::DEVICE_INTERFACE,
+        )?;
+        match uuid {
+            Some(uuid) => proxy.call_method(method, &(uuid,))?,
+            None => proxy.call_method(method, &())?,
+        };
+        Ok(())
+    }
+
+    fn is_disabled(&self, device: &BluezDevice) -> bool {
+        self.hfp_disabled.lock().unwrap().contains(device.path.as_str())
+    }
+
+    /// Disconnect the device's HFP/HSP profiles, if connected
+    fn disconnect_hands_free(&self, device: &BluezDevice) -> Result<()> {
+        if !device.connected {
+            return Ok(());
+        }
+        for uuid in device.hands_free_uuids() {
+            ignore_not_connected(self.call(device, "DisconnectProfile", Some(uuid)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Treat "not connected" replies as success
+fn ignore_not_connected(result: zbus::Result<()>) -> Result<()> {
+    match result {
+        Err(zbus::Error::MethodError(name, message, _)) if NOT_CONNECTED_ERRORS.contains(&name.as_str()) => {
+            debug!("{}: {}", name, message.unwrap_or_default());
+            Ok(())
+        }
+        other => Ok(other?),
+    }
+}
+
+impl BluetoothBackend for BluezBluetooth {
+    fn list_paired_devices(&self) -> Result<Vec<PairedDevice>> {
+        let devices: Vec<PairedDevice> = self
+            .devices()?
+            .iter()
+            .filter(|d| d.paired)
+            .map(|d| d.paired_device(self.is_disabled(d)))
+            .collect();
+        debug!("Found {} paired Bluetooth devices", devices.len());
+        Ok(devices)
+    }
+
+    fn connect(&self, name: &str) -> Result<()> {
+        info!("Connecting Bluetooth device: {}", name);
+        let device = self.find_device(name)?;
+        if device.connected {
+            info!("Device '{}' is already connected", device.name);
+            return Ok(());
+        }
+        self.call(&device, "Connect", None)?;
+        if self.is_disabled(&device) {
+            self.disconnect_hands_free(&BluezDevice { connected: true, ..device })?;
+        }
+        Ok(())
+    }
+
+    /// Disconnect and connect again, keeping hands-free off if it was disabled
+    fn reconnect(&self, name: &str) -> Result<()> {
+        info!("Reconnecting Bluetooth device: {}", name);
+        let device = self.find_device(name)?;
+        ignore_not_connected(self.call(&device, "Disconnect", None))?;
+        self.call(&device, "Connect", None)?;
+        if self.is_disabled(&device) {
+            self.disconnect_hands_free(&BluezDevice { connected: true, ..device })?;
+        }
+        info!("Successfully reconnected device: {}", name);
+        Ok(())
+    }
+
+    /// `false` for devices without HFP/HSP and for devices this backend disabled
+    fn is_hfp_enabled(&self, name: &str) -> Result<bool> {
+        let device = self.find_device(name)?;
+        Ok(!device.hands_free_uuids().is_empty() && !self.is_disabled(&device))
+    }
+
+    fn disable_hfp(&self, name: &str) -> Result<()> {
+        info!("Disabling HFP for device: {}", name);
+        let device = self.find_device(name)?;
+        if device.hands_free_uuids().is_empty() {
+            warn!("Device '{}' does not support HFP or HSP", device.name);
+            return Err(AppError::ConfigError(
+                "Device does not support Hands-Free Profile".to_string(),
+            ));
+        }
+
+        self.hfp_disabled.lock().unwrap().insert(device.path.to_string());
+        self.disconnect_hands_free(&device)?;
+        info!("HFP disabled for '{}' - device should switch to stereo mode", device.name);
+        Ok(())
+    }
+
+    /// Forget the disabled state and connect HFP (or HSP) again
+    fn enable_hfp(&self, name: &str) -> Result<()> {
+        info!("Enabling HFP for device: {}", name);
+        let device = self.find_device(name)?;
+        self.hfp_disabled.lock().unwrap().remove(device.path.as_str());
+
+        if let (true, Some(uuid)) = (device.connected, device.hands_free_uuids().first()) {
+            self.call(&device, "ConnectProfile", Some(uuid))?;
+        }
+        info!("HFP enabled for '{}' - hands-free mode now available", device.name);
+        Ok(())
+    }
+
+    /// Disconnect HFP/HSP again whenever the device is connected
+    ///
+    /// BlueZ does not say which profiles are connected, and a headset that
+    /// reconnects on its own brings hands-free back, so the disabled state
+    /// cannot be trusted after a reconnect.
+    fn reapply_disabled_hfp(&self, name: &str) -> Result<bool> {
+        let device = self.find_device(name)?;
+        if !device.connected || device.hands_free_uuids().is_empty() {
+            return Ok(false);
+        }
+        self.hfp_disabled.lock().unwrap().insert(device.path.to_string());
+        self.disconnect_hands_free(&device)?;
+        Ok(true)
+    }
+
+    fn battery_levels(&self) -> Result<HashMap<String, u8>> {
+        Ok(self
+            .devices()?
+            .into_iter()
+            .filter_map(|d| Some((base_device_name(&d.name), d.battery?)))
+            .collect())
+    }
+}
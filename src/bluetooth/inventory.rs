@@ -0,0 +1,294 @@
+//! Paired Bluetooth device inventory
+//!
+//! Typed view of what `BLUETOOTH_DEVICE_INFO` reports for each paired device:
+//! address, class of device, connection flags, timestamps and installed
+//! profiles. The decoders here are pure so they can be tested on any platform.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bluetooth base UUID (`0000xxxx-0000-1000-8000-00805F9B34FB`) without the short UUID
+const BLUETOOTH_BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805F9B34FB;
+
+/// Mask selecting the 16-bit short UUID inside a base UUID
+const SHORT_UUID_MASK: u128 = 0x0000FFFF_0000_0000_0000_000000000000;
+
+/// Major device class for audio/video devices
+const MAJOR_CLASS_AUDIO_VIDEO: u32 = 0x04;
+
+/// 48-bit Bluetooth device address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BluetoothAddress(pub u64);
+
+impl fmt::Display for BluetoothAddress {
+    /// Formats as `AA:BB:CC:DD:EE:FF`, most significant byte first
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.0.to_be_bytes();
+        write!(
+            f,
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+            bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]
+        )
+    }
+}
+
+impl std::str::FromStr for BluetoothAddress {
+    type Err = String;
+
+    /// Parses `AA:BB:CC:DD:EE:FF`; `_` and `-` are accepted as separators
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split([':', '_', '-']).collect();
+        if parts.len() != 6 || parts.iter().any(|p| p.len() != 2) {
+            return Err(format!("Invalid Bluetooth address '{}'", s));
+        }
+
+        parts.iter().try_fold(0u64, |acc, part| {
+            u8::from_str_radix(part, 16)
+                .map(|byte| (acc << 8) | byte as u64)
+                .map_err(|_| format!("Invalid Bluetooth address '{}'", s))
+        })
+        .map(BluetoothAddress)
+    }
+}
+
+/// Device type decoded from the class of device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    Headset,
+    HandsFree,
+    Microphone,
+    Loudspeaker,
+    Headphones,
+    PortableAudio,
+    CarAudio,
+    HifiAudio,
+    /// Audio/video device of another kind (set-top box, camera, ...)
+    OtherAudioVideo,
+    /// Not an audio/video device (phone, computer, input device, ...)
+    NonAudio,
+}
+
+impl DeviceType {
+    /// Whether this is a device we can route audio to or from
+    pub fn is_audio(&self) -> bool {
+        !matches!(self, DeviceType::OtherAudioVideo | DeviceType::NonAudio)
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DeviceType::Headset => "Headset",
+            DeviceType::HandsFree => "Hands-Free",
+            DeviceType::Microphone => "Microphone",
+            DeviceType::Loudspeaker => "Loudspeaker",
+            DeviceType::Headphones => "Headphones",
+            DeviceType::PortableAudio => "Portable Audio",
+            DeviceType::CarAudio => "Car Audio",
+            DeviceType::HifiAudio => "HiFi Audio",
+            DeviceType::OtherAudioVideo => "Audio/Video",
+            DeviceType::NonAudio => "Other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Decode the device type from a 24-bit class of device
+///
+/// Bits 8-12 hold the major class and bits 2-7 the minor class.
+pub fn decode_class_of_device(class_of_device: u32) -> DeviceType {
+    let major = (class_of_device >> 8) & 0x1F;
+    let minor = (class_of_device >> 2) & 0x3F;
+
+    if major != MAJOR_CLASS_AUDIO_VIDEO {
+        return DeviceType::NonAudio;
+    }
+
+    match minor {
+        1 => DeviceType::Headset,
+        2 => DeviceType::HandsFree,
+        4 => DeviceType::Microphone,
+        5 => DeviceType::Loudspeaker,
+        6 => DeviceType::Headphones,
+        7 => DeviceType::PortableAudio,
+        8 => DeviceType::CarAudio,
+        10 => DeviceType::HifiAudio,
+        _ => DeviceType::OtherAudioVideo,
+    }
+}
+
+/// Bluetooth profile decoded from an installed service UUID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BluetoothProfile {
+    /// Headset Profile (0x1108, 0x1131)
+    Headset,
+    /// Headset Profile audio gateway (0x1112)
+    HeadsetAudioGateway,
+    /// Advanced Audio Distribution source (0x110A)
+    A2dpSource,
+    /// Advanced Audio Distribution sink (0x110B)
+    A2dpSink,
+    /// Audio/Video Remote Control (0x110C, 0x110E, 0x110F)
+    Avrcp,
+    /// Hands-Free Profile (0x111E)
+    HandsFree,
+    /// Hands-Free audio gateway (0x111F)
+    HandsFreeAudioGateway,
+    /// Other service in the Bluetooth base UUID range
+    Other(u16),
+    /// Vendor-specific service UUID
+    Vendor(u128),
+}
+
+impl BluetoothProfile {
+    /// Decode a service UUID given as a 128-bit value
+    pub fn from_uuid(uuid: u128) -> Self {
+        if uuid & !SHORT_UUID_MASK != BLUETOOTH_BASE_UUID {
+            return BluetoothProfile::Vendor(uuid);
+        }
+
+        match ((uuid & SHORT_UUID_MASK) >> 96) as u16 {
+            0x1108 | 0x1131 => BluetoothProfile::Headset,
+            0x1112 => BluetoothProfile::HeadsetAudioGateway,
+            0x110A => BluetoothProfile::A2dpSource,
+            0x110B => BluetoothProfile::A2dpSink,
+            0x110C | 0x110E | 0x110F => BluetoothProfile::Avrcp,
+            0x111E => BluetoothProfile::HandsFree,
+            0x111F => BluetoothProfile::HandsFreeAudioGateway,
+            short => BluetoothProfile::Other(short),
+        }
+    }
+}
+
+/// Convert the fields of a UTC `SYSTEMTIME` to a `SystemTime`
+///
+/// Windows reports an all-zero `SYSTEMTIME` for "never"; that and any
+/// out-of-range value yield `None`.
+pub fn system_time_from_parts(
+    year: u16,
+    month: u16,
+    day: u16,
+    hour: u16,
+    minute: u16,
+    second: u16,
+    milliseconds: u16,
+) -> Option<SystemTime> {
+    if year < 1970
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+        || milliseconds > 999
+    {
+        return None;
+    }
+
+    // Days since 1970-01-01 (Howard Hinnant's days_from_civil)
+    let (y, m, d) = (year as i64, month as i64, day as i64);
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days as u64 * 86_400 + hour as u64 * 3_600 + minute as u64 * 60 + second as u64;
+    Some(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(milliseconds as u64))
+}
+
+/// A paired (remembered or authenticated) Bluetooth device
+#[derive(Debug, Clone)]
+pub struct PairedDevice {
+    pub address: BluetoothAddress,
+    pub name: String,
+    /// Raw 24-bit class of device
+    pub class_of_device: u32,
+    pub device_type: DeviceType,
+    pub connected: bool,
+    pub remembered: bool,
+    pub authenticated: bool,
+    pub last_seen: Option<SystemTime>,
+    pub last_used: Option<SystemTime>,
+    /// Installed (enabled) services; disabled services such as a forced-off HFP are absent
+    pub profiles: Vec<BluetoothProfile>,
+}
+
+impl PairedDevice {
+    /// Whether this is an audio device that is paired but not connected
+    pub fn is_disconnected_audio(&self) -> bool {
+        !self.connected && self.device_type.is_audio()
+    }
+
+    /// Whether the device has the given profile installed
+    pub fn has_profile(&self, profile: BluetoothProfile) -> bool {
+        self.profiles.contains(&profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_display() {
+        assert_eq!(BluetoothAddress(0x0011_2233_AABB).to_string(), "00:11:22:33:AA:BB");
+        assert_eq!(BluetoothAddress(0xA1B2_C3D4_E5F6).to_string(), "A1:B2:C3:D4:E5:F6");
+    }
+
+    #[test]
+    fn test_address_from_str() {
+        let address: BluetoothAddress = "AC:80:0A:2B:3C:4D".parse().unwrap();
+        assert_eq!(address, BluetoothAddress(0xAC80_0A2B_3C4D));
+        assert_eq!("ac_80_0a_2b_3c_4d".parse::<BluetoothAddress>(), Ok(address));
+        assert!("AC:80:0A:2B:3C".parse::<BluetoothAddress>().is_err());
+        assert!("AC:80:0A:2B:3C:ZZ".parse::<BluetoothAddress>().is_err());
+    }
+
+    #[test]
+    fn test_decode_class_of_device() {
+        // Typical headphones: service bits + Audio/Video major + Headphones minor
+        assert_eq!(decode_class_of_device(0x240418), DeviceType::Headphones);
+        // Wearable headset
+        assert_eq!(decode_class_of_device(0x240404), DeviceType::Headset);
+        // Hands-free car kit
+        assert_eq!(decode_class_of_device(0x240408), DeviceType::HandsFree);
+        // Smartphone (major class Phone)
+        assert_eq!(decode_class_of_device(0x5A020C), DeviceType::NonAudio);
+        // Video camera is audio/video but not an audio device
+        assert_eq!(decode_class_of_device(0x000434), DeviceType::OtherAudioVideo);
+        assert!(!DeviceType::OtherAudioVideo.is_audio());
+        assert!(DeviceType::Loudspeaker.is_audio());
+    }
+
+    #[test]
+    fn test_profile_from_uuid() {
+        assert_eq!(
+            BluetoothProfile::from_uuid(0x0000111E_0000_1000_8000_00805F9B34FB),
+            BluetoothProfile::HandsFree
+        );
+        assert_eq!(
+            BluetoothProfile::from_uuid(0x0000110B_0000_1000_8000_00805F9B34FB),
+            BluetoothProfile::A2dpSink
+        );
+        assert_eq!(
+            BluetoothProfile::from_uuid(0x00001200_0000_1000_8000_00805F9B34FB),
+            BluetoothProfile::Other(0x1200)
+        );
+        let vendor = 0x9BD708D7_64C7_4A9F_9A7B_0D7E4F1A2B3C;
+        assert_eq!(BluetoothProfile::from_uuid(vendor), BluetoothProfile::Vendor(vendor));
+    }
+
+    #[test]
+    fn test_system_time_from_parts() {
+        assert_eq!(system_time_from_parts(1970, 1, 1, 0, 0, 0, 0), Some(UNIX_EPOCH));
+        assert_eq!(
+            system_time_from_parts(2024, 2, 29, 12, 30, 15, 250),
+            Some(UNIX_EPOCH + Duration::from_millis(1_709_209_815_250))
+        );
+        // Zeroed SYSTEMTIME means "never"
+        assert_eq!(system_time_from_parts(0, 0, 0, 0, 0, 0, 0), None);
+        assert_eq!(system_time_from_parts(2024, 13, 1, 0, 0, 0, 0), None);
+    }
+}
@@ -1,7 +1,24 @@
 //! Bluetooth device control module
 //!
-//! Provides functionality to enumerate and control Bluetooth devices using Win32 APIs.
+//! Paired device inventory, battery decoding and name matching are portable;
+//! device control goes through a `BluetoothBackend`, implemented with the
+//! Win32 APIs in `win32` and with BlueZ over D-Bus in `bluez`.
 
-pub mod control;
+pub mod backend;
+pub mod battery;
+#[cfg(target_os = "linux")]
+pub mod bluez;
+pub mod inventory;
+pub mod names;
+#[cfg(windows)]
+pub mod win32;
 
-pub use control::{disable_hfp_by_name, enable_hfp_by_name, reconnect_by_name};
+pub use backend::BluetoothBackend;
+pub use inventory::{BluetoothAddress, BluetoothProfile, DeviceType, PairedDevice};
+#[cfg(windows)]
+pub use win32::control::{
+    connect_by_name, disable_hfp_by_name, enable_hfp_by_name, is_hfp_enabled_by_name,
+    list_paired_devices, reconnect_by_name,
+};
+#[cfg(windows)]
+pub use win32::Win32Bluetooth;
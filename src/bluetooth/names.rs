@@ -0,0 +1,140 @@
+//! Matching user-supplied device names against the names of paired devices
+
+use super::battery::base_device_name;
+
+/// Match quality for device name matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchQuality {
+    NoMatch = 0,
+    Contains = 1,
+    Exact = 2,
+}
+
+/// Normalize a device name for comparison
+pub fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Check if target name matches device name
+pub fn check_name_match(target_normalized: &str, device_name: &str) -> MatchQuality {
+    let device_normalized = normalize_name(device_name);
+
+    // Exact match (case-insensitive)
+    if target_normalized == device_normalized {
+        return MatchQuality::Exact;
+    }
+
+    // Contains match (either direction)
+    if target_normalized.contains(&device_normalized)
+        || device_normalized.contains(target_normalized) {
+        return MatchQuality::Contains;
+    }
+
+    MatchQuality::NoMatch
+}
+
+/// Pick the candidate whose name best matches `target`
+///
+/// An exact (case-insensitive) match wins over a substring match; among
+/// equal matches the first candidate is kept.
+pub fn find_best_match<'a, T>(
+    target: &str,
+    candidates: &'a [T],
+    name_of: impl Fn(&T) -> &str,
+) -> Option<&'a T> {
+    let target_normalized = normalize_name(target);
+    let mut best: Option<(&T, MatchQuality)> = None;
+
+    for candidate in candidates {
+        let quality = check_name_match(&target_normalized, name_of(candidate));
+        if quality != MatchQuality::NoMatch && best.is_none_or(|(_, q)| quality > q) {
+            best = Some((candidate, quality));
+        }
+    }
+
+    best.map(|(candidate, _)| candidate)
+}
+
+/// Paired device name, among `paired_names`, that an audio endpoint belongs to
+///
+/// Endpoint names usually wrap the Bluetooth name ("Headphones (WH-1000XM4)"),
+/// so an exact match (ignoring case and a profile suffix) wins and the
+/// longest paired name contained in the endpoint name is taken otherwise.
+/// Forced-stereo devices are keyed by paired name, so this also tells
+/// whether an endpoint is forced.
+pub fn paired_name_for_endpoint<'a>(
+    endpoint_name: &str,
+    paired_names: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let endpoint = base_device_name(endpoint_name);
+    let mut best: Option<(&str, usize)> = None;
+
+    for name in paired_names {
+        let paired = normalize_name(name);
+        if paired == endpoint {
+            return Some(name);
+        }
+        if !paired.is_empty() && endpoint.contains(&paired) && best.is_none_or(|(_, len)| paired.len() > len) {
+            best = Some((name, paired.len()));
+        }
+    }
+
+    best.map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_best_match_prefers_exact() {
+        let names = ["WH-1000XM4 Hands-Free", "WH-1000XM4", "Speaker"];
+        assert_eq!(find_best_match("wh-1000xm4", &names, |n| n), Some(&"WH-1000XM4"));
+        assert_eq!(find_best_match("speak", &names, |n| n), Some(&"Speaker"));
+        assert_eq!(find_best_match("Buds", &names, |n| n), None);
+    }
+
+    #[test]
+    fn test_paired_name_for_endpoint() {
+        let paired = ["WH-1000XM4", "Buds", "Buds Pro"];
+        let find = |endpoint| paired_name_for_endpoint(endpoint, paired.iter().copied());
+        assert_eq!(find("Headphones (WH-1000XM4)"), Some("WH-1000XM4"));
+        assert_eq!(find("wh-1000xm4 Hands-Free AG"), Some("WH-1000XM4"));
+        assert_eq!(find("Headset (Buds Pro)"), Some("Buds Pro"));
+        assert_eq!(find("Buds"), Some("Buds"));
+        assert_eq!(find("Speakers (Realtek)"), None);
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("  Sony WH-1000XM4  "), "sony wh-1000xm4");
+        assert_eq!(normalize_name("HEADPHONES"), "headphones");
+    }
+
+    #[test]
+    fn test_check_name_match_exact() {
+        let target = "sony wh-1000xm4";
+        let device = "Sony WH-1000XM4";
+        assert_eq!(check_name_match(target, device), MatchQuality::Exact);
+    }
+
+    #[test]
+    fn test_check_name_match_contains() {
+        let target = "sony";
+        let device = "Sony WH-1000XM4";
+        assert_eq!(check_name_match(target, device), MatchQuality::Contains);
+    }
+
+    #[test]
+    fn test_check_name_match_no_match() {
+        let target = "bose";
+        let device = "Sony WH-1000XM4";
+        assert_eq!(check_name_match(target, device), MatchQuality::NoMatch);
+    }
+
+    #[test]
+    fn test_match_quality_ordering() {
+        assert!(MatchQuality::Exact > MatchQuality::Contains);
+        assert!(MatchQuality::Contains > MatchQuality::NoMatch);
+    }
+}
@@ -0,0 +1,102 @@
+//! Reading the Windows battery device property
+
+use crate::bluetooth::battery::{base_device_name, decode_battery_property};
+use crate::error::Result;
+use log::debug;
+use std::collections::HashMap;
+use std::mem;
+use windows::core::{GUID, PCWSTR};
+use windows::Win32::Devices::DeviceAndDriverInstallation::{
+    SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW,
+    SetupDiGetDevicePropertyW, DIGCF_ALLCLASSES, DIGCF_PRESENT, HDEVINFO, SP_DEVINFO_DATA,
+};
+use windows::Win32::Devices::Properties::{
+    DEVPKEY_Device_FriendlyName, DEVPROPKEY, DEVPROPTYPE, DEVPROP_TYPE_STRING,
+};
+use windows::Win32::Foundation::HWND;
+
+/// Battery percentage device property `{104EA319-6EE2-4701-BD47-8DDBF425BBE5},2`
+const BATTERY_PROPERTY_KEY: DEVPROPKEY = DEVPROPKEY {
+    fmtid: GUID::from_u128(0x104EA319_6EE2_4701_BD47_8DDBF425BBE5),
+    pid: 2,
+};
+
+/// Read battery levels for all present Bluetooth devices
+///
+/// Returns percentages keyed by `base_device_name` of the device node. Nodes
+/// without the battery property are skipped.
+pub fn read_battery_levels() -> Result<HashMap<String, u8>> {
+    let mut levels = HashMap::new();
+
+    unsafe {
+        let set = SetupDiGetClassDevsW(
+            None,
+            PCWSTR::null(),
+            HWND::default(),
+            DIGCF_ALLCLASSES | DIGCF_PRESENT,
+        )?;
+
+        let mut index = 0;
+        loop {
+            let mut info = SP_DEVINFO_DATA {
+                cbSize: mem::size_of::<SP_DEVINFO_DATA>() as u32,
+                ..Default::default()
+            };
+            if SetupDiEnumDeviceInfo(set, index, &mut info).is_err() {
+                break;
+            }
+            index += 1;
+
+            let mut value = [0u8; 8];
+            let level = match read_device_property(set, &info, &BATTERY_PROPERTY_KEY, &mut value) {
+                Some((prop_type, size)) => decode_battery_property(prop_type, &value[..size]),
+                None => None,
+            };
+            let Some(level) = level else {
+                continue;
+            };
+
+            let mut name_buf = [0u8; 512];
+            let name = match read_device_property(set, &info, &DEVPKEY_Device_FriendlyName, &mut name_buf) {
+                Some((prop_type, size)) if prop_type == DEVPROP_TYPE_STRING.0 => {
+                    let wide: Vec<u16> = name_buf[..size]
+                        .chunks_exact(2)
+                        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                        .take_while(|&c| c != 0)
+                        .collect();
+                    String::from_utf16_lossy(&wide)
+                }
+                _ => continue,
+            };
+
+            debug!("Battery level for '{}': {}%", name, level);
+            levels.entry(base_device_name(&name)).or_insert(level);
+        }
+
+        let _ = SetupDiDestroyDeviceInfoList(set);
+    }
+
+    Ok(levels)
+}
+
+/// Read a device property into `buffer`, returning its type and size
+unsafe fn read_device_property(
+    set: HDEVINFO,
+    info: &SP_DEVINFO_DATA,
+    key: &DEVPROPKEY,
+    buffer: &mut [u8],
+) -> Option<(u32, usize)> {
+    let mut prop_type = DEVPROPTYPE::default();
+    let mut size = 0u32;
+    SetupDiGetDevicePropertyW(
+        set,
+        info,
+        key,
+        &mut prop_type,
+        Some(&mut *buffer),
+        Some(&mut size as *mut u32),
+        0,
+    )
+    .ok()?;
+    Some((prop_type.0, (size as usize).min(buffer.len())))
+}
@@ -3,7 +3,13 @@
 //! Provides Win32 API-based control of Bluetooth audio devices, including
 //! device enumeration and service reconnection.
 
+use crate::bluetooth::inventory::{
+    decode_class_of_device, system_time_from_parts, BluetoothAddress, BluetoothProfile,
+    PairedDevice,
+};
+use crate::bluetooth::names::{check_name_match, normalize_name, MatchQuality};
 use crate::error::{AppError, Result};
+use crate::retry::{classify_win32_error, RetryPolicy};
 use log::{debug, info, warn};
 use std::mem;
 use std::thread;
@@ -14,7 +20,9 @@ use windows::Win32::Devices::Bluetooth::{
     BluetoothFindNextDevice, BluetoothSetServiceState, BLUETOOTH_DEVICE_INFO,
     BLUETOOTH_DEVICE_SEARCH_PARAMS, HBLUETOOTH_DEVICE_FIND,
 };
-use windows::Win32::Foundation::{BOOL, ERROR_NOT_FOUND, ERROR_SERVICE_DOES_NOT_EXIST, HANDLE};
+use windows::Win32::Foundation::{
+    BOOL, ERROR_NOT_FOUND, ERROR_NO_MORE_ITEMS, ERROR_SERVICE_DOES_NOT_EXIST, HANDLE, SYSTEMTIME,
+};
 
 /// Delay in milliseconds between disabling and re-enabling services
 const RECONNECT_DELAY_MS: u64 = 1000;
@@ -69,6 +77,147 @@ pub fn reconnect_by_name(name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Connect a paired Bluetooth device that is currently disconnected
+///
+/// Windows has no direct "connect" API for classic audio devices; toggling
+/// the installed services makes the Bluetooth stack page the device, which is
+/// what the Settings app does as well.
+///
+/// # Arguments
+/// * `name` - The friendly name of the device to connect
+///
+/// # Returns
+/// * `Ok(())` if the services were re-enabled (or the device is already connected)
+/// * `Err(AppError)` if the device was not found or the services could not be toggled
+pub fn connect_by_name(name: &str) -> Result<()> {
+    info!("Connecting Bluetooth device: {}", name);
+
+    let device_info = find_bluetooth_device_by_name(name)?;
+
+    if device_info.fConnected.as_bool() {
+        info!("Device '{}' is already connected", name);
+        return Ok(());
+    }
+
+    let services = get_device_services(&device_info)?;
+
+    if services.is_empty() {
+        warn!("No services found for device: {}", name);
+        return Err(AppError::ConfigError(
+            "Device has no Bluetooth services configured".to_string(),
+        ));
+    }
+
+    reconnect_device(&device_info, &services)?;
+
+    info!("Connect requested for device: {}", name);
+    Ok(())
+}
+
+/// List all paired Bluetooth devices with their connection state
+///
+/// Includes remembered devices that are currently disconnected. Installed
+/// profiles are looked up per device; a device whose services cannot be
+/// enumerated is still listed with an empty profile list.
+///
+/// # Returns
+/// * `Ok(Vec<PairedDevice>)` - paired devices, empty if there are none
+/// * `Err(AppError)` if enumeration failed for another reason
+pub fn list_paired_devices() -> Result<Vec<PairedDevice>> {
+    let mut devices = Vec::new();
+
+    unsafe {
+        let search_params = BLUETOOTH_DEVICE_SEARCH_PARAMS {
+            dwSize: mem::size_of::<BLUETOOTH_DEVICE_SEARCH_PARAMS>() as u32,
+            fReturnAuthenticated: BOOL(1),
+            fReturnRemembered: BOOL(1),
+            fReturnUnknown: BOOL(0),
+            fReturnConnected: BOOL(1),
+            fIssueInquiry: BOOL(0),
+            cTimeoutMultiplier: 1,
+            hRadio: HANDLE::default(),
+        };
+
+        let mut device_info = BLUETOOTH_DEVICE_INFO {
+            dwSize: mem::size_of::<BLUETOOTH_DEVICE_INFO>() as u32,
+            ..Default::default()
+        };
+
+        let h_find = match BluetoothFindFirstDevice(&search_params, &mut device_info) {
+            Ok(handle) if !handle.is_invalid() => handle,
+            Ok(_) => return Ok(devices),
+            // Nothing is paired
+            Err(e) if e.code() == ERROR_NO_MORE_ITEMS.to_hresult() => {
+                debug!("No paired Bluetooth devices");
+                return Ok(devices);
+            }
+            Err(e) => {
+                warn!("Failed to enumerate paired Bluetooth devices: {}", e);
+                return Err(AppError::WindowsApiError(e));
+            }
+        };
+
+        loop {
+            devices.push(paired_device_from_info(&device_info));
+
+            device_info = BLUETOOTH_DEVICE_INFO {
+                dwSize: mem::size_of::<BLUETOOTH_DEVICE_INFO>() as u32,
+                ..Default::default()
+            };
+
+            if BluetoothFindNextDevice(h_find, &mut device_info).is_err() {
+                break;
+            }
+        }
+
+        let _ = BluetoothFindDeviceClose(h_find);
+    }
+
+    debug!("Found {} paired Bluetooth devices", devices.len());
+    Ok(devices)
+}
+
+/// Build a `PairedDevice` from the raw device info
+fn paired_device_from_info(info: &BLUETOOTH_DEVICE_INFO) -> PairedDevice {
+    let profiles = match get_device_services(info) {
+        Ok(services) => services
+            .iter()
+            .map(|guid| BluetoothProfile::from_uuid(guid.to_u128()))
+            .collect(),
+        Err(e) => {
+            debug!("Could not enumerate services for '{}': {}", device_name_from_info(info), e);
+            Vec::new()
+        }
+    };
+
+    PairedDevice {
+        // Both union fields cover the same 6 address bytes
+        address: BluetoothAddress(unsafe { info.Address.Anonymous.ullLong } & 0xFFFF_FFFF_FFFF),
+        name: device_name_from_info(info),
+        class_of_device: info.ulClassofDevice,
+        device_type: decode_class_of_device(info.ulClassofDevice),
+        connected: info.fConnected.as_bool(),
+        remembered: info.fRemembered.as_bool(),
+        authenticated: info.fAuthenticated.as_bool(),
+        last_seen: system_time_from_systemtime(&info.stLastSeen),
+        last_used: system_time_from_systemtime(&info.stLastUsed),
+        profiles,
+    }
+}
+
+/// Convert a Win32 `SYSTEMTIME` (UTC) to a `SystemTime`
+fn system_time_from_systemtime(st: &SYSTEMTIME) -> Option<std::time::SystemTime> {
+    system_time_from_parts(
+        st.wYear,
+        st.wMonth,
+        st.wDay,
+        st.wHour,
+        st.wMinute,
+        st.wSecond,
+        st.wMilliseconds,
+    )
+}
+
 /// Disable HFP (Hands-Free Profile) for a Bluetooth device to force stereo mode
 ///
 /// This disables only the HFP service, keeping A2DP (stereo audio) connected.
@@ -103,6 +252,23 @@ pub fn disable_hfp_by_name(name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Check whether the HFP service is currently enabled for a Bluetooth device
+///
+/// Disabled services are not reported as installed, so this is `false` both
+/// after `disable_hfp_by_name` and for devices without HFP support.
+///
+/// # Arguments
+/// * `name` - The friendly name of the device
+///
+/// # Returns
+/// * `Ok(bool)` - whether HFP is enabled
+/// * `Err(AppError)` if the device was not found or enumeration failed
+pub fn is_hfp_enabled_by_name(name: &str) -> Result<bool> {
+    let device_info = find_bluetooth_device_by_name(name)?;
+    let services = get_device_services(&device_info)?;
+    Ok(services.iter().any(|s| *s == HFP_SERVICE_GUID))
+}
+
 /// Enable HFP (Hands-Free Profile) for a Bluetooth device to allow hands-free mode
 ///
 /// This re-enables the HFP service after it was disabled by `disable_hfp_by_name`.
@@ -248,19 +414,6 @@ fn find_matching_device(
     }
 }
 
-/// Match quality for device name matching
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-enum MatchQuality {
-    NoMatch = 0,
-    Contains = 1,
-    Exact = 2,
-}
-
-/// Normalize a device name for comparison
-fn normalize_name(name: &str) -> String {
-    name.trim().to_lowercase()
-}
-
 /// Extract device name from BLUETOOTH_DEVICE_INFO
 fn device_name_from_info(info: &BLUETOOTH_DEVICE_INFO) -> String {
     let name_u16: Vec<u16> = info
@@ -273,24 +426,6 @@ fn device_name_from_info(info: &BLUETOOTH_DEVICE_INFO) -> String {
     String::from_utf16_lossy(&name_u16)
 }
 
-/// Check if target name matches device name
-fn check_name_match(target_normalized: &str, device_name: &str) -> MatchQuality {
-    let device_normalized = normalize_name(device_name);
-
-    // Exact match (case-insensitive)
-    if target_normalized == device_normalized {
-        return MatchQuality::Exact;
-    }
-
-    // Contains match (either direction)
-    if target_normalized.contains(&device_normalized)
-        || device_normalized.contains(target_normalized) {
-        return MatchQuality::Contains;
-    }
-
-    MatchQuality::NoMatch
-}
-
 /// Get installed Bluetooth services for a device
 ///
 /// # Arguments
@@ -300,35 +435,40 @@ fn check_name_match(target_normalized: &str, device_name: &str) -> MatchQuality
 /// * `Ok(Vec<GUID>)` - List of installed service GUIDs
 /// * `Err(AppError)` if enumeration failed
 fn get_device_services(device: &BLUETOOTH_DEVICE_INFO) -> Result<Vec<GUID>> {
-    unsafe {
-        let mut service_count: u32 = MAX_SERVICES as u32;
-        let mut services: Vec<GUID> = vec![GUID::zeroed(); MAX_SERVICES];
-
-        let result = BluetoothEnumerateInstalledServices(
-            HANDLE::default(),
-            device,
-            &mut service_count,
-            Some(services.as_mut_ptr()),
-        );
-
-        if result != 0 {
-            return Err(AppError::ConfigError(
-                "Could not enumerate device services".to_string(),
-            ));
-        }
+    let services = RetryPolicy::BLUETOOTH
+        .run(
+            "Enumerate Bluetooth services",
+            |code| classify_win32_error(*code),
+            |_| unsafe {
+                let mut service_count: u32 = MAX_SERVICES as u32;
+                let mut services: Vec<GUID> = vec![GUID::zeroed(); MAX_SERVICES];
+
+                let result = BluetoothEnumerateInstalledServices(
+                    HANDLE::default(),
+                    device,
+                    &mut service_count,
+                    Some(services.as_mut_ptr()),
+                );
+
+                if result != 0 {
+                    return Err(result);
+                }
 
-        // Truncate to actual count
-        services.truncate(service_count as usize);
+                // Truncate to actual count
+                services.truncate(service_count as usize);
+                Ok(services)
+            },
+        )
+        .map_err(|_| AppError::ConfigError("Could not enumerate device services".to_string()))?;
 
-        debug!("Found {} services for device", service_count);
-        Ok(services)
-    }
+    debug!("Found {} services for device", services.len());
+    Ok(services)
 }
 
 /// Reconnect a device by disabling and re-enabling its services
 ///
-/// Implements partial failure recovery: if re-enable fails for some services,
-/// retries individually before giving up.
+/// Transient failures are retried per service by `enable_service` and
+/// `disable_service`; services that still fail are reported together.
 ///
 /// # Arguments
 /// * `device` - The device to reconnect
@@ -364,34 +504,19 @@ fn reconnect_device(device: &BLUETOOTH_DEVICE_INFO, services: &[GUID]) -> Result
             Ok(_) => debug!("Enabled service {}/{}", i + 1, services.len()),
             Err(e) => {
                 warn!("Failed to enable service {}: {}", i + 1, e);
-                failed_services.push((i, *service, e));
+                failed_services.push(i);
             }
         }
     }
 
-    // Retry failed services
+    // If any services still failed after retrying, return error
     if !failed_services.is_empty() {
-        warn!("Retrying {} failed services", failed_services.len());
-        thread::sleep(Duration::from_millis(500));
-
-        let mut still_failed = Vec::new();
-        for (i, service, _) in failed_services {
-            if let Err(e) = enable_service(device, &service) {
-                still_failed.push((i, e));
-            } else {
-                debug!("Retry succeeded for service {}", i + 1);
-            }
-        }
-
-        // If any services still failed, return error
-        if !still_failed.is_empty() {
-            let error_msg = format!(
-                "Failed to reconnect {} of {} services. Try reconnecting manually via Windows Bluetooth settings.",
-                still_failed.len(),
-                services.len()
-            );
-            return Err(AppError::ConfigError(error_msg));
-        }
+        let error_msg = format!(
+            "Failed to reconnect {} of {} services. Try reconnecting manually via Windows Bluetooth settings.",
+            failed_services.len(),
+            services.len()
+        );
+        return Err(AppError::ConfigError(error_msg));
     }
 
     Ok(())
@@ -399,38 +524,43 @@ fn reconnect_device(device: &BLUETOOTH_DEVICE_INFO, services: &[GUID]) -> Result
 
 /// Disable a Bluetooth service
 fn disable_service(device: &BLUETOOTH_DEVICE_INFO, service: &GUID) -> Result<()> {
-    unsafe {
-        let result = BluetoothSetServiceState(
-            HANDLE::default(),
-            device,
-            service,
-            0, // 0 = disable
-        );
-
-        if result != 0 {
-            return Err(map_win32_error(result, "disable"));
-        }
-
-        Ok(())
-    }
+    set_service_state(device, service, false)
 }
 
 /// Enable a Bluetooth service
 fn enable_service(device: &BLUETOOTH_DEVICE_INFO, service: &GUID) -> Result<()> {
-    unsafe {
-        let result = BluetoothSetServiceState(
-            HANDLE::default(),
-            device,
-            service,
-            1, // 1 = enable
-        );
-
-        if result != 0 {
-            return Err(map_win32_error(result, "enable"));
-        }
+    set_service_state(device, service, true)
+}
 
-        Ok(())
-    }
+/// Set a Bluetooth service state, retrying transient failures
+///
+/// Service toggles frequently fail with busy/not-ready errors right after a
+/// device connects, so these are retried with backoff before giving up.
+fn set_service_state(device: &BLUETOOTH_DEVICE_INFO, service: &GUID, enabled: bool) -> Result<()> {
+    let operation = if enabled { "enable" } else { "disable" };
+
+    RetryPolicy::BLUETOOTH
+        .run(
+            &format!("Bluetooth service {}", operation),
+            |code| classify_win32_error(*code),
+            |_| {
+                let result = unsafe {
+                    BluetoothSetServiceState(
+                        HANDLE::default(),
+                        device,
+                        service,
+                        if enabled { 1 } else { 0 }, // 1 = enable, 0 = disable
+                    )
+                };
+
+                if result != 0 {
+                    Err(result)
+                } else {
+                    Ok(())
+                }
+            },
+        )
+        .map_err(|code| map_win32_error(code, operation))
 }
 
 /// Map Win32 error codes to user-friendly messages
@@ -443,41 +573,3 @@ fn map_win32_error(error_code: u32, operation: &str) -> AppError {
 
     AppError::ConfigError(message)
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_normalize_name() {
-        assert_eq!(normalize_name("  Sony WH-1000XM4  "), "sony wh-1000xm4");
-        assert_eq!(normalize_name("HEADPHONES"), "headphones");
-    }
-
-    #[test]
-    fn test_check_name_match_exact() {
-        let target = "sony wh-1000xm4";
-        let device = "Sony WH-1000XM4";
-        assert_eq!(check_name_match(target, device), MatchQuality::Exact);
-    }
-
-    #[test]
-    fn test_check_name_match_contains() {
-        let target = "sony";
-        let device = "Sony WH-1000XM4";
-        assert_eq!(check_name_match(target, device), MatchQuality::Contains);
-    }
-
-    #[test]
-    fn test_check_name_match_no_match() {
-        let target = "bose";
-        let device = "Sony WH-1000XM4";
-        assert_eq!(check_name_match(target, device), MatchQuality::NoMatch);
-    }
-
-    #[test]
-    fn test_match_quality_ordering() {
-        assert!(MatchQuality::Exact > MatchQuality::Contains);
-        assert!(MatchQuality::Contains > MatchQuality::NoMatch);
-    }
-}
@@ -0,0 +1,52 @@
+//! Win32 Bluetooth backend
+//!
+//! Device control uses the classic Bluetooth APIs (`control`); battery levels
+//! come from the device property store (`battery`).
+
+pub mod battery;
+pub mod control;
+
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::bluetooth::inventory::PairedDevice;
+use crate::error::Result;
+use std::collections::HashMap;
+
+/// Bluetooth backend for Windows
+#[derive(Debug, Default)]
+pub struct Win32Bluetooth;
+
+impl Win32Bluetooth {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl BluetoothBackend for Win32Bluetooth {
+    fn list_paired_devices(&self) -> Result<Vec<PairedDevice>> {
+        control::list_paired_devices()
+    }
+
+    fn connect(&self, name: &str) -> Result<()> {
+        control::connect_by_name(name)
+    }
+
+    fn reconnect(&self, name: &str) -> Result<()> {
+        control::reconnect_by_name(name)
+    }
+
+    fn is_hfp_enabled(&self, name: &str) -> Result<bool> {
+        control::is_hfp_enabled_by_name(name)
+    }
+
+    fn disable_hfp(&self, name: &str) -> Result<()> {
+        control::disable_hfp_by_name(name)
+    }
+
+    fn enable_hfp(&self, name: &str) -> Result<()> {
+        control::enable_hfp_by_name(name)
+    }
+
+    fn battery_levels(&self) -> Result<HashMap<String, u8>> {
+        battery::read_battery_levels()
+    }
+}
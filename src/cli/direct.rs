@@ -0,0 +1,265 @@
+//! CLI backend that performs commands in-process
+//!
+//! Used when no tray instance is running: talks to the platform's Bluetooth
+//! and audio backends directly. The tray instance also uses it for commands
+//! that don't touch its state.
+
+use super::{resolve_device_name, ActionReport, CliBackend, CliError, DeviceEntry, StatusReport};
+use crate::audio::device::AudioMode;
+use crate::audio::monitor::{poll_audio_state, MonitorState};
+use crate::audio::session::MicUsingApp;
+use crate::bluetooth::battery::match_battery_level;
+use crate::bluetooth::inventory::{BluetoothProfile, PairedDevice};
+use crate::bluetooth::names::paired_name_for_endpoint;
+use crate::platform::Backends;
+use log::{debug, warn};
+use std::collections::HashSet;
+
+/// Runs CLI commands against the local Bluetooth and audio APIs
+pub struct DirectBackend {
+    backends: Backends,
+    /// Devices the running instance forced to stereo; `restore` re-enables only these
+    forced_stereo: HashSet<String>,
+}
+
+impl Drop for DirectBackend {
+    fn drop(&mut self) {
+        self.backends.audio.detach_thread();
+    }
+}
+
+impl DirectBackend {
+    /// Use the native backends on the calling thread
+    #[cfg(any(windows, target_os = "linux"))]
+    pub fn new() -> Result<Self, CliError> {
+        Self::with_backends(Backends::native())
+    }
+
+    /// Use the given backends on the calling thread
+    pub fn with_backends(backends: Backends) -> Result<Self, CliError> {
+        backends
+            .audio
+            .attach_thread()
+            .map_err(|e| CliError::Unavailable(format!("Failed to initialize audio: {}", e)))?;
+        Ok(Self {
+            backends,
+            forced_stereo: HashSet::new(),
+        })
+    }
+
+    /// Devices recorded as forced to stereo, whose hands-free `restore` turns back on
+    pub fn with_forced_stereo(mut self, devices: &HashSet<String>) -> Self {
+        self.forced_stereo = devices.clone();
+        self
+    }
+
+    /// Devices still recorded as forced to stereo; `restore` drops those whose hands-free is back on
+    pub fn forced_stereo(&self) -> &HashSet<String> {
+        &self.forced_stereo
+    }
+
+    fn paired_devices(&self) -> Result<Vec<PairedDevice>, CliError> {
+        self.backends
+            .bluetooth
+            .list_paired_devices()
+            .map_err(|e| CliError::Unavailable(format!("Bluetooth is not available: {}", e)))
+    }
+
+    /// Apps currently capturing from any microphone
+    fn mic_apps(&self) -> Result<Vec<MicUsingApp>, CliError> {
+        self.backends
+            .audio
+            .poll()
+            .map(|poll| poll.mic_apps)
+            .map_err(|e| CliError::Unavailable(format!("Failed to read audio state: {}", e)))
+    }
+
+    /// Resolve a user-supplied name to the exact name of a paired device
+    ///
+    /// Audio endpoint names, as the tray and MQTT discovery show them
+    /// ("Headphones (WH-1000XM4)"), resolve to the paired device they wrap.
+    pub fn resolve_device(&self, query: &str) -> Result<String, CliError> {
+        let devices = self.paired_devices()?;
+        let names: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+        resolve_device_name(query, &names)
+            .or_else(|| paired_name_for_endpoint(query, names.iter().copied()))
+            .map(str::to_string)
+            .ok_or_else(|| CliError::NotFound(format!("No unique paired device matches '{}'", query)))
+    }
+}
+
+impl CliBackend for DirectBackend {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        let (mode, mic_apps, mut devices) = poll_audio_state(self.backends.audio.as_ref())
+            .map_err(|e| CliError::Unavailable(format!("Failed to read audio state: {}", e)))?;
+
+        let battery_levels = if devices.is_empty() {
+            Default::default()
+        } else {
+            self.backends.bluetooth.battery_levels().unwrap_or_else(|e| {
+                debug!("Failed to read battery levels: {}", e);
+                Default::default()
+            })
+        };
+        for device in &mut devices {
+            device.battery_level = match_battery_level(&device.device.name, &battery_levels);
+        }
+
+        let hfp_apps = if mode == AudioMode::HandsFree {
+            self.backends.audio.bluetooth_output_apps()
+        } else {
+            Vec::new()
+        };
+
+        Ok(StatusReport::from(&MonitorState {
+            current_mode: mode,
+            mic_using_apps: mic_apps,
+            bluetooth_devices: devices,
+            hfp_apps,
+            ..MonitorState::default()
+        }))
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        Ok(self.paired_devices()?.iter().map(DeviceEntry::from).collect())
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        let name = self.resolve_device(device)?;
+        self.backends.bluetooth.disable_hfp(&name)
+            .map_err(|e| CliError::Failed(format!("Failed to force stereo for '{}': {}", name, e)))?;
+        Ok(ActionReport {
+            action: "force-stereo".to_string(),
+            message: format!("Hands-free disabled for '{}'", name),
+            target: Some(name),
+        })
+    }
+
+    fn allow_hands_free(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        let name = self.resolve_device(device)?;
+        self.backends.bluetooth.enable_hfp(&name)
+            .map_err(|e| CliError::Failed(format!("Failed to enable hands-free for '{}': {}", name, e)))?;
+        Ok(ActionReport {
+            action: "allow-hands-free".to_string(),
+            message: format!("Hands-free enabled for '{}'", name),
+            target: Some(name),
+        })
+    }
+
+    fn reconnect(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        let name = self.resolve_device(device)?;
+        self.backends.bluetooth.reconnect(&name)
+            .map_err(|e| CliError::Failed(format!("Failed to reconnect '{}': {}", name, e)))?;
+        Ok(ActionReport {
+            action: "reconnect".to_string(),
+            message: format!("Reconnected '{}'", name),
+            target: Some(name),
+        })
+    }
+
+    fn mute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        let apps = self.mic_apps()?;
+        let app = apps
+            .iter()
+            .find(|app| app.process_id == pid)
+            .ok_or_else(|| CliError::NotFound(format!("Process {} is not using a microphone", pid)))?;
+
+        self.backends.audio.set_app_muted(pid, true)
+            .map_err(|e| CliError::Failed(format!("Failed to mute process {}: {}", pid, e)))?;
+        Ok(ActionReport {
+            action: "mute".to_string(),
+            target: Some(pid.to_string()),
+            message: format!("Muted {} (PID {})", app.display_name, pid),
+        })
+    }
+
+    fn unmute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        let apps = self.mic_apps()?;
+        let app = apps
+            .iter()
+            .find(|app| app.process_id == pid)
+            .ok_or_else(|| CliError::NotFound(format!("Process {} is not using a microphone", pid)))?;
+
+        self.backends.audio.set_app_muted(pid, false)
+            .map_err(|e| CliError::Failed(format!("Failed to unmute process {}: {}", pid, e)))?;
+        Ok(ActionReport {
+            action: "unmute".to_string(),
+            target: Some(pid.to_string()),
+            message: format!("Unmuted {} (PID {})", app.display_name, pid),
+        })
+    }
+
+    /// Best effort: every muted mic app is unmuted and hands-free is
+    /// re-enabled on the devices recorded as forced to stereo. Without a
+    /// running instance nothing is recorded, so only apps are unmuted.
+    /// Devices whose hands-free is on afterwards are dropped from the record.
+    /// Fails only if nothing could be restored while something needed it.
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        let mut restored = Vec::new();
+        let mut failures = Vec::new();
+
+        let mic_apps = self.backends.audio.poll().map(|poll| poll.mic_apps).unwrap_or_else(|e| {
+            warn!("Skipping unmute, audio state not available: {}", e);
+            Vec::new()
+        });
+        for app in mic_apps.iter().filter(|a| a.is_muted) {
+            match self.backends.audio.set_app_muted(app.process_id, false) {
+                Ok(()) => restored.push(format!("unmuted {} (PID {})", app.display_name, app.process_id)),
+                Err(e) => failures.push(format!("unmute PID {}: {}", app.process_id, e)),
+            }
+        }
+
+        match self.backends.bluetooth.list_paired_devices() {
+            Ok(devices) => {
+                let mut forced: Vec<String> = self.forced_stereo.iter().cloned().collect();
+                forced.sort();
+                for key in forced {
+                    // Keys are paired names, or endpoint names if forced before the first inventory
+                    let Some(device) = paired_name_for_endpoint(&key, devices.iter().map(|d| d.name.as_str()))
+                        .and_then(|name| devices.iter().find(|d| d.name == name))
+                    else {
+                        warn!("Forced-stereo device '{}' is not paired, leaving it recorded", key);
+                        continue;
+                    };
+                    if device.has_profile(BluetoothProfile::HandsFree) {
+                        self.forced_stereo.remove(&key);
+                        continue;
+                    }
+                    match self.backends.bluetooth.enable_hfp(&device.name) {
+                        Ok(()) => {
+                            restored.push(format!("enabled hands-free for '{}'", device.name));
+                            self.forced_stereo.remove(&key);
+                        }
+                        Err(e) => failures.push(format!("enable hands-free for '{}': {}", device.name, e)),
+                    }
+                }
+            }
+            Err(e) => warn!("Skipping hands-free restore, Bluetooth not available: {}", e),
+        }
+
+        if restored.is_empty() && !failures.is_empty() {
+            return Err(CliError::Failed(format!("Restore failed: {}", failures.join("; "))));
+        }
+
+        let mut message = if restored.is_empty() {
+            "Nothing to restore".to_string()
+        } else {
+            format!("Restored: {}", restored.join(", "))
+        };
+        if !failures.is_empty() {
+            message.push_str(&format!("\nNot restored: {}", failures.join("; ")));
+        }
+
+        Ok(ActionReport {
+            action: "restore".to_string(),
+            target: None,
+            message,
+        })
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Unavailable(
+            "Bluetooth Audio Mode Manager is not running".to_string(),
+        ))
+    }
+}
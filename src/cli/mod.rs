@@ -0,0 +1,707 @@
+//! Command-line interface for status and control
+//!
+//! Parses subcommands, runs them against a [`CliBackend`] and renders the
+//! result as human-readable text or JSON. Exit codes are stable so scripts
+//! (PowerShell, AutoHotkey) can branch on them.
+//!
+//! The parser, rendering and exit codes are platform independent; the
+//! backend that runs commands against the platform backends lives in
+//! [`direct`].
+
+pub mod direct;
+
+use crate::audio::device::AudioMode;
+use crate::audio::monitor::MonitorState;
+use crate::bluetooth::inventory::{BluetoothProfile, PairedDevice};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use direct::DirectBackend;
+
+/// Process exit codes
+pub mod exit_code {
+    /// Command succeeded
+    pub const SUCCESS: i32 = 0;
+    /// The operation was attempted and failed
+    pub const FAILURE: i32 = 1;
+    /// Invalid command line
+    pub const USAGE: i32 = 2;
+    /// The device or process does not exist
+    pub const NOT_FOUND: i32 = 3;
+    /// Bluetooth or the audio subsystem is not available
+    pub const UNAVAILABLE: i32 = 4;
+}
+
+/// A CLI subcommand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Current mode per device, mic apps and HFP apps
+    Status,
+    /// Paired device inventory
+    Devices,
+    ForceStereo(String),
+    AllowHandsFree(String),
+    Reconnect(String),
+    /// Mute the microphone for a process
+    Mute(u32),
+    /// Unmute the microphone for a process
+    Unmute(u32),
+    /// Unmute muted apps and re-enable hands-free on all paired headsets
+    Restore,
+    /// Open the settings window of the running tray instance
+    OpenSettings,
+    Help,
+    Version,
+}
+
+impl Command {
+    /// Name of the subcommand as typed on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Status => "status",
+            Command::Devices => "devices",
+            Command::ForceStereo(_) => "force-stereo",
+            Command::AllowHandsFree(_) => "allow-hands-free",
+            Command::Reconnect(_) => "reconnect",
+            Command::Mute(_) => "mute",
+            Command::Unmute(_) => "unmute",
+            Command::Restore => "restore",
+            Command::OpenSettings => "open-settings",
+            Command::Help => "help",
+            Command::Version => "version",
+        }
+    }
+}
+
+/// Parsed command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliArgs {
+    pub command: Command,
+    /// Emit JSON instead of text
+    pub json: bool,
+}
+
+/// Error from a CLI command, carrying its exit code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum CliError {
+    Usage(String),
+    NotFound(String),
+    Failed(String),
+    Unavailable(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => exit_code::USAGE,
+            CliError::NotFound(_) => exit_code::NOT_FOUND,
+            CliError::Failed(_) => exit_code::FAILURE,
+            CliError::Unavailable(_) => exit_code::UNAVAILABLE,
+        }
+    }
+
+    /// Machine-readable error kind used in JSON output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CliError::Usage(_) => "usage",
+            CliError::NotFound(_) => "not_found",
+            CliError::Failed(_) => "failed",
+            CliError::Unavailable(_) => "unavailable",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            CliError::Usage(m) | CliError::NotFound(m) | CliError::Failed(m) | CliError::Unavailable(m) => m,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Stable identifier for an audio mode in CLI output
+pub fn mode_id(mode: AudioMode) -> &'static str {
+    match mode {
+        AudioMode::Stereo => "stereo",
+        AudioMode::HandsFree => "hands-free",
+        AudioMode::Unknown => "unknown",
+    }
+}
+
+/// Connected audio device in `status` output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    pub name: String,
+    pub mode: String,
+    pub battery_level: Option<u8>,
+}
+
+/// App in `status` output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppStatus {
+    pub pid: u32,
+    pub name: String,
+    pub display_name: String,
+    /// Only known for mic-using apps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bluetooth_mic: Option<bool>,
+}
+
+/// Result of `status`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub mode: String,
+    pub devices: Vec<DeviceStatus>,
+    pub mic_apps: Vec<AppStatus>,
+    pub hfp_apps: Vec<AppStatus>,
+}
+
+impl From<&MonitorState> for StatusReport {
+    /// Report the monitor's latest poll; apps on a Bluetooth output are
+    /// listed only in hands-free mode, where they may have caused it
+    fn from(state: &MonitorState) -> Self {
+        let hfp_apps = if state.current_mode == AudioMode::HandsFree {
+            state.hfp_apps.as_slice()
+        } else {
+            &[]
+        };
+
+        Self {
+            mode: mode_id(state.current_mode).to_string(),
+            devices: state
+                .bluetooth_devices
+                .iter()
+                .map(|d| DeviceStatus {
+                    name: d.device.name.clone(),
+                    mode: mode_id(d.current_mode).to_string(),
+                    battery_level: d.battery_level,
+                })
+                .collect(),
+            mic_apps: state
+                .mic_using_apps
+                .iter()
+                .map(|app| AppStatus {
+                    pid: app.process_id,
+                    name: app.process_name.clone(),
+                    display_name: app.display_name.clone(),
+                    muted: Some(app.is_muted),
+                    bluetooth_mic: Some(app.is_using_bluetooth_mic),
+                })
+                .collect(),
+            hfp_apps: hfp_apps
+                .iter()
+                .map(|app| AppStatus {
+                    pid: app.process_id,
+                    name: app.process_name.clone(),
+                    display_name: app.display_name.clone(),
+                    muted: None,
+                    bluetooth_mic: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Paired device in `devices` output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceEntry {
+    pub name: String,
+    pub address: String,
+    pub device_type: String,
+    pub connected: bool,
+    /// Whether the Hands-Free service is currently enabled
+    pub hands_free_enabled: bool,
+    pub profiles: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub last_seen: Option<u64>,
+    pub last_used: Option<u64>,
+}
+
+impl From<&PairedDevice> for DeviceEntry {
+    fn from(device: &PairedDevice) -> Self {
+        let unix_secs =
+            |time: Option<SystemTime>| time.and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map(|d| d.as_secs());
+
+        Self {
+            name: device.name.clone(),
+            address: device.address.to_string(),
+            device_type: device.device_type.to_string(),
+            connected: device.connected,
+            hands_free_enabled: device.has_profile(BluetoothProfile::HandsFree),
+            profiles: device.profiles.iter().map(|p| profile_id(*p)).collect(),
+            last_seen: unix_secs(device.last_seen),
+            last_used: unix_secs(device.last_used),
+        }
+    }
+}
+
+/// Stable identifier for a Bluetooth profile in CLI output
+pub fn profile_id(profile: BluetoothProfile) -> String {
+    match profile {
+        BluetoothProfile::Headset => "hsp".to_string(),
+        BluetoothProfile::HeadsetAudioGateway => "hsp-ag".to_string(),
+        BluetoothProfile::A2dpSource => "a2dp-source".to_string(),
+        BluetoothProfile::A2dpSink => "a2dp-sink".to_string(),
+        BluetoothProfile::Avrcp => "avrcp".to_string(),
+        BluetoothProfile::HandsFree => "hfp".to_string(),
+        BluetoothProfile::HandsFreeAudioGateway => "hfp-ag".to_string(),
+        BluetoothProfile::Other(short) => format!("0x{:04x}", short),
+        BluetoothProfile::Vendor(uuid) => format!("{:032x}", uuid),
+    }
+}
+
+/// Result of an action command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionReport {
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub message: String,
+}
+
+/// Operations the CLI can perform
+///
+/// `DirectBackend` calls the Bluetooth and WASAPI code in-process,
+/// `ipc::RemoteBackend` forwards to the running tray instance; tests use a
+/// fake implementation.
+pub trait CliBackend {
+    fn status(&mut self) -> Result<StatusReport, CliError>;
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError>;
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError>;
+    fn allow_hands_free(&mut self, device: &str) -> Result<ActionReport, CliError>;
+    fn reconnect(&mut self, device: &str) -> Result<ActionReport, CliError>;
+    fn mute(&mut self, pid: u32) -> Result<ActionReport, CliError>;
+    fn unmute(&mut self, pid: u32) -> Result<ActionReport, CliError>;
+    fn restore(&mut self) -> Result<ActionReport, CliError>;
+    fn open_settings(&mut self) -> Result<ActionReport, CliError>;
+}
+
+/// Rendered output and exit code of a CLI run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliOutcome {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+/// Resolve a device name typed by the user against known device names
+///
+/// Case-insensitive; an exact match wins, otherwise the name must be
+/// contained in exactly one device name so a typo never hits the wrong
+/// headset.
+pub fn resolve_device_name<'a>(query: &str, names: &[&'a str]) -> Option<&'a str> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+
+    if let Some(name) = names.iter().find(|name| name.to_lowercase() == query) {
+        return Some(name);
+    }
+
+    let mut matches = names.iter().filter(|name| name.to_lowercase().contains(&query));
+    match (matches.next(), matches.next()) {
+        (Some(name), None) => Some(name),
+        _ => None,
+    }
+}
+
+/// Option that starts the app without tray icon, menu or dialogs
+pub const HEADLESS_FLAG: &str = "--headless";
+
+/// Whether the command line (without the program name) asks for the headless daemon
+pub fn is_headless<S: AsRef<str>>(args: &[S]) -> bool {
+    matches!(args, [flag] if flag.as_ref() == HEADLESS_FLAG)
+}
+
+/// Option that runs the tray app on simulated devices from a scenario file
+pub const SIMULATE_FLAG: &str = "--simulate";
+
+/// Option to `--simulate` that also starts the control servers enabled in config
+pub const SERVE_FLAG: &str = "--serve";
+
+/// What `--simulate <file> [--serve]` asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulateArgs<'a> {
+    /// Scenario file
+    pub scenario: &'a str,
+    /// Start the RPC, HTTP and metrics servers enabled in config
+    pub serve: bool,
+}
+
+/// `--simulate` options, if they are the whole command line
+pub fn simulate_args<S: AsRef<str>>(args: &[S]) -> Option<SimulateArgs<'_>> {
+    match args {
+        [flag, path] if flag.as_ref() == SIMULATE_FLAG => Some(SimulateArgs {
+            scenario: path.as_ref(),
+            serve: false,
+        }),
+        [flag, path, serve] if flag.as_ref() == SIMULATE_FLAG && serve.as_ref() == SERVE_FLAG => Some(SimulateArgs {
+            scenario: path.as_ref(),
+            serve: true,
+        }),
+        _ => None,
+    }
+}
+
+/// Parse the command line (without the program name)
+///
+/// Returns `Ok(None)` when no subcommand was given, meaning the tray app
+/// should start. `--json` may appear anywhere. Device names may be given
+/// unquoted; remaining words are joined with spaces.
+pub fn parse_args<S: AsRef<str>>(args: &[S]) -> Result<Option<CliArgs>, CliError> {
+    let mut json = false;
+    let mut words: Vec<&str> = Vec::new();
+
+    for arg in args {
+        match arg.as_ref() {
+            "--json" => json = true,
+            "-h" | "--help" => words.insert(0, "help"),
+            "-V" | "--version" => words.insert(0, "version"),
+            other if other.starts_with("--") => {
+                return Err(CliError::Usage(format!("Unknown option '{}'", other)));
+            }
+            other => words.push(other),
+        }
+    }
+
+    let Some((&name, rest)) = words.split_first() else {
+        return Ok(None);
+    };
+
+    let device_arg = |command: &str| -> Result<String, CliError> {
+        let device = rest.join(" ");
+        if device.trim().is_empty() {
+            return Err(CliError::Usage(format!("'{}' requires a device name", command)));
+        }
+        Ok(device.trim().to_string())
+    };
+
+    let pid_arg = |command: &str| -> Result<u32, CliError> {
+        match rest {
+            [pid] => pid
+                .parse()
+                .map_err(|_| CliError::Usage(format!("Invalid process ID '{}'", pid))),
+            _ => Err(CliError::Usage(format!("'{}' requires exactly one process ID", command))),
+        }
+    };
+
+    let no_args = |command: Command| -> Result<Command, CliError> {
+        if rest.is_empty() {
+            Ok(command)
+        } else {
+            Err(CliError::Usage(format!("'{}' takes no arguments", command.name())))
+        }
+    };
+
+    let command = match name {
+        "status" => no_args(Command::Status)?,
+        "devices" => no_args(Command::Devices)?,
+        "force-stereo" => Command::ForceStereo(device_arg(name)?),
+        "allow-hands-free" => Command::AllowHandsFree(device_arg(name)?),
+        "reconnect" => Command::Reconnect(device_arg(name)?),
+        "mute" => Command::Mute(pid_arg(name)?),
+        "unmute" => Command::Unmute(pid_arg(name)?),
+        "restore" => no_args(Command::Restore)?,
+        "open-settings" => no_args(Command::OpenSettings)?,
+        "help" => Command::Help,
+        "version" => Command::Version,
+        other => return Err(CliError::Usage(format!("Unknown command '{}'", other))),
+    };
+
+    Ok(Some(CliArgs { command, json }))
+}
+
+/// Usage text shown for `help` and usage errors
+pub fn usage() -> String {
+    let exe = env!("CARGO_PKG_NAME");
+    format!(
+        "Bluetooth Audio Mode Manager {version}
+
+Usage: {exe} [COMMAND] [--json]
+       {exe} --headless
+       {exe} --simulate <scenario-file> [--serve]
+
+Without a command the tray application starts. With --headless it runs
+without tray icon, menu or dialogs, taking settings from config.toml and
+commands from the command line and control APIs. When an instance is
+already running, commands are carried out by it. With --simulate the tray
+runs on simulated devices scripted by the scenario file, for demos and
+trying out settings without a headset; hooks are not run, and the RPC,
+HTTP and metrics servers only start with --serve.
+
+Commands:
+  status                     Audio mode per device, mic apps and hands-free apps
+  devices                    Paired Bluetooth devices and their connection state
+  force-stereo <device>      Disable hands-free so the device stays in stereo
+  allow-hands-free <device>  Re-enable hands-free for the device
+  reconnect <device>         Reconnect the device
+  mute <pid>                 Mute the microphone for a process
+  unmute <pid>               Unmute the microphone for a process
+  restore                    Unmute muted apps and re-enable hands-free on forced-stereo headsets
+  open-settings              Open the settings window of the running instance
+  help                       Show this help
+  version                    Show the version
+
+Options:
+  --json                     Print JSON instead of text
+
+Exit codes:
+  0 success, 1 failed, 2 usage error, 3 device or process not found,
+  4 Bluetooth or audio unavailable
+",
+        version = env!("CARGO_PKG_VERSION"),
+        exe = exe,
+    )
+}
+
+/// Run a parsed command and render its output
+pub fn execute(args: &CliArgs, backend: &mut dyn CliBackend) -> CliOutcome {
+    let result = match &args.command {
+        Command::Help => return text_outcome(usage(), exit_code::SUCCESS),
+        Command::Version => {
+            return if args.json {
+                json_outcome(&serde_json::json!({ "version": env!("CARGO_PKG_VERSION") }))
+            } else {
+                text_outcome(format!("{}\n", env!("CARGO_PKG_VERSION")), exit_code::SUCCESS)
+            };
+        }
+        Command::Status => backend.status().map(|report| {
+            if args.json {
+                json_outcome(&report)
+            } else {
+                text_outcome(render_status(&report), exit_code::SUCCESS)
+            }
+        }),
+        Command::Devices => backend.devices().map(|devices| {
+            if args.json {
+                json_outcome(&devices)
+            } else {
+                text_outcome(render_devices(&devices), exit_code::SUCCESS)
+            }
+        }),
+        Command::ForceStereo(device) => backend.force_stereo(device).map(|r| action_outcome(&r, args.json)),
+        Command::AllowHandsFree(device) => backend.allow_hands_free(device).map(|r| action_outcome(&r, args.json)),
+        Command::Reconnect(device) => backend.reconnect(device).map(|r| action_outcome(&r, args.json)),
+        Command::Mute(pid) => backend.mute(*pid).map(|r| action_outcome(&r, args.json)),
+        Command::Unmute(pid) => backend.unmute(*pid).map(|r| action_outcome(&r, args.json)),
+        Command::Restore => backend.restore().map(|r| action_outcome(&r, args.json)),
+        Command::OpenSettings => backend.open_settings().map(|r| action_outcome(&r, args.json)),
+    };
+
+    result.unwrap_or_else(|e| error_outcome(&e, args.json))
+}
+
+/// Render an error (e.g. from parsing) for the given output mode
+pub fn error_outcome(error: &CliError, json: bool) -> CliOutcome {
+    if json {
+        let body = serde_json::json!({
+            "error": {
+                "kind": error.kind(),
+                "code": error.exit_code(),
+                "message": error.message(),
+            }
+        });
+        CliOutcome {
+            stdout: format!("{}\n", body),
+            stderr: String::new(),
+            exit_code: error.exit_code(),
+        }
+    } else {
+        let mut stderr = format!("error: {}\n", error);
+        if matches!(error, CliError::Usage(_)) {
+            stderr.push('\n');
+            stderr.push_str(&usage());
+        }
+        CliOutcome {
+            stdout: String::new(),
+            stderr,
+            exit_code: error.exit_code(),
+        }
+    }
+}
+
+fn text_outcome(stdout: String, exit_code: i32) -> CliOutcome {
+    CliOutcome {
+        stdout,
+        stderr: String::new(),
+        exit_code,
+    }
+}
+
+fn json_outcome<T: Serialize>(value: &T) -> CliOutcome {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => text_outcome(format!("{}\n", json), exit_code::SUCCESS),
+        Err(e) => error_outcome(&CliError::Failed(format!("Failed to serialize output: {}", e)), false),
+    }
+}
+
+fn action_outcome(report: &ActionReport, json: bool) -> CliOutcome {
+    if json {
+        json_outcome(report)
+    } else {
+        text_outcome(format!("{}\n", report.message), exit_code::SUCCESS)
+    }
+}
+
+fn render_status(report: &StatusReport) -> String {
+    let mut out = format!("Mode: {}\n", report.mode);
+
+    out.push_str("\nDevices:\n");
+    if report.devices.is_empty() {
+        out.push_str("  (no Bluetooth audio devices connected)\n");
+    }
+    for device in &report.devices {
+        match device.battery_level {
+            Some(level) => out.push_str(&format!("  {}  [{}]  battery {}%\n", device.name, device.mode, level)),
+            None => out.push_str(&format!("  {}  [{}]\n", device.name, device.mode)),
+        }
+    }
+
+    out.push_str("\nApps using the microphone:\n");
+    if report.mic_apps.is_empty() {
+        out.push_str("  (none)\n");
+    }
+    for app in &report.mic_apps {
+        let mut flags = Vec::new();
+        if app.bluetooth_mic == Some(true) {
+            flags.push("bluetooth mic");
+        }
+        if app.muted == Some(true) {
+            flags.push("muted");
+        }
+        let flags = if flags.is_empty() {
+            String::new()
+        } else {
+            format!("  ({})", flags.join(", "))
+        };
+        out.push_str(&format!("  {:>6}  {}{}\n", app.pid, app.display_name, flags));
+    }
+
+    out.push_str("\nApps using Bluetooth audio:\n");
+    if report.hfp_apps.is_empty() {
+        out.push_str("  (none)\n");
+    }
+    for app in &report.hfp_apps {
+        out.push_str(&format!("  {:>6}  {}\n", app.pid, app.display_name));
+    }
+
+    out
+}
+
+fn render_devices(devices: &[DeviceEntry]) -> String {
+    if devices.is_empty() {
+        return "No paired Bluetooth devices\n".to_string();
+    }
+
+    let mut out = String::new();
+    for device in devices {
+        out.push_str(&format!(
+            "{}  {}  {}  {}{}\n",
+            device.address,
+            if device.connected { "connected   " } else { "disconnected" },
+            device.device_type,
+            device.name,
+            if device.hands_free_enabled { "" } else { "  (hands-free disabled)" },
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<CliArgs>, CliError> {
+        parse_args(args)
+    }
+
+    #[test]
+    fn test_no_args_starts_tray() {
+        assert_eq!(parse(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_headless_flag() {
+        assert!(is_headless(&["--headless"]));
+        assert!(!is_headless::<&str>(&[]));
+        assert!(!is_headless(&["--headless", "status"]));
+        // Only valid on its own, so it is not a CLI option
+        assert!(parse(&["--headless", "status"]).is_err());
+    }
+
+    #[test]
+    fn test_simulate_flag() {
+        assert_eq!(
+            simulate_args(&["--simulate", "demo.txt"]),
+            Some(SimulateArgs {
+                scenario: "demo.txt",
+                serve: false
+            })
+        );
+        assert_eq!(
+            simulate_args(&["--simulate", "demo.txt", "--serve"]),
+            Some(SimulateArgs {
+                scenario: "demo.txt",
+                serve: true
+            })
+        );
+        assert_eq!(simulate_args(&["--simulate"]), None);
+        assert_eq!(simulate_args(&["--simulate", "demo.txt", "status"]), None);
+        assert_eq!(simulate_args(&["status"]), None);
+        assert!(parse(&["--simulate", "demo.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        assert_eq!(parse(&["status"]).unwrap().unwrap().command, Command::Status);
+        assert_eq!(
+            parse(&["force-stereo", "WH-1000XM4"]).unwrap().unwrap().command,
+            Command::ForceStereo("WH-1000XM4".to_string())
+        );
+        // Unquoted names with spaces are joined
+        assert_eq!(
+            parse(&["reconnect", "Jabra", "Evolve2", "65"]).unwrap().unwrap().command,
+            Command::Reconnect("Jabra Evolve2 65".to_string())
+        );
+        assert_eq!(parse(&["mute", "1234"]).unwrap().unwrap().command, Command::Mute(1234));
+        assert_eq!(parse(&["unmute", "1234"]).unwrap().unwrap().command, Command::Unmute(1234));
+    }
+
+    #[test]
+    fn test_json_flag_anywhere() {
+        let args = parse(&["--json", "devices"]).unwrap().unwrap();
+        assert!(args.json);
+        assert_eq!(args.command, Command::Devices);
+        assert!(parse(&["status", "--json"]).unwrap().unwrap().json);
+    }
+
+    #[test]
+    fn test_resolve_device_name() {
+        let names = ["WH-1000XM4", "Jabra Evolve2 65", "Jabra Elite 85t"];
+        assert_eq!(resolve_device_name("wh-1000xm4", &names), Some("WH-1000XM4"));
+        assert_eq!(resolve_device_name("evolve2", &names), Some("Jabra Evolve2 65"));
+        // Ambiguous partial match
+        assert_eq!(resolve_device_name("jabra", &names), None);
+        assert_eq!(resolve_device_name("AirPods", &names), None);
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert_eq!(parse(&["force-stereo"]).unwrap_err().exit_code(), exit_code::USAGE);
+        assert_eq!(parse(&["mute", "abc"]).unwrap_err().exit_code(), exit_code::USAGE);
+        assert_eq!(parse(&["status", "extra"]).unwrap_err().exit_code(), exit_code::USAGE);
+        assert_eq!(parse(&["frobnicate"]).unwrap_err().exit_code(), exit_code::USAGE);
+        assert_eq!(parse(&["--bogus"]).unwrap_err().exit_code(), exit_code::USAGE);
+    }
+}
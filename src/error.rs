@@ -9,7 +9,10 @@ pub enum AppError {
     ConfigError(String),
     UpdateCheckError(String),
     IoError(std::io::Error),
+    #[cfg(windows)]
     WindowsApiError(windows::core::Error),
+    #[cfg(target_os = "linux")]
+    DBusError(zbus::Error),
 }
 
 impl fmt::Display for AppError {
@@ -22,7 +25,10 @@ impl fmt::Display for AppError {
             AppError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
             AppError::UpdateCheckError(msg) => write!(f, "Update check error: {}", msg),
             AppError::IoError(e) => write!(f, "IO error: {}", e),
+            #[cfg(windows)]
             AppError::WindowsApiError(e) => write!(f, "Windows API error: {}", e),
+            #[cfg(target_os = "linux")]
+            AppError::DBusError(e) => write!(f, "D-Bus error: {}", e),
         }
     }
 }
@@ -35,18 +41,35 @@ impl From<std::io::Error> for AppError {
     }
 }
 
+#[cfg(windows)]
 impl From<windows::core::Error> for AppError {
     fn from(err: windows::core::Error) -> Self {
         AppError::WindowsApiError(err)
     }
 }
 
+#[cfg(target_os = "linux")]
+impl From<zbus::Error> for AppError {
+    fn from(err: zbus::Error) -> Self {
+        AppError::DBusError(err)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl From<zbus::fdo::Error> for AppError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        AppError::DBusError(err.into())
+    }
+}
+
+#[cfg(windows)]
 impl From<muda::Error> for AppError {
     fn from(err: muda::Error) -> Self {
         AppError::TrayIconFailed(err.to_string())
     }
 }
 
+#[cfg(windows)]
 impl From<tray_icon::Error> for AppError {
     fn from(err: tray_icon::Error) -> Self {
         AppError::TrayIconFailed(err.to_string())
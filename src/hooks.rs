@@ -0,0 +1,311 @@
+//! User hook commands run when events happen
+//!
+//! Each configured command is started with the event data in `BTAM_*`
+//! environment variables and as a JSON object on stdin. Runs go through a
+//! bounded queue served by `max_concurrent` worker threads; a run that exits
+//! with an error, cannot start, or exceeds the timeout is reported as a
+//! `HookFailure` for the main loop to show.
+
+use crate::bluetooth::PairedDevice;
+use crate::settings::{HookCommand, HookEventKind, HooksConfig};
+use log::{debug, info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Write;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Runs waiting for a free worker; further events are dropped and reported
+pub const QUEUE_CAPACITY: usize = 32;
+
+/// Upper bound for `max_concurrent`, to keep a typo from spawning hundreds of threads
+pub const MAX_WORKERS: u32 = 16;
+
+/// Prefix of the environment variables carrying event data
+pub const ENV_PREFIX: &str = "BTAM_";
+
+/// Keep console programs (powershell, cmd) from flashing a window
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Event data passed to hooks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum HookEvent {
+    ModeChanged {
+        old_mode: String,
+        new_mode: String,
+    },
+    MicSessionStarted {
+        pid: u32,
+        process_name: String,
+        display_name: String,
+        bluetooth_mic: bool,
+    },
+    DeviceConnected {
+        device: String,
+        address: String,
+    },
+    ForceStereoApplied {
+        device: String,
+        /// `user` for an explicit request, `reconnect` when re-applied after a reconnect
+        reason: String,
+    },
+}
+
+impl HookEvent {
+    pub fn kind(&self) -> HookEventKind {
+        match self {
+            HookEvent::ModeChanged { .. } => HookEventKind::ModeChanged,
+            HookEvent::MicSessionStarted { .. } => HookEventKind::MicSessionStarted,
+            HookEvent::DeviceConnected { .. } => HookEventKind::DeviceConnected,
+            HookEvent::ForceStereoApplied { .. } => HookEventKind::ForceStereoApplied,
+        }
+    }
+
+    /// JSON object written to the hook's stdin
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("hook event serialization cannot fail")
+    }
+
+    /// Environment variables for the hook, e.g. `BTAM_EVENT=mode-changed`, `BTAM_NEW_MODE=stereo`
+    pub fn env_vars(&self) -> Vec<(String, String)> {
+        let Ok(Value::Object(fields)) = serde_json::to_value(self) else {
+            return Vec::new();
+        };
+        fields
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                (format!("{}{}", ENV_PREFIX, key.to_ascii_uppercase()), value)
+            })
+            .collect()
+    }
+}
+
+/// Devices connected in `current` that were not connected in `previous`
+pub fn newly_connected<'a>(previous: &[PairedDevice], current: &'a [PairedDevice]) -> Vec<&'a PairedDevice> {
+    let was_connected: HashSet<_> = previous
+        .iter()
+        .filter(|device| device.connected)
+        .map(|device| device.address)
+        .collect();
+    current
+        .iter()
+        .filter(|device| device.connected && !was_connected.contains(&device.address))
+        .collect()
+}
+
+/// A hook run that did not succeed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookFailure {
+    pub command: String,
+    pub event: HookEventKind,
+    pub error: String,
+}
+
+/// Runs one hook command to completion
+pub trait HookExecutor: Send + Sync {
+    fn run(&self, hook: &HookCommand, event: &HookEvent, timeout: Duration) -> Result<(), String>;
+}
+
+/// Executes hooks as child processes
+#[derive(Debug, Default)]
+pub struct ProcessExecutor;
+
+impl HookExecutor for ProcessExecutor {
+    fn run(&self, hook: &HookCommand, event: &HookEvent, timeout: Duration) -> Result<(), String> {
+        let mut command = Command::new(&hook.command);
+        command
+            .args(&hook.args)
+            .envs(event.env_vars())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        #[cfg(windows)]
+        command.creation_flags(CREATE_NO_WINDOW);
+        let mut child = command.spawn().map_err(|e| format!("could not start: {}", e))?;
+
+        // Hooks that ignore stdin close it early; that is not an error
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(event.to_json().as_bytes());
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => return Err(format!("exited with {}", status)),
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("timed out after {} s and was stopped", timeout.as_secs()));
+                }
+                Ok(None) => thread::sleep(WAIT_POLL_INTERVAL),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
+struct Job {
+    hook: HookCommand,
+    event: HookEvent,
+}
+
+/// Queues hook runs and collects their failures
+pub struct HookRunner {
+    commands: Vec<HookCommand>,
+    /// `None` when no hooks are configured
+    queue_tx: Option<SyncSender<Job>>,
+    failures_tx: Sender<HookFailure>,
+    failures_rx: Receiver<HookFailure>,
+}
+
+impl HookRunner {
+    /// Create a runner that starts hooks as processes
+    pub fn new(config: &HooksConfig) -> Self {
+        Self::with_executor(config, Arc::new(ProcessExecutor))
+    }
+
+    /// Create a runner with a custom executor
+    pub fn with_executor(config: &HooksConfig, executor: Arc<dyn HookExecutor>) -> Self {
+        let (failures_tx, failures_rx) = mpsc::channel();
+        let timeout = Duration::from_secs(config.timeout_secs.max(1) as u64);
+
+        let queue_tx = if config.commands.is_empty() {
+            None
+        } else {
+            let (queue_tx, queue_rx) = mpsc::sync_channel::<Job>(QUEUE_CAPACITY);
+            let queue_rx = Arc::new(Mutex::new(queue_rx));
+            let workers = config.max_concurrent.clamp(1, MAX_WORKERS);
+            for index in 0..workers {
+                let queue_rx = Arc::clone(&queue_rx);
+                let executor = Arc::clone(&executor);
+                let failures_tx = failures_tx.clone();
+                let spawned = thread::Builder::new()
+                    .name(format!("hook-worker-{}", index))
+                    .spawn(move || worker(queue_rx, executor, failures_tx, timeout));
+                if let Err(e) = spawned {
+                    warn!("Failed to start hook worker: {}", e);
+                }
+            }
+            info!("{} hook command(s) configured", config.commands.len());
+            Some(queue_tx)
+        };
+
+        Self {
+            commands: config.commands.clone(),
+            queue_tx,
+            failures_tx,
+            failures_rx,
+        }
+    }
+
+    /// Queue every hook configured for this event
+    pub fn fire(&self, event: &HookEvent) {
+        let Some(queue_tx) = &self.queue_tx else {
+            return;
+        };
+
+        let kind = event.kind();
+        for hook in self.commands.iter().filter(|hook| hook.event == kind) {
+            let job = Job {
+                hook: hook.clone(),
+                event: event.clone(),
+            };
+            match queue_tx.try_send(job) {
+                Ok(()) => debug!("Queued hook '{}' for {:?}", hook.command, kind),
+                Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                    let _ = self.failures_tx.send(HookFailure {
+                        command: hook.command.clone(),
+                        event: kind,
+                        error: "skipped because too many hooks are still running".to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Next failure to report, if any
+    pub fn try_recv_failure(&self) -> Option<HookFailure> {
+        self.failures_rx.try_recv().ok()
+    }
+}
+
+fn worker(
+    queue_rx: Arc<Mutex<Receiver<Job>>>,
+    executor: Arc<dyn HookExecutor>,
+    failures_tx: Sender<HookFailure>,
+    timeout: Duration,
+) {
+    loop {
+        // Hold the lock only while waiting, not while the hook runs
+        let job = match queue_rx.lock() {
+            Ok(rx) => rx.recv(),
+            Err(_) => return,
+        };
+        // Queue closed: the runner was dropped
+        let Ok(job) = job else {
+            return;
+        };
+
+        debug!("Running hook '{}' for {:?}", job.hook.command, job.event.kind());
+        if let Err(error) = executor.run(&job.hook, &job.event, timeout) {
+            warn!("Hook '{}' failed: {}", job.hook.command, error);
+            let _ = failures_tx.send(HookFailure {
+                command: job.hook.command.clone(),
+                event: job.event.kind(),
+                error,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_payloads() {
+        let event = HookEvent::ModeChanged {
+            old_mode: "stereo".to_string(),
+            new_mode: "hands-free".to_string(),
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"event":"mode-changed","old_mode":"stereo","new_mode":"hands-free"}"#
+        );
+
+        let mut env = event.env_vars();
+        env.sort();
+        assert_eq!(
+            env,
+            [
+                ("BTAM_EVENT".to_string(), "mode-changed".to_string()),
+                ("BTAM_NEW_MODE".to_string(), "hands-free".to_string()),
+                ("BTAM_OLD_MODE".to_string(), "stereo".to_string()),
+            ]
+        );
+
+        let event = HookEvent::MicSessionStarted {
+            pid: 4242,
+            process_name: "Teams.exe".to_string(),
+            display_name: "Microsoft Teams".to_string(),
+            bluetooth_mic: true,
+        };
+        assert!(event.env_vars().contains(&("BTAM_PID".to_string(), "4242".to_string())));
+        assert!(event.env_vars().contains(&("BTAM_BLUETOOTH_MIC".to_string(), "true".to_string())));
+    }
+}
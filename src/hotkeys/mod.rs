@@ -0,0 +1,320 @@
+//! Global hotkeys for device actions
+//!
+//! Hotkeys are configured as strings such as `Ctrl+Alt+S`. They are parsed
+//! into a modifier set and a Windows virtual-key code, checked for conflicts
+//! with each other, and turned into the tray's `MenuEvent`s for the active
+//! headset. Registration with Windows lives in `register`.
+
+#[cfg(windows)]
+pub mod register;
+
+use crate::audio::BluetoothAudioDevice;
+use crate::settings::HotkeysConfig;
+use crate::tray::MenuEvent;
+use std::fmt;
+use std::str::FromStr;
+
+/// Modifier flags, with the values `RegisterHotKey` expects
+pub const MOD_ALT: u32 = 0x1;
+pub const MOD_CONTROL: u32 = 0x2;
+pub const MOD_SHIFT: u32 = 0x4;
+pub const MOD_WIN: u32 = 0x8;
+
+/// Modifiers in display order
+const MODIFIERS: [(u32, &str); 4] = [(MOD_CONTROL, "Ctrl"), (MOD_ALT, "Alt"), (MOD_SHIFT, "Shift"), (MOD_WIN, "Win")];
+
+const VK_F12: u32 = 0x7B;
+const VK_DELETE: u32 = 0x2E;
+
+/// Named keys and their virtual-key codes (letters, digits, F-keys and numpad keys are computed)
+const NAMED_KEYS: &[(&str, u32)] = &[
+    ("Backspace", 0x08),
+    ("Tab", 0x09),
+    ("Enter", 0x0D),
+    ("Pause", 0x13),
+    ("Escape", 0x1B),
+    ("Space", 0x20),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("End", 0x23),
+    ("Home", 0x24),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("PrintScreen", 0x2C),
+    ("Insert", 0x2D),
+    ("Delete", VK_DELETE),
+];
+
+/// Alternative spellings accepted by the parser
+const KEY_ALIASES: &[(&str, &str)] = &[("Return", "Enter"), ("Esc", "Escape"), ("Del", "Delete"), ("Ins", "Insert")];
+
+fn key_code(name: &str) -> Option<u32> {
+    let name = KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map_or(name, |(_, canonical)| canonical);
+
+    if let Some((_, code)) = NAMED_KEYS.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)) {
+        return Some(*code);
+    }
+
+    let upper = name.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    if bytes.len() == 1 && (bytes[0].is_ascii_uppercase() || bytes[0].is_ascii_digit()) {
+        // 'A'..'Z' and '0'..'9' are their own virtual-key codes
+        return Some(bytes[0] as u32);
+    }
+    if let Some(n) = upper.strip_prefix("NUMPAD").and_then(|n| n.parse::<u32>().ok()) {
+        return (n <= 9).then_some(0x60 + n);
+    }
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        return (1..=24).contains(&n).then_some(0x70 + n - 1);
+    }
+    None
+}
+
+fn key_name(code: u32) -> String {
+    if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, c)| *c == code) {
+        return name.to_string();
+    }
+    match code {
+        0x30..=0x39 | 0x41..=0x5A => char::from(code as u8).to_string(),
+        0x60..=0x69 => format!("Numpad{}", code - 0x60),
+        0x70..=0x87 => format!("F{}", code - 0x70 + 1),
+        _ => format!("0x{:02X}", code),
+    }
+}
+
+/// Why a hotkey string was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyError {
+    Empty,
+    UnknownKey(String),
+    MissingKey,
+    MultipleKeys,
+    DuplicateModifier(String),
+    /// Needs Ctrl, Alt or Win so normal typing is not swallowed
+    NoModifier,
+    /// Reserved by Windows
+    Reserved,
+}
+
+impl fmt::Display for HotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotkeyError::Empty => write!(f, "hotkey is empty"),
+            HotkeyError::UnknownKey(key) => write!(f, "unknown key '{}'", key),
+            HotkeyError::MissingKey => write!(f, "hotkey has modifiers but no key"),
+            HotkeyError::MultipleKeys => write!(f, "hotkey has more than one non-modifier key"),
+            HotkeyError::DuplicateModifier(modifier) => write!(f, "modifier '{}' is repeated", modifier),
+            HotkeyError::NoModifier => write!(f, "hotkey needs Ctrl, Alt or Win"),
+            HotkeyError::Reserved => write!(f, "hotkey is reserved by Windows"),
+        }
+    }
+}
+
+impl std::error::Error for HotkeyError {}
+
+/// A key combination such as Ctrl+Alt+S
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    /// `MOD_*` flags
+    pub modifiers: u32,
+    /// Windows virtual-key code
+    pub key: u32,
+}
+
+impl FromStr for Hotkey {
+    type Err = HotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(HotkeyError::Empty);
+        }
+
+        let mut modifiers = 0;
+        let mut key = None;
+        for part in s.split('+').map(str::trim) {
+            let modifier = match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Some(MOD_CONTROL),
+                "alt" => Some(MOD_ALT),
+                "shift" => Some(MOD_SHIFT),
+                "win" | "windows" | "super" => Some(MOD_WIN),
+                _ => None,
+            };
+            match modifier {
+                Some(flag) if modifiers & flag != 0 => return Err(HotkeyError::DuplicateModifier(part.to_string())),
+                Some(flag) => modifiers |= flag,
+                None if part.is_empty() => return Err(HotkeyError::UnknownKey(s.trim().to_string())),
+                None if key.is_some() => return Err(HotkeyError::MultipleKeys),
+                None => key = Some(key_code(part).ok_or_else(|| HotkeyError::UnknownKey(part.to_string()))?),
+            }
+        }
+
+        let key = key.ok_or(HotkeyError::MissingKey)?;
+        if modifiers & (MOD_CONTROL | MOD_ALT | MOD_WIN) == 0 {
+            return Err(HotkeyError::NoModifier);
+        }
+        let hotkey = Hotkey { modifiers, key };
+        if hotkey.is_reserved() {
+            return Err(HotkeyError::Reserved);
+        }
+        Ok(hotkey)
+    }
+}
+
+impl Hotkey {
+    /// F12 belongs to the debugger, Ctrl+Alt+Del to the secure desktop, Win+L locks the screen
+    fn is_reserved(&self) -> bool {
+        self.key == VK_F12
+            || (self.modifiers & (MOD_CONTROL | MOD_ALT) == (MOD_CONTROL | MOD_ALT) && self.key == VK_DELETE)
+            || (self.modifiers == MOD_WIN && self.key == b'L' as u32)
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (flag, name) in MODIFIERS {
+            if self.modifiers & flag != 0 {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+/// Actions that can be bound to a hotkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    ForceStereo,
+    AllowHandsFree,
+    Reconnect,
+}
+
+impl HotkeyAction {
+    pub const ALL: [HotkeyAction; 3] = [HotkeyAction::ForceStereo, HotkeyAction::AllowHandsFree, HotkeyAction::Reconnect];
+
+    /// Name of the setting under `[hotkeys]`
+    pub fn config_key(self) -> &'static str {
+        match self {
+            HotkeyAction::ForceStereo => "force_stereo",
+            HotkeyAction::AllowHandsFree => "allow_hands_free",
+            HotkeyAction::Reconnect => "reconnect",
+        }
+    }
+
+    fn configured(self, config: &HotkeysConfig) -> &str {
+        match self {
+            HotkeyAction::ForceStereo => &config.force_stereo,
+            HotkeyAction::AllowHandsFree => &config.allow_hands_free,
+            HotkeyAction::Reconnect => &config.reconnect,
+        }
+    }
+
+    /// The menu event this action triggers for a device
+    pub fn menu_event(self, device: String) -> MenuEvent {
+        match self {
+            HotkeyAction::ForceStereo => MenuEvent::ForceStereo(device),
+            HotkeyAction::AllowHandsFree => MenuEvent::AllowHandsFree(device),
+            HotkeyAction::Reconnect => MenuEvent::ReconnectDevice(device),
+        }
+    }
+}
+
+/// A configured hotkey that will not be active
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyProblem {
+    Invalid {
+        action: HotkeyAction,
+        value: String,
+        error: HotkeyError,
+    },
+    /// Bound to two actions; only `kept` gets it
+    Conflict {
+        hotkey: Hotkey,
+        kept: HotkeyAction,
+        dropped: HotkeyAction,
+    },
+    /// Registration failed, usually because another application owns the hotkey
+    Unavailable {
+        action: HotkeyAction,
+        hotkey: Hotkey,
+        error: String,
+    },
+}
+
+impl fmt::Display for HotkeyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotkeyProblem::Invalid { action, value, error } => {
+                write!(f, "hotkeys.{} = '{}': {}", action.config_key(), value, error)
+            }
+            HotkeyProblem::Conflict { hotkey, kept, dropped } => write!(
+                f,
+                "{} is set for both hotkeys.{} and hotkeys.{}; only the first is used",
+                hotkey,
+                kept.config_key(),
+                dropped.config_key()
+            ),
+            HotkeyProblem::Unavailable { action, hotkey, error } => {
+                write!(f, "{} (hotkeys.{}) could not be registered: {}", hotkey, action.config_key(), error)
+            }
+        }
+    }
+}
+
+/// Parse the configured hotkeys; empty settings are unbound
+///
+/// Invalid hotkeys and later duplicates are left out and reported.
+pub fn bindings(config: &HotkeysConfig) -> (Vec<(HotkeyAction, Hotkey)>, Vec<HotkeyProblem>) {
+    let mut bound: Vec<(HotkeyAction, Hotkey)> = Vec::new();
+    let mut problems = Vec::new();
+
+    for action in HotkeyAction::ALL {
+        let value = action.configured(config);
+        if value.trim().is_empty() {
+            continue;
+        }
+        match value.parse::<Hotkey>() {
+            Ok(hotkey) => match bound.iter().find(|(_, existing)| *existing == hotkey) {
+                Some((kept, _)) => problems.push(HotkeyProblem::Conflict {
+                    hotkey,
+                    kept: *kept,
+                    dropped: action,
+                }),
+                None => bound.push((action, hotkey)),
+            },
+            Err(error) => problems.push(HotkeyProblem::Invalid {
+                action,
+                value: value.to_string(),
+                error,
+            }),
+        }
+    }
+    (bound, problems)
+}
+
+/// Headset a hotkey acts on: the default output if it is a connected Bluetooth device, else the first one
+pub fn active_device(default_output: Option<&str>, devices: &[BluetoothAudioDevice]) -> Option<String> {
+    default_output
+        .and_then(|name| devices.iter().find(|d| d.device.name == name))
+        .or_else(|| devices.first())
+        .map(|d| d.device.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names_round_trip() {
+        for name in ["A", "7", "F1", "F24", "Numpad5", "Space", "PageDown", "Delete"] {
+            assert_eq!(key_name(key_code(name).unwrap()), name);
+        }
+        assert_eq!(key_code("esc"), Some(0x1B));
+        assert_eq!(key_code("F25"), None);
+        assert_eq!(key_code("Numpad10"), None);
+    }
+}
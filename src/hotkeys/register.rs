@@ -0,0 +1,74 @@
+//! Registration of hotkeys with Windows
+//!
+//! Hotkeys are registered for the calling thread (no window), so Windows
+//! posts `WM_HOTKEY` to that thread's message queue. Register them on the
+//! thread that runs the message loop.
+
+use super::{Hotkey, HotkeyAction, HotkeyProblem};
+use log::{info, warn};
+use std::collections::HashMap;
+use windows::Win32::Foundation::{ERROR_HOTKEY_ALREADY_REGISTERED, HWND};
+use windows::Win32::UI::Input::KeyboardAndMouse::{RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_NOREPEAT};
+use windows::Win32::UI::WindowsAndMessaging::{MSG, WM_HOTKEY};
+
+/// Hotkeys registered by this thread, by hotkey id
+#[derive(Default)]
+pub struct RegisteredHotkeys {
+    actions: HashMap<i32, HotkeyAction>,
+}
+
+impl RegisteredHotkeys {
+    /// Register each binding, reporting the ones Windows refused
+    pub fn register(bindings: &[(HotkeyAction, Hotkey)]) -> (Self, Vec<HotkeyProblem>) {
+        let mut registered = Self::default();
+        let mut problems = Vec::new();
+
+        for (index, (action, hotkey)) in bindings.iter().enumerate() {
+            let id = index as i32 + 1;
+            // MOD_NOREPEAT: holding the keys down fires once
+            let modifiers = HOT_KEY_MODIFIERS(hotkey.modifiers) | MOD_NOREPEAT;
+            match unsafe { RegisterHotKey(HWND::default(), id, modifiers, hotkey.key) } {
+                Ok(()) => {
+                    info!("Registered hotkey {} for {}", hotkey, action.config_key());
+                    registered.actions.insert(id, *action);
+                }
+                Err(e) => {
+                    let error = if e.code() == ERROR_HOTKEY_ALREADY_REGISTERED.to_hresult() {
+                        "already in use by another application".to_string()
+                    } else {
+                        e.to_string()
+                    };
+                    warn!("Failed to register hotkey {}: {}", hotkey, error);
+                    problems.push(HotkeyProblem::Unavailable {
+                        action: *action,
+                        hotkey: *hotkey,
+                        error,
+                    });
+                }
+            }
+        }
+        (registered, problems)
+    }
+
+    /// The action for a `WM_HOTKEY` message, if it is one of ours
+    pub fn action_for(&self, msg: &MSG) -> Option<HotkeyAction> {
+        if msg.message != WM_HOTKEY {
+            return None;
+        }
+        self.actions.get(&(msg.wParam.0 as i32)).copied()
+    }
+
+    /// Release all hotkeys
+    pub fn unregister_all(&mut self) {
+        for id in self.actions.keys() {
+            let _ = unsafe { UnregisterHotKey(HWND::default(), *id) };
+        }
+        self.actions.clear();
+    }
+}
+
+impl Drop for RegisteredHotkeys {
+    fn drop(&mut self) {
+        self.unregister_all();
+    }
+}
@@ -0,0 +1,333 @@
+//! Loopback HTTP endpoint for dashboards and widgets
+//!
+//! A deliberately small HTTP/1.1 subset: every request is answered and the
+//! connection closed, except `GET /events`, which stays open as a
+//! server-sent event stream. Requests need the bearer token from config.toml,
+//! either in an `Authorization` header or, for clients such as `EventSource`
+//! that cannot set headers, as an `access_token` query parameter.
+
+pub mod server;
+
+use crate::audio::bus::Topic;
+use crate::auth::tokens_match;
+use crate::cli::{CliBackend, CliError};
+use serde::Serialize;
+use serde_json::json;
+
+/// Upper bound for the request line and headers
+pub const MAX_HEADER_SIZE: usize = 16 * 1024;
+
+/// Upper bound for a request body
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Event topics streamed on `/events`
+pub const SSE_TOPICS: &[Topic] = &[Topic::ModeChanged];
+
+/// A parsed HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Path without the query string, still percent-encoded
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub content_length: usize,
+}
+
+impl HttpRequest {
+    /// First header with the given (lowercase) name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// First query parameter with the given name
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Token presented as `Authorization: Bearer` or `access_token`
+    fn presented_token(&self) -> Option<&str> {
+        if let Some(value) = self.header("authorization") {
+            let (scheme, token) = value.split_once(' ')?;
+            return scheme.eq_ignore_ascii_case("bearer").then_some(token.trim());
+        }
+        self.query_param("access_token")
+    }
+}
+
+/// Parse the request line and headers (everything before the blank line)
+pub fn parse_request(head: &str) -> Result<HttpRequest, HttpResponse> {
+    let bad_request = |message: &str| HttpResponse::error(400, "usage", message);
+
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(bad_request("Malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(HttpResponse::error(505, "usage", "Only HTTP/1.x is supported"));
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_query_component(key), decode_query_component(value))
+        })
+        .collect();
+
+    let mut headers = Vec::new();
+    for line in lines.filter(|line| !line.is_empty()) {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(bad_request("Malformed header"));
+        };
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    let content_length = match headers.iter().find(|(name, _)| name == "content-length") {
+        Some((_, value)) => value
+            .parse()
+            .map_err(|_| bad_request("Invalid Content-Length"))?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_SIZE {
+        return Err(HttpResponse::error(413, "usage", "Request body too large"));
+    }
+    if headers.iter().any(|(name, _)| name == "transfer-encoding") {
+        return Err(HttpResponse::error(501, "usage", "Chunked requests are not supported"));
+    }
+
+    Ok(HttpRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        headers,
+        content_length,
+    })
+}
+
+fn decode_query_component(s: &str) -> String {
+    percent_decode(&s.replace('+', " ")).unwrap_or_default()
+}
+
+/// Decode `%XX` escapes; `None` if an escape or the result is invalid
+pub fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// A complete (non-streaming) response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// 200 with a JSON body
+    pub fn json<T: Serialize>(value: &T) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: serde_json::to_string(value).expect("response serialization cannot fail"),
+        }
+    }
+
+    /// Error with a body shaped like the CLI's `--json` errors
+    pub fn error(status: u16, kind: &str, message: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: json!({ "error": { "kind": kind, "message": message } }).to_string(),
+        }
+    }
+
+    pub(crate) fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    /// Allow any origin to read the response
+    ///
+    /// Only for endpoints behind the bearer token; unauthenticated ones such
+    /// as `/metrics` must not be readable by other web pages.
+    pub fn with_cors(self) -> Self {
+        self.with_header("Access-Control-Allow-Origin", "*")
+    }
+
+    /// Serialize including status line and headers; the connection is closed afterwards
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason_phrase(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len()));
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(self.body.as_bytes());
+        bytes
+    }
+}
+
+impl From<CliError> for HttpResponse {
+    fn from(e: CliError) -> Self {
+        let status = match e {
+            CliError::Usage(_) => 400,
+            CliError::NotFound(_) => 404,
+            CliError::Failed(_) => 500,
+            CliError::Unavailable(_) => 503,
+        };
+        Self::error(status, e.kind(), e.message())
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown",
+    }
+}
+
+/// What the server should do with a request
+#[derive(Debug, PartialEq, Eq)]
+pub enum HttpOutcome {
+    Respond(HttpResponse),
+    /// Switch the connection to a server-sent event stream
+    StreamEvents,
+}
+
+/// Authenticate and route a request
+pub fn handle_request(request: &HttpRequest, token: &str, backend: &mut dyn CliBackend) -> HttpOutcome {
+    // CORS preflight carries no credentials
+    if request.method == "OPTIONS" {
+        return HttpOutcome::Respond(
+            HttpResponse {
+                status: 204,
+                headers: Vec::new(),
+                body: String::new(),
+            }
+            .with_header("Access-Control-Allow-Methods", "GET, POST")
+            .with_header("Access-Control-Allow-Headers", "Authorization"),
+        );
+    }
+
+    let authorized = request
+        .presented_token()
+        .is_some_and(|presented| tokens_match(token, presented));
+    if !authorized {
+        return HttpOutcome::Respond(
+            HttpResponse::error(401, "unauthorized", "Missing or invalid bearer token")
+                .with_header("WWW-Authenticate", "Bearer"),
+        );
+    }
+
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let response = match segments.as_slice() {
+        ["status"] => match request.method.as_str() {
+            "GET" => backend.status().map(|report| HttpResponse::json(&report)).unwrap_or_else(HttpResponse::from),
+            _ => method_not_allowed("GET"),
+        },
+        ["events"] => match request.method.as_str() {
+            "GET" => return HttpOutcome::StreamEvents,
+            _ => method_not_allowed("GET"),
+        },
+        ["devices", id, "force-stereo"] => match request.method.as_str() {
+            "POST" => force_stereo(id, backend),
+            _ => method_not_allowed("POST"),
+        },
+        _ => HttpResponse::error(404, "not_found", "No such endpoint"),
+    };
+    HttpOutcome::Respond(response)
+}
+
+fn method_not_allowed(allow: &str) -> HttpResponse {
+    HttpResponse::error(405, "usage", "Method not allowed").with_header("Allow", allow)
+}
+
+/// Force stereo on a device given by address or (abbreviated) name
+fn force_stereo(id: &str, backend: &mut dyn CliBackend) -> HttpResponse {
+    let Some(id) = percent_decode(id) else {
+        return HttpResponse::error(400, "usage", "Invalid device id");
+    };
+
+    let by_address = backend.devices().ok().and_then(|devices| {
+        devices
+            .into_iter()
+            .find(|device| device.address.eq_ignore_ascii_case(&id))
+            .map(|device| device.name)
+    });
+
+    backend
+        .force_stereo(by_address.as_deref().unwrap_or(&id))
+        .map(|report| HttpResponse::json(&report))
+        .unwrap_or_else(HttpResponse::from)
+}
+
+/// Format one server-sent event
+pub fn sse_event(event_type: &str, data: &serde_json::Value) -> String {
+    format!("event: {}\ndata: {}\n\n", event_type, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = parse_request(
+            "GET /status?access_token=a%2Bb&x HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer abc\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/status");
+        assert_eq!(request.query_param("access_token"), Some("a+b"));
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+        assert_eq!(request.presented_token(), Some("abc"));
+
+        assert_eq!(parse_request("GET /status").unwrap_err().status, 400);
+        assert_eq!(parse_request("GET / HTTP/2\r\n").unwrap_err().status, 505);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("WH-1000XM4%20Headset").as_deref(), Some("WH-1000XM4 Headset"));
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%4"), None);
+    }
+}
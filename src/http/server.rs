@@ -0,0 +1,222 @@
+//! Loopback TCP listener for the HTTP endpoint
+//!
+//! One thread per connection. Commands go through a `RemoteBackend` over a
+//! `ChannelTransport`, so they run on the main loop like every other remote
+//! command; `/events` streams from the shared `EventHub`.
+
+use super::{handle_request, parse_request, sse_event, HttpOutcome, HttpResponse, MAX_HEADER_SIZE, SSE_TOPICS};
+use crate::cli::CliBackend;
+use crate::error::{AppError, Result};
+use crate::ipc::{ChannelTransport, IpcCall, RemoteBackend};
+use crate::rpc::EventHub;
+use log::{debug, info, warn};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the accept loop and event streams check for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Comment sent on idle event streams so proxies and clients keep them open
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Clone)]
+struct ConnectionContext {
+    token: String,
+    call_tx: Sender<IpcCall>,
+    hub: EventHub,
+    running: Arc<AtomicBool>,
+}
+
+/// HTTP server listening on a loopback address
+pub struct HttpServer {
+    running: Arc<AtomicBool>,
+    local_addr: SocketAddr,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// Listen on `addr`, which must be a loopback address (port 0 picks a free port)
+    pub fn start(addr: SocketAddr, token: String, call_tx: Sender<IpcCall>, hub: EventHub) -> Result<Self> {
+        if !addr.ip().is_loopback() {
+            return Err(AppError::ConfigError(format!(
+                "HTTP server must listen on a loopback address, not {}",
+                addr
+            )));
+        }
+
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let context = ConnectionContext {
+            token,
+            call_tx,
+            hub,
+            running: Arc::clone(&running),
+        };
+
+        let thread_handle = thread::Builder::new()
+            .name("http-server".to_string())
+            .spawn(move || accept_thread(listener, context))
+            .map_err(AppError::IoError)?;
+
+        info!("HTTP server listening on http://{}", local_addr);
+        Ok(Self {
+            running,
+            local_addr,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting and close all connections, including event streams
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn accept_thread(listener: TcpListener, context: ConnectionContext) {
+    let mut connections: Vec<JoinHandle<()>> = Vec::new();
+
+    while context.running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                debug!("HTTP client connected from {}", peer);
+                let context = context.clone();
+                let spawned = thread::Builder::new()
+                    .name("http-connection".to_string())
+                    .spawn(move || {
+                        if let Err(e) = serve_connection(stream, &context) {
+                            debug!("HTTP connection closed: {}", e);
+                        }
+                    });
+                match spawned {
+                    Ok(handle) => connections.push(handle),
+                    Err(e) => warn!("Failed to start HTTP connection: {}", e),
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                warn!("HTTP accept failed: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+        connections.retain(|handle| !handle.is_finished());
+    }
+
+    for handle in connections {
+        let _ = handle.join();
+    }
+    debug!("HTTP server stopped");
+}
+
+fn serve_connection(mut stream: TcpStream, context: &ConnectionContext) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let (head, body_read) = match read_head(&mut stream)? {
+        Ok(head) => head,
+        Err(response) => return stream.write_all(&response.with_cors().to_bytes()),
+    };
+    let request = match parse_request(&head) {
+        Ok(request) => request,
+        Err(response) => return stream.write_all(&response.with_cors().to_bytes()),
+    };
+
+    // Bodies are not used by any endpoint; read and discard so the client sees a clean close
+    let body_left = request.content_length.saturating_sub(body_read) as u64;
+    io::copy(&mut (&mut stream).take(body_left), &mut io::sink())?;
+
+    let mut backend = RemoteBackend::new(ChannelTransport::new(context.call_tx.clone()));
+    match handle_request(&request, &context.token, &mut backend) {
+        HttpOutcome::Respond(response) => stream.write_all(&response.with_cors().to_bytes()),
+        HttpOutcome::StreamEvents => stream_events(stream, context, &mut backend),
+    }
+}
+
+/// Read up to the blank line ending the headers
+///
+/// Returns the headers and how many body bytes were read along with them.
+pub(crate) fn read_head(stream: &mut TcpStream) -> io::Result<std::result::Result<(String, usize), HttpResponse>> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+
+    loop {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        head.extend_from_slice(&buffer[..read]);
+
+        if let Some(pos) = head.windows(4).position(|w| w == b"\r\n\r\n") {
+            let body_read = head.len() - (pos + 4);
+            head.truncate(pos);
+            return Ok(String::from_utf8(head)
+                .map(|head| (head, body_read))
+                .map_err(|_| HttpResponse::error(400, "usage", "Request is not UTF-8")));
+        }
+        if head.len() > MAX_HEADER_SIZE {
+            return Ok(Err(HttpResponse::error(413, "usage", "Request headers too large")));
+        }
+    }
+}
+
+fn stream_events(mut stream: TcpStream, context: &ConnectionContext, backend: &mut dyn CliBackend) -> io::Result<()> {
+    let events = context
+        .hub
+        .subscribe(Some(SSE_TOPICS.iter().copied().collect()));
+
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+          Access-Control-Allow-Origin: *\r\nConnection: keep-alive\r\n\r\n",
+    )?;
+
+    // Start with the full state so a widget can render before the first change
+    match backend.status() {
+        Ok(report) => {
+            let data = serde_json::to_value(report).expect("report serialization cannot fail");
+            stream.write_all(sse_event("status", &data).as_bytes())?;
+        }
+        Err(e) => debug!("Initial status for event stream failed: {}", e),
+    }
+    stream.flush()?;
+
+    let mut last_write = Instant::now();
+    while context.running.load(Ordering::SeqCst) {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => {
+                stream.write_all(sse_event(event.event_type, &event.payload).as_bytes())?;
+                last_write = Instant::now();
+            }
+            Err(RecvTimeoutError::Timeout) if last_write.elapsed() >= KEEPALIVE_INTERVAL => {
+                stream.write_all(b": keep-alive\n\n")?;
+                last_write = Instant::now();
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(())
+}
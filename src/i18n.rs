@@ -1,14 +1,16 @@
 //! Internationalization support using rust-i18n
 //!
-//! Provides locale detection from Windows and initialization of the i18n system.
+//! Provides locale detection from the OS and initialization of the i18n system.
 
 use log::{info, warn};
-use windows::Win32::Globalization::GetUserDefaultLocaleName;
 
 /// Detect the user's OS locale using Windows API
 ///
 /// Returns the locale string (e.g., "en-US", "zh-CN") or falls back to "en" on failure.
+#[cfg(windows)]
 pub fn detect_locale() -> String {
+    use windows::Win32::Globalization::GetUserDefaultLocaleName;
+
     unsafe {
         let mut buffer = [0u16; 85]; // LOCALE_NAME_MAX_LENGTH
         let len = GetUserDefaultLocaleName(&mut buffer);
@@ -32,6 +34,39 @@ pub fn detect_locale() -> String {
     }
 }
 
+/// Detect the user's locale from the POSIX locale environment variables
+///
+/// Returns the locale string (e.g., "en-US", "zh-CN") or falls back to "en".
+#[cfg(not(windows))]
+pub fn detect_locale() -> String {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find_map(|value| locale_from_posix(&value));
+
+    match locale {
+        Some(locale) => {
+            info!("Detected system locale: {}", locale);
+            locale
+        }
+        None => {
+            warn!("No usable locale in LC_ALL, LC_MESSAGES or LANG, falling back to 'en'");
+            "en".to_string()
+        }
+    }
+}
+
+/// Convert a POSIX locale such as `de_DE.UTF-8` to a BCP 47 tag (`de-DE`)
+///
+/// Returns `None` for unset values and the `C`/`POSIX` locales.
+pub fn locale_from_posix(value: &str) -> Option<String> {
+    let name = value.split(['.', '@']).next().unwrap_or_default();
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return None;
+    }
+    Some(name.replace('_', "-"))
+}
+
 /// Initialize the i18n system with optional language override
 ///
 /// If `config_language` is Some, uses that locale. Otherwise, detects the system locale.
@@ -94,6 +129,17 @@ mod tests {
         assert!(current_locale.starts_with("ja"), "Locale should be Japanese");
     }
 
+    #[test]
+    fn test_locale_from_posix() {
+        assert_eq!(locale_from_posix("de_DE.UTF-8").as_deref(), Some("de-DE"));
+        assert_eq!(locale_from_posix("zh_TW").as_deref(), Some("zh-TW"));
+        assert_eq!(locale_from_posix("sr_RS@latin").as_deref(), Some("sr-RS"));
+        assert_eq!(locale_from_posix("ja").as_deref(), Some("ja"));
+        assert_eq!(locale_from_posix("C.UTF-8"), None);
+        assert_eq!(locale_from_posix("POSIX"), None);
+        assert_eq!(locale_from_posix(""), None);
+    }
+
     #[test]
     fn test_get_language_display_names_returns_expected_list() {
         let languages = get_language_display_names();
@@ -0,0 +1,330 @@
+//! Command forwarding between a second instance and the running tray instance
+//!
+//! A second process started with a CLI command sends it to the running
+//! instance instead of touching Bluetooth itself, so the tray's state (forced
+//! stereo devices, open settings window) stays in sync. Messages are single
+//! JSON lines carrying a protocol version; the transport is abstracted so the
+//! dispatcher and client can be tested without real pipes.
+
+#[cfg(windows)]
+pub mod pipe;
+
+use crate::cli::{ActionReport, CliBackend, CliError, DeviceEntry, StatusReport};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// Version of the request/response format
+///
+/// Bump when a change is not backwards compatible; both sides reject
+/// messages with a different version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Upper bound for a single message, to protect against runaway peers
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// How long a server thread waits for the main loop to answer a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Request sent by the second instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    #[serde(flatten)]
+    pub command: RequestCommand,
+}
+
+/// Command carried by a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum RequestCommand {
+    Status,
+    Devices,
+    ForceStereo { device: String },
+    AllowHandsFree { device: String },
+    Reconnect { device: String },
+    Mute { pid: u32 },
+    Unmute { pid: u32 },
+    Restore,
+    OpenSettings,
+}
+
+/// Response sent back by the running instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    #[serde(flatten)]
+    pub body: ResponseBody,
+}
+
+/// Result carried by a response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", content = "data", rename_all = "kebab-case")]
+pub enum ResponseBody {
+    Status(StatusReport),
+    Devices(Vec<DeviceEntry>),
+    Action(ActionReport),
+    Error(CliError),
+}
+
+/// Sends one encoded request and returns the encoded response
+pub trait Transport {
+    fn round_trip(&mut self, request: &str) -> Result<String, CliError>;
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn round_trip(&mut self, request: &str) -> Result<String, CliError> {
+        (**self).round_trip(request)
+    }
+}
+
+/// A request waiting for the main loop to answer
+///
+/// Server threads (pipe, JSON-RPC) create these; the main loop owns the app
+/// state, runs `dispatch` and replies.
+pub struct IpcCall {
+    pub request: String,
+    reply_tx: Sender<String>,
+}
+
+impl IpcCall {
+    /// Create a call and the receiver its reply arrives on
+    pub fn new(request: String) -> (Self, Receiver<String>) {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        (Self { request, reply_tx }, reply_rx)
+    }
+
+    /// Send the response line back to the waiting caller
+    pub fn reply(self, response: String) {
+        let _ = self.reply_tx.send(response);
+    }
+}
+
+/// Transport that hands requests to the main loop over a channel
+#[derive(Clone)]
+pub struct ChannelTransport {
+    call_tx: Sender<IpcCall>,
+}
+
+impl ChannelTransport {
+    pub fn new(call_tx: Sender<IpcCall>) -> Self {
+        Self { call_tx }
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn round_trip(&mut self, request: &str) -> Result<String, CliError> {
+        let (call, reply_rx) = IpcCall::new(request.to_string());
+        self.call_tx
+            .send(call)
+            .map_err(|_| CliError::Unavailable("The application is shutting down".to_string()))?;
+        reply_rx
+            .recv_timeout(REPLY_TIMEOUT)
+            .map_err(|_| CliError::Failed("Timed out waiting for the application".to_string()))
+    }
+}
+
+/// Encode a command as a request line (without the trailing newline)
+pub fn encode_request(command: RequestCommand) -> String {
+    let request = Request {
+        version: PROTOCOL_VERSION,
+        command,
+    };
+    serde_json::to_string(&request).expect("request serialization cannot fail")
+}
+
+/// Decode a request line, checking the protocol version first
+///
+/// The version is read before the command so that a newer client with an
+/// unknown command gets a version error rather than a parse error.
+pub fn decode_request(line: &str) -> Result<RequestCommand, CliError> {
+    let value: serde_json::Value = serde_json::from_str(line.trim())
+        .map_err(|e| CliError::Usage(format!("Malformed request: {}", e)))?;
+
+    check_version(&value)?;
+
+    serde_json::from_value::<Request>(value)
+        .map(|request| request.command)
+        .map_err(|e| CliError::Usage(format!("Unsupported request: {}", e)))
+}
+
+/// Decode a response line from the running instance
+pub fn decode_response(line: &str) -> Result<ResponseBody, CliError> {
+    let value: serde_json::Value = serde_json::from_str(line.trim())
+        .map_err(|e| CliError::Failed(format!("Malformed response from running instance: {}", e)))?;
+
+    check_version(&value)?;
+
+    serde_json::from_value::<Response>(value)
+        .map(|response| response.body)
+        .map_err(|e| CliError::Failed(format!("Malformed response from running instance: {}", e)))
+}
+
+fn check_version(value: &serde_json::Value) -> Result<(), CliError> {
+    match value.get("version").and_then(serde_json::Value::as_u64) {
+        Some(version) if version == PROTOCOL_VERSION as u64 => Ok(()),
+        Some(version) => Err(CliError::Unavailable(format!(
+            "IPC protocol version {} does not match version {}; restart Bluetooth Audio Mode Manager",
+            version, PROTOCOL_VERSION
+        ))),
+        None => Err(CliError::Usage("Message has no protocol version".to_string())),
+    }
+}
+
+/// Handle one request line on the running instance and return the response line
+pub fn dispatch(line: &str, backend: &mut dyn CliBackend) -> String {
+    let body = match decode_request(line) {
+        Ok(command) => execute(command, backend),
+        Err(e) => ResponseBody::Error(e),
+    };
+
+    let response = Response {
+        version: PROTOCOL_VERSION,
+        body,
+    };
+    serde_json::to_string(&response).expect("response serialization cannot fail")
+}
+
+fn execute(command: RequestCommand, backend: &mut dyn CliBackend) -> ResponseBody {
+    let result = match command {
+        RequestCommand::Status => backend.status().map(ResponseBody::Status),
+        RequestCommand::Devices => backend.devices().map(ResponseBody::Devices),
+        RequestCommand::ForceStereo { device } => backend.force_stereo(&device).map(ResponseBody::Action),
+        RequestCommand::AllowHandsFree { device } => backend.allow_hands_free(&device).map(ResponseBody::Action),
+        RequestCommand::Reconnect { device } => backend.reconnect(&device).map(ResponseBody::Action),
+        RequestCommand::Mute { pid } => backend.mute(pid).map(ResponseBody::Action),
+        RequestCommand::Unmute { pid } => backend.unmute(pid).map(ResponseBody::Action),
+        RequestCommand::Restore => backend.restore().map(ResponseBody::Action),
+        RequestCommand::OpenSettings => backend.open_settings().map(ResponseBody::Action),
+    };
+    result.unwrap_or_else(ResponseBody::Error)
+}
+
+/// CLI backend that forwards every command to the running instance
+pub struct RemoteBackend<T: Transport> {
+    transport: T,
+}
+
+impl<T: Transport> RemoteBackend<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    fn call(&mut self, command: RequestCommand) -> Result<ResponseBody, CliError> {
+        let response = self.transport.round_trip(&encode_request(command))?;
+        match decode_response(&response)? {
+            ResponseBody::Error(e) => Err(e),
+            body => Ok(body),
+        }
+    }
+
+    fn call_action(&mut self, command: RequestCommand) -> Result<ActionReport, CliError> {
+        match self.call(command)? {
+            ResponseBody::Action(report) => Ok(report),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(body: ResponseBody) -> CliError {
+    CliError::Failed(format!("Unexpected response from running instance: {:?}", body))
+}
+
+impl<T: Transport> CliBackend for RemoteBackend<T> {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        match self.call(RequestCommand::Status)? {
+            ResponseBody::Status(report) => Ok(report),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        match self.call(RequestCommand::Devices)? {
+            ResponseBody::Devices(devices) => Ok(devices),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::ForceStereo {
+            device: device.to_string(),
+        })
+    }
+
+    fn allow_hands_free(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::AllowHandsFree {
+            device: device.to_string(),
+        })
+    }
+
+    fn reconnect(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::Reconnect {
+            device: device.to_string(),
+        })
+    }
+
+    fn mute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::Mute { pid })
+    }
+
+    fn unmute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::Unmute { pid })
+    }
+
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::Restore)
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        self.call_action(RequestCommand::OpenSettings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_wire_format() {
+        let line = encode_request(RequestCommand::ForceStereo {
+            device: "Jabra".to_string(),
+        });
+        assert_eq!(line, r#"{"version":1,"command":"force-stereo","device":"Jabra"}"#);
+        assert_eq!(
+            decode_request(&line),
+            Ok(RequestCommand::ForceStereo {
+                device: "Jabra".to_string()
+            })
+        );
+        assert_eq!(
+            decode_request(r#"{"version":1,"command":"open-settings"}"#),
+            Ok(RequestCommand::OpenSettings)
+        );
+    }
+
+    #[test]
+    fn test_version_is_checked_before_command() {
+        let err = decode_request(r#"{"version":2,"command":"teleport"}"#).unwrap_err();
+        assert!(matches!(err, CliError::Unavailable(_)));
+
+        let err = decode_request(r#"{"command":"status"}"#).unwrap_err();
+        assert!(matches!(err, CliError::Usage(_)));
+
+        let err = decode_request(r#"{"version":1,"command":"teleport"}"#).unwrap_err();
+        assert!(matches!(err, CliError::Usage(_)));
+    }
+
+    #[test]
+    fn test_error_response_round_trip() {
+        let response = Response {
+            version: PROTOCOL_VERSION,
+            body: ResponseBody::Error(CliError::NotFound("No device".to_string())),
+        };
+        let line = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            line,
+            r#"{"version":1,"result":"error","data":{"kind":"not_found","message":"No device"}}"#
+        );
+        assert_eq!(decode_response(&line), Ok(response.body));
+    }
+}
@@ -0,0 +1,249 @@
+//! Named pipe transport for command forwarding
+//!
+//! The running instance serves one connection at a time on a background
+//! thread and hands each request to the main loop, which owns the app state.
+//! A connection carries exactly one request line and one response line.
+
+use super::{ChannelTransport, IpcCall, Transport, MAX_MESSAGE_SIZE};
+use crate::cli::CliError;
+use crate::error::{AppError, Result};
+use log::{debug, error, info, warn};
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_FILE_NOT_FOUND, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED,
+    GENERIC_READ, GENERIC_WRITE, HANDLE,
+};
+use windows::Win32::Storage::FileSystem::{
+    CreateFileW, FlushFileBuffers, ReadFile, WriteFile, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_NONE,
+    OPEN_EXISTING, PIPE_ACCESS_DUPLEX,
+};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, WaitNamedPipeW, NMPWAIT_NOWAIT,
+    PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES,
+    PIPE_WAIT,
+};
+
+/// Pipe name; local connections only
+pub const PIPE_NAME: &str = r"\\.\pipe\BtAudioModeManager";
+
+/// How long a client waits for a busy server
+const BUSY_WAIT_MS: u32 = 5000;
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Named pipe server run by the tray instance
+pub struct PipeServer {
+    running: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl PipeServer {
+    /// Start serving on a background thread, handing requests to `call_tx`
+    pub fn start(call_tx: Sender<IpcCall>) -> Result<Self> {
+        let running = Arc::new(AtomicBool::new(true));
+        let running_clone = Arc::clone(&running);
+
+        let thread_handle = thread::Builder::new()
+            .name("ipc-server".to_string())
+            .spawn(move || server_thread(call_tx, running_clone))
+            .map_err(AppError::IoError)?;
+
+        info!("IPC server listening on {}", PIPE_NAME);
+        Ok(Self {
+            running,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Stop the server thread
+    ///
+    /// Pending requests must be dropped first (by dropping the receiver) so
+    /// a connection waiting for its reply does not hold up the shutdown.
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // Unblock ConnectNamedPipe with a throwaway connection
+        if let Ok(pipe) = open_client(PIPE_NAME) {
+            unsafe {
+                let _ = CloseHandle(pipe);
+            }
+        }
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for PipeServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn server_thread(call_tx: Sender<IpcCall>, running: Arc<AtomicBool>) {
+    let name = wide(PIPE_NAME);
+
+    while running.load(Ordering::SeqCst) {
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                PCWSTR::from_raw(name.as_ptr()),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                None,
+            )
+        };
+        if pipe.is_invalid() {
+            error!("Failed to create IPC pipe: {:?}", unsafe { GetLastError() });
+            return;
+        }
+
+        let connected = match unsafe { ConnectNamedPipe(pipe, None) } {
+            Ok(()) => true,
+            // Client connected between CreateNamedPipe and ConnectNamedPipe
+            Err(e) => e.code() == ERROR_PIPE_CONNECTED.to_hresult(),
+        };
+
+        if connected && running.load(Ordering::SeqCst) {
+            if let Err(e) = serve_connection(pipe, &call_tx) {
+                warn!("IPC connection failed: {}", e);
+            }
+        }
+
+        unsafe {
+            let _ = DisconnectNamedPipe(pipe);
+            let _ = CloseHandle(pipe);
+        }
+    }
+
+    debug!("IPC server stopped");
+}
+
+fn serve_connection(pipe: HANDLE, call_tx: &Sender<IpcCall>) -> Result<()> {
+    let request = read_line(pipe)?;
+    debug!("IPC request: {}", request);
+
+    let mut transport = ChannelTransport::new(call_tx.clone());
+    let response = transport
+        .round_trip(&request)
+        .map_err(|e| AppError::ConfigError(e.to_string()))?;
+    write_line(pipe, &response)
+}
+
+/// Whether a tray instance is serving the pipe
+pub fn is_instance_running() -> bool {
+    let name = wide(PIPE_NAME);
+    unsafe {
+        if WaitNamedPipeW(PCWSTR::from_raw(name.as_ptr()), NMPWAIT_NOWAIT).as_bool() {
+            return true;
+        }
+        // All instances busy still means a server exists
+        GetLastError() != ERROR_FILE_NOT_FOUND
+    }
+}
+
+/// Client side of the pipe, one connection per request
+#[derive(Debug, Default)]
+pub struct PipeTransport;
+
+impl Transport for PipeTransport {
+    fn round_trip(&mut self, request: &str) -> std::result::Result<String, CliError> {
+        let pipe = open_client(PIPE_NAME).map_err(|e| CliError::Unavailable(format!("Cannot reach running instance: {}", e)))?;
+
+        let result = write_line(pipe, request)
+            .and_then(|_| read_line(pipe))
+            .map_err(|e| CliError::Failed(format!("Communication with running instance failed: {}", e)));
+
+        unsafe {
+            let _ = CloseHandle(pipe);
+        }
+        result
+    }
+}
+
+/// Connect to a pipe server, waiting once if all instances are busy
+pub(crate) fn open_client(pipe_name: &str) -> Result<HANDLE> {
+    let name = wide(pipe_name);
+
+    for _ in 0..2 {
+        let result = unsafe {
+            CreateFileW(
+                PCWSTR::from_raw(name.as_ptr()),
+                (GENERIC_READ | GENERIC_WRITE).0,
+                FILE_SHARE_NONE,
+                None,
+                OPEN_EXISTING,
+                FILE_ATTRIBUTE_NORMAL,
+                HANDLE::default(),
+            )
+        };
+
+        match result {
+            Ok(pipe) => return Ok(pipe),
+            Err(e) if e.code() == ERROR_PIPE_BUSY.to_hresult() => unsafe {
+                let _ = WaitNamedPipeW(PCWSTR::from_raw(name.as_ptr()), BUSY_WAIT_MS);
+            },
+            Err(e) => return Err(AppError::WindowsApiError(e)),
+        }
+    }
+
+    Err(AppError::ConfigError("Running instance is busy".to_string()))
+}
+
+/// Read bytes up to the first newline (or end of stream)
+fn read_line(pipe: HANDLE) -> Result<String> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+
+    loop {
+        let mut read = 0u32;
+        let ok = unsafe { ReadFile(pipe, Some(&mut buffer), Some(&mut read as *mut u32), None) };
+        if ok.is_err() || read == 0 {
+            break;
+        }
+
+        let chunk = &buffer[..read as usize];
+        if let Some(pos) = chunk.iter().position(|&b| b == b'\n') {
+            data.extend_from_slice(&chunk[..pos]);
+            break;
+        }
+        data.extend_from_slice(chunk);
+
+        if data.len() > MAX_MESSAGE_SIZE {
+            return Err(AppError::ConfigError("IPC message too large".to_string()));
+        }
+    }
+
+    if data.is_empty() {
+        return Err(AppError::ConfigError("Empty IPC message".to_string()));
+    }
+    String::from_utf8(data).map_err(|_| AppError::ConfigError("IPC message is not UTF-8".to_string()))
+}
+
+fn write_line(pipe: HANDLE, line: &str) -> Result<()> {
+    let mut data = Vec::with_capacity(line.len() + 1);
+    data.extend_from_slice(line.as_bytes());
+    data.push(b'\n');
+
+    let mut written_total = 0;
+    while written_total < data.len() {
+        let mut written = 0u32;
+        unsafe { WriteFile(pipe, Some(&data[written_total..]), Some(&mut written as *mut u32), None)? };
+        written_total += written as usize;
+    }
+
+    unsafe { FlushFileBuffers(pipe)? };
+    Ok(())
+}
+
+pub(crate) fn wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(std::iter::once(0)).collect()
+}
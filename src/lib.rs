@@ -1,18 +1,36 @@
 //! Bluetooth Audio Mode Manager Library
 //!
 //! A Windows application for managing Bluetooth audio device modes (stereo vs hands-free).
+//!
+//! The core (mode detection, settings, watchdog, remote control) is portable;
+//! platform access goes through the backends in `platform`, and the tray UI
+//! and the WASAPI/Win32 backends are only built on Windows.
 
 // Initialize i18n with locales directory and English fallback
 rust_i18n::i18n!("locales", fallback = "en");
 
+pub mod app;
 pub mod audio;
+pub mod auth;
 pub mod bluetooth;
+pub mod cli;
 pub mod error;
+pub mod hooks;
+pub mod hotkeys;
+pub mod http;
 pub mod i18n;
+pub mod ipc;
 pub mod logging;
+pub mod metrics;
+pub mod mqtt;
 pub mod notifications;
+pub mod platform;
 pub mod process;
+pub mod retry;
+pub mod rpc;
 pub mod settings;
+pub mod simulation;
+pub mod snapshot;
 pub mod tray;
 pub mod update;
 
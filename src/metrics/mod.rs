@@ -0,0 +1,238 @@
+//! Prometheus / OpenMetrics exporter
+//!
+//! `Metrics` is a cheap-to-clone handle the main loop, reconnect threads and
+//! the audio monitor record into; `render` produces the OpenMetrics text
+//! served by `server`.
+
+pub mod server;
+
+use crate::audio::{AudioMode, BluetoothAudioDevice};
+use crate::cli::mode_id;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Prefix of every metric name
+const PREFIX: &str = "btam";
+
+/// Upper bounds of the poll duration histogram buckets, in seconds
+pub const POLL_DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// Modes reported by the per-device mode gauge
+const MODES: [AudioMode; 3] = [AudioMode::Stereo, AudioMode::HandsFree, AudioMode::Unknown];
+
+/// Why force stereo was applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForceStereoReason {
+    /// Requested from the menu, CLI or a remote API
+    User,
+    /// Re-applied after the device reconnected
+    Reconnect,
+}
+
+impl ForceStereoReason {
+    fn label(self) -> &'static str {
+        match self {
+            ForceStereoReason::User => "user",
+            ForceStereoReason::Reconnect => "reconnect",
+        }
+    }
+}
+
+/// Who started a reconnect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReconnectSource {
+    User,
+    Watchdog,
+}
+
+impl ReconnectSource {
+    fn label(self) -> &'static str {
+        match self {
+            ReconnectSource::User => "user",
+            ReconnectSource::Watchdog => "watchdog",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Non-cumulative count per bucket, plus one for +Inf
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; POLL_DURATION_BUCKETS.len() + 1];
+        }
+        let index = POLL_DURATION_BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(POLL_DURATION_BUCKETS.len());
+        self.buckets[index] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    device_modes: BTreeMap<String, AudioMode>,
+    mode_transitions: BTreeMap<(&'static str, &'static str), u64>,
+    force_stereo: BTreeMap<ForceStereoReason, u64>,
+    /// By source and whether the reconnect succeeded
+    reconnects: BTreeMap<(ReconnectSource, bool), u64>,
+    poll_duration: Histogram,
+}
+
+/// Shared metric registry
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Registry) -> R) -> R {
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut registry)
+    }
+
+    /// Replace the per-device modes with the connected devices of a poll
+    pub fn set_devices(&self, devices: &[BluetoothAudioDevice]) {
+        self.with(|r| {
+            r.device_modes = devices
+                .iter()
+                .map(|device| (device.device.name.clone(), device.current_mode))
+                .collect();
+        });
+    }
+
+    pub fn record_mode_transition(&self, old_mode: AudioMode, new_mode: AudioMode) {
+        self.with(|r| *r.mode_transitions.entry((mode_id(old_mode), mode_id(new_mode))).or_default() += 1);
+    }
+
+    pub fn record_force_stereo(&self, reason: ForceStereoReason) {
+        self.with(|r| *r.force_stereo.entry(reason).or_default() += 1);
+    }
+
+    pub fn record_reconnect(&self, source: ReconnectSource, success: bool) {
+        self.with(|r| *r.reconnects.entry((source, success)).or_default() += 1);
+    }
+
+    pub fn observe_poll_duration(&self, duration: Duration) {
+        self.with(|r| r.poll_duration.observe(duration.as_secs_f64()));
+    }
+
+    /// Render all metrics in the OpenMetrics text format
+    pub fn render(&self) -> String {
+        self.with(|r| render(r))
+    }
+}
+
+/// Escape a label value (backslash, double quote and line feed)
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+}
+
+fn render(r: &Registry) -> String {
+    let mut out = String::new();
+
+    family(&mut out, "device_mode", "gauge", "1 for the current audio mode of each connected Bluetooth device, 0 otherwise.");
+    for (device, current) in &r.device_modes {
+        for mode in MODES {
+            let _ = writeln!(
+                out,
+                "{}_device_mode{{device=\"{}\",mode=\"{}\"}} {}",
+                PREFIX,
+                escape(device),
+                mode_id(mode),
+                (*current == mode) as u8
+            );
+        }
+    }
+
+    family(&mut out, "mode_transitions", "counter", "Changes of the overall audio mode.");
+    for ((from, to), count) in &r.mode_transitions {
+        let _ = writeln!(out, "{}_mode_transitions_total{{from=\"{}\",to=\"{}\"}} {}", PREFIX, from, to, count);
+    }
+
+    family(&mut out, "force_stereo_actions", "counter", "Times hands-free was disabled to force stereo.");
+    for reason in [ForceStereoReason::User, ForceStereoReason::Reconnect] {
+        let count = r.force_stereo.get(&reason).copied().unwrap_or(0);
+        let _ = writeln!(out, "{}_force_stereo_actions_total{{reason=\"{}\"}} {}", PREFIX, reason.label(), count);
+    }
+
+    family(&mut out, "reconnects", "counter", "Finished device reconnects.");
+    for source in [ReconnectSource::User, ReconnectSource::Watchdog] {
+        for (success, result) in [(true, "success"), (false, "failure")] {
+            let count = r.reconnects.get(&(source, success)).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "{}_reconnects_total{{source=\"{}\",result=\"{}\"}} {}",
+                PREFIX,
+                source.label(),
+                result,
+                count
+            );
+        }
+    }
+
+    family(&mut out, "monitor_poll_duration_seconds", "histogram", "Time taken by one audio monitor poll.");
+    let _ = writeln!(out, "# UNIT {}_monitor_poll_duration_seconds seconds", PREFIX);
+    let mut cumulative = 0;
+    for (index, bound) in POLL_DURATION_BUCKETS.iter().enumerate() {
+        cumulative += r.poll_duration.buckets.get(index).copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "{}_monitor_poll_duration_seconds_bucket{{le=\"{:?}\"}} {}",
+            PREFIX, bound, cumulative
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_monitor_poll_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+        PREFIX, r.poll_duration.count
+    );
+    let _ = writeln!(out, "{}_monitor_poll_duration_seconds_sum {:?}", PREFIX, r.poll_duration.sum);
+    let _ = writeln!(out, "{}_monitor_poll_duration_seconds_count {}", PREFIX, r.poll_duration.count);
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(escape(r#"Bob's "Buds" \ 2"#), r#"Bob's \"Buds\" \\ 2"#);
+        assert_eq!(escape("a\nb"), "a\\nb");
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.005);
+        histogram.observe(0.3);
+        histogram.observe(10.0);
+        assert_eq!(histogram.buckets, [1, 0, 0, 0, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(histogram.count, 3);
+    }
+}
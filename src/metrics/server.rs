@@ -0,0 +1,125 @@
+//! Loopback listener serving `GET /metrics`
+//!
+//! Scrapes are answered on the accept thread: rendering takes microseconds
+//! and a stalled client is cut off by the read timeout.
+
+use super::{Metrics, CONTENT_TYPE};
+use crate::error::{AppError, Result};
+use crate::http::server::read_head;
+use crate::http::{parse_request, HttpRequest, HttpResponse};
+use log::{debug, info, warn};
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the accept loop checks for shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a scraper may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answer one request
+pub fn handle_request(request: &HttpRequest, metrics: &Metrics) -> HttpResponse {
+    if request.path != "/metrics" {
+        return HttpResponse::error(404, "not_found", "No such endpoint");
+    }
+    if request.method != "GET" {
+        return HttpResponse::error(405, "usage", "Method not allowed").with_header("Allow", "GET");
+    }
+    HttpResponse {
+        status: 200,
+        headers: vec![("Content-Type", CONTENT_TYPE.to_string())],
+        body: metrics.render(),
+    }
+}
+
+/// Metrics endpoint listening on a loopback address
+pub struct MetricsServer {
+    running: Arc<AtomicBool>,
+    local_addr: SocketAddr,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Listen on `addr`, which must be a loopback address (port 0 picks a free port)
+    pub fn start(addr: SocketAddr, metrics: Metrics) -> Result<Self> {
+        if !addr.ip().is_loopback() {
+            return Err(AppError::ConfigError(format!(
+                "Metrics server must listen on a loopback address, not {}",
+                addr
+            )));
+        }
+
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = Arc::clone(&running);
+        let thread_handle = thread::Builder::new()
+            .name("metrics-server".to_string())
+            .spawn(move || accept_thread(listener, metrics, thread_running))
+            .map_err(AppError::IoError)?;
+
+        info!("Metrics available at http://{}/metrics", local_addr);
+        Ok(Self {
+            running,
+            local_addr,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting and wait for the accept thread
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn accept_thread(listener: TcpListener, metrics: Metrics, running: Arc<AtomicBool>) {
+    while running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = serve_connection(stream, &metrics) {
+                    debug!("Metrics connection closed: {}", e);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                warn!("Metrics accept failed: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+    debug!("Metrics server stopped");
+}
+
+fn serve_connection(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let response = match read_head(&mut stream)? {
+        Ok((head, _)) => match parse_request(&head) {
+            Ok(request) => handle_request(&request, metrics),
+            Err(response) => response,
+        },
+        Err(response) => response,
+    };
+    stream.write_all(&response.to_bytes())
+}
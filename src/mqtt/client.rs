@@ -0,0 +1,352 @@
+//! MQTT connection thread
+//!
+//! One thread owns the broker connection: it publishes state snapshots handed
+//! over by the main loop, answers the command topic through a
+//! `ChannelTransport` so commands run on the main loop like every other remote
+//! command, keeps the connection alive and reconnects with backoff.
+
+use super::codec::{self, connack_reason, Packet, Will};
+use super::{error_result, parse_command, slug, Message, PublishedState, StatePublisher, Topics, OFFLINE, ONLINE};
+use crate::error::{AppError, Result};
+use crate::ipc::{encode_request, ChannelTransport, IpcCall, Transport};
+use crate::retry::{JitterSource, RetryPolicy, XorShiftJitter};
+use crate::settings::MqttConfig;
+use log::{debug, info, warn};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Keep-alive announced to the broker
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// How long to wait for the TCP connection and the CONNACK
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the connection thread checks for shutdown and new state
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Backoff between reconnect attempts (1 s doubling up to 5 min)
+const RECONNECT_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: u32::MAX,
+    initial_delay: Duration::from_secs(1),
+    max_delay: Duration::from_secs(300),
+    multiplier: 2.0,
+    jitter: 0.25,
+};
+
+/// Connection parameters resolved from `MqttConfig`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttSettings {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topics: Topics,
+    pub discovery: bool,
+}
+
+impl MqttSettings {
+    /// Fill in defaults: the client id falls back to `btam-<computer name>`
+    pub fn from_config(config: &MqttConfig) -> Self {
+        let client_id = if config.client_id.is_empty() {
+            let host = std::env::var("COMPUTERNAME").unwrap_or_default();
+            format!("btam-{}", slug(&host))
+        } else {
+            config.client_id.clone()
+        };
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+
+        Self {
+            host: config.host.clone(),
+            port: config.port,
+            username: non_empty(&config.username),
+            password: non_empty(&config.password),
+            topics: Topics {
+                base: config.base_topic.trim_end_matches('/').to_string(),
+                discovery_prefix: config.discovery_prefix.trim_end_matches('/').to_string(),
+                node_id: slug(&client_id),
+            },
+            client_id,
+            discovery: config.discovery,
+        }
+    }
+}
+
+/// Background MQTT client
+pub struct MqttClient {
+    running: Arc<AtomicBool>,
+    state_tx: Sender<PublishedState>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl MqttClient {
+    /// Start the connection thread; connecting happens in the background
+    pub fn start(settings: MqttSettings, call_tx: Sender<IpcCall>) -> Result<Self> {
+        let running = Arc::new(AtomicBool::new(true));
+        let (state_tx, state_rx) = mpsc::channel();
+
+        let worker = Worker {
+            settings,
+            transport: ChannelTransport::new(call_tx),
+            running: Arc::clone(&running),
+            state_rx,
+            latest: None,
+        };
+        let thread_handle = thread::Builder::new()
+            .name("mqtt-client".to_string())
+            .spawn(move || worker.run())
+            .map_err(AppError::IoError)?;
+
+        Ok(Self {
+            running,
+            state_tx,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Hand the current state to the connection thread; unchanged topics are not republished
+    pub fn publish_state(&self, snapshot: PublishedState) {
+        let _ = self.state_tx.send(snapshot);
+    }
+
+    /// Publish `offline`, disconnect and wait for the thread to exit
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MqttClient {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// An open broker connection
+struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    last_sent: Instant,
+    last_received: Instant,
+}
+
+impl Connection {
+    fn open(settings: &MqttSettings) -> io::Result<Self> {
+        let addr = (settings.host.as_str(), settings.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("'{}' did not resolve to an address", settings.host)))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let mut connection = Self {
+            stream,
+            buffer: Vec::new(),
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+        };
+        connection.send(&Packet::Connect {
+            client_id: settings.client_id.clone(),
+            keep_alive_secs: KEEP_ALIVE.as_secs() as u16,
+            username: settings.username.clone(),
+            password: settings.password.clone(),
+            will: Some(Will {
+                topic: settings.topics.availability(),
+                payload: OFFLINE.as_bytes().to_vec(),
+                retain: true,
+            }),
+        })?;
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        while Instant::now() < deadline {
+            for packet in connection.poll()? {
+                if let Packet::ConnAck { return_code, .. } = packet {
+                    if return_code != 0 {
+                        return Err(io::Error::other(format!(
+                            "broker refused the connection: {}",
+                            connack_reason(return_code)
+                        )));
+                    }
+                    return Ok(connection);
+                }
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::TimedOut, "no CONNACK from broker"))
+    }
+
+    fn send(&mut self, packet: &Packet) -> io::Result<()> {
+        self.stream.write_all(&codec::encode(packet))?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn publish(&mut self, message: Message) -> io::Result<()> {
+        debug!("MQTT publish {} = {}", message.topic, message.payload);
+        self.send(&Packet::Publish {
+            topic: message.topic,
+            payload: message.payload.into_bytes(),
+            retain: message.retain,
+        })
+    }
+
+    /// Read what arrived within one poll interval and return the complete packets
+    fn poll(&mut self) -> io::Result<Vec<Packet>> {
+        let mut chunk = [0u8; 4096];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "broker closed the connection")),
+            Ok(n) => {
+                self.buffer.extend_from_slice(&chunk[..n]);
+                self.last_received = Instant::now();
+            }
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut packets = Vec::new();
+        while let Some((packet, used)) =
+            codec::decode(&self.buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        {
+            self.buffer.drain(..used);
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+}
+
+struct Worker {
+    settings: MqttSettings,
+    transport: ChannelTransport,
+    running: Arc<AtomicBool>,
+    state_rx: Receiver<PublishedState>,
+    /// Most recent snapshot, republished after reconnecting
+    latest: Option<PublishedState>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        let mut publisher = StatePublisher::new(self.settings.topics.clone(), self.settings.discovery);
+        let mut jitter = XorShiftJitter::from_time();
+        let mut failures = 0u32;
+
+        while self.running.load(Ordering::SeqCst) {
+            match Connection::open(&self.settings) {
+                Ok(connection) => {
+                    info!(
+                        "Connected to MQTT broker {}:{} as '{}'",
+                        self.settings.host, self.settings.port, self.settings.client_id
+                    );
+                    failures = 0;
+                    publisher.reset();
+                    if let Err(e) = self.session(connection, &mut publisher) {
+                        warn!("MQTT connection lost: {}", e);
+                    }
+                }
+                Err(e) => warn!(
+                    "Could not connect to MQTT broker {}:{}: {}",
+                    self.settings.host, self.settings.port, e
+                ),
+            }
+
+            let delay = RECONNECT_POLICY.delay_for(failures, jitter.next_sample());
+            failures = failures.saturating_add(1);
+            let deadline = Instant::now() + delay;
+            while self.running.load(Ordering::SeqCst) && Instant::now() < deadline {
+                self.take_latest();
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Keep only the newest snapshot, returning true if one arrived
+    fn take_latest(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(snapshot) = self.state_rx.try_recv() {
+            self.latest = Some(snapshot);
+            changed = true;
+        }
+        changed
+    }
+
+    fn session(&mut self, mut connection: Connection, publisher: &mut StatePublisher) -> io::Result<()> {
+        let topics = self.settings.topics.clone();
+        connection.publish(Message {
+            topic: topics.availability(),
+            payload: ONLINE.to_string(),
+            retain: true,
+        })?;
+        connection.send(&Packet::Subscribe {
+            packet_id: 1,
+            topics: vec![topics.command()],
+        })?;
+        self.take_latest();
+
+        let mut dirty = true;
+        loop {
+            if !self.running.load(Ordering::SeqCst) {
+                connection.publish(Message {
+                    topic: topics.availability(),
+                    payload: OFFLINE.to_string(),
+                    retain: true,
+                })?;
+                return connection.send(&Packet::Disconnect);
+            }
+
+            dirty |= self.take_latest();
+            if dirty {
+                if let Some(snapshot) = &self.latest {
+                    for message in publisher.messages(snapshot) {
+                        connection.publish(message)?;
+                    }
+                }
+                dirty = false;
+            }
+
+            for packet in connection.poll()? {
+                match packet {
+                    Packet::Publish { topic, payload, .. } if topic == topics.command() => {
+                        let result = self.run_command(&payload);
+                        connection.publish(Message {
+                            topic: topics.command_result(),
+                            payload: result,
+                            retain: false,
+                        })?;
+                    }
+                    Packet::SubAck { return_codes, .. } if return_codes.contains(&0x80) => {
+                        warn!("MQTT broker refused the subscription to {}", topics.command());
+                    }
+                    _ => {}
+                }
+            }
+
+            if connection.last_sent.elapsed() >= KEEP_ALIVE / 2 {
+                connection.send(&Packet::PingReq)?;
+            }
+            if connection.last_received.elapsed() > KEEP_ALIVE * 3 / 2 {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "broker stopped responding"));
+            }
+        }
+    }
+
+    /// Run a command-topic payload on the main loop and return the response line
+    fn run_command(&mut self, payload: &[u8]) -> String {
+        let command = match parse_command(payload) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("Rejected MQTT command: {}", e);
+                return error_result(e);
+            }
+        };
+        info!("MQTT command: {:?}", command);
+        self.transport
+            .round_trip(&encode_request(command))
+            .unwrap_or_else(error_result)
+    }
+}
@@ -0,0 +1,365 @@
+//! Minimal MQTT 3.1.1 packet codec
+//!
+//! Covers what a QoS 0 client needs: CONNECT with a last will, PUBLISH,
+//! SUBSCRIBE, PINGREQ and DISCONNECT, plus the broker's replies. Packets with
+//! QoS 1/2 are decoded but their packet id is ignored, since we never ask for
+//! more than QoS 0.
+
+use std::fmt;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+/// Largest remaining length the protocol can express
+pub const MAX_REMAINING_LENGTH: usize = 268_435_455;
+
+/// Last will published by the broker when the client drops off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Will {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Connect {
+        client_id: String,
+        keep_alive_secs: u16,
+        username: Option<String>,
+        password: Option<String>,
+        will: Option<Will>,
+    },
+    ConnAck {
+        session_present: bool,
+        /// 0 = accepted
+        return_code: u8,
+    },
+    Publish {
+        topic: String,
+        payload: Vec<u8>,
+        retain: bool,
+    },
+    Subscribe {
+        packet_id: u16,
+        topics: Vec<String>,
+    },
+    SubAck {
+        packet_id: u16,
+        return_codes: Vec<u8>,
+    },
+    PingReq,
+    PingResp,
+    Disconnect,
+}
+
+/// A packet that violates the protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError(pub String);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Malformed MQTT packet: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Human-readable reason for a CONNACK return code
+pub fn connack_reason(return_code: u8) -> &'static str {
+    match return_code {
+        0 => "accepted",
+        1 => "unacceptable protocol version",
+        2 => "client identifier rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorized",
+        _ => "unknown reason",
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, s: &[u8]) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s);
+}
+
+fn put_remaining_length(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+/// Encode a packet for the wire
+pub fn encode(packet: &Packet) -> Vec<u8> {
+    let (header, body) = match packet {
+        Packet::Connect {
+            client_id,
+            keep_alive_secs,
+            username,
+            password,
+            will,
+        } => {
+            let mut flags = 0x02; // clean session
+            if let Some(will) = will {
+                flags |= 0x04;
+                if will.retain {
+                    flags |= 0x20;
+                }
+            }
+            if password.is_some() {
+                flags |= 0x40;
+            }
+            if username.is_some() {
+                flags |= 0x80;
+            }
+
+            let mut body = Vec::new();
+            put_string(&mut body, b"MQTT");
+            body.push(4); // protocol level 3.1.1
+            body.push(flags);
+            body.extend_from_slice(&keep_alive_secs.to_be_bytes());
+            put_string(&mut body, client_id.as_bytes());
+            if let Some(will) = will {
+                put_string(&mut body, will.topic.as_bytes());
+                put_string(&mut body, &will.payload);
+            }
+            if let Some(username) = username {
+                put_string(&mut body, username.as_bytes());
+            }
+            if let Some(password) = password {
+                put_string(&mut body, password.as_bytes());
+            }
+            (CONNECT << 4, body)
+        }
+        Packet::ConnAck {
+            session_present,
+            return_code,
+        } => (CONNACK << 4, vec![*session_present as u8, *return_code]),
+        Packet::Publish { topic, payload, retain } => {
+            let mut body = Vec::new();
+            put_string(&mut body, topic.as_bytes());
+            body.extend_from_slice(payload);
+            (PUBLISH << 4 | *retain as u8, body)
+        }
+        Packet::Subscribe { packet_id, topics } => {
+            let mut body = packet_id.to_be_bytes().to_vec();
+            for topic in topics {
+                put_string(&mut body, topic.as_bytes());
+                body.push(0); // requested QoS
+            }
+            (SUBSCRIBE << 4 | 0x02, body)
+        }
+        Packet::SubAck {
+            packet_id,
+            return_codes,
+        } => {
+            let mut body = packet_id.to_be_bytes().to_vec();
+            body.extend_from_slice(return_codes);
+            (SUBACK << 4, body)
+        }
+        Packet::PingReq => (PINGREQ << 4, Vec::new()),
+        Packet::PingResp => (PINGRESP << 4, Vec::new()),
+        Packet::Disconnect => (DISCONNECT << 4, Vec::new()),
+    };
+
+    let mut out = vec![header];
+    put_remaining_length(&mut out, body.len());
+    out.extend_from_slice(&body);
+    out
+}
+
+/// Cursor over a packet body
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if self.data.len() < n {
+            return Err(DecodeError("truncated packet".to_string()));
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| DecodeError("string is not UTF-8".to_string()))
+    }
+}
+
+/// Decode one packet from the start of `buf`
+///
+/// Returns the packet and the number of bytes it used, or `None` if `buf`
+/// does not yet hold a complete packet.
+pub fn decode(buf: &[u8]) -> Result<Option<(Packet, usize)>, DecodeError> {
+    let Some(&header) = buf.first() else {
+        return Ok(None);
+    };
+
+    let mut remaining = 0usize;
+    let mut multiplier = 1usize;
+    let mut pos = 1;
+    loop {
+        let Some(&byte) = buf.get(pos) else {
+            return Ok(None);
+        };
+        remaining += (byte & 0x7F) as usize * multiplier;
+        pos += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        multiplier *= 128;
+        if pos > 4 {
+            return Err(DecodeError("remaining length too long".to_string()));
+        }
+    }
+
+    let total = pos + remaining;
+    if buf.len() < total {
+        return Ok(None);
+    }
+
+    let mut body = Reader { data: &buf[pos..total] };
+    let packet = match header >> 4 {
+        CONNECT => {
+            if body.bytes()? != b"MQTT" {
+                return Err(DecodeError("unsupported protocol name".to_string()));
+            }
+            let _level = body.u8()?;
+            let flags = body.u8()?;
+            let keep_alive_secs = body.u16()?;
+            let client_id = body.string()?;
+            let will = if flags & 0x04 != 0 {
+                Some(Will {
+                    topic: body.string()?,
+                    payload: body.bytes()?.to_vec(),
+                    retain: flags & 0x20 != 0,
+                })
+            } else {
+                None
+            };
+            let username = if flags & 0x80 != 0 { Some(body.string()?) } else { None };
+            let password = if flags & 0x40 != 0 { Some(body.string()?) } else { None };
+            Packet::Connect {
+                client_id,
+                keep_alive_secs,
+                username,
+                password,
+                will,
+            }
+        }
+        CONNACK => Packet::ConnAck {
+            session_present: body.u8()? & 0x01 != 0,
+            return_code: body.u8()?,
+        },
+        PUBLISH => {
+            let topic = body.string()?;
+            if (header >> 1) & 0x03 != 0 {
+                let _packet_id = body.u16()?;
+            }
+            Packet::Publish {
+                topic,
+                payload: body.data.to_vec(),
+                retain: header & 0x01 != 0,
+            }
+        }
+        SUBSCRIBE => {
+            let packet_id = body.u16()?;
+            let mut topics = Vec::new();
+            while !body.data.is_empty() {
+                topics.push(body.string()?);
+                let _qos = body.u8()?;
+            }
+            Packet::Subscribe { packet_id, topics }
+        }
+        SUBACK => Packet::SubAck {
+            packet_id: body.u16()?,
+            return_codes: body.data.to_vec(),
+        },
+        PINGREQ => Packet::PingReq,
+        PINGRESP => Packet::PingResp,
+        DISCONNECT => Packet::Disconnect,
+        other => return Err(DecodeError(format!("unsupported packet type {}", other))),
+    };
+
+    Ok(Some((packet, total)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let packets = [
+            Packet::Connect {
+                client_id: "btam-desk".to_string(),
+                keep_alive_secs: 30,
+                username: Some("user".to_string()),
+                password: Some("secret".to_string()),
+                will: Some(Will {
+                    topic: "btam/status".to_string(),
+                    payload: b"offline".to_vec(),
+                    retain: true,
+                }),
+            },
+            Packet::Publish {
+                topic: "btam/mode".to_string(),
+                payload: vec![b'x'; 300],
+                retain: true,
+            },
+            Packet::Subscribe {
+                packet_id: 1,
+                topics: vec!["btam/command".to_string()],
+            },
+            Packet::PingReq,
+        ];
+
+        for packet in packets {
+            let bytes = encode(&packet);
+            assert_eq!(decode(&bytes), Ok(Some((packet, bytes.len()))));
+            assert_eq!(decode(&bytes[..bytes.len() - 1]), Ok(None));
+        }
+    }
+
+    #[test]
+    fn test_remaining_length_encoding() {
+        let publish = Packet::Publish {
+            topic: "t".to_string(),
+            payload: vec![0; 200],
+            retain: false,
+        };
+        // 2 + 1 topic bytes + 200 payload = 203 -> 0xCB 0x01
+        assert_eq!(&encode(&publish)[..3], &[0x30, 0xCB, 0x01]);
+        assert_eq!(encode(&Packet::PingReq), [0xC0, 0x00]);
+    }
+}
@@ -0,0 +1,364 @@
+//! MQTT publishing of headset state for home automation
+//!
+//! Publishes the overall mode, mic use and per-device mode, battery and mic
+//! use to retained topics, announces them to Home Assistant through MQTT
+//! discovery, and accepts the same commands as the CLI on a command topic.
+//! Everything here is pure topic and payload logic; the connection lives in
+//! `client`.
+
+pub mod client;
+pub mod codec;
+
+use crate::audio::{AudioMode, BluetoothAudioDevice, MicUsingApp};
+use crate::cli::{mode_id, CliError};
+use crate::ipc::{Response, ResponseBody, RequestCommand, PROTOCOL_VERSION};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Payloads of the availability topic
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+/// Reduce a name to `[a-z0-9_]` for use in topics and ids
+pub fn slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    let slug = slug.trim_matches('_');
+    if slug.is_empty() {
+        "device".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Topic layout under the configured base topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topics {
+    pub base: String,
+    pub discovery_prefix: String,
+    /// Client id, also used as the Home Assistant node id
+    pub node_id: String,
+}
+
+impl Topics {
+    pub fn availability(&self) -> String {
+        format!("{}/status", self.base)
+    }
+
+    pub fn command(&self) -> String {
+        format!("{}/command", self.base)
+    }
+
+    pub fn command_result(&self) -> String {
+        format!("{}/command/result", self.base)
+    }
+
+    pub fn mode(&self) -> String {
+        format!("{}/mode", self.base)
+    }
+
+    pub fn mic_in_use(&self) -> String {
+        format!("{}/mic_in_use", self.base)
+    }
+
+    pub fn device(&self, device_slug: &str, field: &str) -> String {
+        format!("{}/devices/{}/{}", self.base, device_slug, field)
+    }
+
+    fn discovery(&self, component: &str, object_id: &str) -> String {
+        format!("{}/{}/{}/{}/config", self.discovery_prefix, component, self.node_id, object_id)
+    }
+}
+
+/// A message to publish
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+impl Message {
+    fn retained(topic: String, payload: impl Into<String>) -> Self {
+        Self {
+            topic,
+            payload: payload.into(),
+            retain: true,
+        }
+    }
+}
+
+/// Published state of one connected audio device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedDevice {
+    pub name: String,
+    pub mode: &'static str,
+    pub battery_level: Option<u8>,
+    /// In hands-free mode while an app records from a Bluetooth mic
+    pub mic_in_use: bool,
+}
+
+/// Everything published for one monitor state update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedState {
+    pub mode: &'static str,
+    /// Any app is using a microphone
+    pub mic_in_use: bool,
+    pub devices: Vec<PublishedDevice>,
+}
+
+impl PublishedState {
+    pub fn new(mode: AudioMode, devices: &[BluetoothAudioDevice], mic_apps: &[MicUsingApp]) -> Self {
+        let bluetooth_mic = mic_apps.iter().any(|app| app.is_using_bluetooth_mic);
+        Self {
+            mode: mode_id(mode),
+            mic_in_use: !mic_apps.is_empty(),
+            devices: devices
+                .iter()
+                .map(|device| PublishedDevice {
+                    name: device.device.name.clone(),
+                    mode: mode_id(device.current_mode),
+                    battery_level: device.battery_level,
+                    mic_in_use: bluetooth_mic && device.current_mode == AudioMode::HandsFree,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+/// Turns state snapshots into the messages that need publishing
+///
+/// Only topics whose payload changed are returned; discovery configs are sent
+/// the first time a device is seen after (re)connecting.
+pub struct StatePublisher {
+    topics: Topics,
+    discovery: bool,
+    /// Last payload per state topic
+    published: HashMap<String, String>,
+    /// Devices announced since the last reset, by slug
+    announced: BTreeMap<String, String>,
+    global_announced: bool,
+}
+
+impl StatePublisher {
+    pub fn new(topics: Topics, discovery: bool) -> Self {
+        Self {
+            topics,
+            discovery,
+            published: HashMap::new(),
+            announced: BTreeMap::new(),
+            global_announced: false,
+        }
+    }
+
+    /// Forget what was published, e.g. after reconnecting to a broker that may have restarted
+    pub fn reset(&mut self) {
+        self.published.clear();
+        self.global_announced = false;
+        self.announced.clear();
+    }
+
+    pub fn messages(&mut self, snapshot: &PublishedState) -> Vec<Message> {
+        let mut messages = Vec::new();
+
+        if self.discovery && !self.global_announced {
+            messages.extend(self.global_discovery());
+            self.global_announced = true;
+        }
+
+        let mut state = vec![
+            (self.topics.mode(), snapshot.mode.to_string()),
+            (self.topics.mic_in_use(), on_off(snapshot.mic_in_use).to_string()),
+        ];
+
+        let mut present = Vec::new();
+        for device in &snapshot.devices {
+            let device_slug = slug(&device.name);
+            if !self.announced.contains_key(&device_slug) {
+                if self.discovery {
+                    messages.extend(self.device_discovery(&device_slug, &device.name));
+                }
+                self.announced.insert(device_slug.clone(), device.name.clone());
+            }
+
+            state.push((self.topics.device(&device_slug, "mode"), device.mode.to_string()));
+            state.push((self.topics.device(&device_slug, "mic_in_use"), on_off(device.mic_in_use).to_string()));
+            if let Some(level) = device.battery_level {
+                state.push((self.topics.device(&device_slug, "battery"), level.to_string()));
+            }
+            present.push(device_slug);
+        }
+
+        // Devices that went away keep their discovery config but show as disconnected
+        for device_slug in self.announced.keys().filter(|s| !present.contains(s)) {
+            state.push((self.topics.device(device_slug, "mode"), "disconnected".to_string()));
+            state.push((self.topics.device(device_slug, "mic_in_use"), on_off(false).to_string()));
+        }
+
+        for (topic, payload) in state {
+            if self.published.get(&topic) != Some(&payload) {
+                self.published.insert(topic.clone(), payload.clone());
+                messages.push(Message::retained(topic, payload));
+            }
+        }
+        messages
+    }
+
+    fn availability(&self) -> Value {
+        json!({
+            "availability_topic": self.topics.availability(),
+            "payload_available": ONLINE,
+            "payload_not_available": OFFLINE,
+        })
+    }
+
+    fn config(&self, component: &str, object_id: &str, mut fields: Value, device: &Value) -> Message {
+        if let (Some(fields), Value::Object(availability)) = (fields.as_object_mut(), self.availability()) {
+            fields.extend(availability);
+            fields.insert(
+                "unique_id".to_string(),
+                Value::String(format!("{}_{}", self.topics.node_id, object_id)),
+            );
+            fields.insert("device".to_string(), device.clone());
+        }
+        Message::retained(self.topics.discovery(component, object_id), fields.to_string())
+    }
+
+    fn app_device(&self) -> Value {
+        json!({
+            "identifiers": [self.topics.node_id],
+            "name": format!("Bluetooth Audio Mode Manager ({})", self.topics.node_id),
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        })
+    }
+
+    fn global_discovery(&self) -> Vec<Message> {
+        let device = self.app_device();
+        vec![
+            self.config(
+                "sensor",
+                "mode",
+                json!({ "name": "Audio mode", "state_topic": self.topics.mode(), "icon": "mdi:headphones" }),
+                &device,
+            ),
+            self.config(
+                "binary_sensor",
+                "mic_in_use",
+                json!({ "name": "Microphone in use", "state_topic": self.topics.mic_in_use(), "icon": "mdi:microphone" }),
+                &device,
+            ),
+        ]
+    }
+
+    fn device_discovery(&self, device_slug: &str, name: &str) -> Vec<Message> {
+        let device = json!({
+            "identifiers": [format!("{}_{}", self.topics.node_id, device_slug)],
+            "name": name,
+            "via_device": self.topics.node_id,
+        });
+        let press = json!({ "command": "force-stereo", "device": name }).to_string();
+
+        vec![
+            self.config(
+                "sensor",
+                &format!("{}_mode", device_slug),
+                json!({ "name": "Mode", "state_topic": self.topics.device(device_slug, "mode"), "icon": "mdi:headphones" }),
+                &device,
+            ),
+            self.config(
+                "sensor",
+                &format!("{}_battery", device_slug),
+                json!({
+                    "name": "Battery",
+                    "state_topic": self.topics.device(device_slug, "battery"),
+                    "device_class": "battery",
+                    "unit_of_measurement": "%",
+                    "state_class": "measurement",
+                }),
+                &device,
+            ),
+            self.config(
+                "binary_sensor",
+                &format!("{}_mic_in_use", device_slug),
+                json!({ "name": "Microphone in use", "state_topic": self.topics.device(device_slug, "mic_in_use"), "icon": "mdi:microphone" }),
+                &device,
+            ),
+            self.config(
+                "button",
+                &format!("{}_force_stereo", device_slug),
+                json!({
+                    "name": "Force stereo",
+                    "command_topic": self.topics.command(),
+                    "payload_press": press,
+                    "icon": "mdi:headphones-settings",
+                }),
+                &device,
+            ),
+        ]
+    }
+}
+
+/// Parse a command topic payload such as `{"command":"force-stereo","device":"WH-1000XM4"}`
+///
+/// The broker does not authenticate publishers the way the RPC and HTTP
+/// tokens do, so only the device actions the Home Assistant buttons send
+/// are accepted; everything else is refused.
+pub fn parse_command(payload: &[u8]) -> Result<RequestCommand, CliError> {
+    let command: RequestCommand =
+        serde_json::from_slice(payload).map_err(|e| CliError::Usage(format!("Invalid MQTT command: {}", e)))?;
+    match command {
+        RequestCommand::ForceStereo { .. } | RequestCommand::AllowHandsFree { .. } | RequestCommand::Reconnect { .. } => {
+            Ok(command)
+        }
+        other => Err(CliError::Usage(format!("Command {:?} is not accepted over MQTT", other))),
+    }
+}
+
+/// Result payload for a command that could not be parsed
+pub fn error_result(error: CliError) -> String {
+    let response = Response {
+        version: PROTOCOL_VERSION,
+        body: ResponseBody::Error(error),
+    };
+    serde_json::to_string(&response).expect("response serialization cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("WH-1000XM4"), "wh_1000xm4");
+        assert_eq!(slug("  Jabra Evolve2 65 "), "jabra_evolve2_65");
+        assert_eq!(slug("ヘッドホン"), "device");
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command(br#"{"command":"force-stereo","device":"WH-1000XM4"}"#),
+            Ok(RequestCommand::ForceStereo {
+                device: "WH-1000XM4".to_string()
+            })
+        );
+        assert!(matches!(parse_command(b"stereo please"), Err(CliError::Usage(_))));
+        assert!(matches!(parse_command(br#"{"command":"restore"}"#), Err(CliError::Usage(_))));
+        assert!(matches!(parse_command(br#"{"command":"mute","pid":1234}"#), Err(CliError::Usage(_))));
+        assert!(parse_command(br#"{"command":"reconnect","device":"WH-1000XM4"}"#).is_ok());
+    }
+}
@@ -0,0 +1,260 @@
+//! User notifications
+//!
+//! Which events are shown is decided here; showing them is platform
+//! specific. Windows uses toasts with a message box fallback (`toast`),
+//! other platforms and headless mode write notifications to the log.
+
+#[cfg(windows)]
+mod toast;
+
+use crate::audio::device::AudioMode;
+use crate::error::{ErrorSeverity, Result};
+use log::{info, warn};
+
+#[cfg(windows)]
+pub use toast::register_aumid;
+
+/// Notification types
+#[derive(Debug, Clone)]
+pub enum NotificationType {
+    /// Audio mode changed
+    ModeChange { old: AudioMode, new: AudioMode },
+    /// New app started using microphone
+    MicUsageStart { app_name: String },
+    /// App stopped using microphone
+    MicUsageStop { app_name: String },
+    /// Update available
+    UpdateAvailable { version: String },
+    /// Headset battery dropped to the configured threshold
+    LowBattery { device: String, level: u8 },
+    /// Error notification
+    Error { message: String, severity: ErrorSeverity },
+    /// Generic info notification
+    Info { title: String, message: String },
+}
+
+/// Manages user notifications
+#[derive(Clone)]
+pub struct NotificationManager {
+    enabled: bool,
+    notify_mode_change: bool,
+    notify_mic_usage: bool,
+    notify_errors: bool,
+    notify_updates: bool,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    use_toast: bool,
+    /// If true, always use MessageBox even when toast is enabled (for unregistered apps)
+    #[cfg_attr(not(windows), allow(dead_code))]
+    force_message_box: bool,
+    /// If true, write notifications to the log instead of showing them (headless mode)
+    #[cfg_attr(not(windows), allow(dead_code))]
+    log_only: bool,
+}
+
+impl NotificationManager {
+    /// Create a new notification manager
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            notify_mode_change: true,
+            notify_mic_usage: true,
+            notify_errors: true,
+            notify_updates: true,
+            use_toast: true,
+            // Try toast first - it will appear briefly even without AUMID registration
+            // If it doesn't work well, user can set this to true in settings
+            force_message_box: false,
+            log_only: false,
+        }
+    }
+
+    /// Set whether to force MessageBox instead of toast (for unpackaged apps)
+    pub fn set_force_message_box(&mut self, force: bool) {
+        self.force_message_box = force;
+    }
+
+    /// Set whether notifications only go to the log (no toasts or message boxes)
+    pub fn set_log_only(&mut self, log_only: bool) {
+        self.log_only = log_only;
+    }
+
+    /// Update notification settings
+    pub fn update_settings(
+        &mut self,
+        notify_mode_change: bool,
+        notify_mic_usage: bool,
+        notify_errors: bool,
+        notify_updates: bool,
+    ) {
+        self.notify_mode_change = notify_mode_change;
+        self.notify_mic_usage = notify_mic_usage;
+        self.notify_errors = notify_errors;
+        self.notify_updates = notify_updates;
+    }
+
+    /// Enable or disable all notifications
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Show a notification based on type
+    pub fn show(&self, notification: NotificationType) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        match &notification {
+            NotificationType::ModeChange { old, new } => {
+                if self.notify_mode_change {
+                    let title = rust_i18n::t!("notify_audio_mode_changed");
+                    let message = rust_i18n::t!("msg_mode_switched", old = old.display_localized(), new = new.display_localized());
+                    self.show_notification(&title, &message, ToastIcon::Info)?;
+                }
+            }
+            NotificationType::MicUsageStart { app_name } => {
+                if self.notify_mic_usage {
+                    let title = rust_i18n::t!("notify_mic_in_use");
+                    let message = rust_i18n::t!("msg_mic_started", app = app_name);
+                    self.show_notification(&title, &message, ToastIcon::Info)?;
+                }
+            }
+            NotificationType::MicUsageStop { app_name } => {
+                if self.notify_mic_usage {
+                    let title = rust_i18n::t!("notify_mic_released");
+                    let message = rust_i18n::t!("msg_mic_stopped", app = app_name);
+                    self.show_notification(&title, &message, ToastIcon::Info)?;
+                }
+            }
+            NotificationType::UpdateAvailable { version } => {
+                if self.notify_updates {
+                    let title = rust_i18n::t!("notify_update_available");
+                    let message = rust_i18n::t!("msg_update_available", version = version);
+                    self.show_notification(&title, &message, ToastIcon::Info)?;
+                }
+            }
+            NotificationType::LowBattery { device, level } => {
+                // Enabled/disabled through the threshold in NotificationConfig
+                let title = rust_i18n::t!("notify_low_battery");
+                let message = rust_i18n::t!("msg_low_battery", device = device, level = level);
+                self.show_notification(&title, &message, ToastIcon::Warning)?;
+            }
+            NotificationType::Error { message, severity } => {
+                if self.notify_errors {
+                    let icon = match severity {
+                        ErrorSeverity::Fatal => ToastIcon::Error,
+                        ErrorSeverity::Recoverable => ToastIcon::Warning,
+                        ErrorSeverity::Minor => return Ok(()), // Don't show toast for minor
+                    };
+                    let title = match severity {
+                        ErrorSeverity::Fatal => rust_i18n::t!("notify_error"),
+                        ErrorSeverity::Recoverable => rust_i18n::t!("notify_warning"),
+                        ErrorSeverity::Minor => rust_i18n::t!("notify_notice"),
+                    };
+                    self.show_notification(&title, message, icon)?;
+                }
+            }
+            NotificationType::Info { title, message } => {
+                self.show_notification(title, message, ToastIcon::Info)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Show a notification - tries toast first, falls back to MessageBox
+    #[cfg(windows)]
+    fn show_notification(&self, title: &str, message: &str, icon: ToastIcon) -> Result<()> {
+        if self.log_only {
+            log_notification(title, message, icon);
+            return Ok(());
+        }
+
+        // For unpackaged apps, toast notifications won't appear in the notification center
+        // without proper AUMID registration (Start menu shortcut). Use MessageBox instead.
+        if self.force_message_box {
+            return toast::show_message_box(title, message, icon);
+        }
+
+        if self.use_toast {
+            match toast::show_toast(title, message) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    log::debug!("Toast notification failed, falling back to MessageBox: {}", e);
+                }
+            }
+        }
+
+        // Fallback to MessageBox
+        toast::show_message_box(title, message, icon)
+    }
+
+    /// Show a notification - only the log is available on this platform
+    #[cfg(not(windows))]
+    fn show_notification(&self, title: &str, message: &str, icon: ToastIcon) -> Result<()> {
+        log_notification(title, message, icon);
+        Ok(())
+    }
+}
+
+impl Default for NotificationManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Toast notification icon type (used for MessageBox fallback)
+#[derive(Debug, Clone, Copy)]
+enum ToastIcon {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Write a notification to the log instead of showing it
+fn log_notification(title: &str, message: &str, icon: ToastIcon) {
+    match icon {
+        ToastIcon::Info => info!("Notification: {}: {}", title, message),
+        ToastIcon::Warning | ToastIcon::Error => warn!("Notification: {}: {}", title, message),
+    }
+}
+
+/// Escape XML special characters
+#[cfg_attr(not(windows), allow(dead_code))]
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_manager_new() {
+        let manager = NotificationManager::new();
+        assert!(manager.enabled);
+        assert!(manager.notify_mode_change);
+    }
+
+    #[test]
+    fn test_notification_disabled() {
+        let mut manager = NotificationManager::new();
+        manager.set_enabled(false);
+        // Should not error even when disabled
+        let result = manager.show(NotificationType::Info {
+            title: "Test".to_string(),
+            message: "Test message".to_string(),
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(escape_xml("Hello & World"), "Hello &amp; World");
+        assert_eq!(escape_xml("<test>"), "&lt;test&gt;");
+        assert_eq!(escape_xml("\"quoted\""), "&quot;quoted&quot;");
+    }
+}
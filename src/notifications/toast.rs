@@ -0,0 +1,204 @@
+//! Windows toast notifications with a message box fallback
+
+use super::{escape_xml, ToastIcon};
+use crate::error::{AppError, Result};
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use windows::core::{HSTRING, PCWSTR};
+use windows::Data::Xml::Dom::XmlDocument;
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegCreateKeyExW, RegSetValueExW, HKEY, HKEY_CURRENT_USER, KEY_WRITE, REG_OPTION_NON_VOLATILE, REG_SZ,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    MessageBoxW, MB_ICONERROR, MB_ICONINFORMATION, MB_ICONWARNING, MB_OK, MB_SETFOREGROUND,
+};
+
+/// Application User Model ID for toast notifications
+/// This must match any Start menu shortcut for the app to work properly
+const APP_USER_MODEL_ID: &str = "Z-M-Huang.BtAudioModeManager";
+
+/// Display name shown in notification center
+const APP_DISPLAY_NAME: &str = "Bluetooth Audio Manager";
+
+/// Register the Application User Model ID (AUMID) in the Windows Registry.
+/// This is required for toast notifications to appear in the notification center
+/// for unpackaged desktop applications.
+///
+/// The registration is done under HKEY_CURRENT_USER so no admin privileges are required.
+pub fn register_aumid() -> Result<()> {
+    unsafe {
+        // Registry path: HKEY_CURRENT_USER\Software\Classes\AppUserModelId\<AUMID>
+        let subkey = format!("Software\\Classes\\AppUserModelId\\{}", APP_USER_MODEL_ID);
+        let subkey_wide: Vec<u16> = OsStr::new(&subkey)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+
+        let mut hkey = HKEY::default();
+        let result = RegCreateKeyExW(
+            HKEY_CURRENT_USER,
+            PCWSTR::from_raw(subkey_wide.as_ptr()),
+            0,
+            None,
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            None,
+            &mut hkey,
+            None,
+        );
+
+        if result.is_err() {
+            warn!("Failed to create registry key for AUMID: {:?}", result);
+            return Err(AppError::ConfigError(format!(
+                "Failed to create AUMID registry key: {:?}",
+                result
+            )));
+        }
+
+        // Set DisplayName value
+        let display_name_wide: Vec<u16> = OsStr::new(APP_DISPLAY_NAME)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let value_name: Vec<u16> = OsStr::new("DisplayName")
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+
+        let result = RegSetValueExW(
+            hkey,
+            PCWSTR::from_raw(value_name.as_ptr()),
+            0,
+            REG_SZ,
+            Some(std::slice::from_raw_parts(
+                display_name_wide.as_ptr() as *const u8,
+                display_name_wide.len() * 2,
+            )),
+        );
+
+        if result.is_err() {
+            warn!("Failed to set DisplayName registry value: {:?}", result);
+        }
+
+        // Set IconUri value (path to app icon)
+        if let Ok(exe_path) = std::env::current_exe() {
+            if let Some(exe_dir) = exe_path.parent() {
+                let icon_path = exe_dir.join("resources").join("app.ico");
+                let icon_path_str = icon_path.to_string_lossy();
+                let icon_uri_wide: Vec<u16> = OsStr::new(icon_path_str.as_ref())
+                    .encode_wide()
+                    .chain(std::iter::once(0))
+                    .collect();
+                let icon_value_name: Vec<u16> = OsStr::new("IconUri")
+                    .encode_wide()
+                    .chain(std::iter::once(0))
+                    .collect();
+
+                let result = RegSetValueExW(
+                    hkey,
+                    PCWSTR::from_raw(icon_value_name.as_ptr()),
+                    0,
+                    REG_SZ,
+                    Some(std::slice::from_raw_parts(
+                        icon_uri_wide.as_ptr() as *const u8,
+                        icon_uri_wide.len() * 2,
+                    )),
+                );
+
+                if result.is_err() {
+                    warn!("Failed to set IconUri registry value: {:?}", result);
+                }
+            }
+        }
+
+        // Close the registry key
+        let _ = RegCloseKey(hkey);
+
+        info!("AUMID registered successfully: {}", APP_USER_MODEL_ID);
+        Ok(())
+    }
+}
+
+/// Show Windows toast notification using WinRT API
+pub(super) fn show_toast(title: &str, message: &str) -> Result<()> {
+    // Escape XML special characters
+    let title_escaped = escape_xml(title);
+    let message_escaped = escape_xml(message);
+
+    // Create toast XML content
+    // Using ToastGeneric template for Windows 10/11
+    let toast_xml = format!(
+        r#"<toast>
+            <visual>
+                <binding template="ToastGeneric">
+                    <text>{}</text>
+                    <text>{}</text>
+                </binding>
+            </visual>
+            <audio silent="true"/>
+        </toast>"#,
+        title_escaped, message_escaped
+    );
+
+    // Parse the XML
+    let xml_doc = XmlDocument::new()
+        .map_err(|e| AppError::ConfigError(format!("Failed to create XmlDocument: {}", e)))?;
+
+    xml_doc
+        .LoadXml(&HSTRING::from(&toast_xml))
+        .map_err(|e| AppError::ConfigError(format!("Failed to load toast XML: {}", e)))?;
+
+    // Create the toast notification
+    let toast = ToastNotification::CreateToastNotification(&xml_doc)
+        .map_err(|e| AppError::ConfigError(format!("Failed to create toast: {}", e)))?;
+
+    // Get the toast notifier with our App User Model ID
+    let notifier = ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_USER_MODEL_ID))
+        .map_err(|e| AppError::ConfigError(format!("Failed to create notifier: {}", e)))?;
+
+    // Show the toast
+    notifier
+        .Show(&toast)
+        .map_err(|e| AppError::ConfigError(format!("Failed to show toast: {}", e)))?;
+
+    info!("Toast notification shown: {} - {}", title, message);
+    Ok(())
+}
+
+/// Show a message box as fallback (async - spawns a thread)
+pub(super) fn show_message_box(title: &str, message: &str, icon: ToastIcon) -> Result<()> {
+    let title = title.to_string();
+    let message = message.to_string();
+
+    // Spawn a thread so MessageBox doesn't block the main event loop
+    std::thread::spawn(move || {
+        let title_wide: Vec<u16> = OsStr::new(&title)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let message_wide: Vec<u16> = OsStr::new(&message)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+
+        let icon_flags = match icon {
+            ToastIcon::Info => MB_ICONINFORMATION,
+            ToastIcon::Warning => MB_ICONWARNING,
+            ToastIcon::Error => MB_ICONERROR,
+        };
+
+        unsafe {
+            MessageBoxW(
+                HWND::default(),
+                PCWSTR::from_raw(message_wide.as_ptr()),
+                PCWSTR::from_raw(title_wide.as_ptr()),
+                MB_OK | icon_flags | MB_SETFOREGROUND,
+            );
+        }
+    });
+
+    Ok(())
+}
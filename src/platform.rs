@@ -0,0 +1,53 @@
+//! Platform backends
+//!
+//! The core reaches the audio stack and the Bluetooth radio only through the
+//! `AudioBackend` and `BluetoothBackend` traits. `Backends` bundles one of
+//! each so it can be handed to the monitor, the command line and the app core.
+
+use crate::audio::backend::AudioBackend;
+use crate::bluetooth::backend::BluetoothBackend;
+use std::sync::Arc;
+
+/// The audio and Bluetooth backends the core runs on
+#[derive(Clone)]
+pub struct Backends {
+    pub audio: Arc<dyn AudioBackend>,
+    pub bluetooth: Arc<dyn BluetoothBackend>,
+}
+
+impl Backends {
+    pub fn new(audio: Arc<dyn AudioBackend>, bluetooth: Arc<dyn BluetoothBackend>) -> Self {
+        Self { audio, bluetooth }
+    }
+
+    /// WASAPI and the Win32 Bluetooth APIs
+    #[cfg(windows)]
+    pub fn native() -> Self {
+        Self::new(
+            Arc::new(crate::audio::wasapi::WasapiBackend::new()),
+            Arc::new(crate::bluetooth::win32::Win32Bluetooth::new()),
+        )
+    }
+
+    /// PipeWire or PulseAudio through `pactl`, and BlueZ for device control
+    ///
+    /// Without a reachable system bus the card profiles stand in for BlueZ,
+    /// so forcing stereo still works but connecting does not.
+    #[cfg(target_os = "linux")]
+    pub fn native() -> Self {
+        let pulse = Arc::new(crate::audio::pulse::PulseBackend::new());
+        match crate::bluetooth::bluez::BluezBluetooth::system() {
+            Ok(bluez) => Self::new(pulse, Arc::new(bluez)),
+            Err(e) => {
+                log::warn!("BlueZ is not available, using card profiles: {}", e);
+                Self::new(pulse.clone(), pulse)
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Backends {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Backends").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,165 @@
+//! Process management with security validation and TOCTOU mitigation
+//!
+//! This module handles process termination with multiple security layers:
+//! - System process blacklist
+//! - Privilege level checking
+//! - TOCTOU mitigation with mutex-protected operations
+//! - User confirmation dialogs
+//! - Runtime elevation for privileged processes
+//! - Audit logging
+//!
+//! The checks and the termination itself use the Windows APIs (`win32`);
+//! other platforms refuse to terminate processes.
+
+use crate::audio::session::MicUsingApp;
+use crate::error::{AppError, Result};
+use log::info;
+use std::sync::{Arc, Mutex};
+
+#[cfg(windows)]
+mod win32;
+
+/// System processes that should never be terminated
+#[cfg_attr(not(windows), allow(dead_code))]
+const SYSTEM_PROCESS_BLACKLIST: &[&str] = &[
+    "csrss.exe",
+    "winlogon.exe",
+    "lsass.exe",
+    "services.exe",
+    "smss.exe",
+    "wininit.exe",
+    "svchost.exe",
+    "dwm.exe",
+    "explorer.exe", // Could crash desktop
+    "system",
+    "registry",
+];
+
+/// Result of a termination attempt for audit logging
+#[derive(Debug, Clone)]
+pub struct TerminationAttempt {
+    pub timestamp: std::time::SystemTime,
+    pub process_id: u32,
+    pub process_name: String,
+    pub outcome: TerminationOutcome,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum TerminationOutcome {
+    Success,
+    Blocked,
+    Failed,
+    UserCancelled,
+    ElevationRequired,
+}
+
+impl std::fmt::Display for TerminationOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TerminationOutcome::Success => write!(f, "SUCCESS"),
+            TerminationOutcome::Blocked => write!(f, "BLOCKED"),
+            TerminationOutcome::Failed => write!(f, "FAILED"),
+            TerminationOutcome::UserCancelled => write!(f, "USER_CANCELLED"),
+            TerminationOutcome::ElevationRequired => write!(f, "ELEVATION_REQUIRED"),
+        }
+    }
+}
+
+/// Process manager with security validation
+pub struct ProcessManager {
+    /// Mutex for TOCTOU mitigation - protects check-and-terminate operations
+    operation_lock: Arc<Mutex<()>>,
+    /// Current list of mic-using apps (shared with monitor thread)
+    #[cfg_attr(not(windows), allow(dead_code))]
+    mic_apps: Arc<Mutex<Vec<MicUsingApp>>>,
+    /// Audit log of termination attempts
+    audit_log: Mutex<Vec<TerminationAttempt>>,
+}
+
+impl ProcessManager {
+    /// Create a new process manager
+    pub fn new(mic_apps: Arc<Mutex<Vec<MicUsingApp>>>) -> Self {
+        Self {
+            operation_lock: Arc::new(Mutex::new(())),
+            mic_apps,
+            audit_log: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get the operation lock for use by monitor thread when updating mic apps list
+    pub fn get_operation_lock(&self) -> Arc<Mutex<()>> {
+        Arc::clone(&self.operation_lock)
+    }
+
+    /// Check if a process name is in the system blacklist
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn is_blacklisted(process_name: &str) -> bool {
+        let lower_name = process_name.to_lowercase();
+        SYSTEM_PROCESS_BLACKLIST
+            .iter()
+            .any(|&blocked| lower_name == blocked)
+    }
+
+    /// Log a termination attempt
+    fn log_attempt(&self, attempt: TerminationAttempt) {
+        info!(
+            "Termination attempt: PID={}, Name={}, Outcome={}, Reason={}",
+            attempt.process_id, attempt.process_name, attempt.outcome, attempt.reason
+        );
+
+        if let Ok(mut log) = self.audit_log.lock() {
+            log.push(attempt);
+            // Keep only last 100 entries
+            if log.len() > 100 {
+                log.remove(0);
+            }
+        }
+    }
+
+    /// Get recent audit log entries
+    pub fn get_audit_log(&self) -> Vec<TerminationAttempt> {
+        self.audit_log
+            .lock()
+            .map(|log| log.clone())
+            .unwrap_or_default()
+    }
+
+    /// Terminating processes is only implemented on Windows
+    #[cfg(not(windows))]
+    pub fn terminate_process(&self, process_id: u32, _show_dialog: bool) -> Result<()> {
+        let reason = "Terminating processes is not supported on this platform".to_string();
+        self.log_attempt(TerminationAttempt {
+            timestamp: std::time::SystemTime::now(),
+            process_id,
+            process_name: format!("PID {}", process_id),
+            outcome: TerminationOutcome::Blocked,
+            reason: reason.clone(),
+        });
+        Err(AppError::ProcessError(reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blacklist_check() {
+        assert!(ProcessManager::is_blacklisted("csrss.exe"));
+        assert!(ProcessManager::is_blacklisted("CSRSS.EXE"));
+        assert!(ProcessManager::is_blacklisted("lsass.exe"));
+        assert!(ProcessManager::is_blacklisted("svchost.exe"));
+        assert!(!ProcessManager::is_blacklisted("notepad.exe"));
+        assert!(!ProcessManager::is_blacklisted("chrome.exe"));
+    }
+
+    #[test]
+    fn test_termination_outcome_display() {
+        assert_eq!(format!("{}", TerminationOutcome::Success), "SUCCESS");
+        assert_eq!(format!("{}", TerminationOutcome::Blocked), "BLOCKED");
+        assert_eq!(format!("{}", TerminationOutcome::Failed), "FAILED");
+        assert_eq!(format!("{}", TerminationOutcome::UserCancelled), "USER_CANCELLED");
+        assert_eq!(format!("{}", TerminationOutcome::ElevationRequired), "ELEVATION_REQUIRED");
+    }
+}
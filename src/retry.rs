@@ -0,0 +1,359 @@
+//! Retry and backoff policy for transient Bluetooth and COM failures
+//!
+//! `BluetoothSetServiceState` and audio endpoint activation often fail for a
+//! short window right after a headset connects. This module classifies Win32
+//! and HRESULT codes as transient or permanent, and retries transient failures
+//! with exponential backoff and jitter.
+
+use crate::error::AppError;
+use log::{debug, warn};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Whether a failure is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The operation may succeed if attempted again shortly
+    Transient,
+    /// Retrying will not help (bad arguments, missing device, access denied)
+    Permanent,
+}
+
+// Win32 error codes treated as transient
+const ERROR_NOT_READY: u32 = 21;
+const ERROR_GEN_FAILURE: u32 = 31;
+const ERROR_SEM_TIMEOUT: u32 = 121;
+const ERROR_BUSY: u32 = 170;
+const ERROR_DEVICE_NOT_CONNECTED: u32 = 1167;
+const ERROR_RETRY: u32 = 1237;
+const ERROR_TIMEOUT: u32 = 1460;
+const ERROR_DEVICE_NOT_AVAILABLE: u32 = 4319;
+
+// HRESULTs treated as transient (stored as u32 for readability)
+const E_PENDING: u32 = 0x8000_000A;
+const RPC_E_CALL_REJECTED: u32 = 0x8001_0001;
+const RPC_E_SERVERCALL_RETRYLATER: u32 = 0x8001_010A;
+const AUDCLNT_E_DEVICE_IN_USE: u32 = 0x8889_000A;
+const AUDCLNT_E_SERVICE_NOT_RUNNING: u32 = 0x8889_0010;
+
+/// Facility code for HRESULTs wrapping a Win32 error (`HRESULT_FROM_WIN32`)
+const FACILITY_WIN32: u32 = 7;
+
+/// Classify a Win32 error code as returned by the Bluetooth APIs
+///
+/// Anything not explicitly known to be transient is treated as permanent,
+/// so unexpected failures surface immediately instead of being retried.
+pub fn classify_win32_error(code: u32) -> ErrorClass {
+    match code {
+        ERROR_NOT_READY
+        | ERROR_GEN_FAILURE
+        | ERROR_SEM_TIMEOUT
+        | ERROR_BUSY
+        | ERROR_DEVICE_NOT_CONNECTED
+        | ERROR_RETRY
+        | ERROR_TIMEOUT
+        | ERROR_DEVICE_NOT_AVAILABLE => ErrorClass::Transient,
+        _ => ErrorClass::Permanent,
+    }
+}
+
+/// Classify an HRESULT as returned by COM/WASAPI calls
+///
+/// HRESULTs wrapping a Win32 error are classified by the wrapped code.
+/// `AUDCLNT_E_DEVICE_INVALIDATED` is permanent: retries reuse the same
+/// `IMMDevice`, which stays invalid; the next poll enumerates devices anew.
+pub fn classify_hresult(hr: i32) -> ErrorClass {
+    let hr = hr as u32;
+
+    match hr {
+        E_PENDING
+        | RPC_E_CALL_REJECTED
+        | RPC_E_SERVERCALL_RETRYLATER
+        | AUDCLNT_E_DEVICE_IN_USE
+        | AUDCLNT_E_SERVICE_NOT_RUNNING => ErrorClass::Transient,
+        _ if (hr >> 16) & 0x1FFF == FACILITY_WIN32 => classify_win32_error(hr & 0xFFFF),
+        _ => ErrorClass::Permanent,
+    }
+}
+
+/// Classify an application error
+///
+/// Only errors that still carry the underlying API code can be transient;
+/// errors that were already turned into messages are treated as permanent.
+pub fn classify_app_error(error: &AppError) -> ErrorClass {
+    match error {
+        #[cfg(windows)]
+        AppError::WindowsApiError(e) => classify_hresult(e.code().0),
+        #[cfg(target_os = "linux")]
+        AppError::DBusError(zbus::Error::MethodError(name, _, _)) => match name.as_str() {
+            "org.bluez.Error.InProgress"
+            | "org.bluez.Error.NotReady"
+            | "org.freedesktop.DBus.Error.NoReply"
+            | "org.freedesktop.DBus.Error.Timeout" => ErrorClass::Transient,
+            _ => ErrorClass::Permanent,
+        },
+        AppError::IoError(e) => match e.kind() {
+            std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::Interrupted
+            | std::io::ErrorKind::WouldBlock => ErrorClass::Transient,
+            _ => ErrorClass::Permanent,
+        },
+        _ => ErrorClass::Permanent,
+    }
+}
+
+/// Source of time for backoff delays
+///
+/// Abstracted so tests can run retries without actually sleeping.
+pub trait Clock {
+    fn sleep(&self, duration: Duration);
+}
+
+/// Clock backed by `std::thread::sleep`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Source of jitter samples in the range `[0.0, 1.0)`
+pub trait JitterSource {
+    fn next_sample(&mut self) -> f64;
+}
+
+/// Small xorshift generator, good enough to spread out retries
+#[derive(Debug, Clone)]
+pub struct XorShiftJitter {
+    state: u64,
+}
+
+impl XorShiftJitter {
+    /// Create a generator with an explicit seed (zero is replaced by a constant)
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Create a generator seeded from the system clock
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::with_seed(nanos)
+    }
+}
+
+impl JitterSource for XorShiftJitter {
+    fn next_sample(&mut self) -> f64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        // Use the top 53 bits for a uniformly distributed f64 in [0, 1)
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Exponential backoff policy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_delay: Duration,
+    /// Upper bound for any single delay
+    pub max_delay: Duration,
+    /// Factor applied to the delay after each retry
+    pub multiplier: f64,
+    /// Fraction of the delay that is randomized (0.0 = none, 1.0 = full jitter)
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Policy for Bluetooth service state changes (~2.5s worst case)
+    pub const BLUETOOTH: RetryPolicy = RetryPolicy {
+        max_attempts: 4,
+        initial_delay: Duration::from_millis(250),
+        max_delay: Duration::from_secs(2),
+        multiplier: 2.0,
+        jitter: 0.25,
+    };
+
+    /// Policy for WASAPI session and endpoint operations (~0.7s worst case)
+    pub const AUDIO_SESSION: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(500),
+        multiplier: 2.0,
+        jitter: 0.25,
+    };
+
+    /// Policy that never retries
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_attempts: 1,
+        initial_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+        multiplier: 1.0,
+        jitter: 0.0,
+    };
+
+    /// Compute the delay before retry number `retry` (0-based)
+    ///
+    /// `sample` is a jitter sample in `[0.0, 1.0)`; the jittered part of the
+    /// delay is scaled by it, so a sample of 1.0 yields the full backoff.
+    pub fn delay_for(&self, retry: u32, sample: f64) -> Duration {
+        let base = self.initial_delay.as_secs_f64() * self.multiplier.powi(retry as i32);
+        let capped = base.min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let scaled = capped * (1.0 - jitter) + capped * jitter * sample.clamp(0.0, 1.0);
+        Duration::from_secs_f64(scaled)
+    }
+
+    /// Run an operation, retrying transient failures with the system clock
+    pub fn run<T, E, C, F>(&self, operation: &str, classify: C, op: F) -> std::result::Result<T, E>
+    where
+        E: std::fmt::Display,
+        C: Fn(&E) -> ErrorClass,
+        F: FnMut(u32) -> std::result::Result<T, E>,
+    {
+        self.run_with(
+            &SystemClock,
+            &mut XorShiftJitter::from_time(),
+            operation,
+            classify,
+            op,
+        )
+    }
+
+    /// Run an operation with an explicit clock and jitter source
+    ///
+    /// The closure receives the 0-based attempt number. Permanent failures and
+    /// the failure of the last attempt are returned unchanged.
+    pub fn run_with<T, E, C, F>(
+        &self,
+        clock: &dyn Clock,
+        jitter: &mut dyn JitterSource,
+        operation: &str,
+        classify: C,
+        mut op: F,
+    ) -> std::result::Result<T, E>
+    where
+        E: std::fmt::Display,
+        C: Fn(&E) -> ErrorClass,
+        F: FnMut(u32) -> std::result::Result<T, E>,
+    {
+        let max_attempts = self.max_attempts.max(1);
+        let mut attempt = 0;
+
+        loop {
+            match op(attempt) {
+                Ok(value) => {
+                    if attempt > 0 {
+                        debug!("{} succeeded after {} retries", operation, attempt);
+                    }
+                    return Ok(value);
+                }
+                Err(e) => {
+                    if classify(&e) == ErrorClass::Permanent {
+                        debug!("{} failed permanently: {}", operation, e);
+                        return Err(e);
+                    }
+
+                    if attempt + 1 >= max_attempts {
+                        warn!(
+                            "{} failed after {} attempts: {}",
+                            operation, max_attempts, e
+                        );
+                        return Err(e);
+                    }
+
+                    let delay = self.delay_for(attempt, jitter.next_sample());
+                    debug!(
+                        "{} failed with transient error ({}), retrying in {:?} (attempt {}/{})",
+                        operation,
+                        e,
+                        delay,
+                        attempt + 2,
+                        max_attempts
+                    );
+                    clock.sleep(delay);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_win32_error() {
+        assert_eq!(classify_win32_error(ERROR_BUSY), ErrorClass::Transient);
+        assert_eq!(classify_win32_error(ERROR_TIMEOUT), ErrorClass::Transient);
+        // ERROR_NOT_FOUND and ERROR_SERVICE_DOES_NOT_EXIST
+        assert_eq!(classify_win32_error(1168), ErrorClass::Permanent);
+        assert_eq!(classify_win32_error(1060), ErrorClass::Permanent);
+        // ERROR_ACCESS_DENIED
+        assert_eq!(classify_win32_error(5), ErrorClass::Permanent);
+    }
+
+    #[test]
+    fn test_classify_hresult() {
+        assert_eq!(
+            classify_hresult(AUDCLNT_E_DEVICE_IN_USE as i32),
+            ErrorClass::Transient
+        );
+        // AUDCLNT_E_DEVICE_INVALIDATED
+        assert_eq!(
+            classify_hresult(0x8889_0004_u32 as i32),
+            ErrorClass::Permanent
+        );
+        // HRESULT_FROM_WIN32(ERROR_BUSY)
+        assert_eq!(
+            classify_hresult(0x8007_00AA_u32 as i32),
+            ErrorClass::Transient
+        );
+        // E_NOTFOUND = HRESULT_FROM_WIN32(ERROR_NOT_FOUND)
+        assert_eq!(
+            classify_hresult(0x8007_0490_u32 as i32),
+            ErrorClass::Permanent
+        );
+        // E_INVALIDARG
+        assert_eq!(
+            classify_hresult(0x8007_0057_u32 as i32),
+            ErrorClass::Permanent
+        );
+    }
+
+    #[test]
+    fn test_delay_without_jitter_is_exponential_and_capped() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::BLUETOOTH
+        };
+        assert_eq!(policy.delay_for(0, 0.5), Duration::from_millis(250));
+        assert_eq!(policy.delay_for(1, 0.5), Duration::from_millis(500));
+        assert_eq!(policy.delay_for(2, 0.5), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(10, 0.5), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_xorshift_samples_in_range() {
+        let mut jitter = XorShiftJitter::with_seed(42);
+        for _ in 0..1000 {
+            let sample = jitter.next_sample();
+            assert!((0.0..1.0).contains(&sample));
+        }
+    }
+}
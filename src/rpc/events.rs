@@ -0,0 +1,229 @@
+//! Monitor events streamed to JSON-RPC subscribers
+
+use super::notification;
+use crate::audio::bus::{BusMessage, EventBus, Subscription, Topic};
+use crate::audio::monitor::MonitorEvent;
+use crate::audio::watchdog::WatchdogOutcome;
+use crate::cli::{mode_id, AppStatus, DeviceEntry, DeviceStatus};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// Event type sent when a subscriber fell behind and events were dropped
+pub const LAGGED_EVENT_TYPE: &str = "lagged";
+
+/// Convert a monitor event to its type name and JSON payload
+pub fn event_to_json(event: &MonitorEvent) -> (&'static str, Value) {
+    match event {
+        MonitorEvent::StateUpdate {
+            mode,
+            mic_using_apps,
+            devices,
+            ..
+        } => {
+            let devices: Vec<DeviceStatus> = devices
+                .iter()
+                .map(|d| DeviceStatus {
+                    name: d.device.name.clone(),
+                    mode: mode_id(d.current_mode).to_string(),
+                    battery_level: d.battery_level,
+                })
+                .collect();
+            let mic_apps: Vec<AppStatus> = mic_using_apps
+                .iter()
+                .map(|app| AppStatus {
+                    pid: app.process_id,
+                    name: app.process_name.clone(),
+                    display_name: app.display_name.clone(),
+                    muted: Some(app.is_muted),
+                    bluetooth_mic: Some(app.is_using_bluetooth_mic),
+                })
+                .collect();
+            (
+                "stateUpdate",
+                json!({ "mode": mode_id(*mode), "devices": devices, "mic_apps": mic_apps }),
+            )
+        }
+        MonitorEvent::ModeChanged { old_mode, new_mode } => (
+            "modeChanged",
+            json!({ "old_mode": mode_id(*old_mode), "new_mode": mode_id(*new_mode) }),
+        ),
+        MonitorEvent::PairedDevicesUpdated(devices) => {
+            let devices: Vec<DeviceEntry> = devices.iter().map(DeviceEntry::from).collect();
+            ("pairedDevicesUpdated", json!({ "devices": devices }))
+        }
+        MonitorEvent::Watchdog(record) => {
+            let (outcome, error) = match &record.outcome {
+                WatchdogOutcome::Triggered => ("triggered", None),
+                WatchdogOutcome::Succeeded => ("succeeded", None),
+                WatchdogOutcome::Failed(error) => ("failed", Some(error.as_str())),
+                WatchdogOutcome::GaveUp => ("gave-up", None),
+            };
+            (
+                "watchdog",
+                json!({
+                    "device": record.device,
+                    "outcome": outcome,
+                    "error": error,
+                    "attempt": record.attempt,
+                    "max_attempts": record.max_attempts,
+                    "stuck_for_secs": record.stuck_for.as_secs(),
+                }),
+            )
+        }
+        MonitorEvent::ForceStereoReapplied(device) => ("forceStereoReapplied", json!({ "device": device })),
+        MonitorEvent::ForceStereoReapplyFailed { device, error } => (
+            "forceStereoReapplyFailed",
+            json!({ "device": device, "error": error }),
+        ),
+        MonitorEvent::Error(message) => ("error", json!({ "message": message })),
+        MonitorEvent::Shutdown => ("shutdown", json!({})),
+    }
+}
+
+/// A monitor event as delivered to subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub struct HubEvent {
+    pub event_type: &'static str,
+    pub payload: Value,
+}
+
+impl HubEvent {
+    /// The JSON-RPC `event` notification line for this event
+    pub fn to_notification(&self) -> String {
+        let mut params = json!({ "type": self.event_type });
+        if let (Some(params), Value::Object(payload)) = (params.as_object_mut(), &self.payload) {
+            params.extend(payload.clone());
+        }
+        notification("event", params)
+    }
+}
+
+impl From<BusMessage> for HubEvent {
+    fn from(message: BusMessage) -> Self {
+        match message {
+            BusMessage::Event(event) => {
+                let (event_type, payload) = event_to_json(&event);
+                HubEvent { event_type, payload }
+            }
+            BusMessage::Lagged(missed) => HubEvent {
+                event_type: LAGGED_EVENT_TYPE,
+                payload: json!({ "missed": missed }),
+            },
+        }
+    }
+}
+
+/// Monitor events for RPC and HTTP subscribers, as JSON
+///
+/// A view of the monitor's `EventBus`; cloning shares it. Each subscriber
+/// has its own bounded queue, and one that falls behind gets a `lagged`
+/// event with the number of events it missed.
+#[derive(Clone, Default)]
+pub struct EventHub {
+    bus: EventBus,
+}
+
+impl EventHub {
+    /// A hub with its own bus, e.g. for tests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A hub on the bus the monitor publishes to
+    pub fn on(bus: EventBus) -> Self {
+        Self { bus }
+    }
+
+    /// Subscribe to the given topics (`None` = all)
+    pub fn subscribe(&self, topics: Option<HashSet<Topic>>) -> HubSubscription {
+        HubSubscription(self.bus.subscribe(topics))
+    }
+
+    /// Number of live subscriptions
+    pub fn subscriber_count(&self) -> usize {
+        self.bus.subscriber_count()
+    }
+
+    /// Deliver a monitor event to every matching subscriber
+    pub fn publish(&self, event: &MonitorEvent) {
+        self.bus.publish(event.clone());
+    }
+}
+
+/// A subscription to the hub; dropping it unsubscribes
+pub struct HubSubscription(Subscription);
+
+impl HubSubscription {
+    /// Events queued right now
+    pub fn try_iter(&self) -> impl Iterator<Item = HubEvent> + '_ {
+        self.0.try_iter().map(HubEvent::from)
+    }
+
+    /// Wait up to `timeout` for the next event
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<HubEvent, RecvTimeoutError> {
+        self.0.recv_timeout(timeout).map(HubEvent::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::device::AudioMode;
+
+    #[test]
+    fn test_mode_changed_payload() {
+        let (event_type, payload) = event_to_json(&MonitorEvent::ModeChanged {
+            old_mode: AudioMode::Stereo,
+            new_mode: AudioMode::HandsFree,
+        });
+        assert_eq!(event_type, "modeChanged");
+        assert_eq!(payload, json!({ "old_mode": "stereo", "new_mode": "hands-free" }));
+    }
+
+    #[test]
+    fn test_event_types_match_topics() {
+        let event = MonitorEvent::ForceStereoReapplyFailed {
+            device: "Jabra".to_string(),
+            error: "boom".to_string(),
+        };
+        assert_eq!(event_to_json(&event).0, event.topic().name());
+        assert_eq!(event_to_json(&MonitorEvent::Shutdown).0, Topic::Shutdown.name());
+    }
+
+    #[test]
+    fn test_hub_filters_and_prunes() {
+        let hub = EventHub::new();
+        let all = hub.subscribe(None);
+        let errors_only = hub.subscribe(Some(HashSet::from([Topic::Error])));
+        let dropped = hub.subscribe(None);
+        drop(dropped);
+        assert_eq!(hub.subscriber_count(), 2);
+
+        hub.publish(&MonitorEvent::ForceStereoReapplied("Jabra".to_string()));
+
+        let event = all.try_iter().next().unwrap();
+        assert_eq!(
+            event.to_notification(),
+            r#"{"jsonrpc":"2.0","method":"event","params":{"device":"Jabra","type":"forceStereoReapplied"}}"#
+        );
+        assert!(errors_only.try_iter().next().is_none());
+
+        hub.publish(&MonitorEvent::Error("boom".to_string()));
+        assert!(errors_only.try_iter().next().is_some());
+    }
+
+    #[test]
+    fn test_slow_subscriber_gets_lagged_event() {
+        let hub = EventHub::new();
+        let slow = hub.subscribe(None);
+        for _ in 0..crate::audio::bus::DEFAULT_CAPACITY + 2 {
+            hub.publish(&MonitorEvent::Shutdown);
+        }
+
+        let first = slow.try_iter().next().unwrap();
+        assert_eq!(first.event_type, LAGGED_EVENT_TYPE);
+        assert_eq!(first.payload, json!({ "missed": 2 }));
+    }
+}
@@ -0,0 +1,303 @@
+//! Local JSON-RPC 2.0 control API
+//!
+//! Lets scripts and other tools query state, subscribe to monitor events and
+//! run the same actions as the CLI. Messages are newline-delimited JSON-RPC
+//! 2.0 objects. Every connection must call `authenticate` with the access
+//! token from config.toml before anything else.
+//!
+//! Commands are executed through a `CliBackend`, which on the tray is a
+//! `RemoteBackend` handing requests to the main loop, so the RPC server shares
+//! the exact behaviour of the CLI and pipe forwarding.
+
+pub mod events;
+#[cfg(windows)]
+pub mod pipe;
+pub mod server;
+
+pub use events::{EventHub, HubEvent, HubSubscription};
+
+use crate::audio::bus::Topic;
+use crate::auth::tokens_match;
+use crate::cli::{CliBackend, CliError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// Version of the method set; bumped on incompatible changes
+pub const RPC_PROTOCOL_VERSION: u32 = 1;
+
+/// JSON-RPC error codes
+pub mod error_code {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// Not authenticated, or wrong token
+    pub const UNAUTHORIZED: i64 = -32000;
+    /// Device or process not found
+    pub const NOT_FOUND: i64 = -32001;
+    /// The action was attempted and failed
+    pub const FAILED: i64 = -32002;
+    /// The Bluetooth or audio stack is unavailable
+    pub const UNAVAILABLE: i64 = -32003;
+}
+
+/// Error returned in a JSON-RPC error response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(error_code::INVALID_PARAMS, message)
+    }
+}
+
+impl From<CliError> for RpcError {
+    fn from(e: CliError) -> Self {
+        let code = match e {
+            CliError::Usage(_) => error_code::INVALID_PARAMS,
+            CliError::NotFound(_) => error_code::NOT_FOUND,
+            CliError::Failed(_) => error_code::FAILED,
+            CliError::Unavailable(_) => error_code::UNAVAILABLE,
+        };
+        Self::new(code, e.message())
+    }
+}
+
+/// Build a success response line
+pub fn success(id: &Value, result: Value) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+}
+
+/// Build an error response line
+pub fn failure(id: &Value, error: &RpcError) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+    .to_string()
+}
+
+/// Build a notification line (a message without an id)
+pub fn notification(method: &str, params: Value) -> String {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string()
+}
+
+#[derive(Deserialize)]
+struct TokenParams {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceParams {
+    device: String,
+}
+
+#[derive(Deserialize)]
+struct PidParams {
+    pid: u32,
+}
+
+#[derive(Deserialize)]
+struct SubscribeParams {
+    #[serde(default)]
+    events: Option<Vec<String>>,
+}
+
+fn to_value<T: Serialize>(result: Result<T, CliError>) -> Result<Value, RpcError> {
+    Ok(serde_json::to_value(result?).expect("report serialization cannot fail"))
+}
+
+fn parse_params<P: DeserializeOwned>(params: Value) -> Result<P, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::invalid_params(format!("Invalid params: {}", e)))
+}
+
+/// Protocol state of one client connection
+pub struct Session {
+    token: String,
+    authenticated: bool,
+    subscription: Option<HubSubscription>,
+}
+
+impl Session {
+    /// Create a session that accepts `token` in `authenticate`
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            authenticated: false,
+            subscription: None,
+        }
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// Handle one request line; returns the response line, or `None` for notifications
+    pub fn handle_line(&mut self, line: &str, backend: &mut dyn CliBackend, hub: &EventHub) -> Option<String> {
+        let value: Value = match serde_json::from_str(line.trim()) {
+            Ok(value) => value,
+            Err(e) => {
+                let error = RpcError::new(error_code::PARSE_ERROR, format!("Parse error: {}", e));
+                return Some(failure(&Value::Null, &error));
+            }
+        };
+
+        let Value::Object(mut request) = value else {
+            let error = RpcError::new(error_code::INVALID_REQUEST, "Request must be a single JSON object");
+            return Some(failure(&Value::Null, &error));
+        };
+
+        // A request without an id is a notification: run it, but never reply
+        let id = request.remove("id");
+        let reply_id = id.clone().unwrap_or(Value::Null);
+
+        let is_v2 = request.get("jsonrpc").and_then(Value::as_str) == Some("2.0");
+        let method = match request.remove("method") {
+            Some(Value::String(method)) if is_v2 => method,
+            _ => {
+                let error = RpcError::new(
+                    error_code::INVALID_REQUEST,
+                    "Request needs \"jsonrpc\": \"2.0\" and a string method",
+                );
+                return Some(failure(&reply_id, &error));
+            }
+        };
+
+        let params = match request.remove("params") {
+            None | Some(Value::Null) => Value::Object(Default::default()),
+            Some(params @ Value::Object(_)) => params,
+            Some(_) => {
+                let error = RpcError::invalid_params("Params must be an object");
+                return id.map(|id| failure(&id, &error));
+            }
+        };
+
+        let result = self.call(&method, params, backend, hub);
+        let id = id?;
+        Some(match result {
+            Ok(result) => success(&id, result),
+            Err(error) => failure(&id, &error),
+        })
+    }
+
+    /// Event notification lines queued for a subscribed session
+    pub fn pending_events(&self) -> Vec<String> {
+        match &self.subscription {
+            Some(subscription) => subscription.try_iter().map(|event| event.to_notification()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn call(&mut self, method: &str, params: Value, backend: &mut dyn CliBackend, hub: &EventHub) -> Result<Value, RpcError> {
+        if method == "authenticate" {
+            let params: TokenParams = parse_params(params)?;
+            if !tokens_match(&self.token, &params.token) {
+                return Err(RpcError::new(error_code::UNAUTHORIZED, "Invalid access token"));
+            }
+            self.authenticated = true;
+            return Ok(json!({
+                "protocol_version": RPC_PROTOCOL_VERSION,
+                "server_version": env!("CARGO_PKG_VERSION"),
+            }));
+        }
+
+        if !self.authenticated {
+            return Err(RpcError::new(error_code::UNAUTHORIZED, "Call authenticate first"));
+        }
+
+        match method {
+            "getState" => to_value(backend.status()),
+            "getDevices" => to_value(backend.devices()),
+            "subscribe" => {
+                let params: SubscribeParams = parse_params(params)?;
+                let filter = match params.events {
+                    Some(events) => Some(
+                        events
+                            .iter()
+                            .map(|event| event.parse::<Topic>())
+                            .collect::<Result<HashSet<_>, _>>()
+                            .map_err(RpcError::invalid_params)?,
+                    ),
+                    None => None,
+                };
+                self.subscription = Some(hub.subscribe(filter));
+                Ok(json!({ "subscribed": true }))
+            }
+            "unsubscribe" => {
+                let was_subscribed = self.subscription.take().is_some();
+                Ok(json!({ "subscribed": false, "was_subscribed": was_subscribed }))
+            }
+            "forceStereo" => {
+                let params: DeviceParams = parse_params(params)?;
+                to_value(backend.force_stereo(&params.device))
+            }
+            "allowHandsFree" => {
+                let params: DeviceParams = parse_params(params)?;
+                to_value(backend.allow_hands_free(&params.device))
+            }
+            "reconnect" => {
+                let params: DeviceParams = parse_params(params)?;
+                to_value(backend.reconnect(&params.device))
+            }
+            "muteApp" => {
+                let params: PidParams = parse_params(params)?;
+                to_value(backend.mute(params.pid))
+            }
+            "unmuteApp" => {
+                let params: PidParams = parse_params(params)?;
+                to_value(backend.unmute(params.pid))
+            }
+            _ => Err(RpcError::new(
+                error_code::METHOD_NOT_FOUND,
+                format!("Method '{}' not found", method),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_errors_map_to_codes() {
+        assert_eq!(
+            RpcError::from(CliError::NotFound("x".to_string())).code,
+            error_code::NOT_FOUND
+        );
+        assert_eq!(
+            RpcError::from(CliError::Usage("x".to_string())).code,
+            error_code::INVALID_PARAMS
+        );
+        assert_eq!(
+            RpcError::from(CliError::Unavailable("x".to_string())).code,
+            error_code::UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn test_response_shapes() {
+        assert_eq!(
+            success(&json!(1), json!({ "ok": true })),
+            r#"{"id":1,"jsonrpc":"2.0","result":{"ok":true}}"#
+        );
+        assert_eq!(
+            failure(&Value::Null, &RpcError::new(error_code::PARSE_ERROR, "bad")),
+            r#"{"error":{"code":-32700,"message":"bad"},"id":null,"jsonrpc":"2.0"}"#
+        );
+    }
+}
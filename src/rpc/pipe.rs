@@ -0,0 +1,173 @@
+//! Named pipe listener for the JSON-RPC server
+//!
+//! Unlike the command-forwarding pipe, connections here are long-lived: a
+//! client can send any number of requests and keeps receiving event
+//! notifications while subscribed.
+
+use super::server::{ConnectionContext, RpcStream, POLL_INTERVAL};
+use super::EventHub;
+use crate::error::{AppError, Result};
+use crate::ipc::pipe::{open_client, wide};
+use crate::ipc::IpcCall;
+use log::{debug, error, info, warn};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, GetLastError, ERROR_BROKEN_PIPE, ERROR_PIPE_CONNECTED, HANDLE};
+use windows::Win32::Storage::FileSystem::{FlushFileBuffers, ReadFile, WriteFile, PIPE_ACCESS_DUPLEX};
+use windows::Win32::System::Pipes::{
+    ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PeekNamedPipe, PIPE_READMODE_BYTE,
+    PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+/// Pipe name of the JSON-RPC server; local connections only
+pub const RPC_PIPE_NAME: &str = r"\\.\pipe\BtAudioModeManager-rpc";
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Server end of one connected pipe instance
+struct PipeStream(HANDLE);
+
+// The handle is owned by exactly one connection thread
+unsafe impl Send for PipeStream {}
+
+impl RpcStream for PipeStream {
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut available = 0u32;
+        let peeked = unsafe { PeekNamedPipe(self.0, None, 0, None, Some(&mut available as *mut u32), None) };
+        if let Err(e) = peeked {
+            if e.code() == ERROR_BROKEN_PIPE.to_hresult() {
+                return Ok(0);
+            }
+            return Err(io::Error::other(e));
+        }
+
+        if available == 0 {
+            thread::sleep(POLL_INTERVAL);
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let len = buf.len().min(available as usize);
+        let mut read = 0u32;
+        unsafe { ReadFile(self.0, Some(&mut buf[..len]), Some(&mut read as *mut u32), None) }.map_err(io::Error::other)?;
+        Ok(read as usize)
+    }
+}
+
+impl Write for PipeStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0u32;
+        unsafe { WriteFile(self.0, Some(buf), Some(&mut written as *mut u32), None) }.map_err(io::Error::other)?;
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        unsafe { FlushFileBuffers(self.0) }.map_err(io::Error::other)
+    }
+}
+
+impl Drop for PipeStream {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DisconnectNamedPipe(self.0);
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+/// JSON-RPC server on a named pipe, run by the tray instance
+pub struct RpcPipeServer {
+    running: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl RpcPipeServer {
+    /// Start serving; requests are handed to the main loop over `call_tx`
+    pub fn start(token: String, call_tx: Sender<IpcCall>, hub: EventHub) -> Result<Self> {
+        let running = Arc::new(AtomicBool::new(true));
+        let context = ConnectionContext {
+            token,
+            call_tx,
+            hub,
+            running: Arc::clone(&running),
+        };
+
+        let thread_handle = thread::Builder::new()
+            .name("rpc-server".to_string())
+            .spawn(move || accept_thread(context))
+            .map_err(AppError::IoError)?;
+
+        info!("RPC server listening on {}", RPC_PIPE_NAME);
+        Ok(Self {
+            running,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Stop accepting and close all connections
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // Unblock ConnectNamedPipe with a throwaway connection
+        if let Ok(pipe) = open_client(RPC_PIPE_NAME) {
+            unsafe {
+                let _ = CloseHandle(pipe);
+            }
+        }
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for RpcPipeServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn accept_thread(context: ConnectionContext) {
+    let name = wide(RPC_PIPE_NAME);
+    let mut connections: Vec<JoinHandle<()>> = Vec::new();
+
+    while context.running.load(Ordering::SeqCst) {
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                PCWSTR::from_raw(name.as_ptr()),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                None,
+            )
+        };
+        if pipe.is_invalid() {
+            error!("Failed to create RPC pipe: {:?}", unsafe { GetLastError() });
+            break;
+        }
+        let stream = PipeStream(pipe);
+
+        let connected = match unsafe { ConnectNamedPipe(pipe, None) } {
+            Ok(()) => true,
+            Err(e) => e.code() == ERROR_PIPE_CONNECTED.to_hresult(),
+        };
+
+        if connected && context.running.load(Ordering::SeqCst) {
+            debug!("RPC client connected");
+            match context.spawn(stream) {
+                Ok(handle) => connections.push(handle),
+                Err(e) => warn!("Failed to start RPC connection: {}", e),
+            }
+        }
+        connections.retain(|handle| !handle.is_finished());
+    }
+
+    for handle in connections {
+        let _ = handle.join();
+    }
+    debug!("RPC server stopped");
+}
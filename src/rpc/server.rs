@@ -0,0 +1,209 @@
+//! Connection handling for the JSON-RPC server
+//!
+//! Each connection gets its own thread and `Session`. Commands go through a
+//! `RemoteBackend` over a `ChannelTransport`, so they run on the main loop like
+//! pipe-forwarded CLI commands. The loopback TCP listener exists for tests and
+//! tools that cannot open named pipes; the tray uses the pipe listener.
+
+use super::{error_code, failure, EventHub, RpcError, Session};
+use crate::error::{AppError, Result};
+use crate::ipc::{ChannelTransport, IpcCall, RemoteBackend, MAX_MESSAGE_SIZE};
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often idle connections check for events and shutdown
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A bidirectional byte stream a session can be served over
+pub trait RpcStream: Write {
+    /// Read available bytes, waiting at most about `POLL_INTERVAL`
+    ///
+    /// Returns `Ok(0)` when the peer closed the stream, and an error of kind
+    /// `WouldBlock` or `TimedOut` when nothing arrived in time.
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl RpcStream for TcpStream {
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read(buf)
+    }
+}
+
+/// Shared settings for every connection of a server
+#[derive(Clone)]
+pub(crate) struct ConnectionContext {
+    pub token: String,
+    pub call_tx: Sender<IpcCall>,
+    pub hub: EventHub,
+    pub running: Arc<AtomicBool>,
+}
+
+impl ConnectionContext {
+    /// Serve one connection on a new thread
+    pub fn spawn<S: RpcStream + Send + 'static>(&self, stream: S) -> Result<JoinHandle<()>> {
+        let context = self.clone();
+        thread::Builder::new()
+            .name("rpc-connection".to_string())
+            .spawn(move || {
+                let mut backend = RemoteBackend::new(ChannelTransport::new(context.call_tx.clone()));
+                let mut session = Session::new(context.token.clone());
+                if let Err(e) = serve_connection(stream, &mut session, &mut backend, &context.hub, &context.running) {
+                    debug!("RPC connection closed: {}", e);
+                }
+            })
+            .map_err(AppError::IoError)
+    }
+}
+
+/// Serve requests and event notifications until the peer disconnects or `running` is cleared
+pub fn serve_connection<S: RpcStream>(
+    mut stream: S,
+    session: &mut Session,
+    backend: &mut dyn crate::cli::CliBackend,
+    hub: &EventHub,
+    running: &AtomicBool,
+) -> io::Result<()> {
+    let mut pending = Vec::new();
+    let mut buffer = [0u8; 4096];
+
+    while running.load(Ordering::SeqCst) {
+        match stream.read_available(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => pending.extend_from_slice(&buffer[..read]),
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+
+        while let Some(pos) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = session.handle_line(&line, backend, hub) {
+                write_line(&mut stream, &response)?;
+            }
+        }
+
+        if pending.len() > MAX_MESSAGE_SIZE {
+            let error = RpcError::new(error_code::INVALID_REQUEST, "Message too large");
+            write_line(&mut stream, &failure(&Value::Null, &error))?;
+            return Ok(());
+        }
+
+        for event in session.pending_events() {
+            write_line(&mut stream, &event)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_line<S: Write>(stream: &mut S, line: &str) -> io::Result<()> {
+    stream.write_all(line.as_bytes())?;
+    stream.write_all(b"\n")?;
+    stream.flush()
+}
+
+/// JSON-RPC server listening on a loopback TCP port
+pub struct RpcServer {
+    running: Arc<AtomicBool>,
+    local_addr: SocketAddr,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl RpcServer {
+    /// Listen on `addr`, which must be a loopback address (port 0 picks a free port)
+    pub fn start_tcp(addr: SocketAddr, token: String, call_tx: Sender<IpcCall>, hub: EventHub) -> Result<Self> {
+        if !addr.ip().is_loopback() {
+            return Err(AppError::ConfigError(format!(
+                "RPC server must listen on a loopback address, not {}",
+                addr
+            )));
+        }
+
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let context = ConnectionContext {
+            token,
+            call_tx,
+            hub,
+            running: Arc::clone(&running),
+        };
+
+        let thread_handle = thread::Builder::new()
+            .name("rpc-server".to_string())
+            .spawn(move || accept_thread(listener, context))
+            .map_err(AppError::IoError)?;
+
+        info!("RPC server listening on {}", local_addr);
+        Ok(Self {
+            running,
+            local_addr,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting and close all connections
+    pub fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for RpcServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn accept_thread(listener: TcpListener, context: ConnectionContext) {
+    let mut connections = Vec::new();
+
+    while context.running.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                debug!("RPC client connected from {}", peer);
+                let configured = stream
+                    .set_nonblocking(false)
+                    .and_then(|_| stream.set_read_timeout(Some(POLL_INTERVAL)));
+                if let Err(e) = configured {
+                    warn!("Failed to configure RPC connection: {}", e);
+                    continue;
+                }
+                match context.spawn(stream) {
+                    Ok(handle) => connections.push(handle),
+                    Err(e) => warn!("Failed to start RPC connection: {}", e),
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                warn!("RPC accept failed: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+        connections.retain(|handle: &JoinHandle<()>| !handle.is_finished());
+    }
+
+    for handle in connections {
+        let _ = handle.join();
+    }
+    debug!("RPC server stopped");
+}
@@ -4,10 +4,11 @@ use crate::error::{AppError, Result};
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 /// Current configuration version
-pub const CONFIG_VERSION: u32 = 2;
+pub const CONFIG_VERSION: u32 = 11;
 
 /// Portable mode marker filename
 const PORTABLE_MARKER: &str = "portable.txt";
@@ -16,7 +17,7 @@ const PORTABLE_MARKER: &str = "portable.txt";
 const CONFIG_FILENAME: &str = "config.toml";
 
 /// Application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AppConfig {
     /// Configuration version for migration
     #[serde(default = "default_version")]
@@ -37,13 +38,41 @@ pub struct AppConfig {
     /// Update settings
     #[serde(default)]
     pub updates: UpdateConfig,
+
+    /// Hands-free watchdog settings
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+
+    /// Local JSON-RPC control API settings
+    #[serde(default)]
+    pub rpc: RpcConfig,
+
+    /// Loopback HTTP endpoint settings
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// User hook commands run on events
+    #[serde(default)]
+    pub hooks: HooksConfig,
+
+    /// MQTT publishing settings
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+
+    /// Prometheus / OpenMetrics exporter settings
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    /// Global hotkeys for device actions
+    #[serde(default)]
+    pub hotkeys: HotkeysConfig,
 }
 
 fn default_version() -> u32 {
     CONFIG_VERSION
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GeneralConfig {
     /// Start with Windows
     #[serde(default)]
@@ -86,7 +115,7 @@ impl Default for GeneralConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NotificationConfig {
     /// Show notification on mode change
     #[serde(default = "default_true")]
@@ -103,6 +132,14 @@ pub struct NotificationConfig {
     /// Show notification for updates
     #[serde(default = "default_true")]
     pub notify_updates: bool,
+
+    /// Warn when a headset's battery drops to this percentage (0 = disabled)
+    #[serde(default = "default_low_battery_threshold")]
+    pub low_battery_threshold: u8,
+}
+
+fn default_low_battery_threshold() -> u8 {
+    20
 }
 
 impl Default for NotificationConfig {
@@ -112,11 +149,12 @@ impl Default for NotificationConfig {
             notify_mic_usage: true,
             notify_errors: true,
             notify_updates: true,
+            low_battery_threshold: default_low_battery_threshold(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LoggingConfig {
     /// Log level (trace, debug, info, warn, error)
     #[serde(default = "default_log_level")]
@@ -129,6 +167,10 @@ pub struct LoggingConfig {
     /// Number of log files to keep
     #[serde(default = "default_max_log_files")]
     pub max_files: u32,
+
+    /// Record every raw audio poll to a JSONL file in the log directory
+    #[serde(default)]
+    pub record_polls: bool,
 }
 
 fn default_log_level() -> String {
@@ -149,11 +191,12 @@ impl Default for LoggingConfig {
             level: "info".to_string(),
             max_file_size: 5 * 1024 * 1024,
             max_files: 3,
+            record_polls: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UpdateConfig {
     /// Check for updates automatically
     #[serde(default = "default_true")]
@@ -187,6 +230,255 @@ impl Default for UpdateConfig {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    /// Automatically reconnect devices stuck in hands-free mode (opt-in)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Seconds in hands-free mode without Bluetooth mic use before reconnecting
+    #[serde(default = "default_watchdog_timeout")]
+    pub hands_free_timeout_secs: u32,
+
+    /// Minimum seconds between reconnect attempts for the same device
+    #[serde(default = "default_watchdog_cooldown")]
+    pub cooldown_secs: u32,
+
+    /// Maximum reconnect attempts before giving up until the device recovers
+    #[serde(default = "default_watchdog_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_watchdog_timeout() -> u32 {
+    30
+}
+
+fn default_watchdog_cooldown() -> u32 {
+    120
+}
+
+fn default_watchdog_max_attempts() -> u32 {
+    3
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hands_free_timeout_secs: 30,
+            cooldown_secs: 120,
+            max_attempts: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RpcConfig {
+    /// Serve the JSON-RPC API on a local named pipe (opt-in)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Access token clients must present; generated on first start when empty
+    #[serde(default)]
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpConfig {
+    /// Serve status and events over HTTP on 127.0.0.1 (opt-in)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// TCP port on 127.0.0.1
+    #[serde(default = "default_http_port")]
+    pub port: u16,
+
+    /// Bearer token clients must present; generated on first start when empty
+    #[serde(default)]
+    pub token: String,
+}
+
+fn default_http_port() -> u16 {
+    8731
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8731,
+            token: String::new(),
+        }
+    }
+}
+
+/// Events a hook command can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEventKind {
+    ModeChanged,
+    MicSessionStarted,
+    DeviceConnected,
+    ForceStereoApplied,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookCommand {
+    /// Event that triggers the command
+    pub event: HookEventKind,
+
+    /// Program to run
+    pub command: String,
+
+    /// Arguments passed to the program
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Seconds a hook may run before it is killed
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u32,
+
+    /// Hooks running at the same time; further runs wait in a queue
+    #[serde(default = "default_hook_concurrency")]
+    pub max_concurrent: u32,
+
+    /// Configured hook commands (`[[hooks.commands]]`)
+    #[serde(default)]
+    pub commands: Vec<HookCommand>,
+}
+
+fn default_hook_timeout() -> u32 {
+    10
+}
+
+fn default_hook_concurrency() -> u32 {
+    2
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            max_concurrent: 2,
+            commands: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MqttConfig {
+    /// Publish state to an MQTT broker (opt-in)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Broker host name or address
+    #[serde(default = "default_mqtt_host")]
+    pub host: String,
+
+    /// Broker TCP port
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+
+    /// User name; empty connects anonymously
+    #[serde(default)]
+    pub username: String,
+
+    /// Password; only sent with a user name
+    #[serde(default)]
+    pub password: String,
+
+    /// Client id; empty uses `btam-<computer name>`
+    #[serde(default)]
+    pub client_id: String,
+
+    /// Prefix of all state and command topics
+    #[serde(default = "default_mqtt_base_topic")]
+    pub base_topic: String,
+
+    /// Home Assistant discovery prefix
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+
+    /// Publish Home Assistant discovery configs
+    #[serde(default = "default_true")]
+    pub discovery: bool,
+}
+
+fn default_mqtt_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_base_topic() -> String {
+    "bt_audio_mode_manager".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: default_mqtt_host(),
+            port: 1883,
+            username: String::new(),
+            password: String::new(),
+            client_id: String::new(),
+            base_topic: default_mqtt_base_topic(),
+            discovery_prefix: default_mqtt_discovery_prefix(),
+            discovery: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Serve metrics on 127.0.0.1 (opt-in)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// TCP port on 127.0.0.1
+    #[serde(default = "default_metrics_port")]
+    pub port: u16,
+}
+
+fn default_metrics_port() -> u16 {
+    9731
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9731,
+        }
+    }
+}
+
+/// Global hotkeys such as `Ctrl+Alt+S`; empty leaves the action unbound
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HotkeysConfig {
+    /// Force stereo on the active headset
+    #[serde(default)]
+    pub force_stereo: String,
+
+    /// Allow hands-free on the active headset
+    #[serde(default)]
+    pub allow_hands_free: String,
+
+    /// Reconnect the active headset
+    #[serde(default)]
+    pub reconnect: String,
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -195,6 +487,13 @@ impl Default for AppConfig {
             notifications: NotificationConfig::default(),
             logging: LoggingConfig::default(),
             updates: UpdateConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            rpc: RpcConfig::default(),
+            http: HttpConfig::default(),
+            hooks: HooksConfig::default(),
+            mqtt: MqttConfig::default(),
+            metrics: MetricsConfig::default(),
+            hotkeys: HotkeysConfig::default(),
         }
     }
 }
@@ -216,6 +515,51 @@ impl AppConfig {
                 info!("Migrated config from v1 to v2: added language field");
             }
 
+            // v2 to v3: Added [watchdog] section (disabled by default via serde)
+            if self.config_version < 3 {
+                info!("Migrated config from v2 to v3: added watchdog settings");
+            }
+
+            // v3 to v4: Added low_battery_threshold to NotificationConfig (serde default)
+            if self.config_version < 4 {
+                info!("Migrated config from v3 to v4: added low battery threshold");
+            }
+
+            // v4 to v5: Added [rpc] section (disabled by default via serde)
+            if self.config_version < 5 {
+                info!("Migrated config from v4 to v5: added RPC settings");
+            }
+
+            // v5 to v6: Added [http] section (disabled by default via serde)
+            if self.config_version < 6 {
+                info!("Migrated config from v5 to v6: added HTTP settings");
+            }
+
+            // v6 to v7: Added [hooks] section (no commands by default)
+            if self.config_version < 7 {
+                info!("Migrated config from v6 to v7: added hook settings");
+            }
+
+            // v7 to v8: Added [mqtt] section (disabled by default via serde)
+            if self.config_version < 8 {
+                info!("Migrated config from v7 to v8: added MQTT settings");
+            }
+
+            // v8 to v9: Added [metrics] section (disabled by default via serde)
+            if self.config_version < 9 {
+                info!("Migrated config from v8 to v9: added metrics settings");
+            }
+
+            // v9 to v10: Added [hotkeys] section (all unbound by default)
+            if self.config_version < 10 {
+                info!("Migrated config from v9 to v10: added hotkey settings");
+            }
+
+            // v10 to v11: Added logging.record_polls (off by default via serde)
+            if self.config_version < 11 {
+                info!("Migrated config from v10 to v11: added poll recording option");
+            }
+
             self.config_version = CONFIG_VERSION;
         }
     }
@@ -333,6 +677,7 @@ impl ConfigManager {
     }
 
     /// Set auto-start in Windows registry
+    #[cfg(windows)]
     pub fn set_auto_start(&self, enabled: bool) -> Result<()> {
         use windows::core::PCWSTR;
         use windows::Win32::System::Registry::{
@@ -406,6 +751,7 @@ impl ConfigManager {
     }
 
     /// Check if auto-start is enabled
+    #[cfg(windows)]
     pub fn is_auto_start_enabled(&self) -> bool {
         use windows::core::PCWSTR;
         use windows::Win32::System::Registry::{
@@ -453,6 +799,50 @@ impl ConfigManager {
             result.is_ok() && size > 0
         }
     }
+
+    /// Auto-start is left to the session or service manager on other platforms
+    #[cfg(not(windows))]
+    pub fn set_auto_start(&self, enabled: bool) -> Result<()> {
+        if enabled {
+            log::warn!("Auto-start is not supported on this platform; start the app from your session or service manager");
+        }
+        Ok(())
+    }
+
+    /// Auto-start is left to the session or service manager on other platforms
+    #[cfg(not(windows))]
+    pub fn is_auto_start_enabled(&self) -> bool {
+        false
+    }
+}
+
+/// Notices edits to the config file by its modification time
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, treating its current contents as seen
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = Self::modified_time(&path);
+        Self { path, modified }
+    }
+
+    /// Whether the file was written, created or removed since the last call
+    pub fn changed(&mut self) -> bool {
+        let modified = Self::modified_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
 }
 
 #[cfg(test)]
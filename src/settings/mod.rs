@@ -1,7 +1,9 @@
 //! Settings and configuration module
 
 pub mod config;
+#[cfg(windows)]
 pub mod window;
 
-pub use config::{AppConfig, ConfigManager};
+pub use config::{AppConfig, ConfigManager, ConfigWatcher, HookCommand, HookEventKind, HooksConfig, HotkeysConfig, MetricsConfig, MqttConfig};
+#[cfg(windows)]
 pub use window::SettingsWindow;
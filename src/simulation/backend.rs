@@ -0,0 +1,416 @@
+//! Simulated audio and Bluetooth backend
+//!
+//! Holds a small model of the world (headsets with their format, HFP service
+//! and battery, apps capturing or playing audio) that scenario steps change
+//! as time passes. Calls from the monitor and the command line act on the
+//! same model, so a reconnect or force-stereo shows up in the next poll.
+
+use super::runner::Timeline;
+use super::scenario::{Action, Scenario, Step};
+use crate::audio::backend::{AudioBackend, AudioPoll, Endpoint};
+use crate::audio::device::{AudioDevice, AudioMode};
+use crate::audio::session::{HfpUsingApp, MicUsingApp};
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::bluetooth::battery::base_device_name;
+use crate::bluetooth::inventory::{decode_class_of_device, BluetoothAddress, BluetoothProfile, PairedDevice};
+use crate::bluetooth::names::find_best_match;
+use crate::error::{AppError, Result};
+use log::{debug, info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Class of device of a simulated headset (audio/video, wearable headset)
+const HEADSET_CLASS: u32 = 0x240404;
+
+/// First process id handed out to simulated apps
+const FIRST_PID: u32 = 1000;
+
+/// Non-Bluetooth output that is always present
+const SPEAKERS: &str = "Speakers";
+
+#[derive(Debug, Clone)]
+struct SimDevice {
+    name: String,
+    address: BluetoothAddress,
+    connected: bool,
+    mode: AudioMode,
+    hfp_enabled: bool,
+    battery: Option<u8>,
+    reconnect_fails: bool,
+}
+
+#[derive(Debug, Clone)]
+struct SimStream {
+    pid: u32,
+    app: String,
+    /// Capture device name; `None` for the Bluetooth mic or Bluetooth playback
+    device: Option<String>,
+    muted: bool,
+}
+
+impl SimStream {
+    fn process_name(&self) -> String {
+        self.app.to_lowercase().replace(' ', "")
+    }
+}
+
+#[derive(Debug, Default)]
+struct World {
+    pending: VecDeque<Step>,
+    elapsed: Duration,
+    devices: Vec<SimDevice>,
+    captures: Vec<SimStream>,
+    playback: Vec<SimStream>,
+    pids: HashMap<String, u32>,
+    actions: Vec<String>,
+}
+
+impl World {
+    fn pid(&mut self, app: &str) -> u32 {
+        let next = FIRST_PID + self.pids.len() as u32;
+        *self.pids.entry(app.to_string()).or_insert(next)
+    }
+
+    /// Device for a scenario step: exact or partial name, else the first
+    /// connected device (so `endpoint` and `headset` work as generic names)
+    fn find(&mut self, name: &str) -> Option<&mut SimDevice> {
+        let address = find_best_match(name, &self.devices, |d| d.name.as_str())
+            .map(|found| found.address)
+            .or_else(|| self.devices.iter().find(|d| d.connected).map(|d| d.address))?;
+        self.devices.iter_mut().find(|d| d.address == address)
+    }
+
+    /// Device for a backend call, matched by name like the real backends
+    fn named(&mut self, name: &str) -> Result<&mut SimDevice> {
+        let address = find_best_match(name, &self.devices, |d| d.name.as_str())
+            .map(|d| d.address)
+            .ok_or_else(|| AppError::ConfigError(format!("Bluetooth device '{}' not found", name)))?;
+        Ok(self.devices.iter_mut().find(|d| d.address == address).unwrap())
+    }
+
+    fn find_or_add(&mut self, name: &str) -> &mut SimDevice {
+        let exists = self.devices.iter().any(|d| d.name.eq_ignore_ascii_case(name));
+        if !exists {
+            let address = BluetoothAddress(0x5A_0000_0000 + self.devices.len() as u64 + 1);
+            self.devices.push(SimDevice {
+                name: name.to_string(),
+                address,
+                connected: false,
+                mode: AudioMode::Stereo,
+                hfp_enabled: true,
+                battery: None,
+                reconnect_fails: false,
+            });
+        }
+        self.devices.iter_mut().find(|d| d.name.eq_ignore_ascii_case(name)).unwrap()
+    }
+
+    fn apply(&mut self, step: Step) {
+        debug!("Simulation t={:?}: {:?}", step.at, step.action);
+        match step.action {
+            Action::Connect { device, mode } => {
+                let device = self.find_or_add(&device);
+                device.connected = true;
+                device.mode = mode;
+            }
+            Action::Disconnect { device } => match self.find(&device) {
+                Some(device) => device.connected = false,
+                None => warn!("Simulation: no device '{}' to disconnect", device),
+            },
+            Action::SetMode { device, mode } => match self.find(&device) {
+                Some(device) => device.mode = mode,
+                None => warn!("Simulation: no device '{}' to switch to {}", device, mode),
+            },
+            Action::Battery { device, level } => self.find_or_add(&device).battery = Some(level),
+            Action::ReenableHandsFree { device } => self.find_or_add(&device).hfp_enabled = true,
+            Action::ReconnectFails { device, fails } => self.find_or_add(&device).reconnect_fails = fails,
+            Action::OpenCapture { app, device } => {
+                let pid = self.pid(&app);
+                self.captures.retain(|s| s.pid != pid);
+                self.captures.push(SimStream { pid, app, device, muted: false });
+            }
+            Action::CloseCapture { app } => self.captures.retain(|s| s.app != app),
+            Action::OpenPlayback { app } => {
+                let pid = self.pid(&app);
+                self.playback.retain(|s| s.pid != pid);
+                self.playback.push(SimStream { pid, app, device: None, muted: false });
+            }
+            Action::ClosePlayback { app } => self.playback.retain(|s| s.app != app),
+        }
+    }
+
+    fn advance_to(&mut self, elapsed: Duration) {
+        self.elapsed = self.elapsed.max(elapsed);
+        while self.pending.front().is_some_and(|step| step.at <= self.elapsed) {
+            let step = self.pending.pop_front().unwrap();
+            self.apply(step);
+        }
+    }
+
+    /// Whether a capture stream records from a connected Bluetooth mic
+    fn on_bluetooth_mic(&self, stream: &SimStream) -> bool {
+        match &stream.device {
+            None => self.devices.iter().any(|d| d.connected),
+            Some(name) => self
+                .devices
+                .iter()
+                .any(|d| d.connected && find_best_match(name, std::slice::from_ref(d), |d| d.name.as_str()).is_some()),
+        }
+    }
+}
+
+/// Inventory entry of a simulated or replayed headset
+pub(super) fn paired_headset(address: BluetoothAddress, name: &str, connected: bool, hfp_enabled: bool) -> PairedDevice {
+    let mut profiles = vec![BluetoothProfile::A2dpSink, BluetoothProfile::Avrcp];
+    if hfp_enabled {
+        profiles.push(BluetoothProfile::HandsFree);
+    }
+    PairedDevice {
+        address,
+        name: name.to_string(),
+        class_of_device: HEADSET_CLASS,
+        device_type: decode_class_of_device(HEADSET_CLASS),
+        connected,
+        remembered: true,
+        authenticated: true,
+        last_seen: None,
+        last_used: None,
+        profiles,
+    }
+}
+
+/// Audio and Bluetooth backend driven by a `Scenario`
+pub struct SimulatedBackend {
+    world: Mutex<World>,
+    /// Set for real-time simulations, which advance on every poll
+    started: Option<Instant>,
+    /// Name Bluetooth endpoints like Windows does instead of after the paired device
+    windows_endpoint_names: bool,
+}
+
+impl SimulatedBackend {
+    /// Backend whose time only moves through `advance_to`
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            world: Mutex::new(World {
+                pending: scenario.steps.into(),
+                ..Default::default()
+            }),
+            started: None,
+            windows_endpoint_names: false,
+        }
+    }
+
+    /// Backend that plays the scenario in real time from now on
+    pub fn real_time(scenario: Scenario) -> Self {
+        Self {
+            started: Some(Instant::now()),
+            ..Self::new(scenario)
+        }
+    }
+
+    /// Name Bluetooth endpoints "Headphones (<paired name>)" like Windows does
+    ///
+    /// By default endpoints carry the paired device's name, which hides
+    /// code that confuses the two.
+    pub fn with_windows_endpoint_names(mut self) -> Self {
+        self.windows_endpoint_names = true;
+        self
+    }
+
+    /// Whether all steps have been applied
+    pub fn is_finished(&self) -> bool {
+        self.world.lock().unwrap().pending.is_empty()
+    }
+
+    /// Calls that changed state (`reconnect WH-1000XM4`, `mute 1000`, ...), in order
+    pub fn actions(&self) -> Vec<String> {
+        self.world.lock().unwrap().actions.clone()
+    }
+
+    /// Process id given to an app, if it appeared in the scenario
+    pub fn pid_of(&self, app: &str) -> Option<u32> {
+        self.world.lock().unwrap().pids.get(app).copied()
+    }
+
+    fn world(&self) -> std::sync::MutexGuard<'_, World> {
+        let mut world = self.world.lock().unwrap();
+        if let Some(started) = self.started {
+            world.advance_to(started.elapsed());
+        }
+        world
+    }
+}
+
+impl Timeline for SimulatedBackend {
+    /// Apply every step due at `elapsed` since the start of the scenario
+    fn advance_to(&self, elapsed: Duration) {
+        self.world.lock().unwrap().advance_to(elapsed);
+    }
+
+    fn duration(&self) -> Duration {
+        let world = self.world.lock().unwrap();
+        world.pending.back().map_or(world.elapsed, |step| step.at)
+    }
+}
+
+impl AudioBackend for SimulatedBackend {
+    fn poll(&self) -> Result<AudioPoll> {
+        let world = self.world();
+
+        let mut endpoints = vec![Endpoint {
+            sample_rate: Some(48000),
+            channels: Some(2),
+            ..Endpoint::new(AudioDevice {
+                id: "sim:speakers".to_string(),
+                name: SPEAKERS.to_string(),
+                is_bluetooth: false,
+            })
+        }];
+        for device in world.devices.iter().filter(|d| d.connected) {
+            let mono = device.mode == AudioMode::HandsFree;
+            endpoints.push(Endpoint {
+                sample_rate: Some(if mono { 16000 } else { 48000 }),
+                channels: Some(if mono { 1 } else { 2 }),
+                meter_channels: Some(if mono { 1 } else { 2 }),
+                ..Endpoint::new(AudioDevice {
+                    id: format!("sim:{}", device.address),
+                    name: if self.windows_endpoint_names {
+                        format!("Headphones ({})", device.name)
+                    } else {
+                        device.name.clone()
+                    },
+                    is_bluetooth: true,
+                })
+            });
+        }
+
+        let mic_apps = world
+            .captures
+            .iter()
+            .map(|stream| {
+                let mut app = MicUsingApp::new(stream.pid, stream.process_name(), stream.app.clone());
+                app.is_muted = stream.muted;
+                app.is_using_bluetooth_mic = world.on_bluetooth_mic(stream);
+                app
+            })
+            .collect();
+
+        Ok(AudioPoll { endpoints, mic_apps })
+    }
+
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp> {
+        let world = self.world();
+        if !world.devices.iter().any(|d| d.connected) {
+            return Vec::new();
+        }
+        world
+            .playback
+            .iter()
+            .map(|stream| HfpUsingApp::new(stream.pid, stream.process_name(), stream.app.clone()))
+            .collect()
+    }
+
+    fn set_app_muted(&self, process_id: u32, muted: bool) -> Result<()> {
+        let mut world = self.world();
+        let mut found = false;
+        for stream in world.captures.iter_mut().filter(|s| s.pid == process_id) {
+            stream.muted = muted;
+            found = true;
+        }
+        if !found {
+            return Err(AppError::AudioSessionError(format!(
+                "No capture session found for process {}",
+                process_id
+            )));
+        }
+        world
+            .actions
+            .push(format!("{} {}", if muted { "mute" } else { "unmute" }, process_id));
+        Ok(())
+    }
+
+    fn mute_all(&self) -> Result<()> {
+        let mut world = self.world();
+        for stream in &mut world.captures {
+            stream.muted = true;
+        }
+        world.actions.push("mute_all".to_string());
+        Ok(())
+    }
+}
+
+impl BluetoothBackend for SimulatedBackend {
+    fn list_paired_devices(&self) -> Result<Vec<PairedDevice>> {
+        Ok(self
+            .world()
+            .devices
+            .iter()
+            .map(|device| paired_headset(device.address, &device.name, device.connected, device.hfp_enabled))
+            .collect())
+    }
+
+    fn connect(&self, name: &str) -> Result<()> {
+        let mut world = self.world();
+        let device = world.named(name)?;
+        if device.connected {
+            return Ok(());
+        }
+        device.connected = true;
+        device.mode = AudioMode::Stereo;
+        let action = format!("connect {}", device.name);
+        world.actions.push(action);
+        Ok(())
+    }
+
+    fn reconnect(&self, name: &str) -> Result<()> {
+        let mut world = self.world();
+        let device = world.named(name)?;
+        let action = format!("reconnect {}", device.name);
+        if device.reconnect_fails {
+            let error = format!("Simulated reconnect of '{}' failed", device.name);
+            world.actions.push(action);
+            return Err(AppError::IoError(std::io::Error::new(std::io::ErrorKind::TimedOut, error)));
+        }
+        device.connected = true;
+        device.mode = AudioMode::Stereo;
+        world.actions.push(action);
+        info!("Simulated reconnect of '{}'", name);
+        Ok(())
+    }
+
+    fn is_hfp_enabled(&self, name: &str) -> Result<bool> {
+        Ok(self.world().named(name)?.hfp_enabled)
+    }
+
+    /// Turning HFP off drops a device in hands-free back to stereo
+    fn disable_hfp(&self, name: &str) -> Result<()> {
+        let mut world = self.world();
+        let device = world.named(name)?;
+        device.hfp_enabled = false;
+        device.mode = AudioMode::Stereo;
+        let action = format!("disable_hfp {}", device.name);
+        world.actions.push(action);
+        Ok(())
+    }
+
+    fn enable_hfp(&self, name: &str) -> Result<()> {
+        let mut world = self.world();
+        let device = world.named(name)?;
+        device.hfp_enabled = true;
+        let action = format!("enable_hfp {}", device.name);
+        world.actions.push(action);
+        Ok(())
+    }
+
+    fn battery_levels(&self) -> Result<HashMap<String, u8>> {
+        Ok(self
+            .world()
+            .devices
+            .iter()
+            .filter(|d| d.connected)
+            .filter_map(|d| Some((base_device_name(&d.name), d.battery?)))
+            .collect())
+    }
+}
@@ -0,0 +1,16 @@
+//! Simulated platform for end-to-end tests and demos
+//!
+//! `SimulatedBackend` implements both backend traits on top of a scripted
+//! `Scenario`, `ReplayBackend` on top of a recording of real polls.
+//! `ScenarioRunner` runs the audio monitor over either on a virtual clock so
+//! the whole poll → events → policy → actions path is deterministic.
+
+pub mod backend;
+pub mod replay;
+pub mod runner;
+pub mod scenario;
+
+pub use backend::SimulatedBackend;
+pub use replay::ReplayBackend;
+pub use runner::{ScenarioRunner, TimedEvent, Timeline};
+pub use scenario::{Action, Scenario, Step};
@@ -0,0 +1,234 @@
+//! Replay of recorded audio polls
+//!
+//! Feeds a recording made with `logging.record_polls` back through the
+//! monitor: each poll returns the latest record at or before the current
+//! scenario time, so detection and policy see what the user's machine saw.
+//! The recording only holds audio polls; the Bluetooth side is derived from
+//! it (every Bluetooth endpoint that ever appears is a paired headset,
+//! connected while it has an endpoint) and only logs actions.
+
+use super::backend::paired_headset;
+use super::runner::Timeline;
+use crate::audio::backend::{AudioBackend, AudioPoll};
+use crate::audio::recording::{PollRecord, Recording};
+use crate::audio::session::HfpUsingApp;
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::bluetooth::inventory::{BluetoothAddress, PairedDevice};
+use crate::bluetooth::names::find_best_match;
+use crate::error::{AppError, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    records: Vec<PollRecord>,
+    /// Index of the record in effect; `None` before the first one
+    current: Option<usize>,
+    /// Bluetooth endpoint names in order of first appearance
+    devices: Vec<String>,
+    hfp_disabled: HashSet<String>,
+    muted: HashSet<u32>,
+    actions: Vec<String>,
+}
+
+impl ReplayState {
+    fn current_poll(&self) -> Option<&AudioPoll> {
+        self.records[self.current?].poll.as_ref()
+    }
+
+    fn is_connected(&self, name: &str) -> bool {
+        self.current_poll().is_some_and(|poll| {
+            poll.endpoints
+                .iter()
+                .any(|e| e.device.is_bluetooth && e.device.name == name)
+        })
+    }
+
+    /// Device for a backend call, matched by name like the real backends
+    fn named(&self, name: &str) -> Result<String> {
+        find_best_match(name, &self.devices, |d| d.as_str())
+            .cloned()
+            .ok_or_else(|| AppError::ConfigError(format!("Bluetooth device '{}' not found", name)))
+    }
+}
+
+/// Audio and Bluetooth backend that plays back a poll recording
+pub struct ReplayBackend {
+    state: Mutex<ReplayState>,
+}
+
+impl ReplayBackend {
+    pub fn new(records: Vec<PollRecord>) -> Self {
+        let mut devices: Vec<String> = Vec::new();
+        let endpoints = records.iter().filter_map(|r| r.poll.as_ref()).flat_map(|p| &p.endpoints);
+        for endpoint in endpoints.filter(|e| e.device.is_bluetooth) {
+            if !devices.contains(&endpoint.device.name) {
+                devices.push(endpoint.device.name.clone());
+            }
+        }
+
+        Self {
+            state: Mutex::new(ReplayState {
+                records,
+                devices,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Replay a recording file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Ok(Self::new(Recording::read(path)?.records))
+    }
+
+    /// Whether the last record has been reached
+    pub fn is_finished(&self) -> bool {
+        let state = self.state();
+        state.current.is_some_and(|i| i + 1 >= state.records.len())
+    }
+
+    /// Calls that would have changed state (`reconnect WH-1000XM4`, `mute 1000`, ...), in order
+    pub fn actions(&self) -> Vec<String> {
+        self.state().actions.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, ReplayState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Timeline for ReplayBackend {
+    fn advance_to(&self, elapsed: Duration) {
+        let mut state = self.state();
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let next = state.current.map_or(0, |i| i + 1);
+        let due = state.records[next..]
+            .iter()
+            .take_while(|record| record.elapsed_ms <= elapsed_ms)
+            .count();
+        if due > 0 {
+            state.current = Some(next + due - 1);
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        let last = self.state().records.last().map_or(0, |record| record.elapsed_ms);
+        Duration::from_millis(last)
+    }
+}
+
+impl AudioBackend for ReplayBackend {
+    fn poll(&self) -> Result<AudioPoll> {
+        let state = self.state();
+        let Some(index) = state.current else {
+            return Ok(AudioPoll::default());
+        };
+        let record = &state.records[index];
+        if let Some(error) = &record.error {
+            return Err(AppError::AudioSessionError(format!("Recorded poll failed: {}", error)));
+        }
+
+        let mut poll = record.poll.clone().unwrap_or_default();
+        for app in &mut poll.mic_apps {
+            app.is_muted |= state.muted.contains(&app.process_id);
+        }
+        Ok(poll)
+    }
+
+    /// Not part of recordings
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp> {
+        Vec::new()
+    }
+
+    fn set_app_muted(&self, process_id: u32, muted: bool) -> Result<()> {
+        let mut state = self.state();
+        let recorded = state
+            .current_poll()
+            .is_some_and(|poll| poll.mic_apps.iter().any(|app| app.process_id == process_id));
+        if !recorded {
+            return Err(AppError::AudioSessionError(format!(
+                "No capture session found for process {}",
+                process_id
+            )));
+        }
+        if muted {
+            state.muted.insert(process_id);
+        } else {
+            state.muted.remove(&process_id);
+        }
+        state
+            .actions
+            .push(format!("{} {}", if muted { "mute" } else { "unmute" }, process_id));
+        Ok(())
+    }
+
+    fn mute_all(&self) -> Result<()> {
+        let mut state = self.state();
+        let pids: Vec<u32> = state
+            .current_poll()
+            .map(|poll| poll.mic_apps.iter().map(|app| app.process_id).collect())
+            .unwrap_or_default();
+        state.muted.extend(pids);
+        state.actions.push("mute_all".to_string());
+        Ok(())
+    }
+}
+
+impl BluetoothBackend for ReplayBackend {
+    fn list_paired_devices(&self) -> Result<Vec<PairedDevice>> {
+        let state = self.state();
+        Ok(state
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(n, name)| {
+                let address = BluetoothAddress(0x5A_0000_0000 + n as u64 + 1);
+                paired_headset(address, name, state.is_connected(name), !state.hfp_disabled.contains(name))
+            })
+            .collect())
+    }
+
+    fn connect(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let device = state.named(name)?;
+        if !state.is_connected(&device) {
+            state.actions.push(format!("connect {}", device));
+        }
+        Ok(())
+    }
+
+    fn reconnect(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let device = state.named(name)?;
+        state.actions.push(format!("reconnect {}", device));
+        Ok(())
+    }
+
+    fn is_hfp_enabled(&self, name: &str) -> Result<bool> {
+        let state = self.state();
+        Ok(!state.hfp_disabled.contains(&state.named(name)?))
+    }
+
+    fn disable_hfp(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let device = state.named(name)?;
+        state.actions.push(format!("disable_hfp {}", device));
+        state.hfp_disabled.insert(device);
+        Ok(())
+    }
+
+    fn enable_hfp(&self, name: &str) -> Result<()> {
+        let mut state = self.state();
+        let device = state.named(name)?;
+        state.actions.push(format!("enable_hfp {}", device));
+        state.hfp_disabled.remove(&device);
+        Ok(())
+    }
+
+    /// Not part of recordings
+    fn battery_levels(&self) -> Result<HashMap<String, u8>> {
+        Ok(HashMap::new())
+    }
+}
@@ -0,0 +1,168 @@
+//! Deterministic scenario runner
+//!
+//! Drives a `MonitorLoop` over a `SimulatedBackend` (or a `ReplayBackend`)
+//! on a virtual clock: one poll every `POLL_INTERVAL` of scenario time, slow
+//! Bluetooth calls run inline, and every event is recorded with the time it
+//! was emitted. Events the queue had to drop are counted in
+//! `dropped_events`, for callers to check.
+
+use super::backend::SimulatedBackend;
+use super::scenario::Scenario;
+use crate::audio::backend::AudioBackend;
+use crate::audio::bus::{BusMessage, EventBus, Subscription};
+use crate::audio::device::AudioMode;
+use crate::audio::monitor::{MonitorCommand, MonitorEvent, MonitorLoop, MonitorState, TaskMode, POLL_INTERVAL};
+use crate::audio::watchdog::WatchdogRecord;
+use crate::bluetooth::backend::BluetoothBackend;
+use crate::platform::Backends;
+use log::warn;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long to keep polling after the last step when running to the end
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Events are collected after every poll, so this is never reached in practice
+const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// A backend whose world changes over scenario time
+pub trait Timeline {
+    /// Bring the world to `elapsed` since the start; time never moves back
+    fn advance_to(&self, elapsed: Duration);
+
+    /// Time of the last scheduled change
+    fn duration(&self) -> Duration;
+}
+
+/// A monitor event and the scenario time it was emitted at
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub at: Duration,
+    pub event: MonitorEvent,
+}
+
+/// Runs a scenario through the audio monitor
+pub struct ScenarioRunner<B = SimulatedBackend> {
+    backend: Arc<B>,
+    monitor: MonitorLoop,
+    events: Subscription,
+    state: Arc<Mutex<MonitorState>>,
+    /// Virtual start of the scenario
+    epoch: Instant,
+    /// Scenario time of the next poll
+    next_poll: Duration,
+    timed_events: Vec<TimedEvent>,
+    /// Events lost because the queue was full
+    dropped_events: u64,
+    end: Duration,
+}
+
+impl ScenarioRunner {
+    pub fn new(scenario: Scenario) -> Self {
+        Self::with_backend(Arc::new(SimulatedBackend::new(scenario)))
+    }
+}
+
+impl<B: Timeline + AudioBackend + BluetoothBackend + 'static> ScenarioRunner<B> {
+    /// Run the monitor over any backend with a timeline, e.g. a replay
+    pub fn with_backend(backend: Arc<B>) -> Self {
+        let end = backend.duration() + SETTLE_TIME;
+        let bus = EventBus::new();
+        let events = bus.subscribe_with_capacity(None, EVENT_QUEUE_CAPACITY);
+        let state = Arc::new(Mutex::new(MonitorState::default()));
+        let monitor = MonitorLoop::new(
+            Backends::new(backend.clone(), backend.clone()),
+            bus,
+            Arc::clone(&state),
+            TaskMode::Inline,
+        );
+
+        Self {
+            backend,
+            monitor,
+            events,
+            state,
+            epoch: Instant::now(),
+            next_poll: Duration::ZERO,
+            timed_events: Vec::new(),
+            dropped_events: 0,
+            end,
+        }
+    }
+
+    /// The simulated world, e.g. to check which actions were taken
+    pub fn backend(&self) -> &Arc<B> {
+        &self.backend
+    }
+
+    /// Apply a monitor command before the next poll
+    pub fn send(&mut self, command: MonitorCommand) {
+        self.monitor.handle_command(command);
+        self.collect(self.next_poll);
+    }
+
+    /// Poll until scenario time `until` (inclusive)
+    pub fn run_until(&mut self, until: Duration) {
+        while self.next_poll <= until {
+            let now = self.next_poll;
+            self.backend.advance_to(now);
+            self.monitor.poll(self.epoch + now);
+            self.collect(now);
+            self.next_poll += POLL_INTERVAL;
+        }
+    }
+
+    /// Poll through the last step and a short settling time after it
+    pub fn run_to_end(&mut self) {
+        self.run_until(self.end);
+    }
+
+    fn collect(&mut self, at: Duration) {
+        for message in self.events.try_iter() {
+            match message {
+                BusMessage::Event(event) => self.timed_events.push(TimedEvent { at, event }),
+                BusMessage::Lagged(missed) => {
+                    warn!("Scenario runner dropped {} events", missed);
+                    self.dropped_events += missed;
+                }
+            }
+        }
+    }
+
+    /// Every event emitted so far
+    pub fn events(&self) -> &[TimedEvent] {
+        &self.timed_events
+    }
+
+    /// Events lost because they were not collected in time; `events` misses these
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events
+    }
+
+    /// Mode changes as `(time, old, new)`
+    pub fn mode_changes(&self) -> Vec<(Duration, AudioMode, AudioMode)> {
+        self.timed_events
+            .iter()
+            .filter_map(|timed| match timed.event {
+                MonitorEvent::ModeChanged { old_mode, new_mode } => Some((timed.at, old_mode, new_mode)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Watchdog records with the time they were emitted
+    pub fn watchdog_records(&self) -> Vec<(Duration, WatchdogRecord)> {
+        self.timed_events
+            .iter()
+            .filter_map(|timed| match &timed.event {
+                MonitorEvent::Watchdog(record) => Some((timed.at, record.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Shared state after the latest poll
+    pub fn state(&self) -> MonitorState {
+        self.state.lock().unwrap().clone()
+    }
+}
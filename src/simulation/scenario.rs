@@ -0,0 +1,247 @@
+//! Scenario language for the simulated backend
+//!
+//! A scenario is a list of timed steps separated by `;` or newlines, with
+//! `#` starting a comment:
+//!
+//! ```text
+//! t=0 headset connects stereo; t=2s Teams opens capture; t=3s endpoint becomes mono
+//! ```
+//!
+//! Each step is `t=<time> <subject> <verb> ...`. Times are a number with an
+//! optional `ms`, `s` or `m` unit (seconds if omitted), and units can be
+//! combined (`1m30s`). Steps:
+//!
+//! | Step | Effect |
+//! |------|--------|
+//! | `<device> connects [stereo\|hands-free]` | Device connects (stereo by default) |
+//! | `<device> disconnects` | Device disconnects |
+//! | `<device> becomes mono\|stereo` | Endpoint format changes; `endpoint` means the first connected device |
+//! | `<device> battery <n>%` | Device reports a battery level |
+//! | `<device> re-enables hands-free` | The OS turns HFP back on (e.g. after re-pairing) |
+//! | `<device> reconnect fails\|works` | Later reconnects of the device fail or succeed |
+//! | `<app> opens capture [on <device>]` | App starts recording, from the Bluetooth mic unless another device is named |
+//! | `<app> closes capture` | App stops recording |
+//! | `<app> opens playback` / `<app> closes playback` | App plays audio to the Bluetooth output |
+
+use crate::audio::device::AudioMode;
+use crate::error::{AppError, Result};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A parsed scenario, steps in time order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scenario {
+    pub steps: Vec<Step>,
+}
+
+/// One timed step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Time since the start of the scenario
+    pub at: Duration,
+    pub action: Action,
+}
+
+/// What happens at a step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Connect { device: String, mode: AudioMode },
+    Disconnect { device: String },
+    SetMode { device: String, mode: AudioMode },
+    Battery { device: String, level: u8 },
+    ReenableHandsFree { device: String },
+    ReconnectFails { device: String, fails: bool },
+    OpenCapture { app: String, device: Option<String> },
+    CloseCapture { app: String },
+    OpenPlayback { app: String },
+    ClosePlayback { app: String },
+}
+
+impl Scenario {
+    /// Read a scenario file
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::ConfigError(format!("Could not read scenario {:?}: {}", path, e)))?;
+        text.parse()
+    }
+
+    /// Time of the last step
+    pub fn duration(&self) -> Duration {
+        self.steps.last().map(|step| step.at).unwrap_or_default()
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = AppError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut steps = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            for step in line.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                steps.push(parse_step(step).map_err(|e| {
+                    AppError::ConfigError(format!("Scenario line {}: '{}': {}", number + 1, step, e))
+                })?);
+            }
+        }
+        // Stable, so steps at the same time keep their written order
+        steps.sort_by_key(|step: &Step| step.at);
+        Ok(Self { steps })
+    }
+}
+
+fn parse_step(step: &str) -> std::result::Result<Step, String> {
+    let (time, rest) = step
+        .strip_prefix("t=")
+        .and_then(|s| s.split_once(char::is_whitespace))
+        .ok_or("expected 't=<time> <step>'")?;
+    let at = parse_time(time)?;
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    Ok(Step { at, action: parse_action(&words)? })
+}
+
+/// Parse `500ms`, `2s`, `1.5s`, `1m30s` or a bare number of seconds
+pub fn parse_time(text: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("invalid time '{}'", text);
+    if let Ok(seconds) = text.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).ok_or_else(invalid)?;
+        let (number, tail) = rest.split_at(split);
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        let unit_len = tail.find(|c: char| c.is_ascii_digit()).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let seconds = match unit {
+            "ms" => value / 1000.0,
+            "s" => value,
+            "m" => value * 60.0,
+            _ => return Err(invalid()),
+        };
+        total += Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?;
+        rest = tail;
+    }
+    Ok(total)
+}
+
+fn parse_action(words: &[&str]) -> std::result::Result<Action, String> {
+    const VERBS: &[&str] = &["connects", "disconnects", "becomes", "battery", "re-enables", "reconnect", "opens", "closes"];
+    let verb_at = words
+        .iter()
+        .position(|word| VERBS.contains(word))
+        .ok_or("no known verb (connects, disconnects, becomes, battery, re-enables, reconnect, opens, closes)")?;
+    if verb_at == 0 {
+        return Err("missing device or app name".to_string());
+    }
+    let subject = words[..verb_at].join(" ");
+    let args = &words[verb_at + 1..];
+
+    let action = match (words[verb_at], args) {
+        ("connects", []) | ("connects", ["stereo"]) => Action::Connect { device: subject, mode: AudioMode::Stereo },
+        ("connects", ["hands-free"]) | ("connects", ["mono"]) => {
+            Action::Connect { device: subject, mode: AudioMode::HandsFree }
+        }
+        ("disconnects", []) => Action::Disconnect { device: subject },
+        ("becomes", ["mono"]) | ("becomes", ["hands-free"]) => {
+            Action::SetMode { device: subject, mode: AudioMode::HandsFree }
+        }
+        ("becomes", ["stereo"]) => Action::SetMode { device: subject, mode: AudioMode::Stereo },
+        ("battery", [level]) => {
+            let level = level
+                .trim_end_matches('%')
+                .parse::<u8>()
+                .ok()
+                .filter(|level| *level <= 100)
+                .ok_or_else(|| format!("invalid battery level '{}'", level))?;
+            Action::Battery { device: subject, level }
+        }
+        ("re-enables", ["hands-free"]) => Action::ReenableHandsFree { device: subject },
+        ("reconnect", ["fails"]) => Action::ReconnectFails { device: subject, fails: true },
+        ("reconnect", ["works"]) => Action::ReconnectFails { device: subject, fails: false },
+        ("opens", ["capture"]) => Action::OpenCapture { app: subject, device: None },
+        ("opens", ["capture", "on", device @ ..]) if !device.is_empty() => Action::OpenCapture {
+            app: subject,
+            device: Some(device.join(" ")),
+        },
+        ("closes", ["capture"]) => Action::CloseCapture { app: subject },
+        ("opens", ["playback"]) => Action::OpenPlayback { app: subject },
+        ("closes", ["playback"]) => Action::ClosePlayback { app: subject },
+        (verb, args) => return Err(format!("cannot parse '{} {}'", verb, args.join(" "))),
+    };
+    Ok(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_time("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_time("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_time("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_time("1m30s"), Ok(Duration::from_secs(90)));
+        assert!(parse_time("2h").is_err());
+        assert!(parse_time("s").is_err());
+    }
+
+    #[test]
+    fn test_parse_example() {
+        let scenario: Scenario = "t=0 headset connects stereo; t=2s Teams opens capture; t=3s endpoint becomes mono"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            scenario.steps,
+            vec![
+                Step {
+                    at: Duration::ZERO,
+                    action: Action::Connect { device: "headset".to_string(), mode: AudioMode::Stereo },
+                },
+                Step {
+                    at: Duration::from_secs(2),
+                    action: Action::OpenCapture { app: "Teams".to_string(), device: None },
+                },
+                Step {
+                    at: Duration::from_secs(3),
+                    action: Action::SetMode { device: "endpoint".to_string(), mode: AudioMode::HandsFree },
+                },
+            ]
+        );
+        assert_eq!(scenario.duration(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_parse_multi_word_names_and_comments() {
+        let scenario: Scenario = "# call on the laptop mic\n\
+                                  t=1s Microsoft Teams opens capture on Built-in Microphone\n\
+                                  t=0 Sony WH-1000XM4 battery 15%  # low"
+            .parse()
+            .unwrap();
+        assert_eq!(scenario.steps.len(), 2);
+        assert_eq!(
+            scenario.steps[0].action,
+            Action::Battery { device: "Sony WH-1000XM4".to_string(), level: 15 }
+        );
+        assert_eq!(
+            scenario.steps[1].action,
+            Action::OpenCapture {
+                app: "Microsoft Teams".to_string(),
+                device: Some("Built-in Microphone".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let error = "t=0 headset connects\nt=1s headset explodes".parse::<Scenario>().unwrap_err();
+        assert!(error.to_string().contains("line 2"), "{}", error);
+        assert!("headset connects".parse::<Scenario>().is_err());
+        assert!("t=0 connects".parse::<Scenario>().is_err());
+        assert!("t=0 headset battery 120%".parse::<Scenario>().is_err());
+    }
+}
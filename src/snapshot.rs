@@ -0,0 +1,119 @@
+//! Versioned, serializable snapshot of the monitor state
+//!
+//! `StateSnapshot` is the stable JSON form of `MonitorState` plus what the
+//! core knows on top of it (forced-stereo devices). Timestamps are wall-clock milliseconds since the Unix epoch.
+//!
+//! Adding an optional field keeps the version; removing or renaming a field
+//! or changing its meaning bumps `SNAPSHOT_VERSION`. The JSON schema is
+//! pinned by `tests/fixtures/snapshots/state_snapshot.schema.json`.
+
+use crate::audio::device::AudioMode;
+use crate::audio::monitor::MonitorState;
+use crate::audio::session::{HfpUsingApp, MicUsingApp};
+use crate::bluetooth::names::paired_name_for_endpoint;
+use crate::error::{AppError, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version written into every snapshot
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The monitor state at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StateSnapshot {
+    /// Schema version of the writer
+    pub version: u32,
+    /// When the snapshot was taken
+    pub taken_at_ms: u64,
+    /// When the monitor last polled the audio stack; `None` before the first poll
+    pub updated_at_ms: Option<u64>,
+    /// Mode of the active Bluetooth output
+    pub mode: AudioMode,
+    /// Connected Bluetooth audio devices
+    pub devices: Vec<DeviceSnapshot>,
+    /// Apps with an active capture session on any input device
+    pub mic_apps: Vec<MicUsingApp>,
+    /// Apps playing audio to a Bluetooth output
+    pub hfp_apps: Vec<HfpUsingApp>,
+}
+
+/// One connected Bluetooth audio device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceSnapshot {
+    /// Audio endpoint id
+    pub id: String,
+    pub name: String,
+    pub mode: AudioMode,
+    /// Mix format sample rate, if it could be read
+    pub sample_rate: Option<u32>,
+    /// Mix format channel count, if it could be read
+    pub channels: Option<u16>,
+    /// Battery level in percent, if the headset reports it
+    pub battery_level: Option<u8>,
+    /// Hands-free is disabled for the device (Force Stereo)
+    pub forced_stereo: bool,
+    /// In hands-free mode while an app records from a Bluetooth mic
+    pub mic_in_use: bool,
+}
+
+impl StateSnapshot {
+    /// Snapshot of the monitor state, taken at `taken_at`
+    pub fn new(state: &MonitorState, taken_at: SystemTime) -> Self {
+        let bluetooth_mic = state.mic_using_apps.iter().any(|app| app.is_using_bluetooth_mic);
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at_ms: unix_millis(taken_at),
+            updated_at_ms: state.updated_at.map(unix_millis),
+            mode: state.current_mode,
+            devices: state
+                .bluetooth_devices
+                .iter()
+                .map(|device| DeviceSnapshot {
+                    id: device.device.id.clone(),
+                    name: device.device.name.clone(),
+                    mode: device.current_mode,
+                    sample_rate: device.sample_rate,
+                    channels: device.channels,
+                    battery_level: device.battery_level,
+                    forced_stereo: false,
+                    mic_in_use: bluetooth_mic && device.current_mode == AudioMode::HandsFree,
+                })
+                .collect(),
+            mic_apps: state.mic_using_apps.clone(),
+            hfp_apps: state.hfp_apps.clone(),
+        }
+    }
+
+    /// Mark the devices forced to stereo
+    ///
+    /// The set holds paired device names, which endpoint names wrap.
+    pub fn with_forced_stereo(mut self, forced_stereo_devices: &HashSet<String>) -> Self {
+        for device in &mut self.devices {
+            device.forced_stereo =
+                paired_name_for_endpoint(&device.name, forced_stereo_devices.iter().map(String::as_str)).is_some();
+        }
+        self
+    }
+
+    /// Parse a snapshot, rejecting ones written by a newer, incompatible version
+    pub fn from_json(json: &str) -> Result<Self> {
+        let snapshot: Self = serde_json::from_str(json)
+            .map_err(|e| AppError::ConfigError(format!("Invalid state snapshot: {}", e)))?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(AppError::ConfigError(format!(
+                "Unsupported state snapshot version {} (expected {} or older)",
+                snapshot.version, SNAPSHOT_VERSION
+            )));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Milliseconds since the Unix epoch (0 for earlier times)
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
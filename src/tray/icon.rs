@@ -1,18 +1,26 @@
 //! System tray icon management
 
-use crate::audio::device::AudioMode;
+use crate::audio::device::{AudioMode, BluetoothAudioDevice};
 use crate::error::{AppError, Result};
 use image::GenericImageView;
 use log::{debug, info, warn};
 use muda::Menu;
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 
+/// First tooltip line when running on simulated devices
+const SIMULATION_MARKER: &str = "SIMULATION";
+
+/// Tooltip line while the audio monitor is down
+const STALE_MARKER: &str = "Monitor not responding, mode may be out of date";
+
 /// Icon states for different audio modes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IconState {
     Stereo,
     HandsFree,
     Unknown,
+    /// The audio monitor stopped; the last known mode may be out of date
+    Stale,
 }
 
 impl From<AudioMode> for IconState {
@@ -29,6 +37,12 @@ impl From<AudioMode> for IconState {
 pub struct TrayIconManager {
     tray_icon: TrayIcon,
     current_state: IconState,
+    /// Show the stale icon and tooltip instead of the current mode
+    stale: bool,
+    /// Battery summary appended to the tooltip ("WH-1000XM4: 80%")
+    battery_summary: Option<String>,
+    /// Prefix the tooltip with the simulation marker
+    simulation: bool,
 }
 
 impl TrayIconManager {
@@ -48,9 +62,18 @@ impl TrayIconManager {
         Ok(Self {
             tray_icon,
             current_state: IconState::Unknown,
+            stale: false,
+            battery_summary: None,
+            simulation: false,
         })
     }
 
+    /// Mark the tooltip as a simulation
+    pub fn mark_simulation(&mut self) -> Result<()> {
+        self.simulation = true;
+        self.refresh_tooltip()
+    }
+
     /// Load the appropriate icon for the given state
     fn load_icon(state: IconState) -> Result<Icon> {
         // Try to load from ICO file first
@@ -58,6 +81,7 @@ impl TrayIconManager {
             IconState::Stereo => "resources/tray_stereo.ico",
             IconState::HandsFree => "resources/tray_handsfree.ico",
             IconState::Unknown => "resources/tray_unknown.ico",
+            IconState::Stale => return Self::generate_fallback_icon(state),
         };
 
         // Try loading from file, fall back to generated icon
@@ -105,6 +129,7 @@ impl TrayIconManager {
             IconState::Stereo => (0, 200, 0),      // Green for stereo
             IconState::HandsFree => (255, 165, 0), // Orange for hands-free
             IconState::Unknown => (128, 128, 128), // Gray for unknown
+            IconState::Stale => (200, 0, 0),       // Red for a stopped monitor
         };
 
         // Create a simple 32x32 RGBA icon
@@ -142,28 +167,72 @@ impl TrayIconManager {
         let new_state = IconState::from(mode);
 
         if new_state != self.current_state {
-            let icon = Self::load_icon(new_state)?;
-            self.tray_icon
-                .set_icon(Some(icon))
-                .map_err(|e| AppError::TrayIconFailed(e.to_string()))?;
+            self.current_state = new_state;
+            if !self.stale {
+                self.apply_icon()?;
+            }
+            self.refresh_tooltip()?;
+            debug!("Tray icon updated to {:?}", new_state);
+        }
 
-            let tooltip = match new_state {
-                IconState::Stereo => "Bluetooth Audio: Stereo Mode",
-                IconState::HandsFree => "Bluetooth Audio: Hands-Free Mode",
-                IconState::Unknown => "Bluetooth Audio Mode Manager",
-            };
+        Ok(())
+    }
 
-            self.tray_icon
-                .set_tooltip(Some(tooltip))
-                .map_err(|e| AppError::TrayIconFailed(e.to_string()))?;
+    /// Mark the shown mode as stale while the audio monitor is down
+    pub fn set_stale(&mut self, stale: bool) -> Result<()> {
+        if stale != self.stale {
+            self.stale = stale;
+            self.apply_icon()?;
+            self.refresh_tooltip()?;
+        }
 
-            self.current_state = new_state;
-            debug!("Tray icon updated to {:?}", new_state);
+        Ok(())
+    }
+
+    /// Show the icon for the current state, or the stale icon
+    fn apply_icon(&self) -> Result<()> {
+        let icon = Self::load_icon(if self.stale { IconState::Stale } else { self.current_state })?;
+        self.tray_icon
+            .set_icon(Some(icon))
+            .map_err(|e| AppError::TrayIconFailed(e.to_string()))
+    }
+
+    /// Show battery levels of the connected devices in the tooltip
+    pub fn update_battery(&mut self, devices: &[BluetoothAudioDevice]) -> Result<()> {
+        let summary = battery_summary(devices);
+
+        if summary != self.battery_summary {
+            self.battery_summary = summary;
+            self.refresh_tooltip()?;
         }
 
         Ok(())
     }
 
+    /// Apply the tooltip for the current state and battery summary
+    fn refresh_tooltip(&self) -> Result<()> {
+        let base = match self.current_state {
+            IconState::Stereo => "Bluetooth Audio: Stereo Mode",
+            IconState::HandsFree => "Bluetooth Audio: Hands-Free Mode",
+            IconState::Unknown | IconState::Stale => "Bluetooth Audio Mode Manager",
+        };
+
+        let mut tooltip = match &self.battery_summary {
+            Some(summary) => format!("{}\n{}", base, summary),
+            None => base.to_string(),
+        };
+        if self.stale {
+            tooltip = format!("{}\n{}", STALE_MARKER, tooltip);
+        }
+        if self.simulation {
+            tooltip = format!("{}\n{}", SIMULATION_MARKER, tooltip);
+        }
+
+        self.tray_icon
+            .set_tooltip(Some(tooltip))
+            .map_err(|e| AppError::TrayIconFailed(e.to_string()))
+    }
+
     /// Update the context menu
     pub fn update_menu(&mut self, menu: Menu) -> Result<()> {
         self.tray_icon.set_menu(Some(Box::new(menu)));
@@ -175,3 +244,13 @@ impl TrayIconManager {
         self.current_state
     }
 }
+
+/// Build the battery line of the tooltip, or `None` if no device reports it
+fn battery_summary(devices: &[BluetoothAudioDevice]) -> Option<String> {
+    let parts: Vec<String> = devices
+        .iter()
+        .filter_map(|d| d.battery_level.map(|level| format!("{}: {}%", d.device.name, level)))
+        .collect();
+
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
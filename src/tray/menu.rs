@@ -1,49 +1,21 @@
 //! Context menu building and event handling
 
-use crate::audio::device::{AudioMode, BluetoothAudioDevice};
-use crate::audio::session::HfpUsingApp;
 use crate::error::Result;
+use crate::tray::model::{
+    MenuContent, MenuEvent, MENU_ID_ABOUT, MENU_ID_CHECK_UPDATES, MENU_ID_EXIT,
+    MENU_ID_MODE_DISPLAY, MENU_ID_SETTINGS, MENU_ID_SIMULATION, MENU_PREFIX_ALLOW_HFP, MENU_PREFIX_CONNECT,
+    MENU_PREFIX_DEVICE, MENU_PREFIX_FORCE_STEREO, MENU_PREFIX_RECONNECT, MENU_PREFIX_TERMINATE_APP,
+};
 use log::info;
 use muda::{Menu, MenuEvent as MudaMenuEvent, MenuItem, PredefinedMenuItem, Submenu};
-use std::collections::{HashMap, HashSet};
-
-/// Menu item identifiers
-pub const MENU_ID_MODE_DISPLAY: &str = "mode_display";
-pub const MENU_ID_SETTINGS: &str = "settings";
-pub const MENU_ID_CHECK_UPDATES: &str = "check_updates";
-pub const MENU_ID_ABOUT: &str = "about";
-pub const MENU_ID_EXIT: &str = "exit";
-pub const MENU_PREFIX_TERMINATE_APP: &str = "terminate_app_";
-pub const MENU_PREFIX_DEVICE: &str = "device_";
-pub const MENU_PREFIX_FORCE_STEREO: &str = "force_stereo_";
-pub const MENU_PREFIX_ALLOW_HFP: &str = "allow_hfp_";
-pub const MENU_PREFIX_RECONNECT: &str = "reconnect_";
-
-/// Events from menu interactions
-#[derive(Debug, Clone)]
-pub enum MenuEvent {
-    /// Terminate a specific app
-    TerminateApp(u32),
-    /// Force stereo mode by disabling HFP
-    ForceStereo(String),
-    /// Allow hands-free mode by enabling HFP
-    AllowHandsFree(String),
-    /// Reconnect a Bluetooth device
-    ReconnectDevice(String),
-    /// Open settings window
-    OpenSettings,
-    /// Check for updates
-    CheckUpdates,
-    /// Show about dialog
-    ShowAbout,
-    /// Exit the application
-    Exit,
-}
+use std::collections::HashMap;
 
 /// Builds and manages the context menu
 pub struct MenuBuilder {
     /// Map of menu item IDs to their purposes
     item_map: HashMap<String, MenuItemPurpose>,
+    /// Show the simulation marker at the top
+    simulation: bool,
 }
 
 /// Internal tracking of menu item purposes for event handling
@@ -54,6 +26,7 @@ enum MenuItemPurpose {
     ForceStereo(String),
     AllowHandsFree(String),
     ReconnectDevice(String),
+    ConnectDevice(String),
     Device(String),
     Static(String),
 }
@@ -63,42 +36,59 @@ impl MenuBuilder {
     pub fn new() -> Self {
         Self {
             item_map: HashMap::new(),
+            simulation: false,
+        }
+    }
+
+    /// Builder for a tray running on simulated devices
+    pub fn for_simulation() -> Self {
+        Self {
+            simulation: true,
+            ..Self::new()
         }
     }
 
-    /// Build the context menu with current state
-    ///
-    /// # Arguments
-    /// * `mode` - Current audio mode
-    /// * `hfp_apps` - Apps outputting to Bluetooth (may have triggered HFP)
-    /// * `devices` - Bluetooth audio devices
-    /// * `forced_stereo_devices` - Set of device names that have been forced to stereo mode
-    pub fn build(
-        &mut self,
-        mode: AudioMode,
-        hfp_apps: &[HfpUsingApp],
-        devices: &[BluetoothAudioDevice],
-        forced_stereo_devices: &HashSet<String>,
-    ) -> Result<Menu> {
+    /// Build the context menu showing `content`
+    pub fn build(&mut self, content: &MenuContent) -> Result<Menu> {
         self.item_map.clear();
         let menu = Menu::new();
 
+        // Simulation marker (disabled), so screenshots can't be mistaken for real devices
+        if self.simulation {
+            let marker = MenuItem::with_id(MENU_ID_SIMULATION, &rust_i18n::t!("menu_simulation"), false, None);
+            menu.append(&marker)?;
+            menu.append(&PredefinedMenuItem::separator())?;
+        }
+
         // Current mode display (disabled)
-        let mode_text = rust_i18n::t!("menu_mode", mode = mode.display_localized());
+        let mode_text = rust_i18n::t!("menu_mode", mode = content.mode.display_localized());
         let mode_item = MenuItem::with_id(MENU_ID_MODE_DISPLAY, &mode_text, false, None);
         menu.append(&mode_item)?;
 
         // Bluetooth devices (shown directly in main menu)
-        if !devices.is_empty() {
+        if !content.devices.is_empty() {
             menu.append(&PredefinedMenuItem::separator())?;
 
-            for device in devices {
+            for device in &content.devices {
                 // Create submenu for each device directly in main menu
                 let device_text = format!("{} ({})", device.device.name, device.current_mode.display_localized());
                 let device_submenu = Submenu::new(&device_text, true);
 
+                // Battery level (disabled, informational)
+                if let Some(level) = device.battery_level {
+                    let battery_text = rust_i18n::t!("menu_battery", level = level);
+                    let battery_item = MenuItem::with_id(
+                        &format!("{}battery_{}", MENU_PREFIX_DEVICE, &device.device.name),
+                        &battery_text,
+                        false,
+                        None,
+                    );
+                    device_submenu.append(&battery_item)?;
+                    device_submenu.append(&PredefinedMenuItem::separator())?;
+                }
+
                 // Check if this device has been forced to stereo
-                let is_forced_stereo = forced_stereo_devices.contains(&device.device.name);
+                let is_forced_stereo = content.is_forced_stereo(&device.device.name);
 
                 // Add Force Stereo option (enabled when HFP is allowed)
                 let force_stereo_id = format!("{}{}", MENU_PREFIX_FORCE_STEREO, &device.device.name);
@@ -133,16 +123,36 @@ impl MenuBuilder {
             }
         }
 
+        // Paired audio devices that are not connected
+        if !content.disconnected.is_empty() {
+            menu.append(&PredefinedMenuItem::separator())?;
+
+            for device_name in &content.disconnected {
+                let device_text = rust_i18n::t!("menu_device_disconnected", device = device_name);
+                let device_submenu = Submenu::new(&device_text, true);
+
+                let connect_id = format!("{}{}", MENU_PREFIX_CONNECT, device_name);
+                let connect_item = MenuItem::with_id(&connect_id, &rust_i18n::t!("menu_connect"), true, None);
+                device_submenu.append(&connect_item)?;
+                self.item_map.insert(
+                    connect_id,
+                    MenuItemPurpose::ConnectDevice(device_name.clone()),
+                );
+
+                menu.append(&device_submenu)?;
+            }
+        }
+
         // Apps using Bluetooth audio (shown regardless of mode when apps are detected)
-        if !hfp_apps.is_empty() {
+        if !content.hfp_apps.is_empty() {
             menu.append(&PredefinedMenuItem::separator())?;
 
             // Show header with count
-            let header_text = rust_i18n::t!("menu_apps_using_hfp", count = hfp_apps.len());
+            let header_text = rust_i18n::t!("menu_apps_using_hfp", count = content.hfp_apps.len());
             let header_item = MenuItem::with_id("apps_header", &header_text, false, None);
             menu.append(&header_item)?;
 
-            for app in hfp_apps {
+            for app in &content.hfp_apps {
                 // Create submenu for each app with terminate option
                 let app_submenu = Submenu::new(&app.display_name, true);
 
@@ -214,6 +224,9 @@ impl MenuBuilder {
                         MenuItemPurpose::ReconnectDevice(name) => {
                             Some(MenuEvent::ReconnectDevice(name.clone()))
                         }
+                        MenuItemPurpose::ConnectDevice(name) => {
+                            Some(MenuEvent::ConnectDevice(name.clone()))
+                        }
                         _ => None,
                     }
                 } else {
@@ -1,7 +1,16 @@
 //! System tray integration module
+//!
+//! Menu events and identifiers (`model`) are portable; the icon and the muda
+//! menu are Windows-only.
 
+#[cfg(windows)]
 pub mod icon;
+#[cfg(windows)]
 pub mod menu;
+pub mod model;
 
+#[cfg(windows)]
 pub use icon::TrayIconManager;
-pub use menu::{MenuBuilder, MenuEvent};
+#[cfg(windows)]
+pub use menu::MenuBuilder;
+pub use model::{MenuContent, MenuEvent};
@@ -0,0 +1,200 @@
+//! Menu identifiers and events, independent of the menu toolkit
+
+use crate::audio::device::{AudioMode, BluetoothAudioDevice};
+use crate::audio::monitor::MonitorState;
+use crate::audio::session::HfpUsingApp;
+use crate::bluetooth::inventory::PairedDevice;
+use crate::bluetooth::names::paired_name_for_endpoint;
+use std::collections::{BTreeSet, HashSet};
+
+/// Menu item identifiers
+pub const MENU_ID_MODE_DISPLAY: &str = "mode_display";
+pub const MENU_ID_SIMULATION: &str = "simulation";
+pub const MENU_ID_SETTINGS: &str = "settings";
+pub const MENU_ID_CHECK_UPDATES: &str = "check_updates";
+pub const MENU_ID_ABOUT: &str = "about";
+pub const MENU_ID_EXIT: &str = "exit";
+pub const MENU_PREFIX_TERMINATE_APP: &str = "terminate_app_";
+pub const MENU_PREFIX_DEVICE: &str = "device_";
+pub const MENU_PREFIX_FORCE_STEREO: &str = "force_stereo_";
+pub const MENU_PREFIX_ALLOW_HFP: &str = "allow_hfp_";
+pub const MENU_PREFIX_RECONNECT: &str = "reconnect_";
+pub const MENU_PREFIX_CONNECT: &str = "connect_";
+
+/// Events from menu interactions
+#[derive(Debug, Clone)]
+pub enum MenuEvent {
+    /// Terminate a specific app
+    TerminateApp(u32),
+    /// Force stereo mode by disabling HFP
+    ForceStereo(String),
+    /// Allow hands-free mode by enabling HFP
+    AllowHandsFree(String),
+    /// Reconnect a Bluetooth device
+    ReconnectDevice(String),
+    /// Connect a paired but disconnected Bluetooth device
+    ConnectDevice(String),
+    /// Open settings window
+    OpenSettings,
+    /// Check for updates
+    CheckUpdates,
+    /// Show about dialog
+    ShowAbout,
+    /// Exit the application
+    Exit,
+}
+
+/// Paired audio devices that are disconnected and not already shown as connected
+///
+/// The audio endpoint list is authoritative for connected devices; the
+/// inventory can lag behind it by up to one refresh. Endpoint names wrap
+/// the paired name ("Headphones (WH-1000XM4)").
+pub fn disconnected_audio_devices<'a>(
+    paired_devices: &'a [PairedDevice],
+    devices: &[BluetoothAudioDevice],
+) -> Vec<&'a PairedDevice> {
+    let mut disconnected: Vec<&PairedDevice> = paired_devices
+        .iter()
+        .filter(|p| p.is_disconnected_audio())
+        .filter(|p| {
+            !devices
+                .iter()
+                .any(|d| paired_name_for_endpoint(&d.device.name, std::iter::once(p.name.as_str())).is_some())
+        })
+        .collect();
+    disconnected.sort_by(|a, b| a.name.cmp(&b.name));
+    disconnected
+}
+
+/// Everything the context menu shows
+///
+/// The tray rebuilds its menu only when this differs from the content of
+/// the menu it shows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuContent {
+    pub mode: AudioMode,
+    pub devices: Vec<BluetoothAudioDevice>,
+    /// Apps outputting to Bluetooth (may have triggered HFP)
+    pub hfp_apps: Vec<HfpUsingApp>,
+    /// Connected devices that have been forced to stereo mode
+    pub forced_stereo: BTreeSet<String>,
+    /// Paired audio devices that are not connected, sorted by name
+    pub disconnected: Vec<String>,
+    /// Language the menu is shown in
+    pub locale: String,
+}
+
+impl MenuContent {
+    pub fn new(state: &MonitorState, forced_stereo_devices: &HashSet<String>, paired_devices: &[PairedDevice]) -> Self {
+        let devices = &state.bluetooth_devices;
+        Self {
+            mode: state.current_mode,
+            devices: devices.clone(),
+            hfp_apps: state.hfp_apps.clone(),
+            forced_stereo: devices
+                .iter()
+                .map(|d| &d.device.name)
+                .filter(|name| paired_name_for_endpoint(name, forced_stereo_devices.iter().map(String::as_str)).is_some())
+                .cloned()
+                .collect(),
+            disconnected: disconnected_audio_devices(paired_devices, devices)
+                .into_iter()
+                .map(|d| d.name.clone())
+                .collect(),
+            locale: rust_i18n::locale().to_string(),
+        }
+    }
+
+    /// Whether the device has been forced to stereo mode
+    pub fn is_forced_stereo(&self, device_name: &str) -> bool {
+        self.forced_stereo.contains(device_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disconnected_audio_devices() {
+        use crate::audio::device::AudioDevice;
+        use crate::bluetooth::inventory::{BluetoothAddress, DeviceType};
+
+        let paired = |name: &str, connected: bool, device_type: DeviceType| PairedDevice {
+            address: BluetoothAddress(0),
+            name: name.to_string(),
+            class_of_device: 0,
+            device_type,
+            connected,
+            remembered: true,
+            authenticated: true,
+            last_seen: None,
+            last_used: None,
+            profiles: Vec::new(),
+        };
+        let inventory = vec![
+            paired("Speaker", false, DeviceType::Loudspeaker),
+            paired("Headphones", false, DeviceType::Headphones),
+            paired("Phone", false, DeviceType::NonAudio),
+            paired("Earbuds", true, DeviceType::Headset),
+            paired("Stale", false, DeviceType::Headset),
+        ];
+        // Endpoint already present even though the inventory says disconnected
+        let connected = vec![BluetoothAudioDevice::new(AudioDevice {
+            id: "1".to_string(),
+            name: "Headset (Stale)".to_string(),
+            is_bluetooth: true,
+        })];
+
+        let names: Vec<&str> = disconnected_audio_devices(&inventory, &connected)
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Headphones", "Speaker"]);
+    }
+
+    #[test]
+    fn test_menu_content_ignores_what_the_menu_does_not_show() {
+        use crate::audio::device::AudioDevice;
+        use crate::audio::session::MicUsingApp;
+
+        let mut state = MonitorState {
+            current_mode: AudioMode::Stereo,
+            bluetooth_devices: vec![BluetoothAudioDevice::new(AudioDevice {
+                id: "1".to_string(),
+                name: "Headset".to_string(),
+                is_bluetooth: true,
+            })],
+            ..MonitorState::default()
+        };
+        let forced = HashSet::from(["Headset".to_string()]);
+        let content = MenuContent::new(&state, &forced, &[]);
+        assert!(content.is_forced_stereo("Headset"));
+
+        // Mic apps and forced devices that are not connected are not in the menu
+        state.mic_using_apps.push(MicUsingApp::new(1, "a.exe".to_string(), "A".to_string()));
+        let more_forced = HashSet::from(["Headset".to_string(), "Speaker".to_string()]);
+        assert_eq!(MenuContent::new(&state, &more_forced, &[]), content);
+
+        state.hfp_apps.push(HfpUsingApp::new(1, "a.exe".to_string(), "A".to_string()));
+        assert_ne!(MenuContent::new(&state, &forced, &[]), content);
+    }
+
+    #[test]
+    fn test_menu_content_matches_forced_paired_name_to_endpoint() {
+        use crate::audio::device::AudioDevice;
+
+        let state = MonitorState {
+            bluetooth_devices: vec![BluetoothAudioDevice::new(AudioDevice {
+                id: "1".to_string(),
+                name: "Headphones (WH-1000XM4)".to_string(),
+                is_bluetooth: true,
+            })],
+            ..MonitorState::default()
+        };
+        // Forced over the command line, which names the paired device
+        let forced = HashSet::from(["WH-1000XM4".to_string()]);
+        let content = MenuContent::new(&state, &forced, &[]);
+        assert!(content.is_forced_stereo("Headphones (WH-1000XM4)"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Fixture tests for the battery level decoders
+
+use std::collections::HashMap;
+use win_bt_stereo_vs_handsfree::bluetooth::battery::{
+    battchg_to_percent, decode_battery_property, decode_gatt_battery_level, match_battery_level,
+    parse_biev_battery, parse_iphoneaccev, parse_xevent_battery,
+};
+
+const FIXTURES: &str = include_str!("fixtures/battery_reports.txt");
+
+fn parse_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("invalid hex in fixture"))
+        .collect()
+}
+
+fn decode(decoder: &str, input: &str) -> Option<u8> {
+    match decoder {
+        "iphoneaccev" => parse_iphoneaccev(input),
+        "xevent" => parse_xevent_battery(input),
+        "biev" => parse_biev_battery(input),
+        "property" => {
+            let (prop_type, data) = input.split_once(':').expect("property fixture needs type:data");
+            decode_battery_property(prop_type.parse().unwrap(), &parse_hex(data))
+        }
+        "gatt" => decode_gatt_battery_level(&parse_hex(input)),
+        other => panic!("unknown decoder '{}' in fixture", other),
+    }
+}
+
+#[test]
+fn test_battery_report_fixtures() {
+    let mut checked = 0;
+
+    for line in FIXTURES.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        assert_eq!(fields.len(), 3, "malformed fixture line: {}", line);
+
+        let expected = match fields[2] {
+            "none" => None,
+            value => Some(value.parse::<u8>().unwrap()),
+        };
+        assert_eq!(decode(fields[0], fields[1]), expected, "fixture: {}", line);
+        checked += 1;
+    }
+
+    assert!(checked >= 20, "expected at least 20 fixtures, found {}", checked);
+}
+
+#[test]
+fn test_battchg_indicator() {
+    assert_eq!(battchg_to_percent(0), Some(0));
+    assert_eq!(battchg_to_percent(3), Some(60));
+    assert_eq!(battchg_to_percent(5), Some(100));
+    assert_eq!(battchg_to_percent(6), None);
+}
+
+#[test]
+fn test_match_battery_level_to_endpoint() {
+    let mut levels = HashMap::new();
+    levels.insert("wh-1000xm4".to_string(), 80);
+    levels.insert("buds".to_string(), 40);
+    levels.insert("galaxy buds2".to_string(), 55);
+
+    assert_eq!(match_battery_level("WH-1000XM4", &levels), Some(80));
+    assert_eq!(match_battery_level("Headphones (WH-1000XM4)", &levels), Some(80));
+    assert_eq!(match_battery_level("WH-1000XM4 Hands-Free AG Audio", &levels), Some(80));
+    // Longest contained name wins
+    assert_eq!(match_battery_level("Headset (Galaxy Buds2)", &levels), Some(55));
+    assert_eq!(match_battery_level("Speakers (Realtek Audio)", &levels), None);
+}
@@ -0,0 +1,364 @@
+//! Tests for the BlueZ backend against a mock `org.bluez` service
+//!
+//! Each test starts its own `dbus-daemon --session` and exports a few
+//! `org.bluez.Device1`/`Battery1` objects on it. Tests are skipped when
+//! `dbus-daemon` is not installed.
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use win_bt_stereo_vs_handsfree::bluetooth::backend::BluetoothBackend;
+use win_bt_stereo_vs_handsfree::bluetooth::bluez::{BluezBluetooth, BLUEZ_SERVICE};
+use win_bt_stereo_vs_handsfree::bluetooth::inventory::{BluetoothProfile, DeviceType};
+use win_bt_stereo_vs_handsfree::error::AppError;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+
+const A2DP_SINK: &str = "0000110b-0000-1000-8000-00805f9b34fb";
+const AVRCP: &str = "0000110e-0000-1000-8000-00805f9b34fb";
+const HFP_HF: &str = "0000111e-0000-1000-8000-00805f9b34fb";
+const HSP_HS: &str = "00001108-0000-1000-8000-00805f9b34fb";
+
+type Calls = Arc<Mutex<Vec<String>>>;
+
+#[derive(zbus::DBusError, Debug)]
+#[zbus(prefix = "org.bluez.Error")]
+enum BluezError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotConnected(String),
+}
+
+struct MockDevice {
+    address: &'static str,
+    alias: &'static str,
+    class: u32,
+    paired: bool,
+    connected: bool,
+    uuids: Vec<&'static str>,
+    calls: Calls,
+}
+
+impl MockDevice {
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(format!("{} {}", self.alias, call));
+    }
+}
+
+#[zbus::interface(name = "org.bluez.Device1")]
+impl MockDevice {
+    fn connect(&mut self) {
+        self.record("Connect".to_string());
+        self.connected = true;
+    }
+
+    fn disconnect(&mut self) -> Result<(), BluezError> {
+        if !self.connected {
+            return Err(BluezError::NotConnected("Not Connected".to_string()));
+        }
+        self.record("Disconnect".to_string());
+        self.connected = false;
+        Ok(())
+    }
+
+    fn connect_profile(&mut self, uuid: String) {
+        self.record(format!("ConnectProfile {}", uuid));
+    }
+
+    fn disconnect_profile(&mut self, uuid: String) -> Result<(), BluezError> {
+        if !self.connected {
+            return Err(BluezError::NotConnected("Not Connected".to_string()));
+        }
+        self.record(format!("DisconnectProfile {}", uuid));
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    #[zbus(property)]
+    fn alias(&self) -> String {
+        self.alias.to_string()
+    }
+
+    #[zbus(property)]
+    fn class(&self) -> u32 {
+        self.class
+    }
+
+    #[zbus(property)]
+    fn paired(&self) -> bool {
+        self.paired
+    }
+
+    #[zbus(property)]
+    fn connected(&self) -> bool {
+        self.connected
+    }
+
+    #[zbus(property, name = "UUIDs")]
+    fn uuids(&self) -> Vec<String> {
+        self.uuids.iter().map(|uuid| uuid.to_string()).collect()
+    }
+}
+
+struct MockBattery(u8);
+
+#[zbus::interface(name = "org.bluez.Battery1")]
+impl MockBattery {
+    #[zbus(property)]
+    fn percentage(&self) -> u8 {
+        self.0
+    }
+}
+
+/// A private bus with a mock `bluetoothd` on it
+struct MockBluez {
+    daemon: Child,
+    address: String,
+    calls: Calls,
+    _service: Connection,
+}
+
+impl Drop for MockBluez {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+impl MockBluez {
+    fn start() -> Option<Self> {
+        let mut daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(e) => {
+                eprintln!("skipping: cannot start dbus-daemon: {}", e);
+                return None;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let calls = Calls::default();
+        let headset = MockDevice {
+            address: "AC:80:0A:2B:3C:4D",
+            alias: "WH-1000XM4",
+            class: 0x240404,
+            paired: true,
+            connected: true,
+            uuids: vec![A2DP_SINK, AVRCP, HFP_HF, HSP_HS],
+            calls: calls.clone(),
+        };
+        let speaker = MockDevice {
+            address: "00:1B:66:AA:BB:CC",
+            alias: "Kitchen Speaker",
+            class: 0x240414,
+            paired: true,
+            connected: false,
+            uuids: vec![A2DP_SINK, AVRCP],
+            calls: calls.clone(),
+        };
+        let phone = MockDevice {
+            address: "11:22:33:44:55:66",
+            alias: "Someone's Phone",
+            class: 0x5a020c,
+            paired: false,
+            connected: false,
+            uuids: Vec::new(),
+            calls: calls.clone(),
+        };
+
+        let service = Builder::address(address.as_str())
+            .unwrap()
+            .name(BLUEZ_SERVICE)
+            .unwrap()
+            .serve_at("/", zbus::fdo::ObjectManager)
+            .unwrap()
+            .serve_at("/org/bluez/hci0/dev_AC_80_0A_2B_3C_4D", headset)
+            .unwrap()
+            .serve_at("/org/bluez/hci0/dev_AC_80_0A_2B_3C_4D", MockBattery(80))
+            .unwrap()
+            .serve_at("/org/bluez/hci0/dev_00_1B_66_AA_BB_CC", speaker)
+            .unwrap()
+            .serve_at("/org/bluez/hci0/dev_11_22_33_44_55_66", phone)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        Some(Self {
+            daemon,
+            address,
+            calls,
+            _service: service,
+        })
+    }
+
+    fn backend(&self) -> BluezBluetooth {
+        let connection = Builder::address(self.address.as_str()).unwrap().build().unwrap();
+        BluezBluetooth::with_connection(connection)
+    }
+
+    /// Calls received since the last check
+    fn take_calls(&self) -> Vec<String> {
+        std::mem::take(&mut *self.calls.lock().unwrap())
+    }
+}
+
+#[test]
+fn test_list_paired_devices() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let devices = bluez.backend().list_paired_devices().unwrap();
+
+    // The unpaired phone is only a discovered device
+    let names: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, vec!["Kitchen Speaker", "WH-1000XM4"]);
+
+    let headset = &devices[1];
+    assert_eq!(headset.address.to_string(), "AC:80:0A:2B:3C:4D");
+    assert_eq!(headset.device_type, DeviceType::Headset);
+    assert!(headset.connected);
+    assert!(headset.has_profile(BluetoothProfile::A2dpSink));
+    assert!(headset.has_profile(BluetoothProfile::HandsFree));
+
+    let speaker = &devices[0];
+    assert_eq!(speaker.device_type, DeviceType::Loudspeaker);
+    assert!(speaker.is_disconnected_audio());
+    assert!(!speaker.has_profile(BluetoothProfile::HandsFree));
+}
+
+#[test]
+fn test_disable_and_enable_hfp() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let backend = bluez.backend();
+    assert!(backend.is_hfp_enabled("WH-1000XM4").unwrap());
+
+    backend.disable_hfp("WH-1000XM4").unwrap();
+    assert_eq!(
+        bluez.take_calls(),
+        vec![
+            format!("WH-1000XM4 DisconnectProfile {}", HFP_HF),
+            format!("WH-1000XM4 DisconnectProfile {}", HSP_HS),
+        ]
+    );
+    assert!(!backend.is_hfp_enabled("WH-1000XM4").unwrap());
+    let devices = backend.list_paired_devices().unwrap();
+    let headset = devices.iter().find(|d| d.name == "WH-1000XM4").unwrap();
+    assert!(!headset.has_profile(BluetoothProfile::HandsFree));
+    assert!(headset.has_profile(BluetoothProfile::A2dpSink));
+
+    backend.enable_hfp("WH-1000XM4").unwrap();
+    assert_eq!(bluez.take_calls(), vec![format!("WH-1000XM4 ConnectProfile {}", HFP_HF)]);
+    assert!(backend.is_hfp_enabled("WH-1000XM4").unwrap());
+}
+
+#[test]
+fn test_reapply_after_headset_reconnects_on_its_own() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let backend = bluez.backend();
+    backend.disable_hfp("WH-1000XM4").unwrap();
+    bluez.take_calls();
+
+    // The headset drops and comes back, bringing hands-free with it
+    let bus = Builder::address(bluez.address.as_str()).unwrap().build().unwrap();
+    let device = zbus::blocking::Proxy::new(
+        &bus,
+        BLUEZ_SERVICE,
+        "/org/bluez/hci0/dev_AC_80_0A_2B_3C_4D",
+        "org.bluez.Device1",
+    )
+    .unwrap();
+    device.call_method("Disconnect", &()).unwrap();
+    device.call_method("Connect", &()).unwrap();
+    bluez.take_calls();
+
+    assert!(backend.reapply_disabled_hfp("WH-1000XM4").unwrap());
+    assert_eq!(
+        bluez.take_calls(),
+        vec![
+            format!("WH-1000XM4 DisconnectProfile {}", HFP_HF),
+            format!("WH-1000XM4 DisconnectProfile {}", HSP_HS),
+        ]
+    );
+
+    // Nothing to do for a device without hands-free
+    assert!(!backend.reapply_disabled_hfp("Kitchen Speaker").unwrap());
+    assert!(bluez.take_calls().is_empty());
+}
+
+#[test]
+fn test_disable_hfp_without_hands_free_fails() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let result = bluez.backend().disable_hfp("Kitchen Speaker");
+    assert!(matches!(result, Err(AppError::ConfigError(_))));
+    assert!(bluez.take_calls().is_empty());
+}
+
+#[test]
+fn test_connect_only_disconnected_devices() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let backend = bluez.backend();
+
+    backend.connect("Kitchen").unwrap();
+    assert_eq!(bluez.take_calls(), vec!["Kitchen Speaker Connect"]);
+
+    backend.connect("WH-1000XM4").unwrap();
+    assert!(bluez.take_calls().is_empty());
+}
+
+#[test]
+fn test_reconnect_keeps_hfp_disabled() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let backend = bluez.backend();
+
+    backend.reconnect("WH-1000XM4").unwrap();
+    assert_eq!(bluez.take_calls(), vec!["WH-1000XM4 Disconnect", "WH-1000XM4 Connect"]);
+
+    backend.disable_hfp("WH-1000XM4").unwrap();
+    bluez.take_calls();
+    backend.reconnect("WH-1000XM4").unwrap();
+    assert_eq!(
+        bluez.take_calls(),
+        vec![
+            "WH-1000XM4 Disconnect".to_string(),
+            "WH-1000XM4 Connect".to_string(),
+            format!("WH-1000XM4 DisconnectProfile {}", HFP_HF),
+            format!("WH-1000XM4 DisconnectProfile {}", HSP_HS),
+        ]
+    );
+}
+
+#[test]
+fn test_reconnect_disconnected_device() {
+    let Some(bluez) = MockBluez::start() else { return };
+    // NotConnected from Disconnect is not an error
+    bluez.backend().reconnect("Kitchen Speaker").unwrap();
+    assert_eq!(bluez.take_calls(), vec!["Kitchen Speaker Connect"]);
+}
+
+#[test]
+fn test_battery_levels() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let levels = bluez.backend().battery_levels().unwrap();
+    assert_eq!(levels.len(), 1);
+    assert_eq!(levels.get("wh-1000xm4"), Some(&80));
+}
+
+#[test]
+fn test_unknown_device_is_not_found() {
+    let Some(bluez) = MockBluez::start() else { return };
+    let backend = bluez.backend();
+    // Discovered but unpaired devices are not candidates
+    assert!(matches!(backend.connect("Someone's Phone"), Err(AppError::ConfigError(_))));
+    assert!(matches!(backend.is_hfp_enabled("Jabra"), Err(AppError::ConfigError(_))));
+}
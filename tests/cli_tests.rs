@@ -0,0 +1,302 @@
+//! Tests for CLI command execution, output and exit codes
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use win_bt_stereo_vs_handsfree::audio::device::{AudioDevice, AudioMode, BluetoothAudioDevice};
+use win_bt_stereo_vs_handsfree::audio::monitor::MonitorState;
+use win_bt_stereo_vs_handsfree::audio::session::{HfpUsingApp, MicUsingApp};
+use win_bt_stereo_vs_handsfree::bluetooth::backend::BluetoothBackend;
+use win_bt_stereo_vs_handsfree::cli::{
+    exit_code, execute, parse_args, ActionReport, AppStatus, CliBackend, CliError, DeviceEntry,
+    DeviceStatus, DirectBackend, StatusReport,
+};
+use win_bt_stereo_vs_handsfree::platform::Backends;
+use win_bt_stereo_vs_handsfree::simulation::{SimulatedBackend, Timeline};
+
+/// Backend with canned state that records the actions it was asked to perform
+#[derive(Default)]
+struct FakeBackend {
+    actions: Vec<String>,
+    unavailable: bool,
+}
+
+impl FakeBackend {
+    fn check_available(&self) -> Result<(), CliError> {
+        if self.unavailable {
+            Err(CliError::Unavailable("Bluetooth is not available".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn action(&mut self, action: &str, device: &str) -> Result<ActionReport, CliError> {
+        self.check_available()?;
+        if device != "WH-1000XM4" {
+            return Err(CliError::NotFound(format!("No unique paired device matches '{}'", device)));
+        }
+        self.actions.push(format!("{} {}", action, device));
+        Ok(ActionReport {
+            action: action.to_string(),
+            target: Some(device.to_string()),
+            message: format!("{} done for '{}'", action, device),
+        })
+    }
+}
+
+impl CliBackend for FakeBackend {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        self.check_available()?;
+        Ok(StatusReport {
+            mode: "hands-free".to_string(),
+            devices: vec![DeviceStatus {
+                name: "WH-1000XM4".to_string(),
+                mode: "hands-free".to_string(),
+                battery_level: Some(80),
+            }],
+            mic_apps: vec![AppStatus {
+                pid: 4242,
+                name: "Teams.exe".to_string(),
+                display_name: "Microsoft Teams".to_string(),
+                muted: Some(false),
+                bluetooth_mic: Some(true),
+            }],
+            hfp_apps: vec![],
+        })
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        self.check_available()?;
+        Ok(vec![DeviceEntry {
+            name: "WH-1000XM4".to_string(),
+            address: "00:11:22:33:AA:BB".to_string(),
+            device_type: "Headphones".to_string(),
+            connected: false,
+            hands_free_enabled: false,
+            profiles: vec!["a2dp-sink".to_string()],
+            last_seen: None,
+            last_used: Some(1_700_000_000),
+        }])
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.action("force-stereo", device)
+    }
+
+    fn allow_hands_free(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.action("allow-hands-free", device)
+    }
+
+    fn reconnect(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.action("reconnect", device)
+    }
+
+    fn mute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        self.check_available()?;
+        if pid != 4242 {
+            return Err(CliError::NotFound(format!("Process {} is not using a microphone", pid)));
+        }
+        self.actions.push(format!("mute {}", pid));
+        Ok(ActionReport {
+            action: "mute".to_string(),
+            target: Some(pid.to_string()),
+            message: format!("Muted PID {}", pid),
+        })
+    }
+
+    fn unmute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        self.check_available()?;
+        self.actions.push(format!("unmute {}", pid));
+        Ok(ActionReport {
+            action: "unmute".to_string(),
+            target: Some(pid.to_string()),
+            message: format!("Unmuted PID {}", pid),
+        })
+    }
+
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        self.check_available()?;
+        Err(CliError::Failed("Restore failed: access denied".to_string()))
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Unavailable("Bluetooth Audio Mode Manager is not running".to_string()))
+    }
+}
+
+fn run(args: &[&str], backend: &mut FakeBackend) -> win_bt_stereo_vs_handsfree::cli::CliOutcome {
+    let args = parse_args(args).expect("valid args").expect("a subcommand");
+    execute(&args, backend)
+}
+
+#[test]
+fn test_status_text() {
+    let outcome = run(&["status"], &mut FakeBackend::default());
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+    assert!(outcome.stdout.contains("Mode: hands-free"));
+    assert!(outcome.stdout.contains("WH-1000XM4  [hands-free]  battery 80%"));
+    assert!(outcome.stdout.contains("4242  Microsoft Teams  (bluetooth mic)"));
+    assert!(outcome.stderr.is_empty());
+}
+
+#[test]
+fn test_status_json() {
+    let outcome = run(&["status", "--json"], &mut FakeBackend::default());
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+
+    let value: serde_json::Value = serde_json::from_str(&outcome.stdout).unwrap();
+    assert_eq!(value["mode"], "hands-free");
+    assert_eq!(value["devices"][0]["battery_level"], 80);
+    assert_eq!(value["mic_apps"][0]["pid"], 4242);
+    assert_eq!(value["mic_apps"][0]["bluetooth_mic"], true);
+    assert!(value["hfp_apps"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_devices_json() {
+    let outcome = run(&["devices", "--json"], &mut FakeBackend::default());
+    let value: serde_json::Value = serde_json::from_str(&outcome.stdout).unwrap();
+    assert_eq!(value[0]["address"], "00:11:22:33:AA:BB");
+    assert_eq!(value[0]["connected"], false);
+    assert_eq!(value[0]["last_used"], 1_700_000_000u64);
+}
+
+#[test]
+fn test_actions_reach_backend() {
+    let mut backend = FakeBackend::default();
+    assert_eq!(run(&["force-stereo", "WH-1000XM4"], &mut backend).exit_code, exit_code::SUCCESS);
+    assert_eq!(run(&["allow-hands-free", "WH-1000XM4"], &mut backend).exit_code, exit_code::SUCCESS);
+    assert_eq!(run(&["reconnect", "WH-1000XM4"], &mut backend).exit_code, exit_code::SUCCESS);
+    assert_eq!(run(&["mute", "4242"], &mut backend).exit_code, exit_code::SUCCESS);
+    assert_eq!(run(&["unmute", "4242"], &mut backend).exit_code, exit_code::SUCCESS);
+    assert_eq!(
+        backend.actions,
+        [
+            "force-stereo WH-1000XM4",
+            "allow-hands-free WH-1000XM4",
+            "reconnect WH-1000XM4",
+            "mute 4242",
+            "unmute 4242"
+        ]
+    );
+}
+
+#[test]
+fn test_error_exit_codes() {
+    let mut backend = FakeBackend::default();
+
+    let outcome = run(&["reconnect", "AirPods"], &mut backend);
+    assert_eq!(outcome.exit_code, exit_code::NOT_FOUND);
+    assert!(outcome.stdout.is_empty());
+    assert!(outcome.stderr.starts_with("error: No unique paired device"));
+
+    assert_eq!(run(&["mute", "1"], &mut backend).exit_code, exit_code::NOT_FOUND);
+    assert_eq!(run(&["restore"], &mut backend).exit_code, exit_code::FAILURE);
+
+    let mut unavailable = FakeBackend {
+        unavailable: true,
+        ..Default::default()
+    };
+    assert_eq!(run(&["status"], &mut unavailable).exit_code, exit_code::UNAVAILABLE);
+    assert_eq!(run(&["open-settings"], &mut backend).exit_code, exit_code::UNAVAILABLE);
+}
+
+#[test]
+fn test_error_json() {
+    let outcome = run(&["mute", "1", "--json"], &mut FakeBackend::default());
+    assert_eq!(outcome.exit_code, exit_code::NOT_FOUND);
+    assert!(outcome.stderr.is_empty());
+
+    let value: serde_json::Value = serde_json::from_str(&outcome.stdout).unwrap();
+    assert_eq!(value["error"]["kind"], "not_found");
+    assert_eq!(value["error"]["code"], exit_code::NOT_FOUND);
+}
+
+#[test]
+fn test_help_and_version() {
+    let outcome = run(&["--help"], &mut FakeBackend::default());
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+    assert!(outcome.stdout.contains("force-stereo <device>"));
+
+    let outcome = run(&["version"], &mut FakeBackend::default());
+    assert_eq!(outcome.stdout.trim(), env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn test_restore_re_enables_only_forced_devices() {
+    let simulated = Arc::new(SimulatedBackend::new(
+        "t=0 WH-1000XM4 connects; t=0 Jabra Evolve2 connects".parse().unwrap(),
+    ));
+    simulated.advance_to(Duration::ZERO);
+    // Hands-free is off on both, but only one was forced by the app
+    simulated.disable_hfp("WH-1000XM4").unwrap();
+    simulated.disable_hfp("Jabra Evolve2").unwrap();
+
+    let forced: HashSet<String> = ["WH-1000XM4".to_string()].into();
+    let mut backend = DirectBackend::with_backends(Backends::new(simulated.clone(), simulated.clone()))
+        .unwrap()
+        .with_forced_stereo(&forced);
+    let outcome = execute(&parse_args(&["restore"]).unwrap().unwrap(), &mut backend);
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+
+    let enabled: Vec<String> = simulated
+        .actions()
+        .into_iter()
+        .filter(|action| action.starts_with("enable_hfp"))
+        .collect();
+    assert_eq!(enabled, ["enable_hfp WH-1000XM4"]);
+}
+
+#[test]
+fn test_restore_matches_forced_endpoint_names_and_keeps_the_rest() {
+    let simulated = Arc::new(SimulatedBackend::new("t=0 WH-1000XM4 connects".parse().unwrap()));
+    simulated.advance_to(Duration::ZERO);
+    simulated.disable_hfp("WH-1000XM4").unwrap();
+
+    // Forced from the tray before the inventory arrived, and a device no longer paired
+    let forced: HashSet<String> = ["Headphones (WH-1000XM4)".to_string(), "Gone Headset".to_string()].into();
+    let mut backend = DirectBackend::with_backends(Backends::new(simulated.clone(), simulated.clone()))
+        .unwrap()
+        .with_forced_stereo(&forced);
+    let outcome = execute(&parse_args(&["restore"]).unwrap().unwrap(), &mut backend);
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+
+    assert!(simulated.actions().contains(&"enable_hfp WH-1000XM4".to_string()));
+    assert_eq!(backend.forced_stereo(), &HashSet::from(["Gone Headset".to_string()]));
+}
+
+#[test]
+fn test_status_from_monitor_state() {
+    let mut device = BluetoothAudioDevice::new(AudioDevice {
+        id: "1".to_string(),
+        name: "Headphones (WH-1000XM4)".to_string(),
+        is_bluetooth: true,
+    });
+    device.current_mode = AudioMode::HandsFree;
+    device.battery_level = Some(60);
+    let mut state = MonitorState {
+        current_mode: AudioMode::HandsFree,
+        mic_using_apps: vec![MicUsingApp::new(42, "teams.exe".to_string(), "Teams".to_string())],
+        bluetooth_devices: vec![device],
+        hfp_apps: vec![HfpUsingApp::new(7, "game.exe".to_string(), "Game".to_string())],
+        ..MonitorState::default()
+    };
+
+    let report = StatusReport::from(&state);
+    assert_eq!(report.mode, "hands-free");
+    assert_eq!(
+        report.devices,
+        [DeviceStatus {
+            name: "Headphones (WH-1000XM4)".to_string(),
+            mode: "hands-free".to_string(),
+            battery_level: Some(60),
+        }]
+    );
+    assert_eq!(report.mic_apps[0].pid, 42);
+    assert_eq!(report.mic_apps[0].muted, Some(false));
+    assert_eq!(report.hfp_apps[0].pid, 7);
+
+    // Bluetooth output apps only matter while they may have caused hands-free
+    state.current_mode = AudioMode::Stereo;
+    assert!(StatusReport::from(&state).hfp_apps.is_empty());
+}
@@ -1,6 +1,8 @@
 //! Tests for configuration loading, saving, and migration
 
-use win_bt_stereo_vs_handsfree::settings::config::{AppConfig, CONFIG_VERSION};
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+use win_bt_stereo_vs_handsfree::settings::config::{AppConfig, ConfigWatcher, CONFIG_VERSION};
 
 #[test]
 fn test_default_config() {
@@ -30,6 +32,7 @@ fn test_default_logging_config() {
     assert_eq!(config.logging.level, "info");
     assert_eq!(config.logging.max_file_size, 5 * 1024 * 1024);
     assert_eq!(config.logging.max_files, 3);
+    assert!(!config.logging.record_polls);
 }
 
 #[test]
@@ -42,6 +45,166 @@ fn test_default_update_config() {
     assert!(config.updates.skipped_version.is_none());
 }
 
+#[test]
+fn test_low_battery_threshold_default_and_disable() {
+    let config = AppConfig::default();
+    assert_eq!(config.notifications.low_battery_threshold, 20);
+
+    let toml_str = r#"
+        [notifications]
+        low_battery_threshold = 0
+    "#;
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(config.notifications.low_battery_threshold, 0);
+    assert!(config.notifications.notify_errors);
+}
+
+#[test]
+fn test_default_watchdog_config() {
+    let config = AppConfig::default();
+
+    // Watchdog is opt-in
+    assert!(!config.watchdog.enabled);
+    assert_eq!(config.watchdog.hands_free_timeout_secs, 30);
+    assert_eq!(config.watchdog.cooldown_secs, 120);
+    assert_eq!(config.watchdog.max_attempts, 3);
+}
+
+#[test]
+fn test_watchdog_section_missing_uses_defaults() {
+    let toml_str = r#"
+        config_version = 2
+
+        [general]
+        auto_start = true
+    "#;
+
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert!(!config.watchdog.enabled);
+    assert_eq!(config.watchdog.max_attempts, 3);
+}
+
+#[test]
+fn test_rpc_disabled_by_default() {
+    let toml_str = r#"
+        config_version = 4
+
+        [general]
+        auto_start = true
+    "#;
+
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert!(!config.rpc.enabled);
+    assert!(config.rpc.token.is_empty());
+}
+
+#[test]
+fn test_http_defaults_and_port() {
+    let config = AppConfig::default();
+    assert!(!config.http.enabled);
+    assert_eq!(config.http.port, 8731);
+
+    let toml_str = r#"
+        [http]
+        enabled = true
+        port = 9000
+    "#;
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.http.enabled);
+    assert_eq!(config.http.port, 9000);
+    assert!(config.http.token.is_empty());
+}
+
+#[test]
+fn test_mqtt_defaults_and_broker() {
+    let config = AppConfig::default();
+    assert!(!config.mqtt.enabled);
+    assert_eq!(config.mqtt.port, 1883);
+    assert_eq!(config.mqtt.base_topic, "bt_audio_mode_manager");
+    assert!(config.mqtt.discovery);
+
+    let toml_str = r#"
+        [mqtt]
+        enabled = true
+        host = "broker.lan"
+        username = "ha"
+    "#;
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.mqtt.enabled);
+    assert_eq!(config.mqtt.host, "broker.lan");
+    assert_eq!(config.mqtt.port, 1883);
+    assert_eq!(config.mqtt.username, "ha");
+    assert_eq!(config.mqtt.discovery_prefix, "homeassistant");
+}
+
+#[test]
+fn test_metrics_defaults_and_port() {
+    let config = AppConfig::default();
+    assert!(!config.metrics.enabled);
+    assert_eq!(config.metrics.port, 9731);
+
+    let toml_str = r#"
+        [metrics]
+        enabled = true
+    "#;
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.metrics.enabled);
+    assert_eq!(config.metrics.port, 9731);
+}
+
+#[test]
+fn test_config_watcher_notices_edits() {
+    let path = std::env::temp_dir().join(format!("btam-config-watch-{}.toml", std::process::id()));
+    let set_modified = |secs: u64| {
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    };
+    fs::write(&path, "[general]\n").unwrap();
+    set_modified(1_000);
+
+    let mut watcher = ConfigWatcher::new(&path);
+    assert!(!watcher.changed());
+
+    fs::write(&path, "[general]\nauto_start = true\n").unwrap();
+    set_modified(2_000);
+    assert!(watcher.changed());
+    assert!(!watcher.changed());
+
+    fs::remove_file(&path).unwrap();
+    assert!(watcher.changed());
+    assert!(!watcher.changed());
+}
+
+#[test]
+fn test_config_equality() {
+    let config = AppConfig::default();
+    assert_eq!(config.clone(), config);
+
+    let mut edited = config.clone();
+    edited.watchdog.enabled = !config.watchdog.enabled;
+    assert_ne!(edited, config);
+}
+
+#[test]
+fn test_hotkeys_unbound_by_default() {
+    let config = AppConfig::default();
+    assert!(config.hotkeys.force_stereo.is_empty());
+    assert!(config.hotkeys.allow_hands_free.is_empty());
+    assert!(config.hotkeys.reconnect.is_empty());
+
+    let toml_str = r#"
+        [hotkeys]
+        force_stereo = "Ctrl+Alt+S"
+    "#;
+    let config: AppConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(config.hotkeys.force_stereo, "Ctrl+Alt+S");
+    assert!(config.hotkeys.allow_hands_free.is_empty());
+}
+
 #[test]
 fn test_config_serialization() {
     let config = AppConfig::default();
@@ -113,7 +276,7 @@ fn test_config_with_extra_fields() {
 #[test]
 fn test_config_version() {
     // Ensure CONFIG_VERSION is at least 1
-    assert!(CONFIG_VERSION >= 1);
+    const { assert!(CONFIG_VERSION >= 1) };
 }
 
 #[test]
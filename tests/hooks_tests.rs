@@ -0,0 +1,206 @@
+//! Tests for queuing, concurrency limits and failure reporting of user hooks
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use win_bt_stereo_vs_handsfree::bluetooth::inventory::{BluetoothAddress, DeviceType};
+use win_bt_stereo_vs_handsfree::bluetooth::PairedDevice;
+use win_bt_stereo_vs_handsfree::hooks::{newly_connected, HookEvent, HookExecutor, HookFailure, HookRunner, QUEUE_CAPACITY};
+use win_bt_stereo_vs_handsfree::settings::{HookCommand, HookEventKind, HooksConfig};
+
+/// Executor that records runs; commands named "fail" fail, "slow" block until released
+#[derive(Default)]
+struct FakeExecutor {
+    runs: Mutex<Vec<(String, HookEvent)>>,
+    running: AtomicUsize,
+    max_running: AtomicUsize,
+    release: Mutex<bool>,
+}
+
+impl HookExecutor for FakeExecutor {
+    fn run(&self, hook: &HookCommand, event: &HookEvent, _timeout: Duration) -> Result<(), String> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+
+        if hook.command == "slow" {
+            while !*self.release.lock().unwrap() {
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        self.runs.lock().unwrap().push((hook.command.clone(), event.clone()));
+        self.running.fetch_sub(1, Ordering::SeqCst);
+
+        if hook.command == "fail" {
+            Err("exited with exit code: 1".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn hook(event: HookEventKind, command: &str) -> HookCommand {
+    HookCommand {
+        event,
+        command: command.to_string(),
+        args: Vec::new(),
+    }
+}
+
+fn config(commands: Vec<HookCommand>, max_concurrent: u32) -> HooksConfig {
+    HooksConfig {
+        timeout_secs: 10,
+        max_concurrent,
+        commands,
+    }
+}
+
+fn mode_changed() -> HookEvent {
+    HookEvent::ModeChanged {
+        old_mode: "stereo".to_string(),
+        new_mode: "hands-free".to_string(),
+    }
+}
+
+fn wait_until(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for hooks");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn test_only_matching_hooks_run() {
+    let executor = Arc::new(FakeExecutor::default());
+    let runner = HookRunner::with_executor(
+        &config(
+            vec![
+                hook(HookEventKind::ModeChanged, "lights"),
+                hook(HookEventKind::DeviceConnected, "tracker"),
+            ],
+            2,
+        ),
+        executor.clone(),
+    );
+
+    runner.fire(&mode_changed());
+    wait_until(|| executor.runs.lock().unwrap().len() == 1);
+    thread::sleep(Duration::from_millis(20));
+
+    let runs = executor.runs.lock().unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0], ("lights".to_string(), mode_changed()));
+}
+
+#[test]
+fn test_failures_are_reported() {
+    let executor = Arc::new(FakeExecutor::default());
+    let runner = HookRunner::with_executor(
+        &config(vec![hook(HookEventKind::ForceStereoApplied, "fail")], 1),
+        executor.clone(),
+    );
+
+    runner.fire(&HookEvent::ForceStereoApplied {
+        device: "WH-1000XM4".to_string(),
+        reason: "user".to_string(),
+    });
+
+    let mut failure = None;
+    wait_until(|| {
+        failure = failure.clone().or_else(|| runner.try_recv_failure());
+        failure.is_some()
+    });
+    assert_eq!(
+        failure.unwrap(),
+        HookFailure {
+            command: "fail".to_string(),
+            event: HookEventKind::ForceStereoApplied,
+            error: "exited with exit code: 1".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_concurrency_is_limited_and_overflow_skipped() {
+    let executor = Arc::new(FakeExecutor::default());
+    let runner = HookRunner::with_executor(
+        &config(vec![hook(HookEventKind::ModeChanged, "slow")], 2),
+        executor.clone(),
+    );
+
+    // Two run, QUEUE_CAPACITY wait, the rest are skipped
+    let fired = QUEUE_CAPACITY + 5;
+    runner.fire(&mode_changed());
+    runner.fire(&mode_changed());
+    wait_until(|| executor.running.load(Ordering::SeqCst) == 2);
+    for _ in 2..fired {
+        runner.fire(&mode_changed());
+    }
+
+    let mut skipped = 0;
+    while let Some(failure) = runner.try_recv_failure() {
+        assert!(failure.error.contains("skipped"));
+        skipped += 1;
+    }
+    assert_eq!(skipped, fired - 2 - QUEUE_CAPACITY);
+
+    *executor.release.lock().unwrap() = true;
+    wait_until(|| executor.runs.lock().unwrap().len() == 2 + QUEUE_CAPACITY);
+    assert_eq!(executor.max_running.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_no_hooks_configured() {
+    let executor = Arc::new(FakeExecutor::default());
+    let runner = HookRunner::with_executor(&HooksConfig::default(), executor.clone());
+    runner.fire(&mode_changed());
+    thread::sleep(Duration::from_millis(20));
+    assert!(executor.runs.lock().unwrap().is_empty());
+    assert!(runner.try_recv_failure().is_none());
+}
+
+#[test]
+fn test_newly_connected_devices() {
+    let device = |address: u64, connected: bool| PairedDevice {
+        address: BluetoothAddress(address),
+        name: format!("Device {}", address),
+        class_of_device: 0,
+        device_type: DeviceType::Headset,
+        connected,
+        remembered: true,
+        authenticated: true,
+        last_seen: None,
+        last_used: None,
+        profiles: Vec::new(),
+    };
+
+    let previous = vec![device(1, true), device(2, false)];
+    let current = vec![device(1, true), device(2, true), device(3, true), device(4, false)];
+    let names: Vec<&str> = newly_connected(&previous, &current)
+        .into_iter()
+        .map(|d| d.name.as_str())
+        .collect();
+    assert_eq!(names, ["Device 2", "Device 3"]);
+}
+
+#[test]
+fn test_hooks_config_from_toml() {
+    let toml_str = r#"
+        [hooks]
+        timeout_secs = 5
+
+        [[hooks.commands]]
+        event = "mic-session-started"
+        command = "powershell.exe"
+        args = ["-File", "C:\\scripts\\on-call.ps1"]
+    "#;
+
+    let config: win_bt_stereo_vs_handsfree::settings::AppConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(config.hooks.timeout_secs, 5);
+    assert_eq!(config.hooks.max_concurrent, 2);
+    assert_eq!(config.hooks.commands.len(), 1);
+    assert_eq!(config.hooks.commands[0].event, HookEventKind::MicSessionStarted);
+    assert_eq!(config.hooks.commands[0].args, ["-File", "C:\\scripts\\on-call.ps1"]);
+}
@@ -0,0 +1,161 @@
+//! Tests for hotkey parsing, conflict detection and action mapping
+
+use win_bt_stereo_vs_handsfree::audio::{AudioDevice, BluetoothAudioDevice};
+use win_bt_stereo_vs_handsfree::hotkeys::{
+    active_device, bindings, Hotkey, HotkeyAction, HotkeyError, HotkeyProblem, MOD_ALT, MOD_CONTROL, MOD_SHIFT,
+    MOD_WIN,
+};
+use win_bt_stereo_vs_handsfree::settings::HotkeysConfig;
+use win_bt_stereo_vs_handsfree::tray::MenuEvent;
+
+fn parse(s: &str) -> Result<Hotkey, HotkeyError> {
+    s.parse()
+}
+
+fn device(name: &str) -> BluetoothAudioDevice {
+    BluetoothAudioDevice::new(AudioDevice {
+        id: name.to_string(),
+        name: name.to_string(),
+        is_bluetooth: true,
+    })
+}
+
+#[test]
+fn test_parse_hotkeys() {
+    assert_eq!(
+        parse("Ctrl+Alt+S"),
+        Ok(Hotkey {
+            modifiers: MOD_CONTROL | MOD_ALT,
+            key: 'S' as u32
+        })
+    );
+    assert_eq!(
+        parse("win + shift + F5"),
+        Ok(Hotkey {
+            modifiers: MOD_WIN | MOD_SHIFT,
+            key: 0x74
+        })
+    );
+    assert_eq!(parse("control+alt+esc"), parse("Ctrl+Alt+Escape"));
+    assert_eq!(parse("Alt+Numpad3").unwrap().key, 0x63);
+}
+
+#[test]
+fn test_display_is_canonical() {
+    assert_eq!(parse("s+alt+ctrl").unwrap().to_string(), "Ctrl+Alt+S");
+    assert_eq!(parse("Shift+Win+pagedown").unwrap().to_string(), "Shift+Win+PageDown");
+    for s in ["Ctrl+Alt+H", "Ctrl+Shift+F1", "Alt+Win+Numpad0", "Ctrl+Space"] {
+        assert_eq!(parse(s).unwrap().to_string(), s);
+        assert_eq!(parse(&parse(s).unwrap().to_string()), parse(s));
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse(""), Err(HotkeyError::Empty));
+    assert_eq!(parse("   "), Err(HotkeyError::Empty));
+    assert_eq!(parse("Ctrl+Alt+Foo"), Err(HotkeyError::UnknownKey("Foo".to_string())));
+    assert_eq!(parse("Ctrl++S"), Err(HotkeyError::UnknownKey("Ctrl++S".to_string())));
+    assert_eq!(parse("Ctrl+Alt"), Err(HotkeyError::MissingKey));
+    assert_eq!(parse("Ctrl+S+H"), Err(HotkeyError::MultipleKeys));
+    assert_eq!(parse("Ctrl+Control+S"), Err(HotkeyError::DuplicateModifier("Control".to_string())));
+    assert_eq!(parse("S"), Err(HotkeyError::NoModifier));
+    assert_eq!(parse("Shift+S"), Err(HotkeyError::NoModifier));
+}
+
+#[test]
+fn test_reserved_hotkeys() {
+    assert_eq!(parse("Ctrl+F12"), Err(HotkeyError::Reserved));
+    assert_eq!(parse("Ctrl+Alt+Del"), Err(HotkeyError::Reserved));
+    assert_eq!(parse("Win+L"), Err(HotkeyError::Reserved));
+    assert!(parse("Win+Shift+L").is_ok());
+}
+
+#[test]
+fn test_bindings_skip_unbound() {
+    let (bound, problems) = bindings(&HotkeysConfig::default());
+    assert!(bound.is_empty());
+    assert!(problems.is_empty());
+
+    let config = HotkeysConfig {
+        force_stereo: "Ctrl+Alt+S".to_string(),
+        allow_hands_free: "Ctrl+Alt+H".to_string(),
+        ..Default::default()
+    };
+    let (bound, problems) = bindings(&config);
+    assert!(problems.is_empty());
+    assert_eq!(
+        bound,
+        vec![
+            (HotkeyAction::ForceStereo, parse("Ctrl+Alt+S").unwrap()),
+            (HotkeyAction::AllowHandsFree, parse("Ctrl+Alt+H").unwrap()),
+        ]
+    );
+}
+
+#[test]
+fn test_bindings_detect_conflicts() {
+    let config = HotkeysConfig {
+        force_stereo: "Ctrl+Alt+S".to_string(),
+        allow_hands_free: "Ctrl+Alt+H".to_string(),
+        // Same combination, written differently
+        reconnect: "alt+ctrl+s".to_string(),
+    };
+    let (bound, problems) = bindings(&config);
+    assert_eq!(bound.len(), 2);
+    assert_eq!(
+        problems,
+        vec![HotkeyProblem::Conflict {
+            hotkey: parse("Ctrl+Alt+S").unwrap(),
+            kept: HotkeyAction::ForceStereo,
+            dropped: HotkeyAction::Reconnect,
+        }]
+    );
+    assert_eq!(
+        problems[0].to_string(),
+        "Ctrl+Alt+S is set for both hotkeys.force_stereo and hotkeys.reconnect; only the first is used"
+    );
+}
+
+#[test]
+fn test_bindings_report_invalid() {
+    let config = HotkeysConfig {
+        force_stereo: "Ctrl+Alt+Nope".to_string(),
+        reconnect: "Ctrl+Alt+R".to_string(),
+        ..Default::default()
+    };
+    let (bound, problems) = bindings(&config);
+    assert_eq!(bound, vec![(HotkeyAction::Reconnect, parse("Ctrl+Alt+R").unwrap())]);
+    assert_eq!(problems.len(), 1);
+    assert_eq!(
+        problems[0].to_string(),
+        "hotkeys.force_stereo = 'Ctrl+Alt+Nope': unknown key 'Nope'"
+    );
+}
+
+#[test]
+fn test_actions_map_to_menu_events() {
+    let name = "WH-1000XM4".to_string();
+    assert!(matches!(
+        HotkeyAction::ForceStereo.menu_event(name.clone()),
+        MenuEvent::ForceStereo(d) if d == name
+    ));
+    assert!(matches!(
+        HotkeyAction::AllowHandsFree.menu_event(name.clone()),
+        MenuEvent::AllowHandsFree(d) if d == name
+    ));
+    assert!(matches!(
+        HotkeyAction::Reconnect.menu_event(name.clone()),
+        MenuEvent::ReconnectDevice(d) if d == name
+    ));
+}
+
+#[test]
+fn test_active_device() {
+    let devices = [device("Buds"), device("WH-1000XM4")];
+    assert_eq!(active_device(Some("WH-1000XM4"), &devices), Some("WH-1000XM4".to_string()));
+    // Default output is not a Bluetooth headset
+    assert_eq!(active_device(Some("Speakers"), &devices), Some("Buds".to_string()));
+    assert_eq!(active_device(None, &devices), Some("Buds".to_string()));
+    assert_eq!(active_device(Some("Buds"), &[]), None);
+}
@@ -0,0 +1,252 @@
+//! Tests for the loopback HTTP endpoint, over real TCP connections
+
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use win_bt_stereo_vs_handsfree::audio::{AudioMode, MonitorEvent};
+use win_bt_stereo_vs_handsfree::cli::{ActionReport, CliBackend, CliError, DeviceEntry, StatusReport};
+use win_bt_stereo_vs_handsfree::http::server::HttpServer;
+use win_bt_stereo_vs_handsfree::ipc::{dispatch, IpcCall};
+use win_bt_stereo_vs_handsfree::rpc::EventHub;
+
+const TOKEN: &str = "fedcba9876543210";
+
+/// Stands in for the tray: one paired headset, records force-stereo calls
+struct FakeTray {
+    forced: Arc<Mutex<Vec<String>>>,
+}
+
+impl CliBackend for FakeTray {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        Ok(StatusReport {
+            mode: "stereo".to_string(),
+            devices: vec![],
+            mic_apps: vec![],
+            hfp_apps: vec![],
+        })
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        Ok(vec![DeviceEntry {
+            name: "WH-1000XM4".to_string(),
+            address: "AA:BB:CC:DD:EE:FF".to_string(),
+            device_type: "headphones".to_string(),
+            connected: true,
+            hands_free_enabled: true,
+            profiles: vec![],
+            last_seen: None,
+            last_used: None,
+        }])
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        if device != "WH-1000XM4" {
+            return Err(CliError::NotFound(format!("No unique paired device matches '{}'", device)));
+        }
+        self.forced.lock().unwrap().push(device.to_string());
+        Ok(ActionReport {
+            action: "force-stereo".to_string(),
+            target: Some(device.to_string()),
+            message: format!("Hands-free disabled for '{}'", device),
+        })
+    }
+
+    fn allow_hands_free(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn reconnect(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn mute(&mut self, _pid: u32) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn unmute(&mut self, _pid: u32) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+}
+
+struct Harness {
+    server: HttpServer,
+    hub: EventHub,
+    forced: Arc<Mutex<Vec<String>>>,
+}
+
+impl Harness {
+    fn start() -> Self {
+        let (call_tx, call_rx) = mpsc::channel::<IpcCall>();
+        let forced = Arc::new(Mutex::new(Vec::new()));
+        let mut tray = FakeTray {
+            forced: Arc::clone(&forced),
+        };
+        thread::spawn(move || {
+            for call in call_rx {
+                let response = dispatch(&call.request, &mut tray);
+                call.reply(response);
+            }
+        });
+
+        let hub = EventHub::new();
+        let server = HttpServer::start(
+            "127.0.0.1:0".parse().unwrap(),
+            TOKEN.to_string(),
+            call_tx,
+            hub.clone(),
+        )
+        .unwrap();
+        Self { server, hub, forced }
+    }
+
+    fn connect(&self, request: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(self.server.local_addr()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        stream
+    }
+
+    /// Send a request and return status code and body
+    fn send(&self, request: &str) -> (u16, String) {
+        let mut response = String::new();
+        self.connect(request).read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        assert!(head.contains("Access-Control-Allow-Origin: *\r\n"));
+        (status, body.to_string())
+    }
+}
+
+fn json(body: &str) -> Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[test]
+fn test_status_requires_bearer_token() {
+    let harness = Harness::start();
+
+    let (status, body) = harness.send("GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    assert_eq!(status, 401);
+    assert_eq!(json(&body)["error"]["kind"], "unauthorized");
+
+    let (status, _) = harness.send("GET /status HTTP/1.1\r\nAuthorization: Bearer nope\r\n\r\n");
+    assert_eq!(status, 401);
+
+    let (status, body) = harness.send(&format!("GET /status HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n", TOKEN));
+    assert_eq!(status, 200);
+    assert_eq!(json(&body)["mode"], "stereo");
+
+    let (status, _) = harness.send(&format!("GET /status?access_token={} HTTP/1.1\r\n\r\n", TOKEN));
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn test_force_stereo_by_name_or_address() {
+    let harness = Harness::start();
+    let auth = format!("Authorization: Bearer {}\r\n", TOKEN);
+
+    let (status, body) = harness.send(&format!(
+        "POST /devices/AA%3ABB%3ACC%3ADD%3AEE%3AFF/force-stereo HTTP/1.1\r\n{}Content-Length: 2\r\n\r\n{{}}",
+        auth
+    ));
+    assert_eq!(status, 200);
+    assert_eq!(json(&body)["target"], "WH-1000XM4");
+
+    let (status, _) = harness.send(&format!("POST /devices/WH-1000XM4/force-stereo HTTP/1.1\r\n{}\r\n", auth));
+    assert_eq!(status, 200);
+
+    let (status, body) = harness.send(&format!("POST /devices/AirPods%20Pro/force-stereo HTTP/1.1\r\n{}\r\n", auth));
+    assert_eq!(status, 404);
+    assert_eq!(json(&body)["error"]["message"], "No unique paired device matches 'AirPods Pro'");
+
+    assert_eq!(*harness.forced.lock().unwrap(), ["WH-1000XM4", "WH-1000XM4"]);
+}
+
+#[test]
+fn test_routing_errors() {
+    let harness = Harness::start();
+    let auth = format!("Authorization: Bearer {}\r\n", TOKEN);
+
+    let (status, _) = harness.send(&format!("GET /nope HTTP/1.1\r\n{}\r\n", auth));
+    assert_eq!(status, 404);
+
+    let (status, _) = harness.send(&format!("POST /status HTTP/1.1\r\n{}\r\n", auth));
+    assert_eq!(status, 405);
+
+    let (status, _) = harness.send(&format!("GET /devices/WH-1000XM4/force-stereo HTTP/1.1\r\n{}\r\n", auth));
+    assert_eq!(status, 405);
+
+    let (status, _) = harness.send("garbage\r\n\r\n");
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn test_events_stream_mode_changes() {
+    let harness = Harness::start();
+    let stream = harness.connect(&format!("GET /events?access_token={} HTTP/1.1\r\n\r\n", TOKEN));
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "HTTP/1.1 200 OK\r\n");
+
+    // Skip headers, then the initial status event
+    let mut lines = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        if line.starts_with("data:") {
+            lines.push(line.clone());
+            break;
+        }
+    }
+    assert!(lines[0].contains(r#""mode":"stereo""#));
+
+    // Not streamed over HTTP
+    harness
+        .hub
+        .publish(&MonitorEvent::ForceStereoReapplied("WH-1000XM4".to_string()));
+    harness.hub.publish(&MonitorEvent::ModeChanged {
+        old_mode: AudioMode::Stereo,
+        new_mode: AudioMode::HandsFree,
+    });
+
+    let mut event = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        if line == "\n" && !event.is_empty() {
+            break;
+        }
+        if line != "\n" {
+            event.push(line.trim_end().to_string());
+        }
+    }
+    assert_eq!(
+        event,
+        [
+            "event: modeChanged",
+            r#"data: {"new_mode":"hands-free","old_mode":"stereo"}"#
+        ]
+    );
+}
+
+#[test]
+fn test_listener_is_loopback_only() {
+    let (call_tx, _call_rx) = mpsc::channel();
+    let result = HttpServer::start("0.0.0.0:0".parse().unwrap(), TOKEN.to_string(), call_tx, EventHub::new());
+    assert!(result.is_err());
+}
@@ -0,0 +1,161 @@
+//! Tests for forwarding CLI commands to the running instance, without pipes
+
+use win_bt_stereo_vs_handsfree::cli::{
+    exit_code, execute, parse_args, ActionReport, CliBackend, CliError, CliOutcome, DeviceEntry,
+    StatusReport,
+};
+use win_bt_stereo_vs_handsfree::ipc::{dispatch, RemoteBackend, Transport};
+
+/// Stands in for the tray instance: records forwarded commands
+#[derive(Default)]
+struct TrayStandIn {
+    forced_stereo: Vec<String>,
+    settings_opened: bool,
+}
+
+impl CliBackend for TrayStandIn {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        Ok(StatusReport {
+            mode: "stereo".to_string(),
+            devices: vec![],
+            mic_apps: vec![],
+            hfp_apps: vec![],
+        })
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        Ok(vec![])
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        if !"Jabra Evolve2 65".to_lowercase().contains(&device.to_lowercase()) {
+            return Err(CliError::NotFound(format!("No unique paired device matches '{}'", device)));
+        }
+        self.forced_stereo.push("Jabra Evolve2 65".to_string());
+        Ok(ActionReport {
+            action: "force-stereo".to_string(),
+            target: Some("Jabra Evolve2 65".to_string()),
+            message: "Hands-free disabled for 'Jabra Evolve2 65'".to_string(),
+        })
+    }
+
+    fn allow_hands_free(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("access denied".to_string()))
+    }
+
+    fn reconnect(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn mute(&mut self, _pid: u32) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn unmute(&mut self, _pid: u32) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        self.settings_opened = true;
+        Ok(ActionReport {
+            action: "open-settings".to_string(),
+            target: None,
+            message: "Settings opened".to_string(),
+        })
+    }
+}
+
+/// Transport that hands requests straight to the dispatcher
+struct Loopback<'a> {
+    tray: &'a mut TrayStandIn,
+    requests: Vec<String>,
+}
+
+impl Transport for Loopback<'_> {
+    fn round_trip(&mut self, request: &str) -> Result<String, CliError> {
+        self.requests.push(request.to_string());
+        Ok(dispatch(request, &mut *self.tray))
+    }
+}
+
+/// Transport that always returns the same response line
+struct Canned(&'static str);
+
+impl Transport for Canned {
+    fn round_trip(&mut self, _request: &str) -> Result<String, CliError> {
+        Ok(self.0.to_string())
+    }
+}
+
+fn forward(args: &[&str], tray: &mut TrayStandIn) -> (CliOutcome, Vec<String>) {
+    let args = parse_args(args).unwrap().unwrap();
+    let mut transport = Loopback { tray, requests: Vec::new() };
+    let outcome = {
+        let mut backend = RemoteBackend::new(&mut transport);
+        execute(&args, &mut backend)
+    };
+    (outcome, transport.requests)
+}
+
+#[test]
+fn test_force_stereo_is_forwarded() {
+    let mut tray = TrayStandIn::default();
+    let (outcome, requests) = forward(&["force-stereo", "Jabra"], &mut tray);
+
+    assert_eq!(requests, [r#"{"version":1,"command":"force-stereo","device":"Jabra"}"#]);
+    assert_eq!(tray.forced_stereo, ["Jabra Evolve2 65"]);
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+    assert_eq!(outcome.stdout, "Hands-free disabled for 'Jabra Evolve2 65'\n");
+}
+
+#[test]
+fn test_open_settings_is_forwarded() {
+    let mut tray = TrayStandIn::default();
+    let (outcome, _) = forward(&["open-settings"], &mut tray);
+    assert!(tray.settings_opened);
+    assert_eq!(outcome.exit_code, exit_code::SUCCESS);
+}
+
+#[test]
+fn test_remote_errors_keep_exit_codes() {
+    let mut tray = TrayStandIn::default();
+
+    let (outcome, _) = forward(&["force-stereo", "AirPods"], &mut tray);
+    assert_eq!(outcome.exit_code, exit_code::NOT_FOUND);
+
+    let (outcome, _) = forward(&["allow-hands-free", "Jabra", "--json"], &mut tray);
+    assert_eq!(outcome.exit_code, exit_code::FAILURE);
+    let value: serde_json::Value = serde_json::from_str(&outcome.stdout).unwrap();
+    assert_eq!(value["error"]["message"], "access denied");
+}
+
+#[test]
+fn test_status_report_survives_round_trip() {
+    let mut tray = TrayStandIn::default();
+    let (remote, _) = forward(&["status", "--json"], &mut tray);
+    let local = execute(&parse_args(&["status", "--json"]).unwrap().unwrap(), &mut TrayStandIn::default());
+    assert_eq!(remote, local);
+}
+
+#[test]
+fn test_version_mismatch_is_reported() {
+    let mut backend = RemoteBackend::new(Canned(
+        r#"{"version":2,"result":"action","data":{"action":"restore","message":"ok"}}"#,
+    ));
+    let outcome = execute(&parse_args(&["restore"]).unwrap().unwrap(), &mut backend);
+    assert_eq!(outcome.exit_code, exit_code::UNAVAILABLE);
+    assert!(outcome.stderr.contains("protocol version 2"));
+}
+
+#[test]
+fn test_malformed_request_gets_error_response() {
+    let response = dispatch("not json", &mut TrayStandIn::default());
+    let value: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(value["version"], 1);
+    assert_eq!(value["result"], "error");
+    assert_eq!(value["data"]["kind"], "usage");
+}
@@ -0,0 +1,93 @@
+//! Snapshot tests for the OpenMetrics output and the metrics endpoint
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use win_bt_stereo_vs_handsfree::audio::{AudioDevice, AudioMode, BluetoothAudioDevice};
+use win_bt_stereo_vs_handsfree::metrics::server::MetricsServer;
+use win_bt_stereo_vs_handsfree::metrics::{ForceStereoReason, Metrics, ReconnectSource, CONTENT_TYPE};
+
+const EMPTY: &str = include_str!("fixtures/metrics_empty.txt");
+const POPULATED: &str = include_str!("fixtures/metrics_populated.txt");
+
+fn device(name: &str, mode: AudioMode) -> BluetoothAudioDevice {
+    BluetoothAudioDevice {
+        current_mode: mode,
+        ..BluetoothAudioDevice::new(AudioDevice {
+            id: name.to_string(),
+            name: name.to_string(),
+            is_bluetooth: true,
+        })
+    }
+}
+
+fn populated() -> Metrics {
+    let metrics = Metrics::new();
+    metrics.set_devices(&[
+        device("WH-1000XM4", AudioMode::HandsFree),
+        device("Bob's \"Buds\"", AudioMode::Stereo),
+    ]);
+    metrics.record_mode_transition(AudioMode::Stereo, AudioMode::HandsFree);
+    metrics.record_mode_transition(AudioMode::HandsFree, AudioMode::Stereo);
+    metrics.record_mode_transition(AudioMode::Stereo, AudioMode::HandsFree);
+    metrics.record_force_stereo(ForceStereoReason::User);
+    metrics.record_force_stereo(ForceStereoReason::Reconnect);
+    metrics.record_force_stereo(ForceStereoReason::Reconnect);
+    metrics.record_reconnect(ReconnectSource::User, true);
+    metrics.record_reconnect(ReconnectSource::Watchdog, false);
+    for millis in [250, 500, 2000] {
+        metrics.observe_poll_duration(Duration::from_millis(millis));
+    }
+    metrics
+}
+
+#[test]
+fn test_empty_snapshot() {
+    assert_eq!(Metrics::new().render(), EMPTY);
+}
+
+#[test]
+fn test_populated_snapshot() {
+    assert_eq!(populated().render(), POPULATED);
+}
+
+#[test]
+fn test_disconnected_devices_are_dropped() {
+    let metrics = populated();
+    metrics.set_devices(&[device("WH-1000XM4", AudioMode::Stereo)]);
+    let text = metrics.render();
+    assert!(text.contains("btam_device_mode{device=\"WH-1000XM4\",mode=\"stereo\"} 1\n"));
+    assert!(!text.contains("Buds"));
+}
+
+fn get(server: &MetricsServer, request: &str) -> String {
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_endpoint_serves_openmetrics() {
+    let server = MetricsServer::start("127.0.0.1:0".parse().unwrap(), populated()).unwrap();
+
+    let response = get(&server, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains(&format!("Content-Type: {}\r\n", CONTENT_TYPE)));
+    assert_eq!(body, POPULATED);
+    // Unauthenticated, so other web pages must not be able to read it
+    assert!(!head.contains("Access-Control-Allow-Origin"));
+
+    assert!(get(&server, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    let response = get(&server, "POST /metrics HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 405"));
+    assert!(response.contains("Allow: GET\r\n"));
+}
+
+#[test]
+fn test_listener_is_loopback_only() {
+    assert!(MetricsServer::start("0.0.0.0:0".parse().unwrap(), Metrics::new()).is_err());
+}
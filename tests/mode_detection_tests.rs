@@ -1,7 +1,28 @@
 //! Integration tests for mode detection
 //! Tests requiring real audio devices are marked with #[ignore] for CI
 
-use win_bt_stereo_vs_handsfree::audio::device::AudioMode;
+use win_bt_stereo_vs_handsfree::audio::backend::{AudioPoll, Endpoint};
+use win_bt_stereo_vs_handsfree::audio::device::{AudioDevice, AudioMode};
+use win_bt_stereo_vs_handsfree::audio::monitor::detect_state;
+use win_bt_stereo_vs_handsfree::audio::session::MicUsingApp;
+
+fn endpoint(name: &str, is_bluetooth: bool, meter_channels: Option<u32>) -> Endpoint {
+    let mut endpoint = Endpoint::new(AudioDevice {
+        id: format!("{{{}}}", name),
+        name: name.to_string(),
+        is_bluetooth,
+    });
+    endpoint.sample_rate = Some(48000);
+    endpoint.channels = Some(2);
+    endpoint.meter_channels = meter_channels;
+    endpoint
+}
+
+fn bluetooth_mic_app() -> MicUsingApp {
+    let mut app = MicUsingApp::new(42, "Teams.exe".to_string(), "Microsoft Teams".to_string());
+    app.is_using_bluetooth_mic = true;
+    app
+}
 
 #[test]
 fn test_audio_mode_display() {
@@ -41,11 +62,67 @@ fn test_mode_detection_logic() {
     assert_eq!(detect_mode(false), AudioMode::Stereo);
 }
 
+#[test]
+fn test_detect_state_mono_meter_is_hands_free() {
+    let poll = AudioPoll {
+        endpoints: vec![endpoint("Speakers", false, None), endpoint("Headset", true, Some(1))],
+        mic_apps: Vec::new(),
+    };
+
+    let (mode, _, devices) = detect_state(poll);
+    assert_eq!(mode, AudioMode::HandsFree);
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].device.name, "Headset");
+    assert_eq!(devices[0].current_mode, AudioMode::HandsFree);
+}
+
+#[test]
+fn test_detect_state_stereo_meter_wins_over_bluetooth_mic() {
+    let poll = AudioPoll {
+        endpoints: vec![endpoint("Headset", true, Some(2))],
+        mic_apps: vec![bluetooth_mic_app()],
+    };
+
+    let (mode, mic_apps, devices) = detect_state(poll);
+    assert_eq!(mode, AudioMode::Stereo);
+    assert_eq!(mic_apps.len(), 1);
+    assert_eq!(devices[0].current_mode, AudioMode::Stereo);
+    assert_eq!(devices[0].sample_rate, Some(48000));
+}
+
+#[test]
+fn test_detect_state_falls_back_to_bluetooth_mic() {
+    let with_mic = AudioPoll {
+        endpoints: vec![endpoint("Headset", true, None)],
+        mic_apps: vec![bluetooth_mic_app()],
+    };
+    assert_eq!(detect_state(with_mic).0, AudioMode::HandsFree);
+
+    let without_mic = AudioPoll {
+        endpoints: vec![endpoint("Headset", true, None)],
+        mic_apps: Vec::new(),
+    };
+    assert_eq!(detect_state(without_mic).0, AudioMode::Stereo);
+}
+
+#[test]
+fn test_detect_state_without_bluetooth_is_unknown() {
+    let poll = AudioPoll {
+        endpoints: vec![endpoint("Speakers", false, None)],
+        mic_apps: vec![bluetooth_mic_app()],
+    };
+
+    let (mode, _, devices) = detect_state(poll);
+    assert_eq!(mode, AudioMode::Unknown);
+    assert!(devices.is_empty());
+}
+
 /// Test with real audio devices - requires hardware
+#[cfg(windows)]
 #[test]
 #[ignore]
 fn test_real_device_enumeration() {
-    use win_bt_stereo_vs_handsfree::audio::device::DeviceManager;
+    use win_bt_stereo_vs_handsfree::audio::wasapi::DeviceManager;
     use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED};
 
     unsafe {
@@ -66,10 +143,11 @@ fn test_real_device_enumeration() {
 }
 
 /// Test with real Bluetooth devices - requires hardware
+#[cfg(windows)]
 #[test]
 #[ignore]
 fn test_real_bluetooth_device_detection() {
-    use win_bt_stereo_vs_handsfree::audio::device::DeviceManager;
+    use win_bt_stereo_vs_handsfree::audio::wasapi::DeviceManager;
     use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED};
 
     unsafe {
@@ -89,10 +167,11 @@ fn test_real_bluetooth_device_detection() {
 }
 
 /// Test real mic usage detection - requires hardware
+#[cfg(windows)]
 #[test]
 #[ignore]
 fn test_real_mic_usage_detection() {
-    use win_bt_stereo_vs_handsfree::audio::session::CaptureSessionManager;
+    use win_bt_stereo_vs_handsfree::audio::wasapi::CaptureSessionManager;
     use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED};
 
     unsafe {
@@ -0,0 +1,477 @@
+//! Tests for the MQTT client against an in-process broker stand-in
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use win_bt_stereo_vs_handsfree::audio::{AudioDevice, AudioMode, BluetoothAudioDevice, MicUsingApp};
+use win_bt_stereo_vs_handsfree::cli::{ActionReport, CliBackend, CliError, DeviceEntry, DirectBackend, StatusReport};
+use win_bt_stereo_vs_handsfree::ipc::{dispatch, IpcCall};
+use win_bt_stereo_vs_handsfree::mqtt::client::{MqttClient, MqttSettings};
+use win_bt_stereo_vs_handsfree::mqtt::codec::{decode, encode, Packet};
+use win_bt_stereo_vs_handsfree::mqtt::{PublishedDevice, PublishedState, StatePublisher, Topics};
+use win_bt_stereo_vs_handsfree::platform::Backends;
+use win_bt_stereo_vs_handsfree::settings::MqttConfig;
+use win_bt_stereo_vs_handsfree::simulation::{SimulatedBackend, Timeline};
+
+const BASE: &str = "btam";
+
+/// What the broker stand-in saw
+#[derive(Default)]
+struct BrokerLog {
+    connects: Vec<Packet>,
+    subscriptions: Vec<String>,
+    publishes: Vec<(String, String)>,
+    retained: HashMap<String, String>,
+    disconnects: usize,
+}
+
+/// Accepts one client at a time, records its packets and can push publishes to it
+struct Broker {
+    addr: SocketAddr,
+    log: Arc<Mutex<BrokerLog>>,
+    inject_tx: Sender<Packet>,
+    kick: Arc<AtomicBool>,
+}
+
+impl Broker {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let log = Arc::new(Mutex::new(BrokerLog::default()));
+        let (inject_tx, inject_rx) = mpsc::channel();
+        let kick = Arc::new(AtomicBool::new(false));
+
+        let broker_log = Arc::clone(&log);
+        let broker_kick = Arc::clone(&kick);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                serve(stream.unwrap(), &broker_log, &inject_rx, &broker_kick);
+            }
+        });
+        Self {
+            addr,
+            log,
+            inject_tx,
+            kick,
+        }
+    }
+
+    fn retained(&self, topic: &str) -> Option<String> {
+        self.log.lock().unwrap().retained.get(topic).cloned()
+    }
+
+    fn publish_count(&self, topic: &str) -> usize {
+        self.log.lock().unwrap().publishes.iter().filter(|(t, _)| t == topic).count()
+    }
+
+    fn last_publish(&self, topic: &str) -> Option<String> {
+        let log = self.log.lock().unwrap();
+        log.publishes.iter().rev().find(|(t, _)| t == topic).map(|(_, p)| p.clone())
+    }
+
+    fn send_command(&self, payload: &str) {
+        self.inject_tx
+            .send(Packet::Publish {
+                topic: format!("{}/command", BASE),
+                payload: payload.as_bytes().to_vec(),
+                retain: false,
+            })
+            .unwrap();
+    }
+}
+
+fn serve(mut stream: TcpStream, log: &Mutex<BrokerLog>, inject_rx: &Receiver<Packet>, kick: &AtomicBool) {
+    stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let mut will = None;
+
+    loop {
+        if kick.swap(false, Ordering::SeqCst) {
+            // Dropped without DISCONNECT: the broker publishes the will
+            if let Some((topic, payload)) = will.take() {
+                log.lock().unwrap().retained.insert(topic, payload);
+            }
+            return;
+        }
+        while let Ok(packet) = inject_rx.try_recv() {
+            stream.write_all(&encode(&packet)).unwrap();
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) => return,
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            Err(_) => continue,
+        }
+
+        while let Some((packet, used)) = decode(&buffer).unwrap() {
+            buffer.drain(..used);
+            let reply = {
+                let mut log = log.lock().unwrap();
+                match &packet {
+                    Packet::Connect { will: Some(w), .. } => {
+                        will = Some((w.topic.clone(), String::from_utf8_lossy(&w.payload).into_owned()));
+                    }
+                    Packet::Publish { topic, payload, retain } => {
+                        let payload = String::from_utf8_lossy(payload).into_owned();
+                        if *retain {
+                            log.retained.insert(topic.clone(), payload.clone());
+                        }
+                        log.publishes.push((topic.clone(), payload));
+                    }
+                    Packet::Subscribe { topics, .. } => log.subscriptions.extend(topics.iter().cloned()),
+                    Packet::Disconnect => log.disconnects += 1,
+                    _ => {}
+                }
+                match &packet {
+                    Packet::Connect { .. } => {
+                        log.connects.push(packet.clone());
+                        Some(Packet::ConnAck {
+                            session_present: false,
+                            return_code: 0,
+                        })
+                    }
+                    Packet::Subscribe { packet_id, topics } => Some(Packet::SubAck {
+                        packet_id: *packet_id,
+                        return_codes: vec![0; topics.len()],
+                    }),
+                    Packet::PingReq => Some(Packet::PingResp),
+                    _ => None,
+                }
+            };
+            if let Some(reply) = reply {
+                stream.write_all(&encode(&reply)).unwrap();
+            }
+            if packet == Packet::Disconnect {
+                return;
+            }
+        }
+    }
+}
+
+/// Stands in for the tray: records the actions it was asked to run
+struct FakeTray {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl CliBackend for FakeTray {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        Ok(Vec::new())
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        if device != "WH-1000XM4" {
+            return Err(CliError::NotFound(format!("No unique paired device matches '{}'", device)));
+        }
+        self.calls.lock().unwrap().push(device.to_string());
+        Ok(ActionReport {
+            action: "force-stereo".to_string(),
+            target: Some(device.to_string()),
+            message: format!("Hands-free disabled for '{}'", device),
+        })
+    }
+
+    fn allow_hands_free(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn reconnect(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn mute(&mut self, _pid: u32) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn unmute(&mut self, _pid: u32) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        self.calls.lock().unwrap().push("restore".to_string());
+        Ok(ActionReport {
+            action: "restore".to_string(),
+            target: None,
+            message: "Restored".to_string(),
+        })
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+}
+
+fn topics() -> Topics {
+    Topics {
+        base: BASE.to_string(),
+        discovery_prefix: "homeassistant".to_string(),
+        node_id: "btam_desk".to_string(),
+    }
+}
+
+fn start_client(broker: &Broker) -> (MqttClient, Arc<Mutex<Vec<String>>>) {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let tray_calls = Arc::clone(&calls);
+    let client = start_client_with(broker, move || Box::new(FakeTray { calls: tray_calls }));
+    (client, calls)
+}
+
+/// Client whose commands run on the backend made by `backend` on the dispatch thread
+fn start_client_with(broker: &Broker, backend: impl FnOnce() -> Box<dyn CliBackend> + Send + 'static) -> MqttClient {
+    let (call_tx, call_rx) = mpsc::channel::<IpcCall>();
+    thread::spawn(move || {
+        let mut backend = backend();
+        for call in call_rx {
+            let response = dispatch(&call.request, backend.as_mut());
+            call.reply(response);
+        }
+    });
+
+    let settings = MqttSettings {
+        host: "127.0.0.1".to_string(),
+        port: broker.addr.port(),
+        client_id: "btam-desk".to_string(),
+        username: Some("ha".to_string()),
+        password: Some("secret".to_string()),
+        topics: topics(),
+        discovery: true,
+    };
+    MqttClient::start(settings, call_tx).unwrap()
+}
+
+fn headset(mode: &'static str, battery_level: Option<u8>) -> PublishedState {
+    PublishedState {
+        mode,
+        mic_in_use: false,
+        devices: vec![PublishedDevice {
+            name: "WH-1000XM4".to_string(),
+            mode,
+            battery_level,
+            mic_in_use: false,
+        }],
+    }
+}
+
+fn wait_until(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out waiting for the broker");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_publishes_state_and_discovery() {
+    let broker = Broker::start();
+    let (client, _) = start_client(&broker);
+
+    client.publish_state(headset("stereo", Some(80)));
+    wait_until(|| broker.retained("btam/devices/wh_1000xm4/battery").is_some());
+
+    assert_eq!(broker.retained("btam/status").as_deref(), Some("online"));
+    assert_eq!(broker.retained("btam/mode").as_deref(), Some("stereo"));
+    assert_eq!(broker.retained("btam/mic_in_use").as_deref(), Some("OFF"));
+    assert_eq!(broker.retained("btam/devices/wh_1000xm4/mode").as_deref(), Some("stereo"));
+    assert_eq!(broker.retained("btam/devices/wh_1000xm4/battery").as_deref(), Some("80"));
+
+    {
+        let log = broker.log.lock().unwrap();
+        assert_eq!(log.subscriptions, ["btam/command"]);
+        let Packet::Connect {
+            client_id,
+            username,
+            will,
+            ..
+        } = &log.connects[0]
+        else {
+            panic!("expected CONNECT");
+        };
+        assert_eq!(client_id, "btam-desk");
+        assert_eq!(username.as_deref(), Some("ha"));
+        let will = will.as_ref().unwrap();
+        assert_eq!((will.topic.as_str(), will.payload.as_slice(), will.retain), ("btam/status", &b"offline"[..], true));
+    }
+
+    let button: Value = serde_json::from_str(
+        &broker
+            .retained("homeassistant/button/btam_desk/wh_1000xm4_force_stereo/config")
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(button["command_topic"], "btam/command");
+    assert_eq!(button["payload_press"], r#"{"command":"force-stereo","device":"WH-1000XM4"}"#);
+    assert_eq!(button["availability_topic"], "btam/status");
+    assert_eq!(button["device"]["name"], "WH-1000XM4");
+
+    let battery: Value = serde_json::from_str(
+        &broker
+            .retained("homeassistant/sensor/btam_desk/wh_1000xm4_battery/config")
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(battery["device_class"], "battery");
+    assert_eq!(battery["state_topic"], "btam/devices/wh_1000xm4/battery");
+    assert_eq!(battery["unique_id"], "btam_desk_wh_1000xm4_battery");
+
+    // Unchanged topics are not republished
+    client.publish_state(headset("stereo", Some(75)));
+    wait_until(|| broker.retained("btam/devices/wh_1000xm4/battery").as_deref() == Some("75"));
+    assert_eq!(broker.publish_count("btam/devices/wh_1000xm4/mode"), 1);
+    assert_eq!(broker.publish_count("homeassistant/button/btam_desk/wh_1000xm4_force_stereo/config"), 1);
+}
+
+#[test]
+fn test_command_topic_forces_stereo() {
+    let broker = Broker::start();
+    let (_client, calls) = start_client(&broker);
+    wait_until(|| !broker.log.lock().unwrap().subscriptions.is_empty());
+
+    broker.send_command(r#"{"command":"force-stereo","device":"WH-1000XM4"}"#);
+    wait_until(|| broker.last_publish("btam/command/result").is_some());
+    let result: Value = serde_json::from_str(&broker.last_publish("btam/command/result").unwrap()).unwrap();
+    assert_eq!(result["result"], "action");
+    assert_eq!(result["data"]["target"], "WH-1000XM4");
+    assert_eq!(*calls.lock().unwrap(), ["WH-1000XM4"]);
+
+    broker.send_command("stereo please");
+    wait_until(|| broker.publish_count("btam/command/result") == 2);
+    let result: Value = serde_json::from_str(&broker.last_publish("btam/command/result").unwrap()).unwrap();
+    assert_eq!(result["result"], "error");
+    assert_eq!(calls.lock().unwrap().len(), 1);
+
+    // Only device actions are accepted from the unauthenticated broker
+    broker.send_command(r#"{"command":"restore"}"#);
+    wait_until(|| broker.publish_count("btam/command/result") == 3);
+    let result: Value = serde_json::from_str(&broker.last_publish("btam/command/result").unwrap()).unwrap();
+    assert_eq!(result["result"], "error");
+    assert_eq!(result["data"]["kind"], "usage");
+    assert_eq!(*calls.lock().unwrap(), ["WH-1000XM4"]);
+}
+
+#[test]
+fn test_force_stereo_button_resolves_endpoint_name() {
+    let simulated = Arc::new(SimulatedBackend::new("t=0 WH-1000XM4 connects".parse().unwrap()).with_windows_endpoint_names());
+    simulated.advance_to(Duration::ZERO);
+    let backends = Backends::new(simulated.clone(), simulated.clone());
+    let broker = Broker::start();
+    let client = start_client_with(&broker, move || Box::new(DirectBackend::with_backends(backends).unwrap()));
+    wait_until(|| !broker.log.lock().unwrap().subscriptions.is_empty());
+
+    // The button is named after the audio endpoint, not the paired device
+    let endpoint = BluetoothAudioDevice::new(AudioDevice {
+        id: "1".to_string(),
+        name: "Headphones (WH-1000XM4)".to_string(),
+        is_bluetooth: true,
+    });
+    client.publish_state(PublishedState::new(AudioMode::Stereo, &[endpoint], &[]));
+    let button = "homeassistant/button/btam_desk/headphones_wh_1000xm4_force_stereo/config";
+    wait_until(|| broker.retained(button).is_some());
+    let config: Value = serde_json::from_str(&broker.retained(button).unwrap()).unwrap();
+
+    broker.send_command(config["payload_press"].as_str().unwrap());
+    wait_until(|| broker.last_publish("btam/command/result").is_some());
+    let result: Value = serde_json::from_str(&broker.last_publish("btam/command/result").unwrap()).unwrap();
+    assert_eq!(result["result"], "action");
+    assert_eq!(result["data"]["target"], "WH-1000XM4");
+    assert_eq!(simulated.actions(), ["disable_hfp WH-1000XM4"]);
+}
+
+#[test]
+fn test_shutdown_publishes_offline() {
+    let broker = Broker::start();
+    let (mut client, _) = start_client(&broker);
+    wait_until(|| broker.retained("btam/status").is_some());
+
+    client.shutdown();
+    assert_eq!(broker.retained("btam/status").as_deref(), Some("offline"));
+    assert_eq!(broker.log.lock().unwrap().disconnects, 1);
+}
+
+#[test]
+fn test_reconnects_and_republishes() {
+    let broker = Broker::start();
+    let (client, _) = start_client(&broker);
+    client.publish_state(headset("hands-free", None));
+    wait_until(|| broker.retained("btam/mode").is_some());
+
+    broker.kick.store(true, Ordering::SeqCst);
+    wait_until(|| broker.log.lock().unwrap().connects.len() == 2 && broker.publish_count("btam/mode") == 2);
+    assert_eq!(broker.retained("btam/status").as_deref(), Some("online"));
+    assert_eq!(broker.retained("btam/devices/wh_1000xm4/mode").as_deref(), Some("hands-free"));
+}
+
+#[test]
+fn test_snapshot_and_departed_devices() {
+    let device = |name: &str, mode: AudioMode| BluetoothAudioDevice {
+        current_mode: mode,
+        battery_level: Some(50),
+        ..BluetoothAudioDevice::new(AudioDevice {
+            id: name.to_string(),
+            name: name.to_string(),
+            is_bluetooth: true,
+        })
+    };
+    let teams = MicUsingApp {
+        process_id: 4242,
+        process_name: "Teams.exe".to_string(),
+        display_name: "Microsoft Teams".to_string(),
+        icon_path: None,
+        is_muted: false,
+        is_using_bluetooth_mic: true,
+    };
+
+    let snapshot = PublishedState::new(
+        AudioMode::HandsFree,
+        &[device("WH-1000XM4", AudioMode::HandsFree), device("Buds", AudioMode::Stereo)],
+        &[teams],
+    );
+    assert_eq!(snapshot.mode, "hands-free");
+    assert!(snapshot.mic_in_use);
+    assert!(snapshot.devices[0].mic_in_use);
+    assert!(!snapshot.devices[1].mic_in_use);
+
+    let mut publisher = StatePublisher::new(topics(), false);
+    assert_eq!(publisher.messages(&snapshot).len(), 2 + 2 * 3);
+
+    let gone = PublishedState::new(AudioMode::Stereo, &[device("Buds", AudioMode::Stereo)], &[]);
+    let changed: Vec<(String, String)> = publisher
+        .messages(&gone)
+        .into_iter()
+        .map(|m| (m.topic, m.payload))
+        .collect();
+    assert_eq!(
+        changed,
+        [
+            ("btam/mode".to_string(), "stereo".to_string()),
+            ("btam/mic_in_use".to_string(), "OFF".to_string()),
+            ("btam/devices/wh_1000xm4/mode".to_string(), "disconnected".to_string()),
+            ("btam/devices/wh_1000xm4/mic_in_use".to_string(), "OFF".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_mqtt_config_defaults() {
+    let config = MqttConfig::default();
+    assert!(!config.enabled);
+    let settings = MqttSettings::from_config(&MqttConfig {
+        client_id: "Desk PC".to_string(),
+        base_topic: "home/btam/".to_string(),
+        ..config
+    });
+    assert_eq!(settings.port, 1883);
+    assert_eq!(settings.username, None);
+    assert_eq!(settings.topics.base, "home/btam");
+    assert_eq!(settings.topics.node_id, "desk_pc");
+    assert!(settings.discovery);
+}
@@ -0,0 +1,200 @@
+//! Tests for the PipeWire/PulseAudio backend against recorded `pactl` output
+//!
+//! Each directory under `fixtures/pactl` holds the JSON printed by
+//! `pactl --format=json list <kind>` on a real system plus the output of
+//! `pactl get-default-source`.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use win_bt_stereo_vs_handsfree::audio::backend::AudioBackend;
+use win_bt_stereo_vs_handsfree::audio::device::AudioMode;
+use win_bt_stereo_vs_handsfree::audio::monitor::detect_state;
+use win_bt_stereo_vs_handsfree::audio::pulse::{Pactl, PulseBackend};
+use win_bt_stereo_vs_handsfree::bluetooth::backend::BluetoothBackend;
+use win_bt_stereo_vs_handsfree::bluetooth::inventory::{BluetoothProfile, DeviceType};
+use win_bt_stereo_vs_handsfree::error::{AppError, Result};
+
+/// Replays a fixture directory and records every command that changes state
+struct FixturePactl {
+    dir: PathBuf,
+    commands: Mutex<Vec<String>>,
+}
+
+impl FixturePactl {
+    fn new(name: &str) -> Arc<Self> {
+        Arc::new(Self {
+            dir: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pactl").join(name),
+            commands: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    fn read(&self, file: &str) -> Result<String> {
+        Ok(std::fs::read_to_string(self.dir.join(file))?)
+    }
+}
+
+impl Pactl for FixturePactl {
+    fn run(&self, args: &[&str]) -> Result<String> {
+        match args {
+            ["--format=json", "list", kind] => self.read(&format!("{}.json", kind)),
+            ["get-default-source"] => self.read("default-source.txt"),
+            [command, ..] if command.starts_with("set-") => {
+                self.commands.lock().unwrap().push(args.join(" "));
+                Ok(String::new())
+            }
+            _ => Err(AppError::AudioSessionError(format!("unexpected pactl {:?}", args))),
+        }
+    }
+}
+
+fn fixture_backend(fixture: &str) -> (PulseBackend, Arc<FixturePactl>) {
+    let pactl = FixturePactl::new(fixture);
+    (PulseBackend::with_pactl(pactl.clone()), pactl)
+}
+
+#[test]
+fn test_a2dp_profile_is_stereo() {
+    let (backend, _) = fixture_backend("pipewire-a2dp");
+    let poll = backend.poll().unwrap();
+
+    let headset = poll.endpoints.iter().find(|e| e.device.is_bluetooth).unwrap();
+    assert_eq!(headset.device.name, "WH-1000XM4");
+    assert_eq!(headset.meter_channels, Some(2));
+    assert_eq!(headset.sample_rate, Some(48000));
+
+    let (mode, mic_apps, devices) = detect_state(poll);
+    assert_eq!(mode, AudioMode::Stereo);
+    assert_eq!(devices.len(), 1);
+
+    // The pavucontrol peak meter and OBS recording a sink monitor are not mic users
+    assert_eq!(mic_apps.len(), 1);
+    assert_eq!(mic_apps[0].process_id, 4242);
+    assert_eq!(mic_apps[0].display_name, "Microsoft Teams");
+    assert!(!mic_apps[0].is_using_bluetooth_mic);
+}
+
+#[test]
+fn test_headset_profile_is_hands_free() {
+    let (backend, _) = fixture_backend("pipewire-hfp");
+    let poll = backend.poll().unwrap();
+
+    let headset = poll.endpoints.iter().find(|e| e.device.is_bluetooth).unwrap();
+    assert_eq!(headset.meter_channels, Some(1));
+    assert_eq!(headset.sample_rate, Some(16000));
+    assert_eq!(headset.channels, Some(1));
+
+    let (mode, mic_apps, devices) = detect_state(poll);
+    assert_eq!(mode, AudioMode::HandsFree);
+    assert_eq!(devices[0].current_mode, AudioMode::HandsFree);
+
+    // Teams has a live stream on the headset and a corked one on the laptop mic
+    assert_eq!(mic_apps.len(), 1);
+    assert!(mic_apps[0].is_using_bluetooth_mic);
+    assert!(!mic_apps[0].is_muted);
+}
+
+#[test]
+fn test_pulseaudio_profile_names() {
+    let (backend, _) = fixture_backend("pulseaudio-hfp");
+    let (mode, mic_apps, _) = detect_state(backend.poll().unwrap());
+    assert_eq!(mode, AudioMode::HandsFree);
+    assert_eq!(mic_apps[0].process_name, "zoom");
+    assert!(mic_apps[0].is_using_bluetooth_mic);
+
+    assert!(backend.is_hfp_enabled("MOMENTUM 4").unwrap());
+    backend.disable_hfp("MOMENTUM 4").unwrap();
+}
+
+#[test]
+fn test_disable_hfp_picks_highest_priority_a2dp_profile() {
+    let (backend, pactl) = fixture_backend("pipewire-hfp");
+    assert!(backend.is_hfp_enabled("WH-1000XM4").unwrap());
+
+    backend.disable_hfp("WH-1000XM4").unwrap();
+    assert_eq!(
+        pactl.commands(),
+        vec!["set-card-profile bluez_card.AC_80_0A_2B_3C_4D a2dp-sink"]
+    );
+}
+
+#[test]
+fn test_enable_hfp_switches_to_headset_profile() {
+    let (backend, pactl) = fixture_backend("pipewire-a2dp");
+    assert!(!backend.is_hfp_enabled("WH-1000XM4").unwrap());
+
+    backend.enable_hfp("WH-1000XM4").unwrap();
+    assert_eq!(
+        pactl.commands(),
+        vec!["set-card-profile bluez_card.AC_80_0A_2B_3C_4D headset-head-unit"]
+    );
+}
+
+#[test]
+fn test_reconnect_cycles_profile() {
+    let (backend, pactl) = fixture_backend("pulseaudio-hfp");
+    backend.reconnect("MOMENTUM").unwrap();
+    assert_eq!(
+        pactl.commands(),
+        vec![
+            "set-card-profile bluez_card.00_1B_66_AA_BB_CC off",
+            "set-card-profile bluez_card.00_1B_66_AA_BB_CC a2dp_sink",
+        ]
+    );
+}
+
+#[test]
+fn test_unknown_device_is_not_found() {
+    let (backend, pactl) = fixture_backend("pipewire-a2dp");
+    assert!(matches!(backend.disable_hfp("Jabra Evolve"), Err(AppError::ConfigError(_))));
+    assert!(pactl.commands().is_empty());
+}
+
+#[test]
+fn test_list_paired_devices() {
+    let (backend, _) = fixture_backend("pipewire-a2dp");
+    let devices = backend.list_paired_devices().unwrap();
+
+    assert_eq!(devices.len(), 1);
+    let device = &devices[0];
+    assert_eq!(device.name, "WH-1000XM4");
+    assert_eq!(device.address.to_string(), "AC:80:0A:2B:3C:4D");
+    assert_eq!(device.device_type, DeviceType::Headset);
+    assert!(device.connected);
+    assert!(device.has_profile(BluetoothProfile::A2dpSink));
+    assert!(device.has_profile(BluetoothProfile::HandsFree));
+}
+
+#[test]
+fn test_set_app_muted() {
+    let (backend, pactl) = fixture_backend("pipewire-hfp");
+    backend.set_app_muted(4242, true).unwrap();
+    // Both Teams capture streams are muted, including the corked one
+    assert_eq!(
+        pactl.commands(),
+        vec!["set-source-output-mute 102 1", "set-source-output-mute 125 1"]
+    );
+
+    assert!(backend.set_app_muted(9999, true).is_err());
+}
+
+#[test]
+fn test_mute_all_mutes_default_source_streams() {
+    let (backend, pactl) = fixture_backend("pipewire-a2dp");
+    backend.mute_all().unwrap();
+    assert_eq!(pactl.commands(), vec!["set-source-output-mute 102 1"]);
+}
+
+#[test]
+fn test_bluetooth_output_apps() {
+    let (backend, _) = fixture_backend("pipewire-a2dp");
+    let apps = backend.bluetooth_output_apps();
+    assert_eq!(apps.len(), 1);
+    assert_eq!(apps[0].display_name, "Spotify");
+
+    let (backend, _) = fixture_backend("pulseaudio-hfp");
+    assert!(backend.bluetooth_output_apps().is_empty());
+}
@@ -0,0 +1,168 @@
+//! Tests for recording audio polls and replaying recordings through the monitor
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use win_bt_stereo_vs_handsfree::audio::backend::AudioBackend;
+use win_bt_stereo_vs_handsfree::audio::device::AudioMode;
+use win_bt_stereo_vs_handsfree::audio::monitor::{MonitorCommand, MonitorEvent, Reply};
+use win_bt_stereo_vs_handsfree::audio::recording::{Recording, RecordingBackend, RecordingLimits, RECORDING_FORMAT};
+use win_bt_stereo_vs_handsfree::audio::watchdog::{WatchdogOutcome, WatchdogSettings};
+use win_bt_stereo_vs_handsfree::simulation::{ReplayBackend, Scenario, ScenarioRunner, SimulatedBackend, Timeline};
+
+const HEADSET: &str = "Headphones (WH-1000XM4)";
+const TEAMS_PID: u32 = 8412;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/recordings").join(name)
+}
+
+fn replay(name: &str) -> ScenarioRunner<ReplayBackend> {
+    ScenarioRunner::with_backend(Arc::new(ReplayBackend::from_file(&fixture(name)).unwrap()))
+}
+
+fn secs(seconds: f64) -> Duration {
+    Duration::from_secs_f64(seconds)
+}
+
+#[test]
+fn test_read_recording() {
+    let recording = Recording::read(&fixture("teams_call_stuck_in_hands_free.jsonl")).unwrap();
+    assert_eq!(recording.header.format, RECORDING_FORMAT);
+    assert_eq!(recording.header.os, "windows");
+    assert_eq!(recording.records.len(), 24);
+    assert_eq!(recording.records[1].elapsed_ms, 503);
+    assert!(recording.records[8].poll.is_none());
+    assert!(recording.records[8].error.as_deref().unwrap().contains("0x88890004"));
+}
+
+#[test]
+fn test_unsupported_format_is_rejected() {
+    let text = "{\"format\":99,\"app_version\":\"9.0.0\",\"os\":\"windows\",\"started\":0}\n";
+    let error = Recording::from_reader(text.as_bytes()).unwrap_err();
+    assert!(error.to_string().contains("format 99"), "{}", error);
+
+    let text = "{\"format\":1,\"app_version\":\"0.3.1\",\"os\":\"windows\",\"started\":0}\n{\"elapsed_ms\":\n";
+    let error = Recording::from_reader(text.as_bytes()).unwrap_err();
+    assert!(error.to_string().contains("line 2"), "{}", error);
+
+    assert!(Recording::from_reader("".as_bytes()).is_err());
+}
+
+#[test]
+fn test_recording_round_trip() {
+    let scenario: Scenario = "t=0 headset connects; t=1s Teams opens capture; t=2s endpoint becomes mono"
+        .parse()
+        .unwrap();
+    let simulated = Arc::new(SimulatedBackend::new(scenario));
+    let path = std::env::temp_dir().join(format!("btam-polls-{}.jsonl", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+    let recorder = RecordingBackend::with_writer(simulated.clone(), Box::new(file)).unwrap();
+
+    let mut polls = Vec::new();
+    for second in 0..3 {
+        simulated.advance_to(Duration::from_secs(second));
+        polls.push(recorder.poll().unwrap());
+    }
+
+    let recording = Recording::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(recording.header.app_version, env!("CARGO_PKG_VERSION"));
+    let recorded: Vec<_> = recording.records.into_iter().map(|r| r.poll.unwrap()).collect();
+    assert_eq!(recorded, polls);
+    assert_eq!(recorded[2].endpoints[1].meter_channels, Some(1));
+}
+
+#[test]
+fn test_recording_rotates_and_keeps_max_files() {
+    let scenario: Scenario = "t=0 headset connects; t=1s Teams opens capture".parse().unwrap();
+    let simulated = Arc::new(SimulatedBackend::new(scenario));
+    let dir = std::env::temp_dir().join(format!("btam-recordings-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("polls-1.jsonl"), "").unwrap();
+
+    let limits = RecordingLimits {
+        max_file_size: 1,
+        max_files: 2,
+    };
+    let recorder = RecordingBackend::create(simulated.clone(), &dir, limits).unwrap();
+    simulated.advance_to(Duration::from_secs(1));
+    for _ in 0..4 {
+        recorder.poll().unwrap();
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+    files.sort();
+    let recordings: Vec<Recording> = files.iter().map(|path| Recording::read(path).unwrap()).collect();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // Every poll after the first goes to a new part; the oldest are pruned
+    assert_eq!(files.len(), 2);
+    assert!(!files.iter().any(|path| path.ends_with("polls-1.jsonl")));
+    for recording in &recordings {
+        assert_eq!(recording.records.len(), 1);
+        assert!(recording.records[0].poll.is_some());
+    }
+}
+
+#[test]
+fn test_replay_detects_hands_free_call() {
+    let mut runner = replay("teams_call_stuck_in_hands_free.jsonl");
+    runner.run_to_end();
+
+    // The meter drops to one channel in the record at 2.515s
+    assert_eq!(runner.mode_changes(), vec![(secs(3.0), AudioMode::Stereo, AudioMode::HandsFree)]);
+    let errors: Vec<Duration> = runner
+        .events()
+        .iter()
+        .filter(|e| matches!(e.event, MonitorEvent::Error(_)))
+        .map(|e| e.at)
+        .collect();
+    assert_eq!(errors, vec![secs(4.5)]);
+
+    let state = runner.state();
+    assert_eq!(state.current_mode, AudioMode::HandsFree);
+    assert_eq!(state.bluetooth_devices[0].device.name, HEADSET);
+    assert!(state.mic_using_apps.is_empty());
+    assert!(runner.backend().is_finished());
+    assert!(runner.backend().actions().is_empty());
+}
+
+#[test]
+fn test_replay_through_watchdog() {
+    let mut runner = replay("teams_call_stuck_in_hands_free.jsonl");
+    runner.send(MonitorCommand::ConfigureWatchdog(Some(WatchdogSettings {
+        timeout: Duration::from_secs(5),
+        cooldown: Duration::from_secs(60),
+        max_attempts: 3,
+    })));
+    runner.run_to_end();
+
+    // Teams is gone from the record at 6.036s, so the headset is stuck from the 6.5s poll
+    let records: Vec<_> = runner
+        .watchdog_records()
+        .into_iter()
+        .map(|(at, record)| (at, record.device, record.outcome))
+        .collect();
+    assert_eq!(
+        records,
+        vec![
+            (secs(11.5), HEADSET.to_string(), WatchdogOutcome::Triggered),
+            (secs(12.0), HEADSET.to_string(), WatchdogOutcome::Succeeded),
+        ]
+    );
+    assert_eq!(runner.backend().actions(), vec![format!("reconnect {}", HEADSET)]);
+}
+
+#[test]
+fn test_replay_mute_commands() {
+    let mut runner = replay("teams_call_stuck_in_hands_free.jsonl");
+    runner.run_until(secs(3.0));
+
+    runner.send(MonitorCommand::MuteApp(TEAMS_PID, Reply::none()));
+    runner.run_until(secs(3.5));
+
+    assert_eq!(runner.backend().actions(), vec![format!("mute {}", TEAMS_PID)]);
+    assert!(runner.state().mic_using_apps[0].is_muted);
+}
@@ -0,0 +1,229 @@
+//! Tests for the retry/backoff policy using a fake clock and a fake error source
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+use win_bt_stereo_vs_handsfree::retry::{
+    classify_win32_error, Clock, ErrorClass, JitterSource, RetryPolicy,
+};
+
+/// Clock that records requested sleeps instead of sleeping
+#[derive(Default)]
+struct FakeClock {
+    sleeps: RefCell<Vec<Duration>>,
+}
+
+impl Clock for FakeClock {
+    fn sleep(&self, duration: Duration) {
+        self.sleeps.borrow_mut().push(duration);
+    }
+}
+
+/// Jitter source returning a fixed sample
+struct FixedJitter(f64);
+
+impl JitterSource for FixedJitter {
+    fn next_sample(&mut self) -> f64 {
+        self.0
+    }
+}
+
+/// Operation that fails with the given Win32 codes before succeeding
+struct FakeErrorSource {
+    failures: VecDeque<u32>,
+    calls: u32,
+}
+
+impl FakeErrorSource {
+    fn new(failures: &[u32]) -> Self {
+        Self {
+            failures: failures.iter().copied().collect(),
+            calls: 0,
+        }
+    }
+
+    fn call(&mut self) -> Result<&'static str, u32> {
+        self.calls += 1;
+        match self.failures.pop_front() {
+            Some(code) => Err(code),
+            None => Ok("done"),
+        }
+    }
+}
+
+const ERROR_BUSY: u32 = 170;
+const ERROR_TIMEOUT: u32 = 1460;
+const ERROR_NOT_FOUND: u32 = 1168;
+
+fn test_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 4,
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(300),
+        multiplier: 2.0,
+        jitter: 0.0,
+    }
+}
+
+#[test]
+fn test_succeeds_first_try_without_sleeping() {
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[]);
+
+    let result = test_policy().run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+
+    assert_eq!(result, Ok("done"));
+    assert_eq!(source.calls, 1);
+    assert!(clock.sleeps.borrow().is_empty());
+}
+
+#[test]
+fn test_retries_transient_errors_with_backoff() {
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[ERROR_BUSY, ERROR_TIMEOUT, ERROR_BUSY]);
+
+    let result = test_policy().run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+
+    assert_eq!(result, Ok("done"));
+    assert_eq!(source.calls, 4);
+    assert_eq!(
+        *clock.sleeps.borrow(),
+        vec![
+            Duration::from_millis(100),
+            Duration::from_millis(200),
+            Duration::from_millis(300), // capped at max_delay
+        ]
+    );
+}
+
+#[test]
+fn test_permanent_error_is_not_retried() {
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[ERROR_NOT_FOUND]);
+
+    let result = test_policy().run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+
+    assert_eq!(result, Err(ERROR_NOT_FOUND));
+    assert_eq!(source.calls, 1);
+    assert!(clock.sleeps.borrow().is_empty());
+}
+
+#[test]
+fn test_permanent_error_after_transient_stops_retrying() {
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[ERROR_BUSY, ERROR_NOT_FOUND]);
+
+    let result = test_policy().run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+
+    assert_eq!(result, Err(ERROR_NOT_FOUND));
+    assert_eq!(source.calls, 2);
+    assert_eq!(clock.sleeps.borrow().len(), 1);
+}
+
+#[test]
+fn test_gives_up_after_max_attempts() {
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[ERROR_BUSY; 10]);
+
+    let result = test_policy().run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+
+    assert_eq!(result, Err(ERROR_BUSY));
+    assert_eq!(source.calls, 4);
+    // No sleep after the final attempt
+    assert_eq!(clock.sleeps.borrow().len(), 3);
+}
+
+#[test]
+fn test_attempt_number_is_passed_to_operation() {
+    let clock = FakeClock::default();
+    let mut attempts = Vec::new();
+
+    let _ = test_policy().run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |attempt| {
+            attempts.push(attempt);
+            Err::<(), u32>(ERROR_BUSY)
+        },
+    );
+
+    assert_eq!(attempts, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn test_jitter_scales_delay_within_bounds() {
+    let policy = RetryPolicy {
+        jitter: 0.5,
+        ..test_policy()
+    };
+
+    // Half the delay is fixed, the other half scales with the sample
+    assert_eq!(policy.delay_for(0, 0.0), Duration::from_millis(50));
+    assert_eq!(policy.delay_for(0, 1.0), Duration::from_millis(100));
+
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[ERROR_BUSY]);
+    let _ = policy.run_with(
+        &clock,
+        &mut FixedJitter(0.0),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+    assert_eq!(*clock.sleeps.borrow(), vec![Duration::from_millis(50)]);
+}
+
+#[test]
+fn test_none_policy_never_retries() {
+    let clock = FakeClock::default();
+    let mut source = FakeErrorSource::new(&[ERROR_BUSY]);
+
+    let result = RetryPolicy::NONE.run_with(
+        &clock,
+        &mut FixedJitter(0.5),
+        "op",
+        |c| classify_win32_error(*c),
+        |_| source.call(),
+    );
+
+    assert_eq!(result, Err(ERROR_BUSY));
+    assert_eq!(source.calls, 1);
+}
+
+#[test]
+fn test_error_class_equality() {
+    assert_eq!(classify_win32_error(ERROR_BUSY), ErrorClass::Transient);
+    assert_ne!(classify_win32_error(ERROR_NOT_FOUND), ErrorClass::Transient);
+}
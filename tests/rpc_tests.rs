@@ -0,0 +1,334 @@
+//! Protocol tests for the JSON-RPC control API, served over loopback TCP
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use win_bt_stereo_vs_handsfree::audio::{AudioMode, MonitorEvent};
+use win_bt_stereo_vs_handsfree::cli::{
+    ActionReport, AppStatus, CliBackend, CliError, DeviceEntry, DeviceStatus, StatusReport,
+};
+use win_bt_stereo_vs_handsfree::ipc::{dispatch, IpcCall};
+use win_bt_stereo_vs_handsfree::rpc::server::RpcServer;
+use win_bt_stereo_vs_handsfree::rpc::{error_code, EventHub};
+
+const TOKEN: &str = "0123456789abcdef";
+
+/// Stands in for the tray: answers forwarded commands and records actions
+struct FakeTray {
+    actions: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeTray {
+    fn action(&self, action: &str, target: String) -> Result<ActionReport, CliError> {
+        self.actions.lock().unwrap().push(format!("{} {}", action, target));
+        Ok(ActionReport {
+            action: action.to_string(),
+            target: Some(target.clone()),
+            message: format!("{} done for {}", action, target),
+        })
+    }
+}
+
+impl CliBackend for FakeTray {
+    fn status(&mut self) -> Result<StatusReport, CliError> {
+        Ok(StatusReport {
+            mode: "hands-free".to_string(),
+            devices: vec![DeviceStatus {
+                name: "Jabra Evolve2 65".to_string(),
+                mode: "hands-free".to_string(),
+                battery_level: Some(80),
+            }],
+            mic_apps: vec![AppStatus {
+                pid: 4242,
+                name: "Teams.exe".to_string(),
+                display_name: "Microsoft Teams".to_string(),
+                muted: Some(false),
+                bluetooth_mic: Some(true),
+            }],
+            hfp_apps: vec![],
+        })
+    }
+
+    fn devices(&mut self) -> Result<Vec<DeviceEntry>, CliError> {
+        Ok(vec![])
+    }
+
+    fn force_stereo(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        if !"jabra evolve2 65".contains(&device.to_lowercase()) {
+            return Err(CliError::NotFound(format!("No unique paired device matches '{}'", device)));
+        }
+        self.action("force-stereo", "Jabra Evolve2 65".to_string())
+    }
+
+    fn allow_hands_free(&mut self, device: &str) -> Result<ActionReport, CliError> {
+        self.action("allow-hands-free", device.to_string())
+    }
+
+    fn reconnect(&mut self, _device: &str) -> Result<ActionReport, CliError> {
+        Err(CliError::Unavailable("Bluetooth is off".to_string()))
+    }
+
+    fn mute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        self.action("mute", pid.to_string())
+    }
+
+    fn unmute(&mut self, pid: u32) -> Result<ActionReport, CliError> {
+        self.action("unmute", pid.to_string())
+    }
+
+    fn restore(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+
+    fn open_settings(&mut self) -> Result<ActionReport, CliError> {
+        Err(CliError::Failed("not supported".to_string()))
+    }
+}
+
+struct Harness {
+    server: RpcServer,
+    hub: EventHub,
+    actions: Arc<Mutex<Vec<String>>>,
+}
+
+impl Harness {
+    /// Start a server plus a thread playing the main loop
+    fn start() -> Self {
+        let (call_tx, call_rx) = mpsc::channel::<IpcCall>();
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let mut tray = FakeTray {
+            actions: Arc::clone(&actions),
+        };
+        thread::spawn(move || {
+            for call in call_rx {
+                let response = dispatch(&call.request, &mut tray);
+                call.reply(response);
+            }
+        });
+
+        let hub = EventHub::new();
+        let server = RpcServer::start_tcp(
+            "127.0.0.1:0".parse().unwrap(),
+            TOKEN.to_string(),
+            call_tx,
+            hub.clone(),
+        )
+        .unwrap();
+
+        Self { server, hub, actions }
+    }
+
+    fn connect(&self) -> Client {
+        let stream = TcpStream::connect(self.server.local_addr()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    fn connect_authenticated(&self) -> Client {
+        let mut client = self.connect();
+        let response = client.call(json!({
+            "jsonrpc": "2.0", "id": 0, "method": "authenticate", "params": { "token": TOKEN }
+        }));
+        assert!(response.get("result").is_some(), "{}", response);
+        client
+    }
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn send_raw(&mut self, line: &str) {
+        self.writer.write_all(line.as_bytes()).unwrap();
+        self.writer.write_all(b"\n").unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn call(&mut self, request: Value) -> Value {
+        self.send_raw(&request.to_string());
+        self.receive()
+    }
+
+    fn request(&mut self, id: u64, method: &str, params: Value) -> Value {
+        self.call(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+}
+
+fn error_code_of(response: &Value) -> i64 {
+    response["error"]["code"].as_i64().unwrap_or_else(|| panic!("not an error: {}", response))
+}
+
+#[test]
+fn test_methods_require_authentication() {
+    let harness = Harness::start();
+    let mut client = harness.connect();
+
+    let response = client.request(1, "getState", json!({}));
+    assert_eq!(response["id"], 1);
+    assert_eq!(error_code_of(&response), error_code::UNAUTHORIZED);
+
+    let response = client.request(2, "authenticate", json!({ "token": "wrong" }));
+    assert_eq!(error_code_of(&response), error_code::UNAUTHORIZED);
+
+    let response = client.request(3, "authenticate", json!({ "token": TOKEN }));
+    assert_eq!(response["jsonrpc"], "2.0");
+    assert_eq!(response["result"]["protocol_version"], 1);
+
+    let response = client.request(4, "getState", json!({}));
+    assert_eq!(response["result"]["mode"], "hands-free");
+}
+
+#[test]
+fn test_malformed_messages() {
+    let harness = Harness::start();
+    let mut client = harness.connect_authenticated();
+
+    client.send_raw("{not json");
+    let response = client.receive();
+    assert_eq!(error_code_of(&response), error_code::PARSE_ERROR);
+    assert_eq!(response["id"], Value::Null);
+
+    client.send_raw(r#"[{"jsonrpc":"2.0","id":1,"method":"getState"}]"#);
+    assert_eq!(error_code_of(&client.receive()), error_code::INVALID_REQUEST);
+
+    let response = client.call(json!({ "id": 7, "method": "getState" }));
+    assert_eq!(error_code_of(&response), error_code::INVALID_REQUEST);
+    assert_eq!(response["id"], 7);
+
+    let response = client.request(8, "teleport", json!({}));
+    assert_eq!(error_code_of(&response), error_code::METHOD_NOT_FOUND);
+}
+
+#[test]
+fn test_invalid_params() {
+    let harness = Harness::start();
+    let mut client = harness.connect_authenticated();
+
+    let response = client.request(1, "forceStereo", json!({}));
+    assert_eq!(error_code_of(&response), error_code::INVALID_PARAMS);
+
+    let response = client.request(2, "muteApp", json!({ "pid": "Teams" }));
+    assert_eq!(error_code_of(&response), error_code::INVALID_PARAMS);
+
+    let response = client.request(3, "forceStereo", json!(["Jabra"]));
+    assert_eq!(error_code_of(&response), error_code::INVALID_PARAMS);
+
+    let response = client.request(4, "subscribe", json!({ "events": ["teleported"] }));
+    assert_eq!(error_code_of(&response), error_code::INVALID_PARAMS);
+}
+
+#[test]
+fn test_get_state_matches_status_report() {
+    let harness = Harness::start();
+    let mut client = harness.connect_authenticated();
+
+    let response = client.request(1, "getState", json!({}));
+    assert_eq!(
+        response["result"],
+        json!({
+            "mode": "hands-free",
+            "devices": [{ "name": "Jabra Evolve2 65", "mode": "hands-free", "battery_level": 80 }],
+            "mic_apps": [{
+                "pid": 4242,
+                "name": "Teams.exe",
+                "display_name": "Microsoft Teams",
+                "muted": false,
+                "bluetooth_mic": true,
+            }],
+            "hfp_apps": [],
+        })
+    );
+}
+
+#[test]
+fn test_actions_and_error_mapping() {
+    let harness = Harness::start();
+    let mut client = harness.connect_authenticated();
+
+    let response = client.request(1, "forceStereo", json!({ "device": "jabra" }));
+    assert_eq!(response["result"]["target"], "Jabra Evolve2 65");
+
+    let response = client.request(2, "unmuteApp", json!({ "pid": 4242 }));
+    assert_eq!(response["result"]["action"], "unmute");
+
+    let response = client.request(3, "forceStereo", json!({ "device": "AirPods" }));
+    assert_eq!(error_code_of(&response), error_code::NOT_FOUND);
+    assert_eq!(response["error"]["message"], "No unique paired device matches 'AirPods'");
+
+    let response = client.request(4, "reconnect", json!({ "device": "Jabra" }));
+    assert_eq!(error_code_of(&response), error_code::UNAVAILABLE);
+
+    assert_eq!(
+        *harness.actions.lock().unwrap(),
+        ["force-stereo Jabra Evolve2 65", "unmute 4242"]
+    );
+}
+
+#[test]
+fn test_notifications_get_no_response() {
+    let harness = Harness::start();
+    let mut client = harness.connect_authenticated();
+
+    client.send_raw(r#"{"jsonrpc":"2.0","method":"muteApp","params":{"pid":4242}}"#);
+    client.send_raw(r#"{"jsonrpc":"2.0","method":"teleport"}"#);
+    let response = client.request(9, "getState", json!({}));
+
+    assert_eq!(response["id"], 9);
+    assert_eq!(*harness.actions.lock().unwrap(), ["mute 4242"]);
+}
+
+#[test]
+fn test_subscribe_streams_matching_events() {
+    let harness = Harness::start();
+    let mut client = harness.connect_authenticated();
+
+    let response = client.request(1, "subscribe", json!({ "events": ["modeChanged"] }));
+    assert_eq!(response["result"]["subscribed"], true);
+
+    harness
+        .hub
+        .publish(&MonitorEvent::ForceStereoReapplied("Jabra Evolve2 65".to_string()));
+    harness.hub.publish(&MonitorEvent::ModeChanged {
+        old_mode: AudioMode::Stereo,
+        new_mode: AudioMode::HandsFree,
+    });
+
+    let event = client.receive();
+    assert_eq!(
+        event,
+        json!({
+            "jsonrpc": "2.0",
+            "method": "event",
+            "params": { "type": "modeChanged", "old_mode": "stereo", "new_mode": "hands-free" },
+        })
+    );
+
+    let response = client.request(2, "unsubscribe", json!({}));
+    assert_eq!(response["result"]["was_subscribed"], true);
+}
+
+#[test]
+fn test_tcp_listener_is_loopback_only() {
+    let (call_tx, _call_rx) = mpsc::channel();
+    let result = RpcServer::start_tcp(
+        "0.0.0.0:0".parse().unwrap(),
+        TOKEN.to_string(),
+        call_tx,
+        EventHub::new(),
+    );
+    assert!(result.is_err());
+}
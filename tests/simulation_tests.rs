@@ -0,0 +1,342 @@
+//! End-to-end tests: scenarios run through the audio monitor on the simulated backend
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use win_bt_stereo_vs_handsfree::audio::backend::AudioBackend;
+use win_bt_stereo_vs_handsfree::audio::device::AudioMode;
+use win_bt_stereo_vs_handsfree::audio::bus::{BusMessage, EventBus, Topic};
+use win_bt_stereo_vs_handsfree::audio::monitor::{
+    MonitorCommand, MonitorError, MonitorEvent, MonitorLoop, MonitorState, MuteOutcome, Reply, StateChanges, TaskMode,
+};
+use win_bt_stereo_vs_handsfree::audio::watchdog::{WatchdogOutcome, WatchdogSettings};
+use win_bt_stereo_vs_handsfree::bluetooth::inventory::BluetoothProfile;
+use win_bt_stereo_vs_handsfree::platform::Backends;
+use win_bt_stereo_vs_handsfree::simulation::{Scenario, ScenarioRunner, SimulatedBackend, Timeline};
+
+fn runner(scenario: &str) -> ScenarioRunner {
+    ScenarioRunner::new(scenario.parse::<Scenario>().unwrap())
+}
+
+fn secs(seconds: f64) -> Duration {
+    Duration::from_secs_f64(seconds)
+}
+
+fn watchdog(timeout: u64, cooldown: u64, max_attempts: u32) -> MonitorCommand {
+    MonitorCommand::ConfigureWatchdog(Some(WatchdogSettings {
+        timeout: Duration::from_secs(timeout),
+        cooldown: Duration::from_secs(cooldown),
+        max_attempts,
+    }))
+}
+
+fn outcomes(runner: &ScenarioRunner) -> Vec<(Duration, WatchdogOutcome)> {
+    assert_eq!(runner.dropped_events(), 0);
+    runner
+        .watchdog_records()
+        .into_iter()
+        .map(|(at, record)| (at, record.outcome))
+        .collect()
+}
+
+#[test]
+fn test_call_switches_to_hands_free() {
+    let mut runner = runner("t=0 headset connects stereo; t=2s Teams opens capture; t=3s endpoint becomes mono");
+    runner.run_to_end();
+
+    assert_eq!(runner.mode_changes(), vec![(secs(3.0), AudioMode::Stereo, AudioMode::HandsFree)]);
+
+    let state = runner.state();
+    assert_eq!(state.current_mode, AudioMode::HandsFree);
+    assert_eq!(state.bluetooth_devices.len(), 1);
+    assert_eq!(state.bluetooth_devices[0].device.name, "headset");
+    assert_eq!(state.mic_using_apps.len(), 1);
+    assert_eq!(state.mic_using_apps[0].display_name, "Teams");
+    assert!(state.mic_using_apps[0].is_using_bluetooth_mic);
+    assert!(runner.backend().actions().is_empty());
+}
+
+#[test]
+fn test_capture_on_another_mic_is_not_bluetooth() {
+    let mut runner = runner("t=0 headset connects; t=1s Zoom opens capture on Built-in Microphone");
+    runner.run_to_end();
+
+    let state = runner.state();
+    assert_eq!(state.current_mode, AudioMode::Stereo);
+    assert!(!state.mic_using_apps[0].is_using_bluetooth_mic);
+}
+
+#[test]
+fn test_watchdog_reconnects_stuck_headset() {
+    let mut runner = runner(
+        "t=0 headset connects stereo
+         t=1s Teams opens capture
+         t=2s endpoint becomes mono
+         t=4s Teams closes capture",
+    );
+    runner.send(watchdog(5, 30, 2));
+    runner.run_until(secs(12.0));
+
+    // Stuck from 4s, reconnect after the 5s timeout; the result is collected on the next poll
+    assert_eq!(
+        outcomes(&runner),
+        vec![(secs(9.0), WatchdogOutcome::Triggered), (secs(9.5), WatchdogOutcome::Succeeded)]
+    );
+    assert_eq!(runner.backend().actions(), vec!["reconnect headset"]);
+    assert_eq!(
+        runner.mode_changes(),
+        vec![
+            (secs(2.0), AudioMode::Stereo, AudioMode::HandsFree),
+            (secs(9.5), AudioMode::HandsFree, AudioMode::Stereo),
+        ]
+    );
+}
+
+#[test]
+fn test_watchdog_gives_up_after_failed_reconnects() {
+    let mut runner = runner("t=0 headset reconnect fails; t=0 headset connects hands-free");
+    runner.send(watchdog(5, 2, 2));
+    runner.run_until(secs(15.0));
+
+    assert_eq!(
+        outcomes(&runner),
+        vec![
+            (secs(5.0), WatchdogOutcome::Triggered),
+            (
+                secs(5.5),
+                WatchdogOutcome::Failed("IO error: Simulated reconnect of 'headset' failed".to_string())
+            ),
+            (secs(7.0), WatchdogOutcome::Triggered),
+            (
+                secs(7.5),
+                WatchdogOutcome::Failed("IO error: Simulated reconnect of 'headset' failed".to_string())
+            ),
+            (secs(9.0), WatchdogOutcome::GaveUp),
+        ]
+    );
+    assert_eq!(runner.backend().actions(), vec!["reconnect headset", "reconnect headset"]);
+    assert_eq!(runner.state().current_mode, AudioMode::HandsFree);
+}
+
+#[test]
+fn test_watchdog_leaves_active_calls_alone() {
+    let mut runner = runner("t=0 headset connects hands-free; t=0 Teams opens capture");
+    runner.send(watchdog(5, 30, 2));
+    runner.run_until(secs(30.0));
+
+    assert!(runner.watchdog_records().is_empty());
+    assert!(runner.backend().actions().is_empty());
+}
+
+#[test]
+fn test_force_stereo_reapplied_on_reconnect() {
+    let mut runner = runner(
+        "t=0 WH-1000XM4 connects stereo
+         t=1s WH-1000XM4 disconnects
+         t=2s WH-1000XM4 re-enables hands-free
+         t=3s WH-1000XM4 connects hands-free",
+    );
+    runner.send(MonitorCommand::SetForcedStereoDevices(HashSet::from(["WH-1000XM4".to_string()])));
+    runner.run_to_end();
+
+    let reapplied: Vec<Duration> = runner
+        .events()
+        .iter()
+        .filter(|e| matches!(&e.event, MonitorEvent::ForceStereoReapplied(device) if device == "WH-1000XM4"))
+        .map(|e| e.at)
+        .collect();
+    assert_eq!(reapplied, vec![secs(3.0)]);
+    assert_eq!(runner.backend().actions(), vec!["disable_hfp WH-1000XM4"]);
+    assert_eq!(runner.state().current_mode, AudioMode::Stereo);
+    assert_eq!(
+        runner.mode_changes(),
+        vec![
+            (secs(1.0), AudioMode::Stereo, AudioMode::Unknown),
+            (secs(3.5), AudioMode::HandsFree, AudioMode::Stereo),
+        ]
+    );
+}
+
+#[test]
+fn test_force_stereo_reapplied_when_endpoint_name_differs() {
+    let scenario: Scenario = "t=0 WH-1000XM4 connects stereo
+         t=1s WH-1000XM4 disconnects
+         t=2s WH-1000XM4 re-enables hands-free
+         t=3s WH-1000XM4 connects hands-free"
+        .parse()
+        .unwrap();
+    let mut runner = ScenarioRunner::with_backend(Arc::new(SimulatedBackend::new(scenario).with_windows_endpoint_names()));
+    // Forced over the command line, which names the paired device
+    runner.send(MonitorCommand::SetForcedStereoDevices(HashSet::from(["WH-1000XM4".to_string()])));
+    runner.run_to_end();
+
+    assert_eq!(runner.state().bluetooth_devices[0].device.name, "Headphones (WH-1000XM4)");
+    assert_eq!(runner.backend().actions(), vec!["disable_hfp WH-1000XM4"]);
+    assert_eq!(runner.state().current_mode, AudioMode::Stereo);
+}
+
+#[test]
+fn test_paired_devices_follow_hfp_state() {
+    let mut runner = runner("t=0 headset connects");
+    runner.run_until(Duration::ZERO);
+
+    let inventories: Vec<_> = runner
+        .events()
+        .iter()
+        .filter_map(|e| match &e.event {
+            MonitorEvent::PairedDevicesUpdated(devices) => Some(devices.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(inventories.len(), 1);
+    assert!(inventories[0][0].connected);
+    assert!(inventories[0][0].has_profile(BluetoothProfile::HandsFree));
+}
+
+#[test]
+fn test_mute_commands_reach_the_backend() {
+    let mut runner = runner("t=0 headset connects; t=0 Teams opens capture");
+    runner.run_until(Duration::ZERO);
+    let pid = runner.backend().pid_of("Teams").unwrap();
+
+    let (reply, muted) = Reply::channel();
+    runner.send(MonitorCommand::MuteApp(pid, reply));
+    let (reply, failed) = Reply::channel();
+    runner.send(MonitorCommand::MuteApp(4321, reply));
+    runner.run_until(secs(0.5));
+
+    assert_eq!(runner.backend().actions(), vec![format!("mute {}", pid)]);
+    assert!(runner.state().mic_using_apps[0].is_muted);
+    assert_eq!(muted.try_take(), Some(Ok(MuteOutcome::Muted(pid))));
+    assert!(matches!(failed.try_take(), Some(Err(MonitorError::Backend(message))) if message.contains("4321")));
+    // Failures go to the caller, not the event stream
+    assert!(!runner.events().iter().any(|e| matches!(e.event, MonitorEvent::Error(_))));
+}
+
+#[test]
+fn test_get_state_is_answered_after_the_next_poll() {
+    let mut runner = runner("t=0 headset connects hands-free; t=500ms Teams opens capture");
+    runner.run_until(Duration::ZERO);
+
+    let (reply, pending) = Reply::channel();
+    runner.send(MonitorCommand::GetState(reply));
+    assert!(pending.try_take().is_none());
+
+    runner.run_until(secs(0.5));
+    let state = pending.try_take().unwrap().unwrap();
+    assert_eq!(state.current_mode, AudioMode::HandsFree);
+    assert_eq!(state.mic_using_apps.len(), 1);
+}
+
+#[test]
+fn test_unanswered_reply_reports_stopped() {
+    let (reply, pending) = Reply::<MuteOutcome>::channel();
+    drop(reply);
+    assert_eq!(pending.wait(Duration::from_secs(1)), Err(MonitorError::Stopped));
+}
+
+#[test]
+fn test_state_updates_only_on_change() {
+    let mut runner = runner("t=0 headset connects stereo; t=2s Teams opens capture; t=3s endpoint becomes mono");
+    runner.run_to_end();
+
+    let updates: Vec<(Duration, StateChanges)> = runner
+        .events()
+        .iter()
+        .filter_map(|e| match &e.event {
+            MonitorEvent::StateUpdate { changes, .. } => Some((e.at, *changes)),
+            _ => None,
+        })
+        .collect();
+    let mic_apps = StateChanges { mic_apps: true, ..StateChanges::default() };
+    let mode = StateChanges { mode: true, devices: true, ..StateChanges::default() };
+    assert_eq!(updates, vec![(Duration::ZERO, StateChanges::ALL), (secs(2.0), mic_apps), (secs(3.0), mode)]);
+}
+
+#[test]
+fn test_battery_level_is_attached() {
+    let mut runner = runner("t=0 headset battery 15%; t=0 headset connects");
+    runner.run_to_end();
+    assert_eq!(runner.state().bluetooth_devices[0].battery_level, Some(15));
+}
+
+#[test]
+fn test_demo_scenario() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/demo-scenario.txt");
+    let mut runner = ScenarioRunner::new(Scenario::read(&path).unwrap());
+    runner.run_to_end();
+
+    assert_eq!(
+        runner.mode_changes(),
+        vec![
+            (secs(12.0), AudioMode::Stereo, AudioMode::HandsFree),
+            (secs(45.0), AudioMode::HandsFree, AudioMode::Stereo),
+            (secs(90.0), AudioMode::Stereo, AudioMode::Unknown),
+        ]
+    );
+    assert_eq!(runner.state().bluetooth_devices[0].battery_level, Some(15));
+    assert_eq!(runner.backend().bluetooth_output_apps()[0].display_name, "Spotify");
+}
+
+#[test]
+fn test_runs_are_deterministic() {
+    let scenario = "t=0 headset connects stereo
+                    t=1s Teams opens capture
+                    t=1.5s endpoint becomes mono
+                    t=3s Teams closes capture
+                    t=20s headset disconnects";
+    let run = || {
+        let mut runner = runner(scenario);
+        runner.send(watchdog(4, 10, 3));
+        runner.run_to_end();
+        let events: Vec<String> = runner
+            .events()
+            .iter()
+            .map(|e| match &e.event {
+                // Records carry a wall-clock timestamp
+                MonitorEvent::Watchdog(record) => format!("{:?} {:?}", e.at, record.outcome),
+                event => format!("{:?} {:?}", e.at, event),
+            })
+            .collect();
+        (events, runner.backend().actions())
+    };
+
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_lagging_subscriber_resyncs_from_state() {
+    let scenario: Scenario = "t=0 WH-1000XM4 connects; t=1s Teams opens capture; t=2s WH-1000XM4 becomes hands-free"
+        .parse()
+        .unwrap();
+    let backend = Arc::new(SimulatedBackend::new(scenario));
+    let bus = EventBus::new();
+    let slow = bus.subscribe_with_capacity(Some(HashSet::from([Topic::StateUpdate])), 1);
+    let state = Arc::new(Mutex::new(MonitorState::default()));
+    let mut monitor = MonitorLoop::new(
+        Backends::new(backend.clone(), backend.clone()),
+        bus,
+        Arc::clone(&state),
+        TaskMode::Inline,
+    );
+    let epoch = Instant::now();
+    for second in 0..3 {
+        backend.advance_to(secs(second as f64));
+        monitor.poll(epoch + secs(second as f64));
+    }
+
+    // Only the last update survived, and it does not say the mic apps changed
+    let messages: Vec<BusMessage> = slow.try_iter().collect();
+    assert!(matches!(messages[0], BusMessage::Lagged(2)));
+    let BusMessage::Event(MonitorEvent::StateUpdate { changes, .. }) = &messages[1] else {
+        panic!("expected a state update, got {:?}", messages[1]);
+    };
+    assert!(!changes.mic_apps);
+
+    let MonitorEvent::StateUpdate { mic_using_apps, changes, .. } = MonitorEvent::resync(&state.lock().unwrap()) else {
+        panic!("resync is a state update");
+    };
+    assert_eq!(changes, StateChanges::ALL);
+    assert_eq!(mic_using_apps.len(), 1);
+    assert_eq!(mic_using_apps[0].display_name, "Teams");
+}
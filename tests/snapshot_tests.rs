@@ -0,0 +1,155 @@
+//! Tests for the versioned state snapshot and its JSON schema
+//!
+//! The schema and an example snapshot are checked in under
+//! `tests/fixtures/snapshots`. A failing comparison means the wire format
+//! changed: keep old fields readable (or bump `SNAPSHOT_VERSION`) and
+//! regenerate the files with `UPDATE_SNAPSHOTS=1 cargo test --test snapshot_tests`.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use win_bt_stereo_vs_handsfree::audio::device::{AudioDevice, AudioMode, BluetoothAudioDevice};
+use win_bt_stereo_vs_handsfree::audio::monitor::MonitorState;
+use win_bt_stereo_vs_handsfree::audio::session::{HfpUsingApp, MicUsingApp};
+use win_bt_stereo_vs_handsfree::snapshot::{StateSnapshot, SNAPSHOT_VERSION};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/snapshots").join(name)
+}
+
+/// Compare against a checked-in file, or rewrite it with `UPDATE_SNAPSHOTS=1`
+fn assert_matches_file(actual: &str, name: &str) {
+    let path = fixture(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        actual, expected,
+        "{} changed; if intended, rerun with UPDATE_SNAPSHOTS=1",
+        name
+    );
+}
+
+fn at(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+fn headset(mode: AudioMode) -> BluetoothAudioDevice {
+    let mut device = BluetoothAudioDevice::new(AudioDevice {
+        id: "{0.0.0.00000000}.{9b1e7d42-3a6f-4e8c-b215-7c4d0e9f6a18}".to_string(),
+        name: "WH-1000XM4".to_string(),
+        is_bluetooth: true,
+    });
+    device.current_mode = mode;
+    device.sample_rate = Some(16000);
+    device.channels = Some(1);
+    device.battery_level = Some(70);
+    device
+}
+
+fn call_state() -> MonitorState {
+    let mut teams = MicUsingApp::new(8412, "ms-teams.exe".to_string(), "Microsoft Teams".to_string());
+    teams.is_using_bluetooth_mic = true;
+    MonitorState {
+        current_mode: AudioMode::HandsFree,
+        mic_using_apps: vec![teams],
+        bluetooth_devices: vec![headset(AudioMode::HandsFree)],
+        hfp_apps: vec![HfpUsingApp::new(
+            8412,
+            "ms-teams.exe".to_string(),
+            "Microsoft Teams".to_string(),
+        )],
+        last_update: Instant::now(),
+        updated_at: Some(at(1_760_793_600_250)),
+    }
+}
+
+fn call_snapshot() -> StateSnapshot {
+    StateSnapshot::new(&call_state(), at(1_760_793_600_500))
+}
+
+#[test]
+fn test_schema_is_stable() {
+    let schema = schemars::schema_for!(StateSnapshot);
+    let json = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+    assert_matches_file(&json, "state_snapshot.schema.json");
+}
+
+#[test]
+fn test_example_snapshot_is_stable() {
+    let json = serde_json::to_string_pretty(&call_snapshot()).unwrap() + "\n";
+    assert_matches_file(&json, "state_snapshot_v1.json");
+}
+
+#[test]
+fn test_example_snapshot_still_parses() {
+    let json = std::fs::read_to_string(fixture("state_snapshot_v1.json")).unwrap();
+    assert_eq!(StateSnapshot::from_json(&json).unwrap(), call_snapshot());
+}
+
+#[test]
+fn test_snapshot_from_state() {
+    let snapshot = call_snapshot();
+    assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+    assert_eq!(snapshot.taken_at_ms, 1_760_793_600_500);
+    assert_eq!(snapshot.updated_at_ms, Some(1_760_793_600_250));
+    assert_eq!(snapshot.mode, AudioMode::HandsFree);
+
+    let device = &snapshot.devices[0];
+    assert_eq!(device.name, "WH-1000XM4");
+    assert_eq!(device.sample_rate, Some(16000));
+    assert_eq!(device.battery_level, Some(70));
+    assert!(device.mic_in_use);
+    assert!(!device.forced_stereo);
+
+    let forced = snapshot.with_forced_stereo(&HashSet::from(["WH-1000XM4".to_string()]));
+    assert!(forced.devices[0].forced_stereo);
+}
+
+#[test]
+fn test_forced_stereo_matches_paired_name_in_endpoint_name() {
+    let mut state = call_state();
+    state.bluetooth_devices[0].device.name = "Headphones (WH-1000XM4)".to_string();
+    let snapshot = StateSnapshot::new(&state, at(1_760_793_600_500));
+
+    let forced = snapshot.clone().with_forced_stereo(&HashSet::from(["WH-1000XM4".to_string()]));
+    assert!(forced.devices[0].forced_stereo);
+    let other = snapshot.with_forced_stereo(&HashSet::from(["Jabra Evolve2".to_string()]));
+    assert!(!other.devices[0].forced_stereo);
+}
+
+#[test]
+fn test_snapshot_before_first_poll() {
+    let snapshot = StateSnapshot::new(&MonitorState::default(), at(1000));
+    assert_eq!(snapshot.updated_at_ms, None);
+    assert_eq!(snapshot.mode, AudioMode::Unknown);
+    assert!(snapshot.devices.is_empty());
+
+    let json = serde_json::to_value(&snapshot).unwrap();
+    assert_eq!(json["mode"], "unknown");
+    assert!(json["updated_at_ms"].is_null());
+}
+
+#[test]
+fn test_modes_use_cli_ids() {
+    for mode in [AudioMode::Stereo, AudioMode::HandsFree, AudioMode::Unknown] {
+        let json = serde_json::to_value(mode).unwrap();
+        assert_eq!(json, win_bt_stereo_vs_handsfree::cli::mode_id(mode));
+        assert_eq!(serde_json::from_value::<AudioMode>(json).unwrap(), mode);
+    }
+}
+
+#[test]
+fn test_newer_versions_are_rejected() {
+    let mut json = serde_json::to_value(call_snapshot()).unwrap();
+    json["version"] = (SNAPSHOT_VERSION + 1).into();
+    let error = StateSnapshot::from_json(&json.to_string()).unwrap_err();
+    assert!(error.to_string().contains("version"), "{}", error);
+
+    // Unknown fields from a compatible writer are ignored
+    let mut json = serde_json::to_value(call_snapshot()).unwrap();
+    json["future_field"] = true.into();
+    assert!(StateSnapshot::from_json(&json.to_string()).is_ok());
+}
@@ -0,0 +1,209 @@
+//! Monitor health detection on real threads: failed start, panics, stalls and restarts
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use win_bt_stereo_vs_handsfree::audio::backend::{AudioBackend, AudioPoll};
+use win_bt_stereo_vs_handsfree::audio::bus::EventBus;
+use win_bt_stereo_vs_handsfree::audio::monitor::AudioMonitor;
+use win_bt_stereo_vs_handsfree::audio::session::HfpUsingApp;
+use win_bt_stereo_vs_handsfree::audio::supervisor::{
+    MonitorHealth, MonitorSupervisor, SupervisorAction, SupervisorSettings,
+};
+use win_bt_stereo_vs_handsfree::error::{AppError, Result};
+use win_bt_stereo_vs_handsfree::platform::Backends;
+use win_bt_stereo_vs_handsfree::simulation::{Scenario, SimulatedBackend};
+
+const STALL_TIMEOUT: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy)]
+enum Fault {
+    /// `attach_thread` fails, like COM initialization
+    AttachFails,
+    /// The first poll panics
+    PollPanics,
+    /// The first poll never returns in time
+    PollHangs,
+    /// Every poll returns an error
+    PollFails,
+    /// The first `n` monitor threads fail to attach, later ones work
+    AttachFailsTimes(u32),
+}
+
+/// Audio backend that misbehaves in a chosen way and otherwise reports nothing
+struct FaultyAudio {
+    fault: Fault,
+    attaches: AtomicU32,
+}
+
+impl FaultyAudio {
+    fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            attaches: AtomicU32::new(0),
+        }
+    }
+}
+
+impl AudioBackend for FaultyAudio {
+    fn attach_thread(&self) -> Result<()> {
+        let attach = self.attaches.fetch_add(1, Ordering::SeqCst);
+        match self.fault {
+            Fault::AttachFails => Err(AppError::ComInitFailed("simulated".into())),
+            Fault::AttachFailsTimes(n) if attach < n => {
+                Err(AppError::ComInitFailed("simulated".into()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn poll(&self) -> Result<AudioPoll> {
+        match self.fault {
+            Fault::PollPanics => panic!("simulated poll panic"),
+            Fault::PollHangs => thread::sleep(Duration::from_secs(2)),
+            Fault::PollFails => return Err(AppError::AudioSessionError("simulated".into())),
+            _ => {}
+        }
+        Ok(AudioPoll::default())
+    }
+
+    fn bluetooth_output_apps(&self) -> Vec<HfpUsingApp> {
+        Vec::new()
+    }
+
+    fn set_app_muted(&self, _process_id: u32, _muted: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn mute_all(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn backends(audio: Arc<FaultyAudio>) -> Backends {
+    let scenario: Scenario = "t=0 headset connects stereo".parse().unwrap();
+    Backends::new(audio, Arc::new(SimulatedBackend::real_time(scenario)))
+}
+
+/// Wait until the monitor reaches `expected`, or fail after a few seconds
+fn wait_for(monitor: &AudioMonitor, expected: MonitorHealth) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let health = monitor.health(Instant::now(), STALL_TIMEOUT);
+        if health == expected {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "monitor stayed {} instead of {}",
+            health,
+            expected
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_healthy_monitor_is_running() {
+    let monitor = AudioMonitor::start(
+        backends(Arc::new(FaultyAudio::new(Fault::AttachFailsTimes(0)))),
+        EventBus::new(),
+    )
+    .unwrap();
+    wait_for(&monitor, MonitorHealth::Running);
+}
+
+#[test]
+fn test_failed_attach_is_dead() {
+    let monitor = AudioMonitor::start(
+        backends(Arc::new(FaultyAudio::new(Fault::AttachFails))),
+        EventBus::new(),
+    )
+    .unwrap();
+    wait_for(&monitor, MonitorHealth::Dead);
+}
+
+#[test]
+fn test_panicking_poll_is_dead_and_state_stays_readable() {
+    let monitor = AudioMonitor::start(
+        backends(Arc::new(FaultyAudio::new(Fault::PollPanics))),
+        EventBus::new(),
+    )
+    .unwrap();
+    wait_for(&monitor, MonitorHealth::Dead);
+    assert!(monitor.get_state().updated_at.is_none());
+}
+
+#[test]
+fn test_failing_polls_are_stalled() {
+    let monitor = AudioMonitor::start(
+        backends(Arc::new(FaultyAudio::new(Fault::PollFails))),
+        EventBus::new(),
+    )
+    .unwrap();
+    wait_for(&monitor, MonitorHealth::Stalled);
+    // The thread itself is still alive, polling and failing
+    assert_ne!(monitor.health(Instant::now(), STALL_TIMEOUT), MonitorHealth::Dead);
+}
+
+#[test]
+fn test_hanging_poll_is_stalled_and_can_be_abandoned() {
+    let monitor = AudioMonitor::start(
+        backends(Arc::new(FaultyAudio::new(Fault::PollHangs))),
+        EventBus::new(),
+    )
+    .unwrap();
+    wait_for(&monitor, MonitorHealth::Stalled);
+
+    let started = Instant::now();
+    monitor.abandon();
+    assert!(
+        started.elapsed() < Duration::from_secs(1),
+        "abandon waited for the stalled thread"
+    );
+}
+
+#[test]
+fn test_supervisor_restarts_until_the_monitor_runs() {
+    let audio = Arc::new(FaultyAudio::new(Fault::AttachFailsTimes(2)));
+    let settings = SupervisorSettings {
+        stall_timeout: STALL_TIMEOUT,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(40),
+        ..SupervisorSettings::default()
+    };
+    let mut supervisor = MonitorSupervisor::new(settings, Instant::now());
+    let mut monitor = AudioMonitor::start(backends(Arc::clone(&audio)), EventBus::new()).unwrap();
+    let mut actions = Vec::new();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !actions.contains(&SupervisorAction::Recovered) {
+        assert!(
+            Instant::now() < deadline,
+            "monitor never recovered: {:?}",
+            actions
+        );
+        let now = Instant::now();
+        for action in supervisor.check(now, monitor.health(now, STALL_TIMEOUT)) {
+            if let SupervisorAction::Restart { .. } = action {
+                monitor.abandon();
+                monitor =
+                    AudioMonitor::start(backends(Arc::clone(&audio)), EventBus::new()).unwrap();
+            }
+            actions.push(action);
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    assert_eq!(
+        actions,
+        vec![
+            SupervisorAction::MarkStale(MonitorHealth::Dead),
+            SupervisorAction::Restart { attempt: 1 },
+            SupervisorAction::Restart { attempt: 2 },
+            SupervisorAction::Recovered,
+        ]
+    );
+    assert_eq!(audio.attaches.load(Ordering::SeqCst), 3);
+}